@@ -0,0 +1,196 @@
+//! Achievements unlocked by what is done in a game, found by observing it after every update.
+//! Unlocks are kept as a set of bits so that they can be saved to flash with the session.
+
+use crate::session::GameStats;
+use crate::tetris::Tetris;
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const SCORE_FOR_ACHIEVEMENT: u32 = 100_000;
+const COMBO_FOR_ACHIEVEMENT: usize = 10;
+/// Lines of a sprint and the updates to clear them in, three minutes at four updates a second.
+const SPRINT_LINES: u32 = 40;
+const SPRINT_TICKS: u32 = 720;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Achievement {
+    /// Four rows cleared by one piece.
+    FirstTetris,
+    /// A T-spin that cleared rows.
+    TSpin,
+    /// SCORE_FOR_ACHIEVEMENT points in a single game.
+    HundredThousand,
+    /// COMBO_FOR_ACHIEVEMENT pieces in a row after the first clearing rows.
+    TenCombo,
+    /// SPRINT_LINES lines cleared within SPRINT_TICKS updates.
+    Sprint,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 5] = [
+        Achievement::FirstTetris,
+        Achievement::TSpin,
+        Achievement::HundredThousand,
+        Achievement::TenCombo,
+        Achievement::Sprint,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl fmt::Display for Achievement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Achievement::FirstTetris => "First Tetris",
+            Achievement::TSpin => "T-Spin",
+            Achievement::HundredThousand => "100K",
+            Achievement::TenCombo => "10 Combo",
+            Achievement::Sprint => "Sprint",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A set of achievements, those unlocked so far or those an update has just unlocked.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Achievements(u32);
+
+impl Achievements {
+    /// Bytes taken by the saved form from to_bytes.
+    pub const ENCODED_LEN: usize = 4;
+
+    pub fn contains(&self, achievement: Achievement) -> bool {
+        self.0 & achievement.bit() != 0
+    }
+
+    pub fn insert(&mut self, achievement: Achievement) {
+        self.0 |= achievement.bit();
+    }
+
+    /// Every achievement in either set.
+    pub fn union(self, other: Achievements) -> Achievements {
+        Achievements(self.0 | other.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Achievement> + '_ {
+        Achievement::ALL
+            .into_iter()
+            .filter(|&achievement| self.contains(achievement))
+    }
+
+    /// Unlock whatever the last update of a game earned, given its statistics with that update
+    /// observed. Returns the achievements unlocked by this update for the frontend to announce,
+    /// those unlocked before are not announced again.
+    pub fn observe(&mut self, tetris: &Tetris, game: &GameStats) -> Achievements {
+        let mut earned = Achievements::default();
+        if let Tetris::Running(ref state) = tetris {
+            if state.rows_cleared() >= 4 {
+                earned.insert(Achievement::FirstTetris);
+            }
+            if state.t_spin() && state.rows_cleared() > 0 {
+                earned.insert(Achievement::TSpin);
+            }
+            if state.combo() >= Some(COMBO_FOR_ACHIEVEMENT) {
+                earned.insert(Achievement::TenCombo);
+            }
+        }
+        if game.score >= SCORE_FOR_ACHIEVEMENT {
+            earned.insert(Achievement::HundredThousand);
+        }
+        if game.lines >= SPRINT_LINES && game.ticks <= SPRINT_TICKS {
+            earned.insert(Achievement::Sprint);
+        }
+
+        let unlocked = Achievements(earned.0 & !self.0);
+        self.0 |= earned.0;
+        unlocked
+    }
+
+    /// The set as little endian bits, for platforms without serde.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        self.0.to_le_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        Achievements(u32::from_le_bytes(*bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::achievement::{Achievement, Achievements};
+    use crate::piece::PieceSelector;
+    use crate::session::GameStats;
+    use crate::tetris::{KeyState, Tetris};
+
+    #[test]
+    fn an_achievement_is_announced_only_when_first_unlocked() {
+        let mut achievements = Achievements::default();
+        let tetris = Tetris::new_with_seed(4);
+        let game = GameStats {
+            score: 150_000,
+            ..GameStats::default()
+        };
+        let unlocked = achievements.observe(&tetris, &game);
+        assert!(unlocked.iter().eq([Achievement::HundredThousand]));
+        assert!(achievements.contains(Achievement::HundredThousand));
+
+        assert!(achievements.observe(&tetris, &game).is_empty());
+    }
+
+    #[test]
+    fn a_tetris_is_seen_as_it_clears() {
+        let mut tetris = Tetris::new_with_seed(4);
+        if let Tetris::Running(ref mut state) = tetris {
+            for y in 0..4 {
+                state.grid.row_mut(y).fill(true);
+                state.grid.row_mut(y)[0] = false;
+            }
+            state.piece = PieceSelector::Line.to_piece((0, 4));
+            state.piece.next_rotation();
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+
+        let mut achievements = Achievements::default();
+        let unlocked = achievements.observe(&tetris, &GameStats::default());
+        assert!(unlocked.contains(Achievement::FirstTetris));
+    }
+
+    #[test]
+    fn a_slow_forty_lines_is_not_a_sprint() {
+        let mut achievements = Achievements::default();
+        let tetris = Tetris::new_with_seed(4);
+        let slow = GameStats {
+            lines: 40,
+            ticks: 10_000,
+            ..GameStats::default()
+        };
+        assert!(achievements.observe(&tetris, &slow).is_empty());
+        let fast = GameStats { ticks: 600, ..slow };
+        assert!(achievements
+            .observe(&tetris, &fast)
+            .contains(Achievement::Sprint));
+    }
+
+    #[test]
+    fn achievements_survive_being_saved() {
+        let mut achievements = Achievements::default();
+        achievements.insert(Achievement::TSpin);
+        achievements.insert(Achievement::Sprint);
+        let restored = Achievements::from_bytes(&achievements.to_bytes());
+        assert!(restored == achievements);
+    }
+}
@@ -0,0 +1,272 @@
+//! Looking back over a finished game. The game is replayed from its seed and inputs, and each
+//! piece placed is judged against the placement a simple evaluator likes best: the holes it
+//! left, whether a better placement was on offer and the moves wasted getting it there. The
+//! same evaluator hints to beginners where to put the falling piece.
+
+use crate::grid::Grid;
+use crate::piece::{PieceSelector, Rotation};
+use crate::tetris::{spawn_piece, KeyState, Placement, Rules, Tetris, TetrisState};
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Weights of the evaluator in hundredths, from Yiyuan Lee's tuned player
+const HEIGHT_WEIGHT: i32 = -51;
+const LINES_WEIGHT: i32 = 76;
+const HOLES_WEIGHT: i32 = -36;
+const BUMPINESS_WEIGHT: i32 = -18;
+
+/// How much better than the placement made another has to be to be suggested, about one hole.
+const SUGGESTION_MARGIN: i32 = -HOLES_WEIGHT;
+
+/// Everything needed to play a game again exactly: it is dealt the same pieces by its seed and
+/// given the same key state each update.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Replay {
+    pub seed: u64,
+    pub rules: Rules,
+    pub inputs: Vec<KeyState>,
+}
+
+/// How one piece of a replayed game was placed.
+#[derive(Clone, Debug)]
+pub struct PieceAnalysis {
+    pub placement: Placement,
+    /// The stack once the piece locked and any rows it completed were cleared.
+    pub stack: Grid,
+    /// Holes added to the stack by the piece, negative if it cleared rows that had some.
+    pub holes_created: i32,
+    /// A placement the evaluator rates clearly higher, if there was one.
+    pub better: Option<Placement>,
+    /// Moves and rotations beyond the fewest that reach the placement from where the piece
+    /// spawned on an open playfield.
+    pub finesse_faults: usize,
+}
+
+/// Empty cells with a filled cell above them in the same column.
+pub fn holes(grid: &Grid) -> usize {
+    (0..grid.width)
+        .map(|x| {
+            let height = column_height(grid, x);
+            (0..height).filter(|&y| !grid[(x, y)]).count()
+        })
+        .sum()
+}
+
+pub(crate) fn column_height(grid: &Grid, x: usize) -> usize {
+    (0..grid.height)
+        .rev()
+        .find(|&y| grid[(x, y)])
+        .map_or(0, |y| y + 1)
+}
+
+/// Rate a stack after a piece cleared rows_cleared rows, higher is better.
+pub fn evaluate(grid: &Grid, rows_cleared: usize) -> i32 {
+    let heights: Vec<usize> = (0..grid.width).map(|x| column_height(grid, x)).collect();
+    let aggregate_height: usize = heights.iter().sum();
+    let bumpiness: usize = heights.windows(2).map(|w| w[0].abs_diff(w[1])).sum();
+    HEIGHT_WEIGHT * aggregate_height as i32
+        + LINES_WEIGHT * rows_cleared as i32
+        + HOLES_WEIGHT * holes(grid) as i32
+        + BUMPINESS_WEIGHT * bumpiness as i32
+}
+
+/// Lock placement into a copy of stack, returning it with complete rows removed as the game
+/// removes them and how many there were.
+fn place(stack: &Grid, placement: Placement, mirror: bool) -> (Grid, usize) {
+    let mut grid = stack.clone();
+    placement
+        .piece_grid(mirror)
+        .copy_into(&mut grid, (placement.x, placement.y));
+
+    let mut rows_cleared = 0;
+    for y in (0..grid.height).rev() {
+        if grid.row(y).iter().all(|&cell| cell) {
+            grid.remove_row(y);
+            rows_cleared += 1;
+        }
+    }
+    (grid, rows_cleared)
+}
+
+/// The placement of kind the evaluator rates highest among those reached by turning it and
+/// dropping it straight down, with its rating. Pieces are mirrored as in mirror mode if mirror
+/// is set. None if it fits nowhere.
+pub fn best_placement(stack: &Grid, kind: PieceSelector, mirror: bool) -> Option<(Placement, i32)> {
+    let mut best: Option<(Placement, i32)> = None;
+    for rotation in [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270] {
+        let grid = Placement {
+            kind,
+            rotation,
+            x: 0,
+            y: 0,
+        }
+        .piece_grid(mirror);
+        if grid.height > stack.height {
+            continue;
+        }
+        for x in 0..=stack.width - grid.width {
+            let top = stack.height - grid.height;
+            if grid.collides(stack, (x, top)) {
+                continue;
+            }
+            let mut y = top;
+            while y > 0 && !grid.collides(stack, (x, y - 1)) {
+                y -= 1;
+            }
+            let placement = Placement {
+                kind,
+                rotation,
+                x,
+                y,
+            };
+            let (placed, rows_cleared) = place(stack, placement, mirror);
+            let rating = evaluate(&placed, rows_cleared);
+            if best.is_none_or(|(_, best_rating)| rating > best_rating) {
+                best = Some((placement, rating));
+            }
+        }
+    }
+    best
+}
+
+/// Where the evaluator would place the falling piece, for a hint to beginners. None between
+/// pieces or if the piece fits nowhere.
+pub fn hint(state: &TetrisState) -> Option<Placement> {
+    let piece = state.piece_in_play()?;
+    best_placement(&state.grid, piece.kind(), state.rules.mirror).map(|(placement, _)| placement)
+}
+
+/// Quarter turns clockwise from one rotation to another, the only way pieces turn.
+fn turns(from: Rotation, to: Rotation) -> usize {
+    (to as usize + 4 - from as usize) % 4
+}
+
+/// Replay a game and judge each piece that locked, in the order they were placed.
+pub fn analyse(replay: &Replay) -> Vec<PieceAnalysis> {
+    let mut tetris = Tetris::new_with_seed(replay.seed);
+    tetris.set_rules(replay.rules);
+
+    let mirror = replay.rules.mirror;
+    let mut analyses = Vec::new();
+    let mut moves = 0;
+    for key_state in &replay.inputs {
+        let Tetris::Running(ref before) = tetris else {
+            break;
+        };
+        let stack = before.grid.clone();
+        let (kind, x, rotation) = (before.piece.kind(), before.piece.x, before.piece.rotation());
+
+        tetris.set_key_state(key_state);
+        tetris.update();
+        let Tetris::Running(ref after) = tetris else {
+            break;
+        };
+
+        if after.piece_locked() {
+            let Some(placement) = after.last_placement() else {
+                continue;
+            };
+            moves += placement.x.abs_diff(x) + turns(rotation, placement.rotation);
+            let fewest = placement.x.abs_diff(spawn_piece(placement.kind).x)
+                + turns(Rotation::R0, placement.rotation);
+
+            let (placed, rows_cleared) = place(&stack, placement, mirror);
+            let rating = evaluate(&placed, rows_cleared);
+            let better = best_placement(&stack, placement.kind, mirror)
+                .filter(|&(_, best)| best - rating >= SUGGESTION_MARGIN)
+                .map(|(better, _)| better);
+            analyses.push(PieceAnalysis {
+                placement,
+                holes_created: holes(&placed) as i32 - holes(&stack) as i32,
+                stack: placed,
+                better,
+                finesse_faults: moves.saturating_sub(fewest),
+            });
+            moves = 0;
+        } else if after.piece.kind() == kind {
+            moves += after.piece.x.abs_diff(x) + turns(rotation, after.piece.rotation());
+        } else {
+            // Swapped with the held piece, which starts its moves from scratch
+            moves = 0;
+        }
+    }
+    analyses
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::{analyse, best_placement, hint, holes, Replay};
+    use crate::grid::Grid;
+    use crate::piece::{PieceSelector, Rotation};
+    use crate::tetris::{spawn_piece, KeyState, Rules, Tetris, GRID_SIZE};
+    use alloc::vec;
+
+    #[test]
+    fn holes_are_empty_cells_under_the_stack() {
+        let mut grid = Grid::new(GRID_SIZE);
+        grid[(0, 2)] = true;
+        grid[(0, 0)] = true;
+        grid[(5, 0)] = true;
+        assert!(holes(&grid) == 1);
+    }
+
+    #[test]
+    fn the_best_placement_fills_a_well() {
+        let mut grid = Grid::new(GRID_SIZE);
+        for x in 1..GRID_SIZE.0 {
+            for y in 0..4 {
+                grid[(x, y)] = true;
+            }
+        }
+        let (placement, _) = best_placement(&grid, PieceSelector::Line, false).unwrap();
+        assert!(placement.x == 0 && placement.y == 0);
+        assert!(matches!(placement.rotation, Rotation::R90 | Rotation::R270));
+    }
+
+    #[test]
+    fn the_hint_is_the_best_placement_of_the_falling_piece() {
+        let mut tetris = Tetris::new_with_seed(3);
+        let Tetris::Running(ref mut state) = tetris else {
+            panic!("Expected a running game");
+        };
+        state.piece = spawn_piece(PieceSelector::O);
+        for x in 0..GRID_SIZE.0 - 2 {
+            state.grid[(x, 0)] = true;
+        }
+        // The O fills the gap at the end of the row
+        let placement = hint(state).unwrap();
+        assert!(placement.kind == PieceSelector::O);
+        assert!(placement.x == GRID_SIZE.0 - 2 && placement.y == 0);
+    }
+
+    #[test]
+    fn a_replay_is_judged_piece_by_piece() {
+        let hard_drop = KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        };
+        let replay = Replay {
+            seed: 3,
+            rules: Rules::default(),
+            inputs: vec![hard_drop; 6],
+        };
+        let analyses = analyse(&replay);
+        assert!(analyses.len() == 6);
+
+        // Dropped where they spawned without a move, so nothing was wasted
+        assert!(analyses.iter().all(|analysis| analysis.finesse_faults == 0));
+
+        let mut tetris = Tetris::new_with_seed(3);
+        for _ in 0..6 {
+            tetris.set_key_state(&hard_drop);
+            tetris.update();
+        }
+        let Tetris::Running(state) = tetris else {
+            panic!("Expected a running game");
+        };
+        assert!(analyses[5].stack == state.grid);
+        assert!(analyses[5].placement == state.last_placement().unwrap());
+    }
+}
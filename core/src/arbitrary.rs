@@ -0,0 +1,150 @@
+//! proptest strategies that generate random but valid grids, pieces and game states, so property
+//! tests and fuzzers exercise the states a real game can reach rather than arbitrary memory.
+
+use crate::grid::Grid;
+use crate::piece::{Piece, PieceSelector};
+use crate::tetris::{spawn_piece, KeyState, Tetris, TetrisState, GRID_SIZE};
+use alloc::vec::Vec;
+use enum_iterator::all;
+use proptest::prelude::*;
+use proptest::sample::{select, Index};
+use rand::{prng::XorShiftRng, SeedableRng};
+
+/// The largest width and height of a grid of random size, small enough that it fits in the fixed
+/// cells of allocator free builds.
+const MAX_RANDOM_SIZE: usize = 16;
+
+impl Arbitrary for Grid {
+    /// The size of the grid, or a random size when None.
+    type Parameters = Option<(usize, usize)>;
+    type Strategy = BoxedStrategy<Grid>;
+
+    fn arbitrary_with(size: Self::Parameters) -> Self::Strategy {
+        let size = match size {
+            Some(size) => Just(size).boxed(),
+            None => (1..=MAX_RANDOM_SIZE, 1..=MAX_RANDOM_SIZE).boxed(),
+        };
+        size.prop_flat_map(|(width, height)| {
+            proptest::collection::vec(any::<bool>(), width * height)
+                .prop_map(move |cells| Grid::from_cells((width, height), &cells))
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for PieceSelector {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<PieceSelector>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        select(all::<PieceSelector>().collect::<Vec<_>>()).boxed()
+    }
+}
+
+impl Arbitrary for Piece {
+    /// The width and height of the playfield. The piece is placed in any rotation with its sides
+    /// inside the playfield, possibly extending above the top as it does when spawned.
+    type Parameters = (usize, usize);
+    type Strategy = BoxedStrategy<Piece>;
+
+    fn arbitrary_with((width, height): Self::Parameters) -> Self::Strategy {
+        (
+            any::<PieceSelector>(),
+            0..4usize,
+            any::<Index>(),
+            any::<Index>(),
+        )
+            .prop_map(move |(kind, rotations, x, y)| {
+                let mut piece = kind.to_piece((0, 0));
+                (0..rotations).for_each(|_| piece.next_rotation());
+                let piece_width = piece.current_rotation().width;
+                piece.x = x.index(width.saturating_sub(piece_width) + 1);
+                piece.y = y.index(height);
+                piece
+            })
+            .boxed()
+    }
+
+    fn arbitrary() -> Self::Strategy {
+        Self::arbitrary_with(GRID_SIZE)
+    }
+}
+
+impl Arbitrary for KeyState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<KeyState>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<[bool; 8]>()
+            .prop_map(
+                |[left, right, rotate, rotate_ccw, rotate_180, hard_drop, soft_drop, hold]| {
+                    KeyState {
+                        left,
+                        right,
+                        rotate,
+                        rotate_ccw,
+                        rotate_180,
+                        hard_drop,
+                        soft_drop,
+                        hold,
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for TetrisState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<TetrisState>;
+
+    /// A random stack with the falling piece anywhere it fits. Cells under the piece are cleared
+    /// and one cell of every complete row is removed, so the state passes validate.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (
+            any_with::<Grid>(Some(GRID_SIZE)),
+            any::<Piece>(),
+            any::<PieceSelector>(),
+            0..1_000_000_000usize,
+            any::<u64>(),
+        )
+            .prop_map(|(mut grid, piece, next_kind, score, seed)| {
+                let shape = piece.current_rotation();
+                for y in 0..shape.height {
+                    for x in 0..shape.width {
+                        let (grid_x, grid_y) = (piece.x + x, piece.y + y);
+                        if shape[(x, y)] && grid_y < grid.height {
+                            grid[(grid_x, grid_y)] = false;
+                        }
+                    }
+                }
+
+                for y in 0..grid.height {
+                    let width = grid.width;
+                    let row = grid.row_mut(y);
+                    if row.iter().all(|&cell| cell) {
+                        row[y % width] = false;
+                    }
+                }
+
+                TetrisState::from_parts(
+                    grid,
+                    piece,
+                    spawn_piece(next_kind),
+                    score,
+                    XorShiftRng::seed_from_u64(seed),
+                )
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for Tetris {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Tetris>;
+
+    /// A game in progress, games that have finished hold no state worth generating.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<TetrisState>().prop_map(Tetris::Running).boxed()
+    }
+}
@@ -0,0 +1,112 @@
+//! Benchmarks of the update and draw hot path, run with `cargo bench`. On the RP2040 at 125MHz a
+//! full update and draw of the playfield should take well under a millisecond.
+
+extern crate test;
+
+use crate::grid::Grid;
+use crate::piece::PieceSelector;
+use crate::tetris::{KeyState, Tetris, TetrisState};
+use test::{black_box, Bencher};
+
+/// A game with the bottom half of the playfield filled in a checkerboard, so that no rows are
+/// complete but collisions have to look at most cells.
+fn half_full_state() -> TetrisState {
+    let mut state = match Tetris::new() {
+        Tetris::Running(state) => state,
+        Tetris::Paused(_) | Tetris::Finished(_) => unreachable!(),
+    };
+    for y in 0..state.grid.height / 2 {
+        for x in 0..state.grid.width {
+            state.grid[(x, y)] = (x + y) % 2 == 0;
+        }
+    }
+    state
+}
+
+#[bench]
+fn collides(b: &mut Bencher) {
+    let state = half_full_state();
+    let piece = PieceSelector::T.to_piece((0, 0));
+    b.iter(|| {
+        for y in 0..state.grid.height {
+            black_box(piece.current_rotation().collides(&state.grid, (4, y)));
+        }
+    });
+}
+
+#[bench]
+fn copy_into(b: &mut Bencher) {
+    let piece = PieceSelector::J.to_piece((0, 0));
+    let mut grid = Grid::new((10, 20));
+    b.iter(|| {
+        for y in 0..grid.height {
+            piece.current_rotation().copy_into(&mut grid, (3, y));
+        }
+        black_box(&grid);
+    });
+}
+
+#[bench]
+fn remove_complete_rows(b: &mut Bencher) {
+    let mut state = half_full_state();
+    b.iter(|| {
+        // Refill four rows so each iteration clears a tetris
+        for y in 0..4 {
+            state.grid.row_mut(y).fill(true);
+        }
+        state.remove_complete_rows();
+        black_box(state.score);
+    });
+}
+
+#[bench]
+fn draw_game_grid(b: &mut Bencher) {
+    let state = half_full_state();
+    let mut frame = [[false; 64]; 128];
+    b.iter(|| {
+        state.draw_game_grid(|x, y, on| frame[x][y] = on, (2, 10), (4, 2));
+        black_box(&frame);
+    });
+}
+
+/// A whole frame as the firmware runs it: one update followed by a draw.
+#[bench]
+fn update_and_draw(b: &mut Bencher) {
+    let mut tetris = Tetris::new();
+    let mut frame = [[false; 64]; 128];
+    let mut tick = 0usize;
+    b.iter(|| {
+        tick += 1;
+        tetris.set_key_state(&KeyState {
+            left: tick % 3 == 0,
+            right: tick % 5 == 0,
+            rotate: tick % 7 == 0,
+            rotate_ccw: false,
+            rotate_180: false,
+            hard_drop: false,
+            soft_drop: false,
+            hold: false,
+        });
+        tetris.update();
+        match tetris {
+            Tetris::Running(ref state) => {
+                state.draw_game_grid(|x, y, on| frame[x][y] = on, (2, 10), (4, 2))
+            }
+            Tetris::Paused(_) | Tetris::Finished(_) => tetris = Tetris::new(),
+        }
+        black_box(&frame);
+    });
+}
+
+/// Headless fast-forwarding, as used for replay seeking.
+#[bench]
+fn update_n(b: &mut Bencher) {
+    b.iter(|| {
+        let mut tetris = Tetris::new_with_seed(1);
+        black_box(tetris.update_n_with(1_000, |tick| KeyState {
+            left: tick % 3 == 0,
+            rotate: tick % 7 == 0,
+            ..KeyState::default()
+        }));
+    });
+}
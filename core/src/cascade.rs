@@ -0,0 +1,146 @@
+//! Cascade gravity, an optional rule where after a clear every group of joined blocks falls on
+//! its own until it lands, rather than the stack only moving down by the rows cleared. Groups
+//! that land can complete more rows, clearing in a chain.
+
+use crate::grid::Grid;
+
+/// The filled cells next to (x, y), with the columns wrapping round in the wrap-around variant.
+fn neighbours(
+    grid: &Grid,
+    (x, y): (usize, usize),
+    wrap: bool,
+) -> impl Iterator<Item = (usize, usize)> {
+    let (width, height) = (grid.width, grid.height);
+    let left = match wrap {
+        true => Some((x + width - 1) % width),
+        false => x.checked_sub(1),
+    };
+    let right = match wrap {
+        true => Some((x + 1) % width),
+        false => Some(x + 1).filter(|&right| right < width),
+    };
+    [
+        left.map(|left| (left, y)),
+        right.map(|right| (right, y)),
+        y.checked_sub(1).map(|below| (x, below)),
+        Some(y + 1)
+            .filter(|&above| above < height)
+            .map(|above| (x, above)),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+/// The cells joined to the filled cell at start through filled cells beside, above or below
+/// each other.
+fn group_of(grid: &Grid, start: (usize, usize), wrap: bool) -> Grid {
+    let mut group = Grid::new((grid.width, grid.height));
+    group[start] = true;
+    // Grown a sweep at a time rather than with a queue, so it needs no allocator
+    let mut grew = true;
+    while grew {
+        grew = false;
+        for y in 0..grid.height {
+            for x in 0..grid.width {
+                if grid[(x, y)]
+                    && !group[(x, y)]
+                    && neighbours(grid, (x, y), wrap).any(|cell| group[cell])
+                {
+                    group[(x, y)] = true;
+                    grew = true;
+                }
+            }
+        }
+    }
+    group
+}
+
+/// Whether every cell of group has a floor under it that is empty or part of the group itself.
+fn can_fall(grid: &Grid, group: &Grid) -> bool {
+    (0..grid.height).all(|y| {
+        (0..grid.width)
+            .all(|x| !group[(x, y)] || (y > 0 && (!grid[(x, y - 1)] || group[(x, y - 1)])))
+    })
+}
+
+/// Move group and its cells in grid down a row.
+fn lower(grid: &mut Grid, group: &mut Grid) {
+    for y in 1..grid.height {
+        for x in 0..grid.width {
+            if group[(x, y)] {
+                group[(x, y)] = false;
+                group[(x, y - 1)] = true;
+                grid[(x, y)] = false;
+                grid[(x, y - 1)] = true;
+            }
+        }
+    }
+}
+
+/// Drop every group of joined cells in grid until each rests on the floor or on another group.
+/// Returns whether any fell.
+pub(crate) fn settle(grid: &mut Grid, wrap: bool) -> bool {
+    let mut fell = false;
+    // A group that falls can leave others with nothing under them, so look again after each
+    let mut falling = true;
+    while falling {
+        falling = false;
+        let mut seen = Grid::new((grid.width, grid.height));
+        'groups: for y in 0..grid.height {
+            for x in 0..grid.width {
+                if !grid[(x, y)] || seen[(x, y)] {
+                    continue;
+                }
+                let mut group = group_of(grid, (x, y), wrap);
+                if can_fall(grid, &group) {
+                    while can_fall(grid, &group) {
+                        lower(grid, &mut group);
+                    }
+                    fell = true;
+                    falling = true;
+                    break 'groups;
+                }
+                group.copy_into(&mut seen, (0, 0));
+            }
+        }
+    }
+    fell
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cascade::settle;
+    use crate::grid::Grid;
+
+    fn grid(rows: &[&str]) -> Grid {
+        let mut grid = Grid::new((5, rows.len()));
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                grid[(x, y)] = cell == '#';
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn floating_groups_fall_until_they_land() {
+        let mut stack = grid(&[".##..", ".#...", ".....", "#...#", "#...#"]);
+        assert!(settle(&mut stack, false));
+        assert!(stack == grid(&[".....", ".....", ".....", "###.#", "##..#"]));
+    }
+
+    #[test]
+    fn a_group_resting_on_another_stays_put() {
+        let mut stack = grid(&["..#..", "..#..", "#####"]);
+        assert!(!settle(&mut stack, false));
+        assert!(stack == grid(&["..#..", "..#..", "#####"]));
+    }
+
+    #[test]
+    fn groups_join_across_the_sides_when_wrapping() {
+        let mut stack = grid(&["#...#", "....#", "....#"]);
+        assert!(!settle(&mut stack, true));
+        assert!(settle(&mut stack, false));
+        assert!(stack == grid(&["....#", "....#", "#...#"]));
+    }
+}
@@ -0,0 +1,84 @@
+//! The daily challenge: the piece sequence is decided by the date, so everyone playing on the
+//! same day is dealt the same pieces. The date comes from the frontend, which has the clock.
+
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Days from 0000-03-01 to 1970-01-01 in the proleptic Gregorian calendar.
+const EPOCH_FROM_MARCH_0000: u32 = 719_468;
+const DAYS_PER_ERA: u32 = 146_097;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Date {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// The date days after 1970-01-01, for frontends whose clock counts from the Unix epoch.
+    pub fn from_days_since_epoch(days: u32) -> Self {
+        // Counted in 400 year eras starting in March, so the leap day falls at the end of a year
+        let days = days + EPOCH_FROM_MARCH_0000;
+        let era = days / DAYS_PER_ERA;
+        let day_of_era = days % DAYS_PER_ERA;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        };
+        let year = year_of_era + era * 400 + u32::from(month <= 2);
+        Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+
+    /// The seed of the day's game, the same on every platform.
+    pub fn seed(&self) -> u64 {
+        // A splitmix64 step, so that neighbouring days get unrelated seeds
+        let mut seed =
+            u64::from(self.year) << 16 | u64::from(self.month) << 8 | u64::from(self.day);
+        seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        seed ^ (seed >> 31)
+    }
+}
+
+/// Formats as YYYY-MM-DD, as sent with leaderboard submissions.
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::daily::Date;
+
+    fn date(year: u16, month: u8, day: u8) -> Date {
+        Date { year, month, day }
+    }
+
+    #[test]
+    fn days_since_the_epoch_become_dates() {
+        assert!(Date::from_days_since_epoch(0) == date(1970, 1, 1));
+        assert!(Date::from_days_since_epoch(11_016) == date(2000, 2, 29));
+        assert!(Date::from_days_since_epoch(20_742) == date(2026, 10, 16));
+    }
+
+    #[test]
+    fn each_day_has_its_own_seed() {
+        assert!(date(2026, 10, 16).seed() == date(2026, 10, 16).seed());
+        assert!(date(2026, 10, 16).seed() != date(2026, 10, 17).seed());
+    }
+}
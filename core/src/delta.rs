@@ -0,0 +1,617 @@
+//! Spectating by acknowledged deltas. Where spectate sends the drawn board with regular
+//! keyframes, here the sender keeps every state it has sent and describes each new state only by
+//! how it differs from the last state the spectator acknowledged: the stack cells that changed,
+//! the falling piece and the score. A lost message or acknowledgement only makes the next delta
+//! a little larger, which suits a slow or lossy link from the Pico.
+//!
+//! The spectator applies each delta, acknowledges its sequence number and keeps the last
+//! HISTORY states it has built. If a delta refers to a state it no longer has the spectator
+//! should ask for a resync, and the sender starts again from an empty board with
+//! DeltaEncoder::reset.
+
+use crate::piece::{Piece, PieceSelector, Rotation};
+use crate::spectate::{Board, MAX_HEIGHT, MAX_WIDTH};
+use crate::tetris::{Tetris, TetrisState, BUFFER_ROWS};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use enum_iterator::all;
+use enum_map::Enum;
+
+/// Unacknowledged states kept by the sender, and applied states kept by the spectator.
+pub const HISTORY: usize = 32;
+
+const FLAG_BASE: u8 = 1 << 0;
+const FLAG_PIECE: u8 = 1 << 1;
+const FLAG_NEXT: u8 = 1 << 2;
+const FLAG_FINISHED: u8 = 1 << 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DeltaError {
+    /// The message ended early.
+    Truncated,
+    /// A field held a value no sender would write.
+    Invalid,
+    /// The delta is relative to a state the spectator no longer has, ask for a resync.
+    MissingBase(u16),
+    /// The delta is relative to a state of a different size.
+    SizeMismatch,
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "the delta ended early"),
+            Self::Invalid => write!(f, "the delta holds an invalid value"),
+            Self::MissingBase(sequence) => write!(f, "state {} is no longer known", sequence),
+            Self::SizeMismatch => write!(f, "the delta does not match the size of its base"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DeltaError {}
+
+/// Where the falling piece is, enough to draw it again on the other side.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PieceView {
+    pub kind: PieceSelector,
+    pub rotation: Rotation,
+    pub x: u8,
+    pub y: u8,
+}
+
+impl PieceView {
+    fn of(piece: &Piece) -> Self {
+        PieceView {
+            kind: piece.kind(),
+            rotation: piece.rotation(),
+            x: piece.x as u8,
+            y: piece.y as u8,
+        }
+    }
+
+    fn to_piece(self) -> Piece {
+        let mut piece = self.kind.to_piece((self.x as usize, self.y as usize));
+        while piece.rotation() != self.rotation {
+            piece.next_rotation();
+        }
+        piece
+    }
+}
+
+/// A game as a spectator sees it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Snapshot {
+    pub width: u8,
+    pub height: u8,
+    /// The placed cells, one bitmask per row with bit x set for a filled cell in column x and
+    /// row 0 at the bottom.
+    pub stack: [u16; MAX_HEIGHT],
+    /// The falling piece, None between pieces and once the game is over.
+    pub piece: Option<PieceView>,
+    pub next: Option<PieceSelector>,
+    pub score: u32,
+    pub finished: bool,
+}
+
+impl Snapshot {
+    fn empty(width: u8, height: u8) -> Self {
+        Snapshot {
+            width,
+            height,
+            stack: [0; MAX_HEIGHT],
+            piece: None,
+            next: None,
+            score: 0,
+            finished: false,
+        }
+    }
+
+    pub fn from_state(state: &TetrisState) -> Self {
+        let (width, height) = (state.grid.width, state.grid.height);
+        assert!(width <= MAX_WIDTH && height <= MAX_HEIGHT);
+
+        let mut stack = [0; MAX_HEIGHT];
+        for (y, mask) in stack.iter_mut().enumerate().take(height) {
+            for (x, &filled) in state.grid.row(y).iter().enumerate() {
+                if filled {
+                    *mask |= 1 << x;
+                }
+            }
+        }
+
+        Snapshot {
+            width: width as u8,
+            height: height as u8,
+            stack,
+            piece: state.piece_in_play().map(PieceView::of),
+            next: Some(state.next_piece.kind()),
+            score: state.score as u32,
+            finished: false,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The board with the falling piece drawn into the stack, as spectate shows it, without the
+    /// hidden buffer rows.
+    pub fn to_board(&self) -> Board {
+        let width = self.width as usize;
+        let height = (self.height as usize).saturating_sub(BUFFER_ROWS);
+        let mut rows = self.stack;
+        rows[height..].fill(0);
+        if let Some(view) = self.piece {
+            let piece = view.to_piece();
+            let grid = piece.current_rotation();
+            for y in 0..grid.height {
+                for (x, &filled) in grid.row(y).iter().enumerate() {
+                    let (x, y) = (piece.x + x, piece.y + y);
+                    if filled && x < width && y < height {
+                        rows[y] |= 1 << x;
+                    }
+                }
+            }
+        }
+
+        Board {
+            width,
+            height,
+            rows,
+            score: self.score,
+        }
+    }
+
+    /// The cells that differ between the two stacks, as (x, y).
+    fn changed_cells(&self, other: &Snapshot) -> Vec<(u8, u8)> {
+        let mut changed = Vec::new();
+        for y in 0..self.height as usize {
+            let mut diff = self.stack[y] ^ other.stack[y];
+            while diff != 0 {
+                let x = diff.trailing_zeros();
+                changed.push((x as u8, y as u8));
+                diff &= diff - 1;
+            }
+        }
+        changed
+    }
+}
+
+/// Whether sequence number a comes after b, allowing for wrap around.
+fn is_newer(a: u16, b: u16) -> bool {
+    (a.wrapping_sub(b) as i16) > 0
+}
+
+/// One state of the game, relative to an earlier state the spectator acknowledged.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Delta {
+    pub sequence: u16,
+    /// The state this is relative to, or None for an empty board.
+    pub base: Option<u16>,
+    pub width: u8,
+    pub height: u8,
+    /// The stack cells that flipped since the base, as (x, y).
+    pub changed: Vec<(u8, u8)>,
+    pub piece: Option<PieceView>,
+    pub next: Option<PieceSelector>,
+    pub score: u32,
+    pub finished: bool,
+}
+
+impl Delta {
+    /// Append the delta to out. The layout is a flags byte, the sequence and base numbers, the
+    /// size, the score, the piece and next piece if present, then a count of changed cells
+    /// followed by a byte of x and a byte of y for each.
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        let mut flags = 0;
+        if self.base.is_some() {
+            flags |= FLAG_BASE;
+        }
+        if self.piece.is_some() {
+            flags |= FLAG_PIECE;
+        }
+        if self.next.is_some() {
+            flags |= FLAG_NEXT;
+        }
+        if self.finished {
+            flags |= FLAG_FINISHED;
+        }
+
+        out.push(flags);
+        out.extend_from_slice(&self.sequence.to_be_bytes());
+        if let Some(base) = self.base {
+            out.extend_from_slice(&base.to_be_bytes());
+        }
+        out.push(self.width);
+        out.push(self.height);
+        out.extend_from_slice(&self.score.to_be_bytes());
+        if let Some(piece) = self.piece {
+            out.push(piece_index(piece.kind));
+            out.push(piece.rotation.into_usize() as u8);
+            out.push(piece.x);
+            out.push(piece.y);
+        }
+        if let Some(next) = self.next {
+            out.push(piece_index(next));
+        }
+        out.extend_from_slice(&(self.changed.len() as u16).to_be_bytes());
+        for &(x, y) in &self.changed {
+            out.push(x);
+            out.push(y);
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, DeltaError> {
+        let mut reader = Reader { data };
+        let flags = reader.u8()?;
+        if flags & !(FLAG_BASE | FLAG_PIECE | FLAG_NEXT | FLAG_FINISHED) != 0 {
+            return Err(DeltaError::Invalid);
+        }
+
+        let sequence = reader.u16()?;
+        let base = match flags & FLAG_BASE {
+            0 => None,
+            _ => Some(reader.u16()?),
+        };
+        let (width, height) = (reader.u8()?, reader.u8()?);
+        if width as usize > MAX_WIDTH || height as usize > MAX_HEIGHT {
+            return Err(DeltaError::Invalid);
+        }
+        let score = u32::from_be_bytes([reader.u8()?, reader.u8()?, reader.u8()?, reader.u8()?]);
+
+        let piece = match flags & FLAG_PIECE {
+            0 => None,
+            _ => {
+                let kind = piece_from_index(reader.u8()?)?;
+                let rotation = reader.u8()? as usize;
+                if rotation >= Rotation::LENGTH {
+                    return Err(DeltaError::Invalid);
+                }
+                Some(PieceView {
+                    kind,
+                    rotation: Rotation::from_usize(rotation),
+                    x: reader.u8()?,
+                    y: reader.u8()?,
+                })
+            }
+        };
+        let next = match flags & FLAG_NEXT {
+            0 => None,
+            _ => Some(piece_from_index(reader.u8()?)?),
+        };
+
+        let count = reader.u16()? as usize;
+        let mut changed = Vec::with_capacity(count.min(MAX_WIDTH * MAX_HEIGHT));
+        for _ in 0..count {
+            let (x, y) = (reader.u8()?, reader.u8()?);
+            if x >= width || y >= height {
+                return Err(DeltaError::Invalid);
+            }
+            changed.push((x, y));
+        }
+
+        if !reader.data.is_empty() {
+            return Err(DeltaError::Invalid);
+        }
+
+        Ok(Delta {
+            sequence,
+            base,
+            width,
+            height,
+            changed,
+            piece,
+            next,
+            score,
+            finished: flags & FLAG_FINISHED != 0,
+        })
+    }
+}
+
+fn piece_index(kind: PieceSelector) -> u8 {
+    all::<PieceSelector>()
+        .position(|other| other == kind)
+        .unwrap() as u8
+}
+
+fn piece_from_index(index: u8) -> Result<PieceSelector, DeltaError> {
+    all::<PieceSelector>()
+        .nth(index as usize)
+        .ok_or(DeltaError::Invalid)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Result<u8, DeltaError> {
+        let (&byte, rest) = self.data.split_first().ok_or(DeltaError::Truncated)?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, DeltaError> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+}
+
+/// The sending side, turning each state of the game into a delta against the latest
+/// acknowledged one.
+#[derive(Default)]
+pub struct DeltaEncoder {
+    next_sequence: u16,
+    acknowledged: Option<(u16, Snapshot)>,
+    /// States sent since the acknowledged one, oldest first.
+    sent: VecDeque<(u16, Snapshot)>,
+}
+
+impl DeltaEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget every acknowledgement so the next delta is against an empty board, used when a
+    /// spectator connects or asks for a resync.
+    pub fn reset(&mut self) {
+        self.acknowledged = None;
+        self.sent.clear();
+    }
+
+    /// Record that the spectator has applied the delta with the given sequence number. Later
+    /// deltas are sent against it, unless a newer state has already been acknowledged.
+    pub fn acknowledge(&mut self, sequence: u16) {
+        if let Some(position) = self.sent.iter().position(|(sent, _)| *sent == sequence) {
+            let newer = self
+                .acknowledged
+                .as_ref()
+                .is_none_or(|(acknowledged, _)| is_newer(sequence, *acknowledged));
+            if newer {
+                self.acknowledged = self.sent.drain(..=position).next_back();
+            }
+        }
+    }
+
+    /// The delta for the current state of the game.
+    pub fn encode(&mut self, tetris: &Tetris) -> Delta {
+        let latest = self
+            .sent
+            .back()
+            .or(self.acknowledged.as_ref())
+            .map(|(_, snapshot)| snapshot);
+        let snapshot = match tetris {
+            Tetris::Running(state) | Tetris::Paused(state) => Snapshot::from_state(state),
+            // The board stays as it was when the game ended
+            Tetris::Finished(_) => Snapshot {
+                piece: None,
+                next: None,
+                finished: true,
+                ..latest.cloned().unwrap_or(Snapshot::empty(0, 0))
+            },
+        };
+
+        let base = self
+            .acknowledged
+            .as_ref()
+            .filter(|(_, base)| base.width == snapshot.width && base.height == snapshot.height);
+        let changed = match base {
+            Some((_, base)) => snapshot.changed_cells(base),
+            None => snapshot.changed_cells(&Snapshot::empty(snapshot.width, snapshot.height)),
+        };
+
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        let delta = Delta {
+            sequence,
+            base: base.map(|(sequence, _)| *sequence),
+            width: snapshot.width,
+            height: snapshot.height,
+            changed,
+            piece: snapshot.piece,
+            next: snapshot.next,
+            score: snapshot.score,
+            finished: snapshot.finished,
+        };
+
+        // A spectator that has stopped acknowledging is sent ever larger deltas against its
+        // last acknowledged state rather than holding on to every state since.
+        if self.sent.len() == HISTORY {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((sequence, snapshot));
+        delta
+    }
+}
+
+/// The spectating side, rebuilding each state from the deltas it receives.
+#[derive(Default)]
+pub struct DeltaDecoder {
+    /// Applied states, oldest first.
+    applied: VecDeque<(u16, Snapshot)>,
+}
+
+impl DeltaDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The newest state applied so far.
+    pub fn view(&self) -> Option<&Snapshot> {
+        self.applied.back().map(|(_, snapshot)| snapshot)
+    }
+
+    /// Apply a delta, returning whether it was newer than the current view. Its sequence number
+    /// should then be acknowledged to the sender, even if it was older.
+    pub fn apply(&mut self, delta: &Delta) -> Result<bool, DeltaError> {
+        let mut snapshot = match delta.base {
+            None => Snapshot::empty(delta.width, delta.height),
+            Some(base) => self
+                .applied
+                .iter()
+                .find(|(sequence, _)| *sequence == base)
+                .map(|(_, snapshot)| snapshot.clone())
+                .ok_or(DeltaError::MissingBase(base))?,
+        };
+        if snapshot.width != delta.width || snapshot.height != delta.height {
+            return Err(DeltaError::SizeMismatch);
+        }
+
+        for &(x, y) in &delta.changed {
+            if x >= delta.width || y >= delta.height {
+                return Err(DeltaError::Invalid);
+            }
+            snapshot.stack[y as usize] ^= 1 << x;
+        }
+        snapshot.piece = delta.piece;
+        snapshot.next = delta.next;
+        snapshot.score = delta.score;
+        snapshot.finished = delta.finished;
+
+        let newest = self
+            .applied
+            .back()
+            .is_none_or(|(latest, _)| is_newer(delta.sequence, *latest));
+        if self.applied.len() == HISTORY {
+            self.applied.pop_front();
+        }
+        // Older deltas are kept as possible bases but placed before the current view
+        let position = self
+            .applied
+            .iter()
+            .rposition(|(sequence, _)| is_newer(delta.sequence, *sequence))
+            .map_or(0, |position| position + 1);
+        self.applied.insert(position, (delta.sequence, snapshot));
+        Ok(newest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::delta::{Delta, DeltaDecoder, DeltaEncoder, DeltaError, Snapshot};
+    use crate::spectate::Board;
+    use crate::tetris::{KeyState, Tetris};
+    use alloc::vec::Vec;
+
+    fn snapshot_of(tetris: &Tetris) -> Snapshot {
+        match tetris {
+            Tetris::Running(state) => Snapshot::from_state(state),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    fn board_of(tetris: &Tetris) -> Board {
+        match tetris {
+            Tetris::Running(state) => Board::from_state(state),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    /// Send the delta over the wire and apply it, returning its encoded length.
+    fn send(encoder: &mut DeltaEncoder, decoder: &mut DeltaDecoder, tetris: &Tetris) -> usize {
+        let delta = encoder.encode(tetris);
+        let mut out = Vec::new();
+        delta.encode(&mut out);
+        let received = Delta::decode(&out).unwrap();
+        assert!(received == delta);
+        decoder.apply(&received).unwrap();
+        out.len()
+    }
+
+    #[test]
+    fn spectator_follows_the_game() {
+        let mut tetris = Tetris::new();
+        let (mut encoder, mut decoder) = (DeltaEncoder::new(), DeltaDecoder::new());
+
+        for frame in 0..300 {
+            tetris.set_key_state(&KeyState {
+                left: frame % 3 == 0,
+                rotate: frame % 5 == 0,
+                ..KeyState::default()
+            });
+            tetris.update();
+            let sequence = encoder.next_sequence;
+            send(&mut encoder, &mut decoder, &tetris);
+            // Every other acknowledgement is lost
+            if frame % 2 == 0 {
+                encoder.acknowledge(sequence);
+            }
+
+            if tetris.is_finished() {
+                assert!(decoder.view().unwrap().is_finished());
+                return;
+            }
+            assert!(*decoder.view().unwrap() == snapshot_of(&tetris));
+            assert!(decoder.view().unwrap().to_board() == board_of(&tetris));
+        }
+    }
+
+    #[test]
+    fn acknowledged_deltas_only_hold_changes() {
+        let mut tetris = Tetris::new();
+        let (mut encoder, mut decoder) = (DeltaEncoder::new(), DeltaDecoder::new());
+
+        // Place a piece so the stack is not empty
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        tetris.set_key_state(&KeyState::default());
+
+        let full = encoder.encode(&tetris);
+        assert!(full.base.is_none() && !full.changed.is_empty());
+        decoder.apply(&full).unwrap();
+        encoder.acknowledge(full.sequence);
+
+        tetris.update();
+        let delta = encoder.encode(&tetris);
+        assert!(delta.base == Some(full.sequence));
+        assert!(delta.changed.is_empty());
+        decoder.apply(&delta).unwrap();
+        assert!(*decoder.view().unwrap() == snapshot_of(&tetris));
+    }
+
+    #[test]
+    fn late_deltas_do_not_replace_the_view() {
+        let mut tetris = Tetris::new();
+        let (mut encoder, mut decoder) = (DeltaEncoder::new(), DeltaDecoder::new());
+
+        let first = encoder.encode(&tetris);
+        tetris.update();
+        let second = encoder.encode(&tetris);
+
+        assert!(decoder.apply(&second).unwrap());
+        assert!(!decoder.apply(&first).unwrap());
+        assert!(*decoder.view().unwrap() == snapshot_of(&tetris));
+    }
+
+    #[test]
+    fn forgotten_bases_ask_for_a_resync() {
+        let tetris = Tetris::new();
+        let mut encoder = DeltaEncoder::new();
+        let first = encoder.encode(&tetris);
+        encoder.acknowledge(first.sequence);
+        let second = encoder.encode(&tetris);
+
+        let mut decoder = DeltaDecoder::new();
+        assert!(decoder.apply(&second) == Err(DeltaError::MissingBase(first.sequence)));
+
+        encoder.reset();
+        assert!(decoder.apply(&encoder.encode(&tetris)).is_ok());
+    }
+
+    #[test]
+    fn malformed_deltas_are_rejected() {
+        let mut out = Vec::new();
+        DeltaEncoder::new().encode(&Tetris::new()).encode(&mut out);
+
+        for len in 0..out.len() {
+            assert!(Delta::decode(&out[..len]) == Err(DeltaError::Truncated));
+        }
+        out.push(0);
+        assert!(Delta::decode(&out) == Err(DeltaError::Invalid));
+        assert!(Delta::decode(&[0xFF, 0, 0]) == Err(DeltaError::Invalid));
+    }
+}
@@ -0,0 +1,151 @@
+//! Adaptive difficulty for casual players. The height of the stack, how often rows are cleared
+//! and misdrops, pieces that leave new holes, are watched as the game is played, and every few
+//! pieces gravity is eased up or down a little within bounds so that the game stays just hard
+//! enough.
+
+use crate::analysis::{column_height, holes};
+use crate::tetris::{Gravity, Rules, Tetris};
+
+/// Pieces locked between adjustments.
+const PIECES_PER_ADJUSTMENT: usize = 10;
+/// Misdrops over an adjustment that show the player is struggling, and the most that still
+/// lets gravity go up.
+const STRUGGLING_MISDROPS: usize = 4;
+const CRUISING_MISDROPS: usize = 1;
+/// Rows cleared over an adjustment that show the player is keeping up, about all the rows the
+/// pieces could fill on a ten wide playfield.
+const CRUISING_ROWS: usize = 3;
+/// Gravity changes by an eighth of itself each adjustment, and at least this much.
+const MIN_STEP: u16 = 16;
+
+/// Watches a game and adjusts its gravity. Call observe after every update.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Adaptive {
+    min: Gravity,
+    max: Gravity,
+    /// Pieces locked, rows cleared and misdrops since the last adjustment.
+    pieces: usize,
+    rows: usize,
+    misdrops: usize,
+    /// Holes in the stack after the last piece locked.
+    holes: usize,
+}
+
+impl Adaptive {
+    /// Keep gravity between min and max, which should include the gravity the game starts with.
+    pub fn new(min: Gravity, max: Gravity) -> Self {
+        Adaptive {
+            min,
+            max,
+            pieces: 0,
+            rows: 0,
+            misdrops: 0,
+            holes: 0,
+        }
+    }
+
+    /// Count the last update of the game, adjusting its gravity once enough pieces have locked.
+    pub fn observe(&mut self, tetris: &mut Tetris) {
+        let Tetris::Running(ref state) = tetris else {
+            return;
+        };
+        if !state.piece_locked() {
+            return;
+        }
+        let holes_now = holes(&state.grid);
+        if holes_now > self.holes {
+            self.misdrops += 1;
+        }
+        self.holes = holes_now;
+        self.pieces += 1;
+        self.rows += state.rows_cleared();
+        if self.pieces < PIECES_PER_ADJUSTMENT {
+            return;
+        }
+
+        let grid = &state.grid;
+        let height = (0..grid.width)
+            .map(|x| column_height(grid, x))
+            .max()
+            .unwrap_or(0);
+        let gravity = state.rules.gravity;
+        let step = (gravity.0 / 8).max(MIN_STEP);
+        let gravity = if height > grid.height / 2 || self.misdrops >= STRUGGLING_MISDROPS {
+            Gravity(gravity.0.saturating_sub(step))
+        } else if height <= grid.height / 4
+            && self.misdrops <= CRUISING_MISDROPS
+            && self.rows >= CRUISING_ROWS
+        {
+            Gravity(gravity.0.saturating_add(step))
+        } else {
+            gravity
+        };
+        let rules = Rules {
+            gravity: gravity.clamp(self.min, self.max),
+            ..state.rules
+        };
+        tetris.set_rules(rules);
+
+        self.pieces = 0;
+        self.rows = 0;
+        self.misdrops = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::difficulty::{Adaptive, PIECES_PER_ADJUSTMENT};
+    use crate::piece::PieceSelector;
+    use crate::tetris::{Gravity, KeyState, Tetris, TetrisState, VISIBLE_HEIGHT};
+
+    fn gravity(tetris: &Tetris) -> Gravity {
+        match tetris {
+            Tetris::Running(state) => state.rules.gravity,
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    /// Lock pieces, each first set up by place.
+    fn lock_pieces(
+        tetris: &mut Tetris,
+        adaptive: &mut Adaptive,
+        mut place: impl FnMut(&mut TetrisState, usize),
+    ) {
+        for piece in 0..PIECES_PER_ADJUSTMENT {
+            if let Tetris::Running(ref mut state) = tetris {
+                place(state, piece);
+            }
+            tetris.set_key_state(&KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            });
+            tetris.update();
+            adaptive.observe(tetris);
+        }
+    }
+
+    #[test]
+    fn gravity_eases_off_for_a_struggling_player() {
+        let mut tetris = Tetris::new_with_seed(4);
+        let mut adaptive = Adaptive::new(Gravity::from_ratio(1, 4), Gravity::SOFT_DROP);
+        // Every piece dropped into the middle, piling up with holes under it
+        lock_pieces(&mut tetris, &mut adaptive, |_, _| {});
+        assert!(gravity(&tetris) < Gravity::ONE);
+        assert!(gravity(&tetris) >= Gravity::from_ratio(1, 4));
+    }
+
+    #[test]
+    fn gravity_picks_up_for_a_player_keeping_up() {
+        let mut tetris = Tetris::new_with_seed(4);
+        let mut adaptive = Adaptive::new(Gravity::from_ratio(1, 4), Gravity::SOFT_DROP);
+        // Every O finishes a pair of rows left ready for it
+        lock_pieces(&mut tetris, &mut adaptive, |state, _| {
+            state.grid.row_mut(0).fill(true);
+            state.grid.row_mut(1).fill(true);
+            state.grid.row_mut(0)[..2].fill(false);
+            state.grid.row_mut(1)[..2].fill(false);
+            state.piece = PieceSelector::O.to_piece((0, VISIBLE_HEIGHT));
+        });
+        assert!(gravity(&tetris) > Gravity::ONE);
+    }
+}
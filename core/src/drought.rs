@@ -0,0 +1,105 @@
+//! How long each kind of piece has gone without being dealt, for stats and for the optional
+//! drought protection of Rules::max_drought.
+
+use crate::piece::PieceSelector;
+use core::cmp::Reverse;
+use enum_iterator::{all, cardinality};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const KINDS: usize = cardinality::<PieceSelector>();
+
+/// Pieces dealt since each kind of piece last came up, and the longest wait seen for each.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Droughts {
+    current: [u32; KINDS],
+    longest: [u32; KINDS],
+}
+
+impl Droughts {
+    /// Pieces dealt since kind was last dealt, or since the game started.
+    pub fn current(&self, kind: PieceSelector) -> u32 {
+        self.current[kind as usize]
+    }
+
+    /// The most pieces dealt in a row without kind this game.
+    pub fn longest(&self, kind: PieceSelector) -> u32 {
+        self.longest[kind as usize]
+    }
+
+    /// The kind that has waited the longest and how long, the first in piece order on a tie.
+    pub fn longest_waiting(&self) -> (PieceSelector, u32) {
+        all::<PieceSelector>()
+            .map(|kind| (kind, self.current(kind)))
+            .min_by_key(|&(_, drought)| Reverse(drought))
+            .unwrap_or((PieceSelector::Line, 0))
+    }
+
+    /// Whether the longest waiting kind has to be dealt next to keep every kind from going more
+    /// than max_drought pieces without being dealt. Kinds are owed pieces in order of how long
+    /// they have waited, so the nth longest waiting has to be dealt within max_drought less its
+    /// drought pieces, and at the latest as the nth piece.
+    pub(crate) fn is_due(&self, max_drought: u32) -> bool {
+        let mut waiting = self.current;
+        waiting.sort_unstable_by_key(|&drought| Reverse(drought));
+        waiting
+            .iter()
+            .enumerate()
+            .any(|(owed, &drought)| drought + owed as u32 >= max_drought)
+    }
+
+    pub(crate) fn deal(&mut self, kind: PieceSelector) {
+        for (index, (current, longest)) in
+            self.current.iter_mut().zip(&mut self.longest).enumerate()
+        {
+            if index == kind as usize {
+                *current = 0;
+            } else {
+                *current += 1;
+                *longest = (*longest).max(*current);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::drought::Droughts;
+    use crate::piece::PieceSelector;
+
+    #[test]
+    fn droughts_count_the_pieces_since_each_kind() {
+        let mut droughts = Droughts::default();
+        for kind in [PieceSelector::Line, PieceSelector::O, PieceSelector::O] {
+            droughts.deal(kind);
+        }
+        assert!(droughts.current(PieceSelector::Line) == 2);
+        assert!(droughts.current(PieceSelector::O) == 0);
+        assert!(droughts.longest(PieceSelector::O) == 1);
+
+        droughts.deal(PieceSelector::Line);
+        assert!(droughts.current(PieceSelector::Line) == 0);
+        assert!(droughts.longest(PieceSelector::Line) == 2);
+    }
+
+    #[test]
+    fn the_longest_waiting_kind_is_found() {
+        let mut droughts = Droughts::default();
+        droughts.deal(PieceSelector::Line);
+        droughts.deal(PieceSelector::J);
+        assert!(droughts.longest_waiting() == (PieceSelector::L, 2));
+    }
+
+    #[test]
+    fn kinds_waiting_alike_are_due_before_reaching_the_cap() {
+        let mut droughts = Droughts::default();
+        for kind in [PieceSelector::Line, PieceSelector::J, PieceSelector::L] {
+            droughts.deal(kind);
+        }
+        // Four kinds have waited three pieces, so with a cap of six they need the next four
+        // pieces between them although none has reached it
+        assert!(!droughts.is_due(7));
+        assert!(droughts.is_due(6));
+    }
+}
@@ -0,0 +1,87 @@
+//! What happened in an update, for frontends to play sounds, flash the playfield or pop up
+//! scores without comparing the game before and after. The events are kept in a small fixed
+//! buffer so that they need no allocator.
+
+use crate::piece::PieceSelector;
+use crate::tetris::{Placement, TopOut};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Events kept for one update, more than it can make. Any past this are dropped.
+pub const MAX_EVENTS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameEvent {
+    /// A piece of this kind came into play from next.
+    PieceSpawned(PieceSelector),
+    /// A piece of this kind was put into hold.
+    PieceHeld(PieceSelector),
+    PieceLocked(Placement),
+    /// Complete rows were removed, with bit y of rows set for each row y of the stack as it was
+    /// before they went. Each step of a cascade clears separately.
+    LinesCleared {
+        lines: usize,
+        rows: u32,
+    },
+    /// The level went up to this one.
+    LevelUp(usize),
+    /// The goal of the game mode was reached, ending the game. Like GameOver, only given to the
+    /// listener of Tetris::update_with.
+    GoalReached,
+    /// The stack topped out. Only given to the listener of Tetris::update_with, as the game it
+    /// ended has no state left to keep it in.
+    GameOver(TopOut),
+}
+
+/// The events of the most recent update, in the order they happened.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Events {
+    events: [GameEvent; MAX_EVENTS],
+    len: usize,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Events {
+            events: [GameEvent::GoalReached; MAX_EVENTS],
+            len: 0,
+        }
+    }
+}
+
+impl Events {
+    pub(crate) fn push(&mut self, event: GameEvent) {
+        if let Some(slot) = self.events.get_mut(self.len) {
+            *slot = event;
+            self.len += 1;
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.events[..self.len].iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::{Events, GameEvent, MAX_EVENTS};
+
+    #[test]
+    fn events_past_the_limit_are_dropped() {
+        let mut events = Events::default();
+        for level in 0..MAX_EVENTS + 2 {
+            events.push(GameEvent::LevelUp(level));
+        }
+        assert!(events.iter().count() == MAX_EVENTS);
+        assert!(events.iter().last() == Some(GameEvent::LevelUp(MAX_EVENTS - 1)));
+
+        events.clear();
+        assert!(events.iter().next().is_none());
+    }
+}
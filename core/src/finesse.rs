@@ -0,0 +1,176 @@
+//! How efficiently pieces are put where they go, for training. Each press of a move or rotate key
+//! is an input, and each piece placed is compared with the fewest inputs that reach the same
+//! place on an open playfield, holding a move key to slide the piece to the wall counting as one.
+//! Drops and hold are not counted.
+
+use crate::piece::{Rotation, Turn};
+use crate::tetris::{spawn_piece, Placement};
+use core::iter;
+use enum_map::Enum;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The widest playfield searched for the fewest inputs.
+const MAX_WIDTH: usize = 64;
+
+const ROTATIONS: [Rotation; Rotation::LENGTH] =
+    [Rotation::R0, Rotation::R90, Rotation::R180, Rotation::R270];
+
+/// Inputs made and wasted over a game.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Finesse {
+    /// Pieces placed where the fewest inputs could be found.
+    pub pieces: u32,
+    pub inputs: u32,
+    /// Inputs made beyond the fewest needed.
+    pub wasted: u32,
+    /// Pieces placed with more inputs than needed.
+    pub faults: u32,
+    /// The inputs made for the last piece placed and the fewest it needed.
+    pub last: Option<(u32, u32)>,
+}
+
+impl Finesse {
+    /// The share of pieces placed without a fault, as a percentage. 100 before any are placed.
+    pub fn score(&self) -> u32 {
+        match self.pieces {
+            0 => 100,
+            pieces => (pieces - self.faults) * 100 / pieces,
+        }
+    }
+
+    pub(crate) fn record(&mut self, inputs: u32, fewest: u32) {
+        self.pieces = self.pieces.saturating_add(1);
+        self.inputs = self.inputs.saturating_add(inputs);
+        self.wasted = self.wasted.saturating_add(inputs.saturating_sub(fewest));
+        self.faults += (inputs > fewest) as u32;
+        self.last = Some((inputs, fewest));
+    }
+}
+
+/// The fewest inputs that bring a piece from where it spawns to placement on an open playfield
+/// width wide, turning halfway round in one input if rotate_180 is set. Rotations that leave the
+/// piece with the same cells are the same place. None if the piece can only get there by
+/// wrapping round the playfield, or the playfield is wider than MAX_WIDTH.
+pub fn fewest_inputs(placement: Placement, width: usize, rotate_180: bool) -> Option<u32> {
+    if width > MAX_WIDTH {
+        return None;
+    }
+    let kind = placement.kind;
+    let grids = ROTATIONS.map(|rotation| {
+        Placement {
+            rotation,
+            x: 0,
+            y: 0,
+            ..placement
+        }
+        .piece_grid(false)
+    });
+    let target = &grids[placement.rotation as usize];
+    let last_x = |rotation: Rotation| width - grids[rotation as usize].width;
+
+    // The inputs to reach each position, filled in one input further each time round
+    let mut inputs = [[u8::MAX; Rotation::LENGTH]; MAX_WIDTH];
+    inputs[spawn_piece(kind).x][Rotation::R0 as usize] = 0;
+    for depth in 0..u8::MAX {
+        let mut reached_any = false;
+        for x in 0..width {
+            for rotation in ROTATIONS {
+                if inputs[x][rotation as usize] != depth {
+                    continue;
+                }
+                if x == placement.x && grids[rotation as usize] == *target {
+                    return Some(depth as u32);
+                }
+                reached_any = true;
+
+                let last = last_x(rotation);
+                let slides = [
+                    x.checked_sub(1),
+                    Some(x + 1).filter(|&right| right <= last),
+                    Some(0),
+                    Some(last),
+                ];
+                let turns = [
+                    Some(Turn::Clockwise),
+                    Some(Turn::CounterClockwise),
+                    rotate_180.then_some(Turn::Half),
+                ];
+                let turned = turns.into_iter().flatten().filter_map(|turn| {
+                    let to = rotation.turned(turn);
+                    let (dx, _) = kind.turn_offset(rotation, turn);
+                    iter::once((0, 0))
+                        .chain(kind.kicks(rotation, turn))
+                        .find_map(|(kick, _)| {
+                            x.checked_add_signed(dx + kick).filter(|&x| x <= last_x(to))
+                        })
+                        .map(|x| (x, to))
+                });
+                let moves = slides.into_iter().flatten().map(|x| (x, rotation));
+                for (x, rotation) in moves.chain(turned) {
+                    let reached = &mut inputs[x][rotation as usize];
+                    *reached = (*reached).min(depth + 1);
+                }
+            }
+        }
+        if !reached_any {
+            break;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::finesse::{fewest_inputs, Finesse};
+    use crate::piece::{PieceSelector, Rotation};
+    use crate::tetris::{spawn_piece, Placement, GRID_SIZE};
+
+    fn fewest(kind: PieceSelector, rotation: Rotation, x: usize) -> Option<u32> {
+        let placement = Placement {
+            kind,
+            rotation,
+            x,
+            y: 0,
+        };
+        fewest_inputs(placement, GRID_SIZE.0, false)
+    }
+
+    #[test]
+    fn the_fewest_inputs_slide_to_the_walls() {
+        let spawn = spawn_piece(PieceSelector::T).x;
+        assert!(fewest(PieceSelector::T, Rotation::R0, spawn) == Some(0));
+        assert!(fewest(PieceSelector::T, Rotation::R0, spawn + 1) == Some(1));
+        assert!(fewest(PieceSelector::T, Rotation::R0, 0) == Some(1));
+        // Along the wall then one back is shorter than three taps
+        assert!(fewest(PieceSelector::O, Rotation::R0, 1) == Some(2));
+        assert!(fewest(PieceSelector::T, Rotation::R180, spawn) == Some(2));
+        assert!(fewest(PieceSelector::Line, Rotation::R90, 0) == Some(2));
+        // Pieces with the same cells in two rotations take the nearer
+        let line = spawn_piece(PieceSelector::Line).x;
+        assert!(fewest(PieceSelector::Line, Rotation::R180, line) == Some(0));
+        assert!(fewest(PieceSelector::O, Rotation::R0, GRID_SIZE.0 - 1).is_none());
+
+        let half_turn = Placement {
+            kind: PieceSelector::T,
+            rotation: Rotation::R180,
+            x: spawn,
+            y: 0,
+        };
+        assert!(fewest_inputs(half_turn, GRID_SIZE.0, true) == Some(1));
+    }
+
+    #[test]
+    fn faults_are_pieces_placed_with_inputs_to_spare() {
+        let mut finesse = Finesse::default();
+        assert!(finesse.score() == 100);
+        finesse.record(2, 2);
+        finesse.record(5, 3);
+        finesse.record(1, 2);
+        assert!(finesse.pieces == 3 && finesse.inputs == 8);
+        assert!(finesse.wasted == 2 && finesse.faults == 1);
+        assert!(finesse.score() == 66);
+        assert!(finesse.last == Some((1, 2)));
+    }
+}
@@ -0,0 +1,148 @@
+//! Arcade style grades for a finished game, from 9 up through 1 and S1 to S9 by score, with the
+//! Grand Master grade for reaching the final level fast enough with a high enough score.
+
+use crate::session::GameStats;
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Grade {
+    Nine,
+    Eight,
+    Seven,
+    Six,
+    Five,
+    Four,
+    Three,
+    Two,
+    One,
+    S1,
+    S2,
+    S3,
+    S4,
+    S5,
+    S6,
+    S7,
+    S8,
+    S9,
+    GrandMaster,
+}
+
+/// The least score for each grade from Eight to S9, Nine needs nothing.
+const SCORE_FOR_GRADE: [u32; 17] = [
+    10_000, 20_000, 35_000, 50_000, 90_000, 140_000, 200_000, 300_000, 400_000, 550_000, 750_000,
+    1_000_000, 1_300_000, 1_650_000, 2_050_000, 2_500_000, 3_000_000,
+];
+
+/// The level that ends an arcade game, and the least score and longest time for reaching it as a
+/// Grand Master.
+pub const FINAL_LEVEL: u32 = 999;
+const GRAND_MASTER_SCORE: u32 = 3_150_000;
+const GRAND_MASTER_SECONDS: u32 = 13 * 60 + 30;
+
+const GRADES: [Grade; 19] = [
+    Grade::Nine,
+    Grade::Eight,
+    Grade::Seven,
+    Grade::Six,
+    Grade::Five,
+    Grade::Four,
+    Grade::Three,
+    Grade::Two,
+    Grade::One,
+    Grade::S1,
+    Grade::S2,
+    Grade::S3,
+    Grade::S4,
+    Grade::S5,
+    Grade::S6,
+    Grade::S7,
+    Grade::S8,
+    Grade::S9,
+    Grade::GrandMaster,
+];
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self as usize {
+            grade @ 0..=8 => write!(f, "{}", 9 - grade),
+            grade @ 9..=17 => write!(f, "S{}", grade - 8),
+            _ => write!(f, "GM"),
+        }
+    }
+}
+
+/// Grades games played at a fixed number of updates a second, as the Grand Master time limit is
+/// in seconds.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Grading {
+    pub ticks_per_second: u32,
+}
+
+impl Grading {
+    pub fn new(ticks_per_second: u32) -> Self {
+        Grading { ticks_per_second }
+    }
+
+    pub fn grade(&self, game: &GameStats) -> Grade {
+        if game.level() >= FINAL_LEVEL
+            && game.score >= GRAND_MASTER_SCORE
+            && game.ticks <= GRAND_MASTER_SECONDS.saturating_mul(self.ticks_per_second)
+        {
+            return Grade::GrandMaster;
+        }
+        let reached = SCORE_FOR_GRADE
+            .iter()
+            .take_while(|&&score| game.score >= score)
+            .count();
+        GRADES[reached]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grade::{Grade, Grading, FINAL_LEVEL, GRAND_MASTER_SCORE, SCORE_FOR_GRADE};
+    use crate::session::GameStats;
+
+    fn game(score: u32, pieces: u32, ticks: u32) -> GameStats {
+        GameStats {
+            score,
+            lines: 0,
+            pieces,
+            ticks,
+            ..GameStats::default()
+        }
+    }
+
+    #[test]
+    fn grades_rise_with_the_score() {
+        let grading = Grading::new(60);
+        assert!(grading.grade(&game(0, 10, 100)) == Grade::Nine);
+        assert!(grading.grade(&game(SCORE_FOR_GRADE[0], 10, 100)) == Grade::Eight);
+        assert!(grading.grade(&game(SCORE_FOR_GRADE[8] - 1, 10, 100)) == Grade::One);
+        assert!(grading.grade(&game(u32::MAX, 10, 100)) == Grade::S9);
+    }
+
+    #[test]
+    fn grand_master_needs_the_final_level_in_time() {
+        let grading = Grading::new(60);
+        let limit = (13 * 60 + 30) * 60;
+        assert!(grading.grade(&game(GRAND_MASTER_SCORE, FINAL_LEVEL, limit)) == Grade::GrandMaster);
+        assert!(grading.grade(&game(GRAND_MASTER_SCORE, FINAL_LEVEL, limit + 1)) == Grade::S9);
+        assert!(grading.grade(&game(GRAND_MASTER_SCORE, FINAL_LEVEL - 1, 100)) == Grade::S9);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn grades_are_shown_as_in_the_arcade() {
+        use alloc::string::ToString;
+        assert!(Grade::Nine.to_string() == "9");
+        assert!(Grade::One.to_string() == "1");
+        assert!(Grade::S1.to_string() == "S1");
+        assert!(Grade::S9.to_string() == "S9");
+        assert!(Grade::GrandMaster.to_string() == "GM");
+    }
+}
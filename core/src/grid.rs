@@ -1,19 +1,57 @@
-use alloc::{format, string::String, vec::Vec};
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use core::{
-    assert,
+    assert, fmt,
     ops::{Index, IndexMut},
     result::Result,
 };
-use itertools::iproduct;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq)]
+/// Without an allocator every grid is backed by a fixed array of this many cells, enough for the
+/// 10x22 playfield with its buffer rows.
+#[cfg(not(feature = "alloc"))]
+pub const MAX_CELLS: usize = 256;
+
+#[cfg(feature = "alloc")]
+type Cells = Vec<bool>;
+#[cfg(not(feature = "alloc"))]
+type Cells = [bool; MAX_CELLS];
+
+/// Returned when looking up a cell outside of the grid.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Selected grid cell ({}, {}) exceed grid size (width={}, height={})",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfBounds {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Grid {
     pub width: usize,
     pub height: usize,
-    pub data: Vec<bool>,
+    /// The cells row by row from the bottom. Without an allocator only the first width * height
+    /// cells are used and the rest are always false.
+    pub data: Cells,
 }
 
 impl Grid {
+    #[cfg(feature = "alloc")]
     pub fn of_data((width, height): (usize, usize), data: Vec<bool>) -> Self {
         assert!(width * height == data.len());
         Grid {
@@ -22,28 +60,88 @@ impl Grid {
             data,
         }
     }
+
+    /// A grid holding a copy of cells, which are given row by row from the bottom.
+    pub fn from_cells((width, height): (usize, usize), cells: &[bool]) -> Self {
+        assert!(width * height == cells.len());
+        let mut grid = Self::new((width, height));
+        grid.data[..cells.len()].copy_from_slice(cells);
+        grid
+    }
+
+    #[cfg(feature = "alloc")]
     pub fn new((width, height): (usize, usize)) -> Self {
         let mut data = Vec::new();
         data.resize(width * height, false);
         Self::of_data((width, height), data)
     }
 
-    fn offset(&self, x: usize, y: usize) -> Result<usize, String> {
+    #[cfg(not(feature = "alloc"))]
+    pub fn new((width, height): (usize, usize)) -> Self {
+        assert!(width * height <= MAX_CELLS);
+        Grid {
+            width,
+            height,
+            data: [false; MAX_CELLS],
+        }
+    }
+
+    fn offset(&self, x: usize, y: usize) -> Result<usize, OutOfBounds> {
         if x < self.width && y < self.height {
             Ok((self.width * y) + x)
         } else {
-            Err(format!(
-                "Selected grid cell ({}, {}) exceed grid size (width={}, height={})",
-                x, y, self.width, self.height
-            ))
+            Err(OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            })
         }
     }
 
-    pub fn get(&mut self, x: usize, y: usize) -> Result<bool, String> {
+    pub fn get(&mut self, x: usize, y: usize) -> Result<bool, OutOfBounds> {
         let offset = self.offset(x, y)?;
         Ok(self.data[offset])
     }
 
+    /// True if the cells match the width and height of the grid.
+    #[cfg(feature = "alloc")]
+    pub fn is_consistent(&self) -> bool {
+        self.data.len() == self.width * self.height
+    }
+
+    /// True if the grid fits in the fixed cells and the unused cells are clear.
+    #[cfg(not(feature = "alloc"))]
+    pub fn is_consistent(&self) -> bool {
+        let len = self.width * self.height;
+        len <= MAX_CELLS && self.data[len..].iter().all(|&cell| !cell)
+    }
+
+    /// The cells of row y, from left to right.
+    pub fn row(&self, y: usize) -> &[bool] {
+        &self.data[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [bool] {
+        &mut self.data[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Remove row y, moving every row above it down one and leaving the top row empty.
+    pub fn remove_row(&mut self, y: usize) {
+        let (start, end) = (y * self.width, self.width * self.height);
+        self.data.copy_within(start + self.width..end, start);
+        self.row_mut(self.height - 1).fill(false);
+    }
+
+    /// The width and height of the part of this grid that lies inside other when placed at
+    /// offset, zero if they do not overlap.
+    fn overlap(&self, other: &Self, (offset_x, offset_y): (usize, usize)) -> (usize, usize) {
+        (
+            self.width.min(other.width.saturating_sub(offset_x)),
+            self.height.min(other.height.saturating_sub(offset_y)),
+        )
+    }
+
     /**
      * Returns true of a grid at a given offset collides with another grid given the applied offset
      * to grid positions.
@@ -54,17 +152,19 @@ impl Grid {
      * overlapping sections will be tested for collision.
      */
     pub fn collides(&self, other: &Self, (offset_x, offset_y): (usize, usize)) -> bool {
-        for (x, y) in iproduct!(0..self.width, 0..self.height) {
-            let (target_x, target_y) = (x + offset_x, y + offset_y);
-            if target_x < other.width
-                && target_y < other.height
-                && self[(x, y)]
-                && other[(target_x, target_y)]
-            {
-                return true;
-            }
+        let (width, height) = self.overlap(other, (offset_x, offset_y));
+        if width == 0 {
+            return false;
         }
-        return false;
+
+        // Compare a row at a time, stopping at the first collision
+        (0..height).any(|y| {
+            let target = &other.row(y + offset_y)[offset_x..offset_x + width];
+            self.row(y)[..width]
+                .iter()
+                .zip(target)
+                .any(|(&ours, &theirs)| ours && theirs)
+        })
     }
 
     /**
@@ -76,13 +176,50 @@ impl Grid {
      * will be ignored.
      */
     pub fn copy_into(&self, other: &mut Self, (offset_x, offset_y): (usize, usize)) {
-        for (x, y) in iproduct!(0..self.width, 0..self.height) {
-            let (target_x, target_y) = (x + offset_x, y + offset_y);
-            if target_x < other.width && target_y < other.height && self[(x, y)] {
-                other[(target_x, target_y)] = true;
+        let (width, height) = self.overlap(other, (offset_x, offset_y));
+        if width == 0 {
+            return;
+        }
+
+        for y in 0..height {
+            let target = &mut other.row_mut(y + offset_y)[offset_x..offset_x + width];
+            for (target, &cell) in target.iter_mut().zip(&self.row(y)[..width]) {
+                *target |= cell;
+            }
+        }
+    }
+
+    /// Like collides, but with the columns of other wrapping round, so that the part of this grid
+    /// past the right edge of other is tested against its left edge instead.
+    pub fn collides_wrapping(&self, other: &Self, (offset_x, offset_y): (usize, usize)) -> bool {
+        let height = self.height.min(other.height.saturating_sub(offset_y));
+        (0..height).any(|y| {
+            let target = other.row(y + offset_y);
+            self.row(y)
+                .iter()
+                .enumerate()
+                .any(|(x, &ours)| ours && target[(offset_x + x) % other.width])
+        })
+    }
+
+    /// Like copy_into, but with the columns of other wrapping round as in collides_wrapping.
+    pub fn copy_into_wrapping(&self, other: &mut Self, (offset_x, offset_y): (usize, usize)) {
+        let height = self.height.min(other.height.saturating_sub(offset_y));
+        for y in 0..height {
+            let width = other.width;
+            let target = other.row_mut(y + offset_y);
+            for (x, &cell) in self.row(y).iter().enumerate() {
+                target[(offset_x + x) % width] |= cell;
             }
         }
     }
+
+    /// Flip the grid left to right.
+    pub fn mirror(&mut self) {
+        for y in 0..self.height {
+            self.row_mut(y).reverse();
+        }
+    }
 }
 
 impl Index<(usize, usize)> for Grid {
@@ -104,14 +241,21 @@ impl IndexMut<(usize, usize)> for Grid {
 #[cfg(test)]
 mod tests {
     use crate::grid::Grid;
-    use alloc::vec;
     use core::assert;
 
     #[test]
+    #[cfg(feature = "alloc")]
     fn create_empty_grid() {
         Grid::new((50, 20));
     }
 
+    #[test]
+    #[cfg(not(feature = "alloc"))]
+    #[should_panic]
+    fn fixed_grids_have_a_maximum_size() {
+        Grid::new((50, 20));
+    }
+
     #[test]
     fn fetch() {
         let _ = Grid::new((10, 10)).get(0, 0).unwrap();
@@ -139,12 +283,32 @@ mod tests {
         assert!(grid[(1, 0)] == true);
     }
 
+    #[test]
+    fn mirroring_flips_each_row() {
+        let mut grid = Grid::from_cells((3, 2), &[true, false, false, true, true, false]);
+        grid.mirror();
+        assert!(grid == Grid::from_cells((3, 2), &[false, false, true, false, true, true]));
+    }
+
+    #[test]
+    fn removing_a_row_lowers_the_rows_above() {
+        let mut grid =
+            Grid::from_cells((2, 4), &[true, false, true, true, false, true, true, false]);
+        grid.remove_row(1);
+        assert!(
+            grid == Grid::from_cells(
+                (2, 4),
+                &[true, false, false, true, true, false, false, false]
+            )
+        );
+    }
+
     #[test]
     fn grid_collides() {
-        let all_empty = Grid::of_data((2, 2), vec![false, false, false, false]);
-        let all_full = Grid::of_data((2, 2), vec![true, true, true, true]);
-        let first_set = Grid::of_data((2, 2), vec![true, false, false, false]);
-        let second_set = Grid::of_data((2, 2), vec![false, true, false, false]);
+        let all_empty = Grid::from_cells((2, 2), &[false, false, false, false]);
+        let all_full = Grid::from_cells((2, 2), &[true, true, true, true]);
+        let first_set = Grid::from_cells((2, 2), &[true, false, false, false]);
+        let second_set = Grid::from_cells((2, 2), &[false, true, false, false]);
 
         // Test that all_empty never collides with all_full
         assert!(!all_empty.collides(&all_full, (0, 0)));
@@ -186,9 +350,9 @@ mod tests {
         b.copy_into(&mut a, (1, 0));
 
         assert!(
-            a == Grid::of_data(
+            a == Grid::from_cells(
                 (4, 4),
-                vec![
+                &[
                     false, true, false, false, false, false, true, false, false, false, false,
                     false, false, false, false, false
                 ]
@@ -200,9 +364,9 @@ mod tests {
         b.copy_into(&mut a, (0, 1));
 
         assert!(
-            a == Grid::of_data(
+            a == Grid::from_cells(
                 (4, 4),
-                vec![
+                &[
                     false, false, false, false, true, false, false, false, false, true, false,
                     false, false, false, false, false
                 ]
@@ -214,9 +378,9 @@ mod tests {
         b.copy_into(&mut a, (3, 0));
 
         assert!(
-            a == Grid::of_data(
+            a == Grid::from_cells(
                 (4, 4),
-                vec![
+                &[
                     false, false, false, true, false, false, false, false, false, false, false,
                     false, false, false, false, false
                 ]
@@ -224,6 +388,18 @@ mod tests {
         );
     }
 
+    #[test]
+    fn wrapping_grids_carry_on_from_the_left_edge() {
+        let piece = Grid::from_cells((3, 1), &[true, true, true]);
+        let mut stack = Grid::new((4, 2));
+        stack[(0, 0)] = true;
+        assert!(piece.collides_wrapping(&stack, (2, 0)));
+        assert!(!piece.collides(&stack, (2, 0)));
+
+        piece.copy_into_wrapping(&mut stack, (3, 1));
+        assert!(stack.row(1) == [true, true, false, true]);
+    }
+
     #[test]
     #[should_panic]
     fn get_out_of_bounds_panics() {
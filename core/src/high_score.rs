@@ -0,0 +1,184 @@
+//! A local table of the best scores, each tagged with the initials of the player who made it and
+//! the day it was made, as on an arcade cabinet.
+
+use crate::daily::Date;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Scores kept in a table, lower ones drop off the end.
+pub const HIGH_SCORES: usize = 10;
+
+/// Bytes taken by each entry of the saved table: the score, initials, year, month, day and
+/// uptime.
+const ENTRY_LEN: usize = 15;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HighScore {
+    /// Three upper case letters or digits.
+    pub initials: [u8; 3],
+    pub score: u32,
+    /// The day of the game, if the frontend has a clock.
+    pub date: Option<Date>,
+    /// Seconds since boot when the game was played, for frontends whose clock had not been set
+    /// so there is no date. Only orders the scores made between two resets.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub uptime: Option<u32>,
+}
+
+impl HighScore {
+    /// The initials as text, or ??? if they are not printable.
+    pub fn initials(&self) -> &str {
+        match core::str::from_utf8(&self.initials) {
+            Ok(initials) if self.initials.iter().all(u8::is_ascii_graphic) => initials,
+            _ => "???",
+        }
+    }
+}
+
+/// The best scores, highest first.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HighScores {
+    entries: [Option<HighScore>; HIGH_SCORES],
+}
+
+impl HighScores {
+    /// Bytes taken by the saved form from to_bytes.
+    pub const ENCODED_LEN: usize = HIGH_SCORES * ENTRY_LEN;
+
+    pub fn new() -> Self {
+        HighScores::default()
+    }
+
+    /// Whether score would make the table, so the player should be asked for their initials.
+    /// A score of zero never does.
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && self.entries[HIGH_SCORES - 1].is_none_or(|lowest| score > lowest.score)
+    }
+
+    /// Add a score, returning its place from zero for the top score, or None if it did not make
+    /// the table. Scores tied with one already in the table go below it.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        if !self.qualifies(entry.score) {
+            return None;
+        }
+        let place = self
+            .entries
+            .iter()
+            .position(|existing| existing.is_none_or(|existing| entry.score > existing.score))?;
+        self.entries[place..].rotate_right(1);
+        self.entries[place] = Some(entry);
+        Some(place)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HighScore> {
+        self.entries.iter().flatten()
+    }
+
+    /// The table as little endian fields, for platforms without serde. Empty places are saved
+    /// with a score of zero, scores without a date with a year of zero and scores without an
+    /// uptime with an uptime of zero.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        for (chunk, entry) in bytes.chunks_exact_mut(ENTRY_LEN).zip(self.entries) {
+            if let Some(entry) = entry {
+                chunk[..4].copy_from_slice(&entry.score.to_le_bytes());
+                chunk[4..7].copy_from_slice(&entry.initials);
+                if let Some(date) = entry.date {
+                    chunk[7..9].copy_from_slice(&date.year.to_le_bytes());
+                    chunk[9] = date.month;
+                    chunk[10] = date.day;
+                }
+                if let Some(uptime) = entry.uptime {
+                    chunk[11..15].copy_from_slice(&uptime.to_le_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let mut scores = HighScores::new();
+        for (entry, chunk) in scores.entries.iter_mut().zip(bytes.chunks_exact(ENTRY_LEN)) {
+            let score = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let year = u16::from_le_bytes([chunk[7], chunk[8]]);
+            let uptime = u32::from_le_bytes([chunk[11], chunk[12], chunk[13], chunk[14]]);
+            *entry = (score > 0).then(|| HighScore {
+                initials: [chunk[4], chunk[5], chunk[6]],
+                score,
+                date: (year > 0).then_some(Date {
+                    year,
+                    month: chunk[9],
+                    day: chunk[10],
+                }),
+                uptime: (uptime > 0).then_some(uptime),
+            });
+        }
+        scores
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::daily::Date;
+    use crate::high_score::{HighScore, HighScores, HIGH_SCORES};
+
+    fn score(score: u32) -> HighScore {
+        HighScore {
+            initials: *b"ABC",
+            score,
+            date: None,
+            uptime: None,
+        }
+    }
+
+    #[test]
+    fn scores_are_kept_best_first() {
+        let mut scores = HighScores::new();
+        assert!(scores.insert(score(100)) == Some(0));
+        assert!(scores.insert(score(300)) == Some(0));
+        assert!(scores.insert(score(200)) == Some(1));
+        assert!(scores.insert(score(100)) == Some(3));
+        assert!(scores
+            .iter()
+            .map(|entry| entry.score)
+            .eq([300, 200, 100, 100]));
+    }
+
+    #[test]
+    fn a_full_table_only_takes_better_scores() {
+        let mut scores = HighScores::new();
+        for points in 1..=HIGH_SCORES as u32 {
+            scores.insert(score(points * 10));
+        }
+        assert!(!scores.qualifies(10) && !scores.qualifies(0));
+        assert!(scores.insert(score(15)) == Some(HIGH_SCORES - 1));
+        assert!(scores.iter().count() == HIGH_SCORES);
+        assert!(scores.iter().last().unwrap().score == 15);
+    }
+
+    #[test]
+    fn a_table_round_trips_through_bytes() {
+        let mut scores = HighScores::new();
+        scores.insert(score(u32::MAX));
+        scores.insert(HighScore {
+            initials: *b"XYZ",
+            score: 1200,
+            date: Some(Date {
+                year: 2024,
+                month: 2,
+                day: 29,
+            }),
+            uptime: None,
+        });
+        scores.insert(HighScore {
+            initials: *b"UPT",
+            score: 900,
+            date: None,
+            uptime: Some(3_600),
+        });
+        assert!(HighScores::from_bytes(&scores.to_bytes()) == scores);
+        assert!(HighScores::from_bytes(&[0; HighScores::ENCODED_LEN]) == HighScores::new());
+    }
+}
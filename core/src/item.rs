@@ -0,0 +1,113 @@
+//! Items for the item mode, earned by clearing several rows at once and used on your own board or
+//! an opponent's.
+
+use crate::grid::Grid;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Rows that have to be cleared at once to earn an item.
+pub const ROWS_FOR_ITEM: usize = 2;
+
+/// Updates that a slow down halves the gravity of the board it is used on.
+pub const SLOW_DOWN_TICKS: u16 = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Item {
+    /// Clears the 3x3 cells around the top of your stack.
+    Bomb,
+    /// Halves the gravity of an opponent for SLOW_DOWN_TICKS updates.
+    SlowDown,
+    /// Shifts every row of an opponent's stack a cell sideways, alternating left and right.
+    LineShift,
+}
+
+/// Whose board an item acts on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Target {
+    Own,
+    Opponent,
+}
+
+impl Item {
+    /// Items in the order they are earned.
+    pub const ALL: [Item; 3] = [Item::Bomb, Item::SlowDown, Item::LineShift];
+
+    pub fn target(self) -> Target {
+        match self {
+            Item::Bomb => Target::Own,
+            Item::SlowDown | Item::LineShift => Target::Opponent,
+        }
+    }
+}
+
+/// The highest filled cell of the stack, the leftmost if several are level.
+fn top_of_stack(grid: &Grid) -> Option<(usize, usize)> {
+    (0..grid.height)
+        .rev()
+        .find_map(|y| grid.row(y).iter().position(|&cell| cell).map(|x| (x, y)))
+}
+
+/// Clear the 3x3 cells around the top of the stack, doing nothing to an empty stack.
+pub(crate) fn explode(grid: &mut Grid) {
+    let Some((x, y)) = top_of_stack(grid) else {
+        return;
+    };
+    for y in y.saturating_sub(1)..(y + 2).min(grid.height) {
+        for x in x.saturating_sub(1)..(x + 2).min(grid.width) {
+            grid[(x, y)] = false;
+        }
+    }
+}
+
+/// Rotate even rows a cell to the right and odd rows a cell to the left, wrapping around the
+/// sides. Undone by calling again with back set.
+pub(crate) fn shift_rows(grid: &mut Grid, back: bool) {
+    for y in 0..grid.height {
+        let row = grid.row_mut(y);
+        if (y % 2 == 0) != back {
+            row.rotate_right(1);
+        } else {
+            row.rotate_left(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::grid::Grid;
+    use crate::item::{explode, shift_rows};
+
+    fn grid(rows: &[&str]) -> Grid {
+        let mut grid = Grid::new((5, rows.len()));
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, cell) in row.chars().enumerate() {
+                grid[(x, y)] = cell == '#';
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn a_bomb_clears_around_the_top_of_the_stack() {
+        let mut stack = grid(&["...#.", "#####", "#####", "#####"]);
+        explode(&mut stack);
+        assert!(stack == grid(&[".....", "##...", "#####", "#####"]));
+    }
+
+    #[test]
+    fn a_bomb_on_an_empty_stack_does_nothing() {
+        let mut stack = grid(&[".....", "....."]);
+        explode(&mut stack);
+        assert!(stack == grid(&[".....", "....."]));
+    }
+
+    #[test]
+    fn line_shift_alternates_and_can_be_undone() {
+        let mut stack = grid(&["#....", "#...."]);
+        shift_rows(&mut stack, false);
+        assert!(stack == grid(&["....#", ".#..."]));
+        shift_rows(&mut stack, true);
+        assert!(stack == grid(&["#....", "#...."]));
+    }
+}
@@ -1,7 +1,48 @@
 #![no_std]
 #![feature(prelude_2024)]
+#![cfg_attr(test, feature(test))]
+#[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
+pub mod achievement;
+#[cfg(feature = "alloc")]
+pub mod analysis;
+#[cfg(feature = "proptest")]
+mod arbitrary;
+#[cfg(test)]
+mod bench;
+pub mod cascade;
+pub mod daily;
+#[cfg(feature = "alloc")]
+pub mod delta;
+#[cfg(feature = "alloc")]
+pub mod difficulty;
+pub mod drought;
+pub mod event;
+pub mod finesse;
+pub mod grade;
 pub mod grid;
+pub mod high_score;
+pub mod item;
+pub mod metrics;
+pub mod mode;
 pub mod piece;
+#[cfg(feature = "alloc")]
+pub mod practice;
+pub mod profile;
+#[cfg(feature = "alloc")]
+pub mod puzzle;
+#[cfg(feature = "alloc")]
+pub mod replay;
+pub mod scoring;
+pub mod sequence;
+pub mod session;
+#[cfg(feature = "alloc")]
+pub mod simulation;
+#[cfg(feature = "alloc")]
+pub mod spectate;
 pub mod tetris;
+#[cfg(feature = "alloc")]
+pub mod versus;
@@ -0,0 +1,57 @@
+//! Counts of the work done by an update, kept by the game as it goes so that frontends can show
+//! a performance HUD and the Pico can profile over defmt without instrumenting the game again.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The work done by the most recent update of a game.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Metrics {
+    /// Cells of the stack written by locking a piece, emptying complete rows, garbage and zen
+    /// mode clearing the stack.
+    pub cells_touched: u32,
+    /// Rows checked for being complete.
+    pub rows_scanned: u32,
+    /// Pieces brought into play.
+    pub pieces_spawned: u32,
+    /// How long the update took by the clock given to Tetris::update_timed, in the units of
+    /// that clock. None unless it was timed.
+    pub elapsed: Option<u32>,
+}
+
+impl Metrics {
+    /// Add the work of another update, for totals over a frame or a second.
+    pub fn add(&mut self, other: &Metrics) {
+        self.cells_touched += other.cells_touched;
+        self.rows_scanned += other.rows_scanned;
+        self.pieces_spawned += other.pieces_spawned;
+        self.elapsed = match (self.elapsed, other.elapsed) {
+            (Some(ours), Some(theirs)) => Some(ours.wrapping_add(theirs)),
+            (ours, theirs) => ours.or(theirs),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn metrics_add_up_over_updates() {
+        let update = Metrics {
+            cells_touched: 4,
+            rows_scanned: 20,
+            pieces_spawned: 1,
+            elapsed: None,
+        };
+        let mut total = Metrics::default();
+        total.add(&update);
+        total.add(&Metrics {
+            elapsed: Some(7),
+            ..update
+        });
+        assert!(total.cells_touched == 8 && total.rows_scanned == 40 && total.pieces_spawned == 2);
+        assert!(total.elapsed == Some(7));
+    }
+}
@@ -0,0 +1,82 @@
+//! Game modes with a goal that ends the game before the stack tops out, checked by the core so
+//! every frontend plays them the same. A game's mode is part of its rules.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Lines cleared to finish a marathon.
+pub const MARATHON_LINES: usize = 150;
+/// Lines cleared to finish a sprint, as fast as possible.
+pub const SPRINT_LINES: usize = 40;
+/// Updates an ultra lasts, two minutes at four updates a second.
+pub const ULTRA_TICKS: u32 = 480;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum GameMode {
+    /// Play until the stack tops out.
+    #[default]
+    Endless,
+    /// Clear MARATHON_LINES lines.
+    Marathon,
+    /// Clear SPRINT_LINES lines, timed by the updates it takes.
+    Sprint,
+    /// Score as much as possible in ULTRA_TICKS updates.
+    Ultra,
+}
+
+impl GameMode {
+    /// The mode with name, as given on command lines: endless, marathon, sprint or ultra.
+    pub fn from_name(name: &str) -> Option<GameMode> {
+        Some(match name {
+            "endless" => GameMode::Endless,
+            "marathon" => GameMode::Marathon,
+            "sprint" => GameMode::Sprint,
+            "ultra" => GameMode::Ultra,
+            _ => return None,
+        })
+    }
+
+    /// The name the mode is given by on command lines and in exported summaries.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Endless => "endless",
+            GameMode::Marathon => "marathon",
+            GameMode::Sprint => "sprint",
+            GameMode::Ultra => "ultra",
+        }
+    }
+
+    /// Whether a game of this mode that has cleared lines in ticks updates has reached its end.
+    pub fn is_complete(self, lines: usize, ticks: u32) -> bool {
+        match self {
+            GameMode::Endless => false,
+            GameMode::Marathon => lines >= MARATHON_LINES,
+            GameMode::Sprint => lines >= SPRINT_LINES,
+            GameMode::Ultra => ticks >= ULTRA_TICKS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::mode::{GameMode, MARATHON_LINES, SPRINT_LINES, ULTRA_TICKS};
+
+    #[test]
+    fn each_mode_ends_at_its_goal() {
+        assert!(!GameMode::Endless.is_complete(10_000, u32::MAX));
+        assert!(!GameMode::Marathon.is_complete(MARATHON_LINES - 1, u32::MAX));
+        assert!(GameMode::Marathon.is_complete(MARATHON_LINES, 0));
+        assert!(GameMode::Sprint.is_complete(SPRINT_LINES + 3, 0));
+        assert!(!GameMode::Ultra.is_complete(10_000, ULTRA_TICKS - 1));
+        assert!(GameMode::Ultra.is_complete(0, ULTRA_TICKS));
+    }
+
+    #[test]
+    fn modes_are_found_by_name() {
+        assert!(GameMode::from_name("sprint") == Some(GameMode::Sprint));
+        assert!(GameMode::from_name("ultra") == Some(GameMode::Ultra));
+        assert!(GameMode::from_name("zen").is_none());
+        assert!(GameMode::from_name(GameMode::Marathon.name()) == Some(GameMode::Marathon));
+    }
+}
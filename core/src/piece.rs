@@ -1,12 +1,14 @@
 use crate::grid::Grid;
-use alloc::vec;
 use core::{clone::Clone, marker::Copy, prelude::rust_2024::derive};
-use enum_iterator::Sequence;
+use enum_iterator::{all, Sequence};
 use enum_map::{enum_map, Enum, EnumMap};
 use rand::prelude::*;
 use rand_derive::Rand;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Enum)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Enum)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Rotation {
     R0 = 0,
     R90 = 1,
@@ -24,17 +26,94 @@ impl Rotation {
             R270 => R0,
         }
     }
+
+    pub fn prev(&self) -> Self {
+        pub use Rotation::*;
+        match self {
+            R0 => R270,
+            R90 => R0,
+            R180 => R90,
+            R270 => R180,
+        }
+    }
+
+    /// The rotation reached by turning from this one.
+    pub fn turned(&self, turn: Turn) -> Self {
+        match turn {
+            Turn::Clockwise => self.next(),
+            Turn::CounterClockwise => self.prev(),
+            Turn::Half => self.next().next(),
+        }
+    }
 }
 
+/// A turn of a piece.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Turn {
+    /// From each rotation to the next.
+    Clockwise,
+    CounterClockwise,
+    /// Two quarter turns made at once, with kicks of their own rather than those of each quarter.
+    Half,
+}
+
+/// Offsets tried in turn as a piece rotates clockwise from each rotation, from the Super Rotation
+/// System, x to the right and y up. The first that leaves the piece clear of the walls and the
+/// stack is taken, so pieces kick off them rather than failing to rotate. Turning back counter
+/// clockwise tries the same offsets reversed.
+const KICKS: [[(isize, isize); 5]; Rotation::LENGTH] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+
+/// The line piece kicks further, being longer.
+const LINE_KICKS: [[(isize, isize); 5]; Rotation::LENGTH] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+
+/// Offsets tried as a piece turns halfway round from each rotation, as in TETR.IO. The piece
+/// passes through no other rotation, so it can turn where neither quarter turn on its own would
+/// fit.
+const HALF_KICKS: [[(isize, isize); 6]; Rotation::LENGTH] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+const LINE_HALF_KICKS: [[(isize, isize); 2]; Rotation::LENGTH] = [
+    [(0, 0), (0, 1)],
+    [(0, 0), (1, 0)],
+    [(0, 0), (0, -1)],
+    [(0, 0), (-1, 0)],
+];
+
+/// Where the bottom left of the piece sits in each rotation within the box the Super Rotation
+/// System turns it in, three cells square and four for the line. The other pieces lie across the
+/// middle row of the box and stand in its middle column. The kicks are offsets from turning in
+/// place in that box.
+const BOX_OFFSETS: [(isize, isize); Rotation::LENGTH] = [(0, 1), (1, 0), (0, 0), (0, 0)];
+const LINE_BOX_OFFSETS: [(isize, isize); Rotation::LENGTH] = [(0, 2), (2, 0), (0, 1), (1, 0)];
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Piece {
+    kind: PieceSelector,
     rotations: EnumMap<Rotation, Grid>,
     current_rotation: Rotation,
     pub x: usize,
     pub y: usize,
 }
 
-#[derive(Rand, Sequence)]
-enum PieceSelector {
+/// The seven tetrominoes, used by frontends to give each piece its own color.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Rand, Sequence)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PieceSelector {
     Line,
     J,
     L,
@@ -45,75 +124,129 @@ enum PieceSelector {
 }
 
 impl PieceSelector {
-    fn to_piece(&self, (x, y): (usize, usize)) -> Piece {
+    pub(crate) fn to_piece(self, (x, y): (usize, usize)) -> Piece {
         pub use Rotation::*;
         let rotations = match self {
             PieceSelector::Line => {
                 enum_map! {
-                    R0 => Grid::of_data((4, 1), vec![true, true, true, true]),
-                    R90 => Grid::of_data((1, 4), vec![true, true, true, true]),
-                    R180 => Grid::of_data((4, 1), vec![true, true, true, true]),
-                    R270 => Grid::of_data((1, 4), vec![true, true, true, true]),
+                    R0 => Grid::from_cells((4, 1), &[true, true, true, true]),
+                    R90 => Grid::from_cells((1, 4), &[true, true, true, true]),
+                    R180 => Grid::from_cells((4, 1), &[true, true, true, true]),
+                    R270 => Grid::from_cells((1, 4), &[true, true, true, true]),
                 }
             }
             PieceSelector::J => {
                 enum_map! {
-                    R0 => Grid::of_data((4, 2), vec![true, false, false, false, true, true, true, true]),
-                    R90 => Grid::of_data((2, 4), vec![true, true, true, false, true, false, true, false]),
-                    R180 => Grid::of_data((4, 2), vec![true, true, true, true, false, false, false, true]),
-                    R270 => Grid::of_data((2, 4), vec![true, false, true, false, true, false, true, true]),
+                    R0 => Grid::from_cells((3, 2), &[true, true, true, true, false, false]),
+                    R90 => Grid::from_cells((2, 3), &[true, false, true, false, true, true]),
+                    R180 => Grid::from_cells((3, 2), &[false, false, true, true, true, true]),
+                    R270 => Grid::from_cells((2, 3), &[true, true, false, true, false, true]),
                 }
             }
 
             PieceSelector::L => {
                 enum_map! {
-                    R0 => Grid::of_data((4, 2), vec![false, false, false, true, true, true, true, true]),
-                    R90 => Grid::of_data((2, 4), vec![true, true, false, true, false, true, false, true]),
-                    R180 => Grid::of_data((4, 2), vec![true, true, true, true, true, false, false, false]),
-                    R270 => Grid::of_data((2, 4), vec![false, true, false, true, false, true, true, true]),
+                    R0 => Grid::from_cells((3, 2), &[true, true, true, false, false, true]),
+                    R90 => Grid::from_cells((2, 3), &[true, true, true, false, true, false]),
+                    R180 => Grid::from_cells((3, 2), &[true, false, false, true, true, true]),
+                    R270 => Grid::from_cells((2, 3), &[false, true, false, true, true, true]),
                 }
             }
             PieceSelector::O => {
                 enum_map! {
-                    R0 => Grid::of_data((2, 2), vec![true, true, true, true]),
-                    R90 => Grid::of_data((2, 2),vec![true, true, true, true]  ),
-                    R180 => Grid::of_data((2, 2), vec![true, true, true, true] ),
-                    R270 => Grid::of_data((2, 2), vec![true, true, true, true] ),
+                    R0 => Grid::from_cells((2, 2), &[true, true, true, true]),
+                    R90 => Grid::from_cells((2, 2),&[true, true, true, true]  ),
+                    R180 => Grid::from_cells((2, 2), &[true, true, true, true] ),
+                    R270 => Grid::from_cells((2, 2), &[true, true, true, true] ),
                 }
             }
             PieceSelector::S => {
                 enum_map! {
-                    R0 => Grid::of_data((3, 2), vec![false, true, true, true, true, false]),
-                    R90 => Grid::of_data((2, 3), vec![true, false, true, true, false, true]),
-                    R180 => Grid::of_data((3, 2), vec![true, true, false, false, true, true]),
-                    R270 => Grid::of_data((2, 3), vec![false, true, true, true, true, false]),
+                    R0 => Grid::from_cells((3, 2), &[true, true, false, false, true, true]),
+                    R90 => Grid::from_cells((2, 3), &[false, true, true, true, true, false]),
+                    R180 => Grid::from_cells((3, 2), &[true, true, false, false, true, true]),
+                    R270 => Grid::from_cells((2, 3), &[false, true, true, true, true, false]),
                 }
             }
             PieceSelector::T => {
                 enum_map! {
-                    R0 => Grid::of_data((3, 2), vec![false, true, false, true, true, true]),
-                    R90 => Grid::of_data((2, 3), vec![true, false, true, true, true, false]),
-                    R180 => Grid::of_data((3, 2), vec![true, true, true, false, true, false]),
-                    R270 => Grid::of_data((2, 3), vec![false, true, true, true, false, true]),
+                    R0 => Grid::from_cells((3, 2), &[true, true, true, false, true, false]),
+                    R90 => Grid::from_cells((2, 3), &[true, false, true, true, true, false]),
+                    R180 => Grid::from_cells((3, 2), &[false, true, false, true, true, true]),
+                    R270 => Grid::from_cells((2, 3), &[false, true, true, true, false, true]),
                 }
             }
             PieceSelector::Z => {
                 enum_map! {
-                    R0 => Grid::of_data((3, 2), vec![true, true, false, false, true, true]),
-                    R90 => Grid::of_data((2, 3), vec![false, true, true, true, true, false]),
-                    R180 => Grid::of_data((3, 2), vec![true, true, false, false, true, true]),
-                    R270 => Grid::of_data((2, 3), vec![false, true, true, true, true, false]),
+                    R0 => Grid::from_cells((3, 2), &[false, true, true, true, true, false]),
+                    R90 => Grid::from_cells((2, 3), &[true, false, true, true, false, true]),
+                    R180 => Grid::from_cells((3, 2), &[false, true, true, true, true, false]),
+                    R270 => Grid::from_cells((2, 3), &[true, false, true, true, false, true]),
                 }
             }
         };
 
         Piece {
+            kind: self,
             x,
             y,
             rotations,
             current_rotation: Rotation::R0,
         }
     }
+
+    /// How far the piece moves when it turns from rotation in place, before any kick.
+    pub fn turn_offset(&self, rotation: Rotation, turn: Turn) -> (isize, isize) {
+        let offsets = match self {
+            PieceSelector::Line => &LINE_BOX_OFFSETS,
+            PieceSelector::O => return (0, 0),
+            _ => &BOX_OFFSETS,
+        };
+        let (from, to) = (
+            offsets[rotation as usize],
+            offsets[rotation.turned(turn) as usize],
+        );
+        (to.0 - from.0, to.1 - from.1)
+    }
+
+    /// The offsets to try, in order, when turning the piece from rotation.
+    pub fn kicks(
+        &self,
+        rotation: Rotation,
+        turn: Turn,
+    ) -> impl Iterator<Item = (isize, isize)> + 'static {
+        let (from, sign) = match turn {
+            Turn::Clockwise | Turn::Half => (rotation, 1),
+            Turn::CounterClockwise => (rotation.prev(), -1),
+        };
+        let kicks: &'static [(isize, isize)] = match (self, turn) {
+            // Square in every rotation, so it never needs to kick
+            (PieceSelector::O, _) => &KICKS[0][..1],
+            (PieceSelector::Line, Turn::Half) => &LINE_HALF_KICKS[from as usize],
+            (_, Turn::Half) => &HALF_KICKS[from as usize],
+            (PieceSelector::Line, _) => &LINE_KICKS[from as usize],
+            _ => &KICKS[from as usize],
+        };
+        kicks.iter().map(move |&(dx, dy)| (dx * sign, dy * sign))
+    }
+
+    /// The letter the piece is known by: I, J, L, O, S, T or Z.
+    pub fn letter(&self) -> char {
+        match self {
+            PieceSelector::Line => 'I',
+            PieceSelector::J => 'J',
+            PieceSelector::L => 'L',
+            PieceSelector::O => 'O',
+            PieceSelector::S => 'S',
+            PieceSelector::T => 'T',
+            PieceSelector::Z => 'Z',
+        }
+    }
+
+    /// The piece known by letter, in either case.
+    pub fn from_letter(letter: char) -> Option<PieceSelector> {
+        all::<PieceSelector>().find(|kind| kind.letter() == letter.to_ascii_uppercase())
+    }
 }
 
 impl Piece {
@@ -121,10 +254,22 @@ impl Piece {
         rng.gen::<PieceSelector>().to_piece(offset)
     }
 
+    pub fn kind(&self) -> PieceSelector {
+        self.kind
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.current_rotation
+    }
+
     pub fn next_rotation(&mut self) {
         self.current_rotation = self.current_rotation.next();
     }
 
+    pub fn turn(&mut self, turn: Turn) {
+        self.current_rotation = self.current_rotation.turned(turn);
+    }
+
     pub fn current_rotation(&self) -> &Grid {
         &self.rotations[self.current_rotation]
     }
@@ -132,11 +277,24 @@ impl Piece {
     pub fn peek_next_rotation(&self) -> &Grid {
         &self.rotations[self.current_rotation.next()]
     }
+
+    /// The grid the piece would have after turning.
+    pub fn peek_turn(&self, turn: Turn) -> &Grid {
+        &self.rotations[self.current_rotation.turned(turn)]
+    }
+
+    /// Flip every rotation of the piece left to right, turning J shapes into L shapes and S
+    /// shapes into Z shapes.
+    pub(crate) fn mirror(&mut self) {
+        for grid in self.rotations.values_mut() {
+            grid.mirror();
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::piece::PieceSelector;
+    use crate::piece::{PieceSelector, Rotation, Turn};
     use enum_iterator::all;
 
     #[test]
@@ -148,4 +306,18 @@ mod test {
             }
         }
     }
+
+    #[test]
+    pub fn turning_back_tries_the_kicks_reversed() {
+        for kind in all::<PieceSelector>() {
+            let rotation = Rotation::R90;
+            let clockwise = kind.kicks(rotation, Turn::Clockwise);
+            let back = kind.kicks(rotation.next(), Turn::CounterClockwise);
+            assert!(clockwise
+                .zip(back)
+                .all(|(there, back)| there == (-back.0, -back.1)));
+            let (dx, dy) = kind.turn_offset(rotation, Turn::Clockwise);
+            assert!(kind.turn_offset(rotation.next(), Turn::CounterClockwise) == (-dx, -dy));
+        }
+    }
 }
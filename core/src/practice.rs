@@ -0,0 +1,214 @@
+//! A sandbox for practicing setups: the playfield and next piece can be edited, and the game can
+//! be stepped backwards and forwards through its history.
+
+use crate::piece::PieceSelector;
+use crate::sequence::PieceSequence;
+use crate::tetris::{EditError, KeyState, Rules, Tetris, TetrisState};
+use alloc::vec::Vec;
+
+/// Steps remembered for stepping back, the oldest are forgotten first.
+pub const HISTORY: usize = 256;
+
+pub struct Practice {
+    tetris: Tetris,
+    /// Earlier states, the most recent last.
+    past: Vec<Tetris>,
+    /// States stepped back from, the nearest last, replayed by stepping forward until the game
+    /// is changed another way.
+    future: Vec<Tetris>,
+}
+
+impl Practice {
+    pub fn new(seed: u64) -> Self {
+        Self::from_game(Tetris::new_with_seed(seed))
+    }
+
+    /// Practice with the pieces of sequence dealt in order, over and over.
+    pub fn with_sequence(sequence: PieceSequence) -> Self {
+        Self::from_game(Tetris::with_sequence(sequence))
+    }
+
+    fn from_game(mut tetris: Tetris) -> Self {
+        tetris.set_rules(Rules {
+            practice: true,
+            ..Rules::default()
+        });
+        Practice {
+            tetris,
+            past: Vec::new(),
+            future: Vec::new(),
+        }
+    }
+
+    pub fn tetris(&self) -> &Tetris {
+        &self.tetris
+    }
+
+    pub fn set_key_state(&mut self, key_state: &KeyState) {
+        self.tetris.set_key_state(key_state);
+    }
+
+    fn remember(&mut self) {
+        if self.past.len() == HISTORY {
+            self.past.remove(0);
+        }
+        self.past.push(self.tetris.clone());
+    }
+
+    /// Run an edit or update, remembering the state before it and forgetting any steps backed
+    /// out of.
+    fn change<T>(&mut self, change: impl FnOnce(&mut Tetris) -> T) -> T {
+        self.remember();
+        self.future.clear();
+        change(&mut self.tetris)
+    }
+
+    fn edit(
+        &mut self,
+        edit: impl FnOnce(&mut TetrisState) -> Result<(), EditError>,
+    ) -> Result<(), EditError> {
+        let Tetris::Running(ref state) = self.tetris else {
+            return Err(EditError::NotPracticing);
+        };
+        // Edited on a copy so that a refused edit leaves nothing to step back over
+        let mut edited = state.clone();
+        edit(&mut edited)?;
+        self.change(|tetris| *tetris = Tetris::Running(edited));
+        Ok(())
+    }
+
+    pub fn set_cell(&mut self, position: (usize, usize), filled: bool) -> Result<(), EditError> {
+        self.edit(|state| state.set_cell(position, filled))
+    }
+
+    pub fn set_next_piece(&mut self, kind: PieceSelector) -> Result<(), EditError> {
+        self.edit(|state| state.set_next_piece(kind))
+    }
+
+    /// Step forward, replaying a step backed out of or else running an update.
+    pub fn step_forward(&mut self) {
+        match self.future.pop() {
+            Some(next) => {
+                self.remember();
+                self.tetris = next;
+            }
+            None => self.change(|tetris| tetris.update()),
+        }
+    }
+
+    /// Step back to the state before the last update or edit. Returns false if there is nothing
+    /// left to step back to.
+    pub fn step_back(&mut self) -> bool {
+        match self.past.pop() {
+            Some(previous) => {
+                self.future
+                    .push(core::mem::replace(&mut self.tetris, previous));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::piece::PieceSelector;
+    use crate::practice::Practice;
+    use crate::sequence::PieceSequence;
+    use crate::tetris::{EditError, KeyState, Rules, Tetris, TetrisState};
+
+    fn state(practice: &Practice) -> &TetrisState {
+        match practice.tetris() {
+            Tetris::Running(state) => state,
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    #[test]
+    fn edits_are_refused_outside_practice() {
+        let mut tetris = Tetris::new_with_seed(1);
+        let Tetris::Running(ref mut state) = tetris else {
+            panic!("Expected a running game");
+        };
+        assert!(state.set_cell((0, 0), true) == Err(EditError::NotPracticing));
+        assert!(state.set_next_piece(PieceSelector::T) == Err(EditError::NotPracticing));
+
+        state.rules = Rules {
+            practice: true,
+            ..Rules::default()
+        };
+        assert!(state.set_cell((0, 0), true) == Ok(()));
+        assert!(state.set_cell((10, 0), true) == Err(EditError::OutOfBounds { x: 10, y: 0 }));
+    }
+
+    #[test]
+    fn a_row_cannot_be_completed_by_editing() {
+        let mut practice = Practice::new(1);
+        for x in 0..9 {
+            practice.set_cell((x, 0), true).unwrap();
+        }
+        assert!(practice.set_cell((9, 0), true) == Err(EditError::CompletesRow { y: 0 }));
+        assert!(state(&practice).validate() == Ok(()));
+    }
+
+    #[test]
+    fn the_next_piece_can_be_picked() {
+        let mut practice = Practice::new(1);
+        practice.set_next_piece(PieceSelector::T).unwrap();
+        assert!(state(&practice).next_piece.kind() == PieceSelector::T);
+    }
+
+    #[test]
+    fn a_sequence_is_dealt_while_stepping_back_and_forth() {
+        let sequence = PieceSequence::from_letters("TI").unwrap();
+        let mut practice = Practice::with_sequence(sequence);
+        assert!(state(&practice).piece.kind() == PieceSelector::T);
+        assert!(state(&practice).next_piece.kind() == PieceSelector::Line);
+        practice.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        practice.step_forward();
+        practice.set_key_state(&KeyState::default());
+        while state(&practice).piece_in_play().is_none() {
+            practice.step_forward();
+        }
+        assert!(state(&practice).piece.kind() == PieceSelector::Line);
+        assert!(state(&practice).next_piece.kind() == PieceSelector::T);
+
+        while practice.step_back() {}
+        assert!(state(&practice).piece.kind() == PieceSelector::T);
+    }
+
+    #[test]
+    fn steps_and_edits_can_be_undone_and_replayed() {
+        let mut practice = Practice::new(1);
+        let start = state(&practice).piece.y;
+        practice.step_forward();
+        practice.set_cell((0, 0), true).unwrap();
+        practice.step_forward();
+        assert!(state(&practice).piece.y == start - 2);
+
+        assert!(practice.step_back());
+        assert!(practice.step_back());
+        assert!(!state(&practice).grid[(0, 0)]);
+        assert!(state(&practice).piece.y == start - 1);
+
+        practice.step_forward();
+        assert!(state(&practice).grid[(0, 0)]);
+        assert!(practice.step_back() && practice.step_back());
+        assert!(!practice.step_back());
+        assert!(state(&practice).piece.y == start);
+    }
+
+    #[test]
+    fn a_new_change_forgets_the_steps_backed_out_of() {
+        let mut practice = Practice::new(1);
+        practice.step_forward();
+        practice.step_back();
+        practice.set_cell((0, 0), true).unwrap();
+        // Stepping forward now runs an update rather than replaying the one undone
+        practice.step_forward();
+        assert!(state(&practice).grid[(0, 0)]);
+    }
+}
@@ -0,0 +1,133 @@
+//! A player's profile: their initials, the rules they like to play by, their lifetime stats and
+//! the achievements they have unlocked. Profiles have a small fixed size saved form so that the
+//! Pico can keep a few in flash, and serde for frontends that store them as files.
+
+use crate::achievement::Achievements;
+use crate::session::Session;
+use crate::tetris::Rules;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Profile {
+    /// Three upper case letters or digits, as entered on a high score table.
+    pub initials: [u8; 3],
+    /// The rules new games are played by.
+    pub rules: Rules,
+    pub session: Session,
+    pub achievements: Achievements,
+}
+
+impl Profile {
+    /// Bytes taken by the saved form from to_bytes.
+    pub const ENCODED_LEN: usize =
+        3 + Rules::ENCODED_LEN + Session::ENCODED_LEN + Achievements::ENCODED_LEN;
+
+    /// A new player known by the first three letters or digits of name, upper cased. Names
+    /// shorter than that are padded with A as arcade initials start.
+    pub fn new(name: &str) -> Self {
+        let mut initials = [b'A'; 3];
+        let letters = name
+            .bytes()
+            .filter(u8::is_ascii_alphanumeric)
+            .map(|letter| letter.to_ascii_uppercase());
+        for (initial, letter) in initials.iter_mut().zip(letters) {
+            *initial = letter;
+        }
+        Profile {
+            initials,
+            ..Profile::default()
+        }
+    }
+
+    /// The initials as text, or ??? if they are not printable.
+    pub fn initials(&self) -> &str {
+        match core::str::from_utf8(&self.initials) {
+            Ok(initials) if self.initials.iter().all(u8::is_ascii_graphic) => initials,
+            _ => "???",
+        }
+    }
+
+    /// The profile as little endian fields, for platforms without serde.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        let fields: [&[u8]; 4] = [
+            &self.initials,
+            &self.rules.to_bytes(),
+            &self.session.to_bytes(),
+            &self.achievements.to_bytes(),
+        ];
+        let mut offset = 0;
+        for field in fields {
+            bytes[offset..offset + field.len()].copy_from_slice(field);
+            offset += field.len();
+        }
+        bytes
+    }
+
+    /// Read a saved profile. Settings saved by a later version that this one does not know are
+    /// read as their defaults.
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let (initials, rest) = bytes.split_at(3);
+        let (rules, rest) = rest.split_at(Rules::ENCODED_LEN);
+        let (session, achievements) = rest.split_at(Session::ENCODED_LEN);
+        Profile {
+            initials: [initials[0], initials[1], initials[2]],
+            rules: Rules::from_bytes(&array(rules)),
+            session: Session::from_bytes(&array(session)),
+            achievements: Achievements::from_bytes(&array(achievements)),
+        }
+    }
+}
+
+/// The first N bytes of bytes, which must have at least that many.
+fn array<const N: usize>(bytes: &[u8]) -> [u8; N] {
+    let mut array = [0; N];
+    array.copy_from_slice(&bytes[..N]);
+    array
+}
+
+#[cfg(test)]
+mod test {
+    use crate::achievement::{Achievement, Achievements};
+    use crate::profile::Profile;
+    use crate::scoring::Scoring;
+    use crate::session::Session;
+    use crate::tetris::{Gravity, LockReset, Rules, TopOutRule};
+
+    #[test]
+    fn initials_are_taken_from_the_name() {
+        assert!(Profile::new("jo-anne").initials() == "JOA");
+        assert!(Profile::new("q").initials() == "QAA");
+    }
+
+    #[test]
+    fn a_profile_survives_being_saved() {
+        let mut achievements = Achievements::default();
+        achievements.insert(Achievement::Sprint);
+        let profile = Profile {
+            rules: Rules {
+                gravity: Gravity::from_ratio(3, 2),
+                lock_delay: 30,
+                lock_reset: LockReset::Infinite,
+                mirror: true,
+                wrap: true,
+                max_drought: Some(12),
+                scoring: Scoring::Nes,
+                countdown: 12,
+                previews: 3,
+                top_out: TopOutRule::LockOut,
+                ..Rules::default()
+            },
+            session: Session {
+                games_played: 3,
+                best_score: 12_345,
+                ..Session::default()
+            },
+            achievements,
+            ..Profile::new("ace")
+        };
+        assert!(Profile::from_bytes(&profile.to_bytes()) == profile);
+    }
+}
@@ -0,0 +1,414 @@
+//! Puzzles: a starting stack, a fixed sequence of pieces and a goal to reach with them, read from
+//! a small text format so a pack of them can be built in and played on any frontend.
+//!
+//! A puzzle is a list of lines, blank lines and those starting with ';' being ignored:
+//!
+//! ```text
+//! name Four in one
+//! goal lines 4 in 1
+//! pieces I
+//! #########.
+//! #########.
+//! #########.
+//! #########.
+//! ```
+//!
+//! The goal is either `lines <rows> in <pieces>` or `tspin in <pieces>`. Pieces are given by the
+//! letters I, J, L, O, S, T and Z, and the stack is written as in simulation scripts, from the top
+//! down to the floor with '#' for a filled cell.
+
+use crate::piece::PieceSelector;
+use crate::tetris::{spawn_piece, KeyState, Tetris, GRID_SIZE};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+/// What has to be done to solve a puzzle, with no more than pieces pieces placed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Goal {
+    ClearLines { lines: usize, pieces: usize },
+    TSpin { pieces: usize },
+}
+
+impl Goal {
+    fn pieces(&self) -> usize {
+        match *self {
+            Goal::ClearLines { pieces, .. } | Goal::TSpin { pieces } => pieces,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<PieceSelector>,
+    /// Rows of the stack from the floor up, shorter than the playfield.
+    pub stack: Vec<Vec<bool>>,
+}
+
+/// The line of a puzzle that could not be read and why.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PuzzleError {
+    /// The line number, counting from one.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {}: {}", self.line, self.message)
+    }
+}
+
+/// The puzzles every frontend comes with, easiest first.
+pub const PUZZLES: &[&str] = &[
+    "name Four in one
+goal lines 4 in 1
+pieces I
+#########.
+#########.
+#########.
+#########.",
+    "name Two by two
+goal lines 2 in 2
+pieces O O
+##..######
+##..######
+##..######",
+    "name Under the ledge
+goal tspin in 1
+pieces T
+....#.....
+##...#####
+###.######",
+];
+
+fn piece_for_letter(letter: char) -> Option<PieceSelector> {
+    Some(match letter {
+        'I' => PieceSelector::Line,
+        'J' => PieceSelector::J,
+        'L' => PieceSelector::L,
+        'O' => PieceSelector::O,
+        'S' => PieceSelector::S,
+        'T' => PieceSelector::T,
+        'Z' => PieceSelector::Z,
+        _ => return None,
+    })
+}
+
+fn parse_goal(words: &[&str]) -> Option<Goal> {
+    let number = |word: &str| word.parse::<usize>().ok().filter(|&number| number > 0);
+    match *words {
+        ["lines", lines, "in", pieces] => Some(Goal::ClearLines {
+            lines: number(lines)?,
+            pieces: number(pieces)?,
+        }),
+        ["tspin", "in", pieces] => Some(Goal::TSpin {
+            pieces: number(pieces)?,
+        }),
+        _ => None,
+    }
+}
+
+impl Puzzle {
+    pub fn parse(text: &str) -> Result<Self, PuzzleError> {
+        let (width, height) = GRID_SIZE;
+        let (mut name, mut goal, mut pieces) = (None, None, None);
+        let mut rows = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |message: String| PuzzleError {
+                line: index + 1,
+                message,
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with(['#', '.']) {
+                if line.len() != width || !line.chars().all(|cell| cell == '#' || cell == '.') {
+                    return Err(error(format!(
+                        "Row \"{}\" is not {} cells wide",
+                        line, width
+                    )));
+                }
+                rows.push(line.chars().map(|cell| cell == '#').collect());
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "name" => name = Some(String::from(line["name".len()..].trim())),
+                "goal" => {
+                    goal = Some(
+                        parse_goal(&words[1..])
+                            .ok_or_else(|| error(format!("Unknown goal \"{}\"", line)))?,
+                    )
+                }
+                "pieces" => {
+                    pieces = Some(
+                        words[1..]
+                            .iter()
+                            .flat_map(|word| word.chars())
+                            .map(|letter| {
+                                piece_for_letter(letter)
+                                    .ok_or_else(|| error(format!("Unknown piece '{}'", letter)))
+                            })
+                            .collect::<Result<Vec<_>, _>>()?,
+                    )
+                }
+                key => return Err(error(format!("Unknown key \"{}\"", key))),
+            }
+        }
+
+        let missing = |what: &str| PuzzleError {
+            line: text.lines().count(),
+            message: format!("The puzzle has no {}", what),
+        };
+        let pieces: Vec<_> = pieces
+            .filter(|pieces| !pieces.is_empty())
+            .ok_or_else(|| missing("pieces"))?;
+        if rows.len() >= height {
+            return Err(missing("room above its stack"));
+        }
+        rows.reverse();
+        Ok(Puzzle {
+            name: name.ok_or_else(|| missing("name"))?,
+            goal: goal.ok_or_else(|| missing("goal"))?,
+            pieces,
+            stack: rows,
+        })
+    }
+}
+
+/// How a puzzle is going.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Playing,
+    Solved,
+    Failed,
+}
+
+/// A puzzle being played, dealing its pieces in order and checking the goal as each one locks.
+pub struct PuzzleGame {
+    tetris: Tetris,
+    goal: Goal,
+    pieces: Vec<PieceSelector>,
+    /// The index into pieces of the piece last put into next.
+    queued: usize,
+    pieces_placed: usize,
+    lines: usize,
+    outcome: Outcome,
+}
+
+impl PuzzleGame {
+    pub fn new(puzzle: &Puzzle) -> Self {
+        let mut tetris = Tetris::new_with_seed(0);
+        if let Tetris::Running(ref mut state) = tetris {
+            for y in 0..state.grid.height {
+                let row = puzzle.stack.get(y);
+                for (x, cell) in state.grid.row_mut(y).iter_mut().enumerate() {
+                    *cell = row.is_some_and(|row| row[x]);
+                }
+            }
+            state.piece = spawn_piece(puzzle.pieces[0]);
+            if let Some(next) = puzzle.pieces.get(1) {
+                state.next_piece = spawn_piece(*next);
+            }
+        }
+        PuzzleGame {
+            tetris,
+            goal: puzzle.goal,
+            pieces: puzzle.pieces.clone(),
+            queued: 1,
+            pieces_placed: 0,
+            lines: 0,
+            outcome: Outcome::Playing,
+        }
+    }
+
+    pub fn tetris(&self) -> &Tetris {
+        &self.tetris
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    pub fn set_key_state(&mut self, key_state: &KeyState) {
+        self.tetris.set_key_state(key_state);
+    }
+
+    /// Update the game until the puzzle is solved or failed, after which it is left as it was.
+    pub fn update(&mut self) -> Outcome {
+        if self.outcome != Outcome::Playing {
+            return self.outcome;
+        }
+        self.tetris.update();
+
+        let Tetris::Running(ref mut state) = self.tetris else {
+            self.outcome = Outcome::Failed;
+            return self.outcome;
+        };
+
+        // Pieces are dealt from the puzzle while it has any left
+        let wanted = state.pieces_dealt() + 1;
+        if wanted > self.queued && wanted < self.pieces.len() {
+            state.next_piece = spawn_piece(self.pieces[wanted]);
+            self.queued = wanted;
+        }
+
+        if state.piece_locked() {
+            self.pieces_placed += 1;
+            self.lines += state.rows_cleared();
+            let solved = match self.goal {
+                Goal::ClearLines { lines, .. } => self.lines >= lines,
+                Goal::TSpin { .. } => state.t_spin(),
+            };
+            if solved {
+                self.outcome = Outcome::Solved;
+            } else if self.pieces_placed >= self.goal.pieces().min(self.pieces.len()) {
+                self.outcome = Outcome::Failed;
+            }
+        }
+        self.outcome
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::piece::PieceSelector;
+    use crate::puzzle::{Goal, Outcome, Puzzle, PuzzleGame, PUZZLES};
+    use crate::tetris::{KeyState, Tetris};
+
+    /// Play a puzzle with a key state for each update, the last held until the puzzle ends.
+    fn play(puzzle: &Puzzle, inputs: &[KeyState]) -> Outcome {
+        let mut game = PuzzleGame::new(puzzle);
+        let mut outcome = Outcome::Playing;
+        let last = inputs.last().copied().unwrap_or_default();
+        for input in inputs.iter().chain(core::iter::repeat(&last)).take(1000) {
+            game.set_key_state(input);
+            outcome = game.update();
+            if outcome != Outcome::Playing {
+                break;
+            }
+        }
+        outcome
+    }
+
+    const RIGHT: KeyState = KeyState {
+        left: false,
+        right: true,
+        rotate: false,
+        rotate_ccw: false,
+        rotate_180: false,
+        hard_drop: false,
+        soft_drop: false,
+        hold: false,
+    };
+
+    #[test]
+    fn every_built_in_puzzle_parses() {
+        for text in PUZZLES {
+            Puzzle::parse(text).unwrap();
+        }
+    }
+
+    #[test]
+    fn a_puzzle_is_read_from_text() {
+        let puzzle = Puzzle::parse(PUZZLES[1]).unwrap();
+        assert!(puzzle.name == "Two by two");
+        assert!(
+            puzzle.goal
+                == Goal::ClearLines {
+                    lines: 2,
+                    pieces: 2
+                }
+        );
+        assert!(puzzle.pieces == [PieceSelector::O, PieceSelector::O]);
+        assert!(puzzle.stack.len() == 3);
+        assert!(!puzzle.stack[0][2] && puzzle.stack[0][4]);
+    }
+
+    #[test]
+    fn mistakes_are_reported_with_their_line() {
+        let error = Puzzle::parse("name Broken\ngoal lines 2\npieces I").unwrap_err();
+        assert!(error.line == 2);
+        assert!(
+            Puzzle::parse("name Broken\ngoal tspin in 1\npieces Q")
+                .unwrap_err()
+                .line
+                == 3
+        );
+        assert!(Puzzle::parse("name Broken\ngoal tspin in 1").is_err());
+    }
+
+    #[test]
+    fn reaching_the_goal_solves_the_puzzle() {
+        let puzzle = Puzzle::parse(PUZZLES[0]).unwrap();
+        // The line stands up and goes to the far right
+        let inputs = [
+            KeyState {
+                rotate: true,
+                ..RIGHT
+            },
+            RIGHT,
+        ];
+        assert!(play(&puzzle, &inputs) == Outcome::Solved);
+    }
+
+    #[test]
+    fn a_t_spin_puzzle_needs_the_last_move_to_be_a_rotation() {
+        let puzzle = Puzzle::parse(PUZZLES[2]).unwrap();
+        let rotate_ccw = KeyState {
+            rotate_ccw: true,
+            ..KeyState::default()
+        };
+        let left = KeyState {
+            left: true,
+            ..KeyState::default()
+        };
+        let none = KeyState::default();
+        // Point the T left, line it up over the slot and turn it back into it once it lands
+        let mut inputs = [none; 20];
+        inputs[..2].copy_from_slice(&[rotate_ccw, left]);
+        // It lands after 19 updates and locks on the next
+        inputs[19] = rotate_ccw;
+        assert!(play(&puzzle, &inputs) == Outcome::Solved);
+
+        // Dropped in without the rotation it only clears a single
+        assert!(play(&puzzle, &inputs[..19]) == Outcome::Failed);
+    }
+
+    #[test]
+    fn running_out_of_pieces_fails_the_puzzle() {
+        let puzzle = Puzzle::parse(PUZZLES[0]).unwrap();
+        assert!(play(&puzzle, &[KeyState::default()]) == Outcome::Failed);
+    }
+
+    #[test]
+    fn pieces_are_dealt_in_order() {
+        let puzzle = Puzzle::parse("name Pieces\ngoal lines 4 in 3\npieces T S Z").unwrap();
+        let mut game = PuzzleGame::new(&puzzle);
+        let mut kinds = [None; 3];
+        for _ in 0..100 {
+            game.set_key_state(&KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            });
+            game.update();
+            game.set_key_state(&KeyState::default());
+            if let Tetris::Running(ref state) = game.tetris() {
+                if let Some(kind) = kinds.get_mut(state.pieces_dealt()) {
+                    *kind = Some(state.piece.kind());
+                }
+            }
+            if game.outcome() != Outcome::Playing {
+                break;
+            }
+        }
+        assert!(kinds[1] == Some(PieceSelector::S) && kinds[2] == Some(PieceSelector::Z));
+        assert!(game.outcome() == Outcome::Failed);
+    }
+}
@@ -0,0 +1,443 @@
+//! A versioned file format for replays, the same on the Pico's flash as on disk so that a game
+//! recorded by one frontend can be watched on another. Only the updates where the keys held
+//! change are stored, and a checksum of the state the game ended in shows whether a replay
+//! still plays out the same on this version of the core.
+//!
+//! The layout, with every number little endian:
+//!
+//! ```text
+//! "TRPL" version mode [date] seed:u64 rules updates:u32 checksum:u32 changes:u32
+//! then for each change: update:u32 keys:u8
+//! ```
+
+use crate::analysis::Replay;
+use crate::daily::Date;
+use crate::tetris::{KeyState, Rules, Tetris, TetrisState};
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+const MAGIC: &[u8; 4] = b"TRPL";
+/// The version written, files of a later version are refused.
+pub const VERSION: u8 = 6;
+/// Bytes of rules in version 1 files, from before the delayed auto shift and auto repeat rate,
+/// which they are read without.
+const VERSION_1_RULES_LEN: usize = 9;
+/// Bytes of rules in version 2 files, from before game modes, which are read as endless.
+const VERSION_2_RULES_LEN: usize = 11;
+/// Bytes of rules in version 3 files, from before the countdown, which they start without.
+const VERSION_3_RULES_LEN: usize = 12;
+/// Bytes of rules in version 4 files, from before the preview queue, which show the next piece
+/// alone.
+const VERSION_4_RULES_LEN: usize = 13;
+/// Bytes of rules in version 5 files, from before the top out rule, which only end on a block
+/// out.
+const VERSION_5_RULES_LEN: usize = 14;
+
+const MODE_MARATHON: u8 = 0;
+const MODE_DAILY: u8 = 1;
+
+/// FNV-1a, small enough for the Pico and stable across platforms.
+const FNV_OFFSET: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// How the game was started, for frontends to show alongside the replay.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Mode {
+    Marathon,
+    /// The daily challenge of that day.
+    Daily(Date),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ReplayError {
+    /// The data does not start as a replay does.
+    NotAReplay,
+    /// Written by a later version of the format.
+    UnsupportedVersion(u8),
+    UnknownMode(u8),
+    /// The data ended early.
+    Truncated,
+    /// A key change is at an update before the previous change or after the last update.
+    Invalid,
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAReplay => write!(f, "not a replay"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "replay version {} is newer than this one", version)
+            }
+            Self::UnknownMode(mode) => write!(f, "unknown game mode {}", mode),
+            Self::Truncated => write!(f, "the replay ended early"),
+            Self::Invalid => write!(f, "the replay holds an invalid value"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReplayError {}
+
+/// A replay as saved, with what it needs to be checked when it is played back.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ReplayFile {
+    pub mode: Mode,
+    pub replay: Replay,
+    /// The checksum of the state the game ended in when it was recorded.
+    pub checksum: u32,
+}
+
+impl ReplayFile {
+    /// Save replay, playing it through to find the checksum of the state it ends in.
+    pub fn new(mode: Mode, replay: Replay) -> Self {
+        let checksum = play(&replay).1;
+        ReplayFile {
+            mode,
+            replay,
+            checksum,
+        }
+    }
+
+    /// Play the replay again, returning false if it ends in a different state than when it was
+    /// recorded, such as after the rules of the game have changed.
+    pub fn verify(&self) -> bool {
+        play(&self.replay).1 == self.checksum
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        match self.mode {
+            Mode::Marathon => out.push(MODE_MARATHON),
+            Mode::Daily(date) => {
+                out.push(MODE_DAILY);
+                out.extend_from_slice(&date.year.to_le_bytes());
+                out.extend_from_slice(&[date.month, date.day]);
+            }
+        }
+        out.extend_from_slice(&self.replay.seed.to_le_bytes());
+        out.extend_from_slice(&self.replay.rules.to_bytes());
+        out.extend_from_slice(&(self.replay.inputs.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.checksum.to_le_bytes());
+
+        let mut held = KeyState::default();
+        let changes: Vec<(usize, &KeyState)> = (self.replay.inputs.iter().enumerate())
+            .filter(|&(_, keys)| core::mem::replace(&mut held, *keys) != *keys)
+            .collect();
+        out.extend_from_slice(&(changes.len() as u32).to_le_bytes());
+        for (update, keys) in changes {
+            out.extend_from_slice(&(update as u32).to_le_bytes());
+            out.push(keys.to_bits());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        let mut reader = Reader { bytes };
+        if reader.array::<4>() != Ok(*MAGIC) {
+            return Err(ReplayError::NotAReplay);
+        }
+        let [version] = reader.array()?;
+        if version > VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        let mode = match reader.array()? {
+            [MODE_MARATHON] => Mode::Marathon,
+            [MODE_DAILY] => {
+                let [year_low, year_high, month, day] = reader.array()?;
+                Mode::Daily(Date {
+                    year: u16::from_le_bytes([year_low, year_high]),
+                    month,
+                    day,
+                })
+            }
+            [mode] => return Err(ReplayError::UnknownMode(mode)),
+        };
+        let seed = u64::from_le_bytes(reader.array()?);
+        let rules = match version {
+            1 => padded_rules(reader.array::<VERSION_1_RULES_LEN>()?),
+            2 => padded_rules(reader.array::<VERSION_2_RULES_LEN>()?),
+            3 => padded_rules(reader.array::<VERSION_3_RULES_LEN>()?),
+            4 => padded_rules(reader.array::<VERSION_4_RULES_LEN>()?),
+            5 => padded_rules(reader.array::<VERSION_5_RULES_LEN>()?),
+            _ => Rules::from_bytes(&reader.array()?),
+        };
+        let updates = reader.u32()? as usize;
+        let checksum = reader.u32()?;
+
+        let changes = reader.u32()?;
+        let mut inputs = Vec::new();
+        let mut held = KeyState::default();
+        for _ in 0..changes {
+            let update = reader.u32()? as usize;
+            let [bits] = reader.array()?;
+            if update < inputs.len() || update >= updates {
+                return Err(ReplayError::Invalid);
+            }
+            inputs.resize(update, held);
+            held = KeyState::from_bits(bits);
+            inputs.push(held);
+        }
+        inputs.resize(updates, held);
+
+        Ok(ReplayFile {
+            mode,
+            replay: Replay {
+                seed,
+                rules,
+                inputs,
+            },
+            checksum,
+        })
+    }
+}
+
+/// Rules saved by an earlier version, padded with zeros so the rules added since are read as
+/// their defaults.
+fn padded_rules<const N: usize>(saved: [u8; N]) -> Rules {
+    let mut rules = [0; Rules::ENCODED_LEN];
+    rules[..N].copy_from_slice(&saved);
+    Rules::from_bytes(&rules)
+}
+
+/// Reads fields from the front of a replay.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        if self.bytes.len() < N {
+            return Err(ReplayError::Truncated);
+        }
+        let (field, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        let mut array = [0; N];
+        array.copy_from_slice(field);
+        Ok(array)
+    }
+
+    fn u32(&mut self) -> Result<u32, ReplayError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+}
+
+/// Play a replay through, returning the game as it ends and the checksum of the last state it
+/// was running in.
+pub fn play(replay: &Replay) -> (Tetris, u32) {
+    let mut tetris = Tetris::new_with_seed(replay.seed);
+    tetris.set_rules(replay.rules);
+    let mut last = FNV_OFFSET;
+    for keys in &replay.inputs {
+        tetris.set_key_state(keys);
+        tetris.update();
+        if let Tetris::Running(ref state) = tetris {
+            last = checksum(state);
+        }
+    }
+    (tetris, last)
+}
+
+/// A checksum of the score, the stack and the falling and next pieces.
+pub fn checksum(state: &TetrisState) -> u32 {
+    let piece = &state.piece;
+    let fields = [
+        &(state.score as u64).to_le_bytes()[..],
+        &[
+            piece.kind() as u8,
+            piece.rotation() as u8,
+            piece.x as u8,
+            piece.y as u8,
+            state.next_piece.kind() as u8,
+        ],
+    ];
+    let cells = (0..state.grid.height)
+        .flat_map(|y| state.grid.row(y).iter())
+        .map(|&filled| filled as u8);
+    fields
+        .into_iter()
+        .flatten()
+        .copied()
+        .chain(cells)
+        .fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(FNV_PRIME)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::analysis::Replay;
+    use crate::daily::Date;
+    use crate::mode::GameMode;
+    use crate::replay::{
+        Mode, ReplayError, ReplayFile, VERSION, VERSION_1_RULES_LEN, VERSION_2_RULES_LEN,
+        VERSION_3_RULES_LEN, VERSION_4_RULES_LEN, VERSION_5_RULES_LEN,
+    };
+    use crate::tetris::{KeyState, Rules, TopOutRule};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Where the rules start in a replay, after the magic, version, mode and seed.
+    const RULES_START: usize = 4 + 1 + 1 + 8;
+
+    fn replay() -> Replay {
+        let left = KeyState {
+            left: true,
+            ..KeyState::default()
+        };
+        let drop = KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        };
+        let mut inputs = vec![left, left, KeyState::default(), drop];
+        inputs.extend([KeyState::default(); 20]);
+        inputs.push(drop);
+        Replay {
+            seed: 42,
+            rules: Rules {
+                wrap: true,
+                das: 2,
+                arr: 1,
+                ..Rules::default()
+            },
+            inputs,
+        }
+    }
+
+    #[test]
+    fn a_replay_survives_being_saved() {
+        let file = ReplayFile::new(
+            Mode::Daily(Date {
+                year: 2026,
+                month: 10,
+                day: 16,
+            }),
+            replay(),
+        );
+        let bytes = file.to_bytes();
+        let read = ReplayFile::from_bytes(&bytes).unwrap();
+        assert!(read == file);
+        assert!(read.verify());
+    }
+
+    #[test]
+    fn version_1_replays_are_read_without_the_auto_shift() {
+        let mut bytes = ReplayFile::new(Mode::Marathon, replay()).to_bytes();
+        // Version 1 rules stop short of the delayed auto shift and auto repeat rate
+        bytes.drain(RULES_START + VERSION_1_RULES_LEN..RULES_START + Rules::ENCODED_LEN);
+        bytes[4] = 1;
+
+        let read = ReplayFile::from_bytes(&bytes).unwrap();
+        assert!(read.replay.inputs == replay().inputs);
+        assert!(
+            read.replay.rules
+                == Rules {
+                    das: 0,
+                    arr: 0,
+                    ..replay().rules
+                }
+        );
+    }
+
+    #[test]
+    fn version_2_replays_are_read_as_endless() {
+        let rules = Rules {
+            mode: GameMode::Sprint,
+            ..replay().rules
+        };
+        let mut bytes = ReplayFile::new(Mode::Marathon, Replay { rules, ..replay() }).to_bytes();
+        bytes.drain(RULES_START + VERSION_2_RULES_LEN..RULES_START + Rules::ENCODED_LEN);
+        bytes[4] = 2;
+
+        let read = ReplayFile::from_bytes(&bytes).unwrap();
+        assert!(read.replay.rules == replay().rules);
+    }
+
+    #[test]
+    fn version_3_replays_start_without_a_countdown() {
+        let rules = Rules {
+            mode: GameMode::Ultra,
+            countdown: 12,
+            ..replay().rules
+        };
+        let mut bytes = ReplayFile::new(Mode::Marathon, Replay { rules, ..replay() }).to_bytes();
+        bytes.drain(RULES_START + VERSION_3_RULES_LEN..RULES_START + Rules::ENCODED_LEN);
+        bytes[4] = 3;
+
+        let read = ReplayFile::from_bytes(&bytes).unwrap();
+        assert!(
+            read.replay.rules
+                == Rules {
+                    countdown: 0,
+                    ..rules
+                }
+        );
+    }
+
+    #[test]
+    fn version_4_replays_show_the_next_piece_alone() {
+        let rules = Rules {
+            countdown: 12,
+            previews: 4,
+            ..replay().rules
+        };
+        let mut bytes = ReplayFile::new(Mode::Marathon, Replay { rules, ..replay() }).to_bytes();
+        bytes.drain(RULES_START + VERSION_4_RULES_LEN..RULES_START + Rules::ENCODED_LEN);
+        bytes[4] = 4;
+
+        let read = ReplayFile::from_bytes(&bytes).unwrap();
+        assert!(
+            read.replay.rules
+                == Rules {
+                    previews: 0,
+                    ..rules
+                }
+        );
+        assert!(read.verify());
+    }
+
+    #[test]
+    fn version_5_replays_only_end_on_a_block_out() {
+        let rules = Rules {
+            top_out: TopOutRule::Garbage,
+            ..replay().rules
+        };
+        let mut bytes = ReplayFile::new(Mode::Marathon, Replay { rules, ..replay() }).to_bytes();
+        bytes.drain(RULES_START + VERSION_5_RULES_LEN..RULES_START + Rules::ENCODED_LEN);
+        bytes[4] = 5;
+
+        let read = ReplayFile::from_bytes(&bytes).unwrap();
+        assert!(
+            read.replay.rules
+                == Rules {
+                    top_out: TopOutRule::BlockOut,
+                    ..rules
+                }
+        );
+        assert!(read.verify());
+    }
+
+    #[test]
+    fn a_replay_that_plays_differently_fails_to_verify() {
+        let mut file = ReplayFile::new(Mode::Marathon, replay());
+        file.replay.inputs.truncate(3);
+        assert!(!file.verify());
+    }
+
+    #[test]
+    fn damaged_replays_are_refused() {
+        let bytes = ReplayFile::new(Mode::Marathon, replay()).to_bytes();
+        assert!(ReplayFile::from_bytes(b"TRIP") == Err(ReplayError::NotAReplay));
+        assert!(ReplayFile::from_bytes(&bytes[..bytes.len() - 1]) == Err(ReplayError::Truncated));
+
+        let mut newer: Vec<u8> = bytes.clone();
+        newer[4] = VERSION + 1;
+        assert!(
+            ReplayFile::from_bytes(&newer) == Err(ReplayError::UnsupportedVersion(VERSION + 1))
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! How points are awarded, so that frontends and modes can score differently without changing
+//! how rows are cleared. Rules pick one of the built in policies, and a mode with its own can
+//! hand it to TetrisState::set_scoring_policy.
+
+use core::fmt;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Lines cleared between one level and the next.
+pub const LINES_PER_LEVEL: usize = 10;
+
+/// What a piece did as it locked, for a policy to score.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LineClear {
+    /// Rows completed by the piece, zero if it completed none.
+    pub rows: usize,
+    /// Whether the piece was a T locked with a T-spin.
+    pub t_spin: bool,
+    /// Lines cleared before this piece divided by LINES_PER_LEVEL, starting from zero.
+    pub level: usize,
+    /// Pieces in a row before this one that cleared rows, zero for a clear after one that did
+    /// not.
+    pub combo: usize,
+    /// Whether this and the previous clear were both difficult, four rows or a T-spin.
+    pub back_to_back: bool,
+    /// Whether the rows cleared were all that was left, leaving the playfield empty.
+    pub perfect_clear: bool,
+    /// Cells in a row of the playfield.
+    pub width: usize,
+}
+
+impl LineClear {
+    /// Four rows at once or a T-spin that cleared rows, which keeps a back to back going.
+    pub fn is_difficult(&self) -> bool {
+        self.rows >= 4 || (self.t_spin && self.rows > 0)
+    }
+}
+
+/// Points for what a game does. Called once for every piece that locks, including those that
+/// clear nothing, and once for every update that drops the falling piece.
+pub trait ScoringPolicy: fmt::Debug {
+    /// Points for a piece locking.
+    fn clear(&self, clear: &LineClear) -> usize;
+
+    /// Points for the falling piece being moved down cells rows by a hard drop or soft drop.
+    fn drop(&self, cells: usize, hard_drop: bool) -> usize;
+}
+
+/// The policy the game has always used: the square of the rows cleared times the width of the
+/// playfield in thousands, ignoring level, combos and T-spins. A perfect clear scores double.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Classic;
+
+// The minimum score for removing a single grid piece
+const BASE_SCORE_UNIT: usize = 1000;
+
+// Points for each cell a piece falls while soft dropped or is moved by a hard drop
+const SOFT_DROP_SCORE: usize = 1;
+const HARD_DROP_SCORE: usize = 2;
+
+impl ScoringPolicy for Classic {
+    fn clear(&self, clear: &LineClear) -> usize {
+        // Combo by squaring rows, you double the base row score for each additional row you
+        // clear.
+        let score = clear.rows * clear.rows * clear.width * BASE_SCORE_UNIT;
+        match clear.perfect_clear {
+            true => score * 2,
+            false => score,
+        }
+    }
+
+    fn drop(&self, cells: usize, hard_drop: bool) -> usize {
+        cells
+            * match hard_drop {
+                true => HARD_DROP_SCORE,
+                false => SOFT_DROP_SCORE,
+            }
+    }
+}
+
+/// Scoring from the Tetris guideline: clears and T-spins scaled by level, half again for a back
+/// to back and a bonus for each piece of a combo and for a perfect clear.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Guideline;
+
+impl ScoringPolicy for Guideline {
+    fn clear(&self, clear: &LineClear) -> usize {
+        let base = match (clear.t_spin, clear.rows) {
+            (false, 0) => 0,
+            (false, 1) => 100,
+            (false, 2) => 300,
+            (false, 3) => 500,
+            (false, _) => 800,
+            (true, rows) => 400 * (rows.min(3) + 1),
+        };
+        let base = match clear.back_to_back {
+            true => base * 3 / 2,
+            false => base,
+        };
+        let combo = match clear.rows {
+            0 => 0,
+            _ => 50 * clear.combo,
+        };
+        let perfect_clear = match (clear.perfect_clear, clear.rows) {
+            (false, _) | (true, 0) => 0,
+            (true, 1) => 800,
+            (true, 2) => 1200,
+            (true, 3) => 1800,
+            (true, _) => match clear.back_to_back {
+                true => 3200,
+                false => 2000,
+            },
+        };
+        (base + combo + perfect_clear) * (clear.level + 1)
+    }
+
+    fn drop(&self, cells: usize, hard_drop: bool) -> usize {
+        Classic.drop(cells, hard_drop)
+    }
+}
+
+/// Scoring of the NES game: clears scaled by level and a point a row for soft dropping. The NES
+/// has no hard drop or perfect clear bonus, so they score nothing.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Nes;
+
+impl ScoringPolicy for Nes {
+    fn clear(&self, clear: &LineClear) -> usize {
+        let base = match clear.rows {
+            0 => 0,
+            1 => 40,
+            2 => 100,
+            3 => 300,
+            _ => 1200,
+        };
+        base * (clear.level + 1)
+    }
+
+    fn drop(&self, cells: usize, hard_drop: bool) -> usize {
+        match hard_drop {
+            true => 0,
+            false => cells,
+        }
+    }
+}
+
+/// The built in scoring policies, chosen by the rules of a game.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Scoring {
+    #[default]
+    Classic,
+    Guideline,
+    Nes,
+}
+
+impl Scoring {
+    /// The scoring with name, as given on command lines: classic, guideline or nes.
+    pub fn from_name(name: &str) -> Option<Scoring> {
+        Some(match name {
+            "classic" => Scoring::Classic,
+            "guideline" => Scoring::Guideline,
+            "nes" => Scoring::Nes,
+            _ => return None,
+        })
+    }
+
+    pub fn policy(self) -> &'static dyn ScoringPolicy {
+        match self {
+            Scoring::Classic => &Classic,
+            Scoring::Guideline => &Guideline,
+            Scoring::Nes => &Nes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scoring::{Classic, Guideline, LineClear, Nes, Scoring, ScoringPolicy};
+
+    #[test]
+    fn the_classic_policy_squares_the_rows_cleared() {
+        let clear = LineClear {
+            rows: 2,
+            level: 5,
+            width: 10,
+            ..LineClear::default()
+        };
+        assert!(Classic.clear(&clear) == 40_000);
+        assert!(Classic.drop(3, true) == 6 && Classic.drop(3, false) == 3);
+    }
+
+    #[test]
+    fn scorings_are_found_by_name() {
+        assert!(Scoring::from_name("classic") == Some(Scoring::Classic));
+        assert!(Scoring::from_name("guideline") == Some(Scoring::Guideline));
+        assert!(Scoring::from_name("nes") == Some(Scoring::Nes));
+        assert!(Scoring::from_name("tgm").is_none());
+    }
+
+    #[test]
+    fn the_guideline_policy_rewards_t_spins_back_to_backs_and_combos() {
+        let tetris = LineClear {
+            rows: 4,
+            level: 1,
+            ..LineClear::default()
+        };
+        assert!(Guideline.clear(&tetris) == 1600);
+
+        let t_spin_double = LineClear {
+            rows: 2,
+            t_spin: true,
+            back_to_back: true,
+            combo: 2,
+            ..LineClear::default()
+        };
+        assert!(t_spin_double.is_difficult());
+        assert!(Guideline.clear(&t_spin_double) == 1800 + 100);
+
+        let t_spin = LineClear {
+            t_spin: true,
+            combo: 2,
+            ..LineClear::default()
+        };
+        assert!(!t_spin.is_difficult());
+        assert!(Guideline.clear(&t_spin) == 400);
+    }
+
+    #[test]
+    fn a_perfect_clear_earns_a_bonus() {
+        let tetris = LineClear {
+            rows: 4,
+            width: 10,
+            perfect_clear: true,
+            ..LineClear::default()
+        };
+        assert!(Classic.clear(&tetris) == 320_000);
+        assert!(Guideline.clear(&tetris) == 800 + 2000);
+        let back_to_back = LineClear {
+            back_to_back: true,
+            ..tetris
+        };
+        assert!(Guideline.clear(&back_to_back) == 1200 + 3200);
+        assert!(Nes.clear(&tetris) == 1200);
+    }
+
+    #[test]
+    fn the_nes_policy_scales_by_level_and_ignores_hard_drops() {
+        let clear = LineClear {
+            rows: 4,
+            level: 9,
+            ..LineClear::default()
+        };
+        assert!(Nes.clear(&clear) == 12_000);
+        assert!(Nes.drop(5, true) == 0 && Nes.drop(5, false) == 5);
+    }
+}
@@ -0,0 +1,81 @@
+//! Pieces dealt in a fixed order rather than at random, repeating once they run out, for drilling
+//! openings and T-spin setups. Kept in a fixed array so that it needs no allocator.
+
+use crate::piece::PieceSelector;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The longest sequence of pieces that can be given.
+pub const MAX_SEQUENCE: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PieceSequence {
+    pieces: [PieceSelector; MAX_SEQUENCE],
+    len: usize,
+    /// The piece dealt next.
+    position: usize,
+}
+
+impl PieceSequence {
+    /// A sequence dealing pieces in order, or None if there are none or more than MAX_SEQUENCE.
+    pub fn new(pieces: &[PieceSelector]) -> Option<Self> {
+        if pieces.is_empty() || pieces.len() > MAX_SEQUENCE {
+            return None;
+        }
+        let mut sequence = PieceSequence {
+            pieces: [PieceSelector::Line; MAX_SEQUENCE],
+            len: pieces.len(),
+            position: 0,
+        };
+        sequence.pieces[..pieces.len()].copy_from_slice(pieces);
+        Some(sequence)
+    }
+
+    /// The sequence spelt out by the letters of its pieces, such as TSZ, as given on command
+    /// lines. None if a letter is not a piece.
+    pub fn from_letters(letters: &str) -> Option<Self> {
+        let mut pieces = [PieceSelector::Line; MAX_SEQUENCE];
+        let mut len = 0;
+        for letter in letters.chars() {
+            *pieces.get_mut(len)? = PieceSelector::from_letter(letter)?;
+            len += 1;
+        }
+        Self::new(&pieces[..len])
+    }
+
+    /// Every piece of the sequence, from the start.
+    pub fn pieces(&self) -> &[PieceSelector] {
+        &self.pieces[..self.len]
+    }
+
+    /// Deal the next piece, going back to the start after the last.
+    pub(crate) fn deal(&mut self) -> PieceSelector {
+        let kind = self.pieces[self.position];
+        self.position = (self.position + 1) % self.len;
+        kind
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::piece::PieceSelector;
+    use crate::sequence::{PieceSequence, MAX_SEQUENCE};
+
+    #[test]
+    fn a_sequence_repeats_once_dealt() {
+        let mut sequence = PieceSequence::from_letters("tSz").unwrap();
+        let pieces = [PieceSelector::T, PieceSelector::S, PieceSelector::Z];
+        assert!(sequence.pieces() == pieces);
+        let dealt: [PieceSelector; 4] = core::array::from_fn(|_| sequence.deal());
+        assert!(dealt[..3] == pieces && dealt[3] == PieceSelector::T);
+    }
+
+    #[test]
+    fn sequences_must_be_pieces_and_fit() {
+        assert!(PieceSequence::from_letters("").is_none());
+        assert!(PieceSequence::from_letters("TX").is_none());
+        assert!(PieceSequence::new(&[PieceSelector::O; MAX_SEQUENCE + 1]).is_none());
+        assert!(PieceSequence::new(&[PieceSelector::O; MAX_SEQUENCE]).is_some());
+    }
+}
@@ -0,0 +1,253 @@
+//! Statistics gathered over every game of a session, for frontends to show lifetime stats and to
+//! save between runs.
+
+use crate::drought::Droughts;
+use crate::grade::FINAL_LEVEL;
+use crate::piece::PieceSelector;
+use crate::tetris::Tetris;
+use enum_iterator::{all, cardinality};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Statistics of a single game, gathered by observing it after every update.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameStats {
+    pub score: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub ticks: u32,
+    /// How long each kind of piece went without being dealt, as of the last update observed.
+    pub droughts: Droughts,
+    /// Pieces of each kind placed.
+    pub piece_counts: PieceCounts,
+}
+
+impl GameStats {
+    /// The arcade level reached, going up by one for each piece placed and each line cleared
+    /// and stopping at the final level.
+    pub fn level(&self) -> u32 {
+        self.pieces.saturating_add(self.lines).min(FINAL_LEVEL)
+    }
+
+    /// Count an update of the game. Call after every update, including the one that ends it.
+    pub fn observe(&mut self, tetris: &Tetris) {
+        self.ticks += 1;
+        if let Tetris::Running(ref state) = tetris {
+            self.score = state.score as u32;
+            self.lines += state.rows_cleared() as u32;
+            self.pieces += state.piece_locked() as u32;
+            self.droughts = *state.droughts();
+            self.piece_counts = state.stats().piece_counts;
+        }
+    }
+}
+
+/// How many of each kind of piece were placed in a game, the statistics panel of the NES game.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PieceCounts([u32; cardinality::<PieceSelector>()]);
+
+impl PieceCounts {
+    pub fn count(&self, kind: PieceSelector) -> u32 {
+        self.0[kind as usize]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.0.iter().sum()
+    }
+
+    /// Each kind of piece with its count, in piece order.
+    pub fn iter(&self) -> impl Iterator<Item = (PieceSelector, u32)> + '_ {
+        all::<PieceSelector>().map(|kind| (kind, self.count(kind)))
+    }
+
+    pub(crate) fn add(&mut self, kind: PieceSelector) {
+        self.0[kind as usize] = self.0[kind as usize].saturating_add(1);
+    }
+}
+
+/// Statistics kept by the game itself as it is played, read with TetrisState::stats. Unlike
+/// GameStats they miss nothing when a frontend runs several updates between looks at the game.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PlayStats {
+    /// Pieces of each kind placed.
+    pub piece_counts: PieceCounts,
+    pub lines: u32,
+    /// Singles, doubles, triples and tetrises, any larger step of a cascade counting as a
+    /// tetris.
+    pub clears: [u32; 4],
+    /// Updates played, the countdown to the start not included.
+    pub ticks: u32,
+}
+
+impl PlayStats {
+    pub fn pieces(&self) -> u32 {
+        self.piece_counts.total()
+    }
+
+    /// Clears of rows rows at once, from one for singles to four for tetrises.
+    pub fn clears_of(&self, rows: usize) -> u32 {
+        match rows {
+            0 => 0,
+            rows => self.clears[rows.min(self.clears.len()) - 1],
+        }
+    }
+
+    /// Pieces placed per update, multiplied by the updates a second for pieces per second.
+    pub fn pieces_per_update(&self) -> f32 {
+        match self.ticks {
+            0 => 0.0,
+            ticks => self.pieces() as f32 / ticks as f32,
+        }
+    }
+
+    pub(crate) fn add_clear(&mut self, rows: usize) {
+        if rows > 0 {
+            self.lines = self.lines.saturating_add(rows as u32);
+            let clears = &mut self.clears[rows.min(4) - 1];
+            *clears = clears.saturating_add(1);
+        }
+    }
+}
+
+/// Totals over every game recorded, kept small and fixed size so it can be saved to flash.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Session {
+    pub games_played: u32,
+    pub best_score: u32,
+    pub total_lines: u32,
+    pub total_pieces: u32,
+    pub total_ticks: u32,
+}
+
+impl Session {
+    /// Bytes taken by the saved form from to_bytes.
+    pub const ENCODED_LEN: usize = 20;
+
+    pub fn new() -> Self {
+        Session::default()
+    }
+
+    /// Add a finished game to the totals.
+    pub fn record(&mut self, game: &GameStats) {
+        self.games_played = self.games_played.saturating_add(1);
+        self.best_score = self.best_score.max(game.score);
+        self.total_lines = self.total_lines.saturating_add(game.lines);
+        self.total_pieces = self.total_pieces.saturating_add(game.pieces);
+        self.total_ticks = self.total_ticks.saturating_add(game.ticks);
+    }
+
+    /// Pieces placed per second over every game, for a game updated ticks_per_second times a
+    /// second.
+    pub fn average_pps(&self, ticks_per_second: u32) -> f32 {
+        match self.total_ticks {
+            0 => 0.0,
+            ticks => self.total_pieces as f32 * ticks_per_second as f32 / ticks as f32,
+        }
+    }
+
+    fn fields(&self) -> [u32; 5] {
+        [
+            self.games_played,
+            self.best_score,
+            self.total_lines,
+            self.total_pieces,
+            self.total_ticks,
+        ]
+    }
+
+    /// The session as little endian fields, for platforms without serde.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(self.fields()) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let mut fields = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+        let mut field = || fields.next().unwrap_or(0);
+        Session {
+            games_played: field(),
+            best_score: field(),
+            total_lines: field(),
+            total_pieces: field(),
+            total_ticks: field(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::piece::PieceSelector;
+    use crate::session::{GameStats, Session};
+    use crate::tetris::{KeyState, Tetris};
+
+    #[test]
+    fn a_game_is_counted_as_it_is_played() {
+        let mut tetris = Tetris::new_with_seed(4);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..2 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+
+        let mut stats = GameStats::default();
+        tetris.update();
+        stats.observe(&tetris);
+        assert!(stats.pieces == 1 && stats.lines == 2 && stats.ticks == 1);
+        assert!(stats.piece_counts.count(PieceSelector::O) == 1);
+        assert!(stats.piece_counts.total() == stats.pieces);
+        assert!(stats.score > 0);
+        if let Tetris::Running(ref state) = tetris {
+            assert!(stats.droughts == *state.droughts());
+        }
+    }
+
+    #[test]
+    fn games_add_up_over_a_session() {
+        let mut session = Session::new();
+        session.record(&GameStats {
+            score: 500,
+            lines: 4,
+            pieces: 30,
+            ticks: 60,
+            ..GameStats::default()
+        });
+        session.record(&GameStats {
+            score: 200,
+            lines: 1,
+            pieces: 10,
+            ticks: 20,
+            ..GameStats::default()
+        });
+        assert!(session.games_played == 2);
+        assert!(session.best_score == 500);
+        assert!(session.total_lines == 5);
+        assert!(session.average_pps(20) == 10.0);
+        assert!(Session::new().average_pps(20) == 0.0);
+    }
+
+    #[test]
+    fn a_session_round_trips_through_bytes() {
+        let session = Session {
+            games_played: 3,
+            best_score: 120_000,
+            total_lines: 42,
+            total_pieces: 400,
+            total_ticks: u32::MAX,
+        };
+        assert!(Session::from_bytes(&session.to_bytes()) == session);
+    }
+}
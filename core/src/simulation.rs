@@ -0,0 +1,211 @@
+//! A small scripting layer for whole game scenarios. A script is a list of steps that set up the
+//! board, hold keys, run updates and check the result, so tests read as a description of the
+//! game rather than as a sequence of calls.
+//!
+//! Boards are written as rows of text from top to bottom ending at the floor, with '#' for a
+//! filled cell of the stack, '@' for a cell of the falling piece and '.' for an empty cell.
+
+use crate::piece::PieceSelector;
+use crate::tetris::{KeyState, Phase, Rules, Tetris, TetrisState};
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+/// One step of a script.
+#[derive(Clone, Copy)]
+pub enum ScriptStep<'a> {
+    /// Replace the stack with these rows, clearing every row above them. Only '#' and '.' may
+    /// be used.
+    SetStack(&'a [&'a str]),
+    /// Replace the falling piece with a new piece of this kind in its first rotation, with its
+    /// bottom left cell at (x, y).
+    Spawn(PieceSelector, (usize, usize)),
+    /// Hold these keys for every following update until the next Hold.
+    Hold(KeyState),
+    /// Play by these rules from now on.
+    SetRules(Rules),
+    /// Run this many updates.
+    Tick(usize),
+    /// The bottom rows of the playfield, including the falling piece, must match. Rows above
+    /// those given are not checked.
+    ExpectBoard(&'a [&'a str]),
+    ExpectScore(usize),
+    ExpectFinished,
+}
+
+/// The first step of a script that failed and why.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    /// The index of the step in the script.
+    pub step: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Step {}: {}", self.step, self.message)
+    }
+}
+
+/// A game driven by scripts.
+pub struct Simulation {
+    tetris: Tetris,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::with_tetris(Tetris::new())
+    }
+}
+
+impl Simulation {
+    /// A simulation of a new game with the fixed piece sequence of Tetris::new.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tetris(tetris: Tetris) -> Self {
+        Simulation { tetris }
+    }
+
+    pub fn tetris(&self) -> &Tetris {
+        &self.tetris
+    }
+
+    /// Run a script against a new game, returning the simulation at the end so it can be
+    /// inspected further.
+    pub fn run(steps: &[ScriptStep]) -> Result<Self, ScriptError> {
+        let mut simulation = Self::new();
+        simulation.run_steps(steps)?;
+        Ok(simulation)
+    }
+
+    /// Run a script against the game in its current state, stopping at the first failed step.
+    pub fn run_steps(&mut self, steps: &[ScriptStep]) -> Result<(), ScriptError> {
+        for (index, step) in steps.iter().enumerate() {
+            self.step(step).map_err(|message| ScriptError {
+                step: index,
+                message,
+            })?;
+        }
+        Ok(())
+    }
+
+    fn running(&mut self) -> Result<&mut TetrisState, String> {
+        match self.tetris {
+            Tetris::Running(ref mut state) => Ok(state),
+            Tetris::Paused(_) => Err(String::from("The game is paused")),
+            Tetris::Finished(_) => Err(String::from("The game has finished")),
+        }
+    }
+
+    fn step(&mut self, step: &ScriptStep) -> Result<(), String> {
+        match *step {
+            ScriptStep::SetStack(rows) => {
+                let state = self.running()?;
+                let bottom = bottom_rows(rows, state.grid.width, state.grid.height, "#.")?;
+                for y in 0..state.grid.height {
+                    let row = bottom.get(y);
+                    for (x, cell) in state.grid.row_mut(y).iter_mut().enumerate() {
+                        *cell = row.is_some_and(|row| row[x] == '#');
+                    }
+                }
+            }
+            ScriptStep::Spawn(kind, position) => {
+                let state = self.running()?;
+                state.piece = kind.to_piece(position);
+                state.phase = Phase::Falling;
+            }
+            ScriptStep::Hold(key_state) => self.tetris.set_key_state(&key_state),
+            ScriptStep::SetRules(rules) => self.tetris.set_rules(rules),
+            ScriptStep::Tick(ticks) => {
+                for _ in 0..ticks {
+                    self.tetris.update();
+                }
+            }
+            ScriptStep::ExpectBoard(rows) => {
+                let state = self.running()?;
+                let expected = bottom_rows(rows, state.grid.width, state.grid.height, "#@.")?;
+                let actual: Vec<Vec<char>> = (0..expected.len())
+                    .map(|y| (0..state.grid.width).map(|x| cell(state, x, y)).collect())
+                    .collect();
+                if actual != expected {
+                    return Err(format!(
+                        "Expected the board\n{}\nbut found\n{}",
+                        show(&expected),
+                        show(&actual)
+                    ));
+                }
+            }
+            ScriptStep::ExpectScore(score) => {
+                let actual = self.running()?.score;
+                if actual != score {
+                    return Err(format!(
+                        "Expected a score of {} but found {}",
+                        score, actual
+                    ));
+                }
+            }
+            ScriptStep::ExpectFinished => {
+                if !self.tetris.is_finished() {
+                    return Err(String::from("Expected the game to have finished"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse rows given from top to bottom into rows indexed from the floor up.
+fn bottom_rows(
+    rows: &[&str],
+    width: usize,
+    height: usize,
+    allowed: &str,
+) -> Result<Vec<Vec<char>>, String> {
+    if rows.len() > height {
+        return Err(format!("{} rows do not fit in the playfield", rows.len()));
+    }
+
+    rows.iter()
+        .rev()
+        .map(|row| {
+            let cells: Vec<char> = row.chars().collect();
+            if cells.len() != width {
+                Err(format!("Row \"{}\" is not {} cells wide", row, width))
+            } else if let Some(bad) = cells.iter().find(|cell| !allowed.contains(**cell)) {
+                Err(format!("Unexpected '{}' in row \"{}\"", bad, row))
+            } else {
+                Ok(cells)
+            }
+        })
+        .collect()
+}
+
+/// The character for a cell of the playfield, as used in boards.
+fn cell(state: &TetrisState, x: usize, y: usize) -> char {
+    let in_piece = state.piece_in_play().is_some_and(|piece| {
+        let grid = piece.current_rotation();
+        x.checked_sub(piece.x)
+            .zip(y.checked_sub(piece.y))
+            .is_some_and(|(piece_x, piece_y)| {
+                piece_x < grid.width && piece_y < grid.height && grid[(piece_x, piece_y)]
+            })
+    });
+
+    if state.grid[(x, y)] {
+        '#'
+    } else if in_piece {
+        '@'
+    } else {
+        '.'
+    }
+}
+
+/// Rows indexed from the floor up, shown from top to bottom.
+fn show(rows: &[Vec<char>]) -> String {
+    rows.iter()
+        .rev()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,381 @@
+//! A compact wire format for watching a game from another device. The board, with the falling
+//! piece drawn in, is sent as a keyframe of packed rows followed by deltas that only hold the
+//! rows that changed. Keyframes are repeated regularly so a spectator that joins late or drops
+//! a message catches up.
+//!
+//! Every message is prefixed with its length in a single byte so it can be sent over a stream
+//! such as TCP.
+
+use crate::tetris::{Tetris, TetrisState};
+use alloc::{format, string::String, vec::Vec};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub const MAX_WIDTH: usize = 16;
+pub const MAX_HEIGHT: usize = 32;
+
+/// Deltas sent between keyframes, about five seconds at the device's frame rate.
+pub const KEYFRAME_INTERVAL: u16 = 50;
+
+const TAG_KEYFRAME: u8 = b'K';
+const TAG_DELTA: u8 = b'D';
+const TAG_FINISHED: u8 = b'F';
+
+/// The board as seen by a spectator, one bitmask per row with bit x set for a filled cell in
+/// column x and row 0 at the bottom. Only the visible rows are sent, not the buffer above them.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Board {
+    pub width: usize,
+    pub height: usize,
+    pub rows: [u16; MAX_HEIGHT],
+    pub score: u32,
+}
+
+impl Board {
+    pub fn from_state(state: &TetrisState) -> Self {
+        let (width, height) = (state.grid.width, state.visible_height());
+        assert!(width <= MAX_WIDTH && height <= MAX_HEIGHT);
+
+        let mut rows = [0; MAX_HEIGHT];
+        state.draw_game_grid(
+            |x, y, filled| {
+                if filled {
+                    rows[height - 1 - y] |= 1 << x;
+                }
+            },
+            (0, 0),
+            (1, 1),
+        );
+
+        Board {
+            width,
+            height,
+            rows,
+            score: state.score as u32,
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & (1 << x) != 0
+    }
+
+    /// A bitmask of the rows that differ between the two boards.
+    fn changed_rows(&self, other: &Board) -> u32 {
+        (0..self.height).fold(0, |mask, y| {
+            if self.rows[y] != other.rows[y] {
+                mask | 1 << y
+            } else {
+                mask
+            }
+        })
+    }
+}
+
+/// Packs cells one bit each with no padding between rows.
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    bit: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    fn push_row(&mut self, row: u16, width: usize) {
+        for x in 0..width {
+            if self.bit.is_multiple_of(8) {
+                self.out.push(0);
+            }
+            if row & (1 << x) != 0 {
+                *self.out.last_mut().unwrap() |= 1 << (self.bit % 8);
+            }
+            self.bit += 1;
+        }
+    }
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read_row(&mut self, width: usize) -> Result<u16, String> {
+        let mut row = 0;
+        for x in 0..width {
+            let byte = self
+                .data
+                .get(self.bit / 8)
+                .ok_or_else(|| String::from("Message ended inside the board"))?;
+            if byte & (1 << (self.bit % 8)) != 0 {
+                row |= 1 << x;
+            }
+            self.bit += 1;
+        }
+        Ok(row)
+    }
+}
+
+/// Turns successive game states into messages, sending a delta against the previous board
+/// where possible.
+#[derive(Default)]
+pub struct Encoder {
+    previous: Option<Board>,
+    deltas_since_keyframe: u16,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start again from a keyframe, used when a new spectator connects.
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Append the length prefixed message for the current state of the game to out.
+    pub fn encode(&mut self, tetris: &Tetris, out: &mut Vec<u8>) {
+        let length_at = out.len();
+        out.push(0);
+
+        match tetris {
+            Tetris::Running(state) | Tetris::Paused(state) => {
+                let board = Board::from_state(state);
+                match self.previous {
+                    Some(ref previous)
+                        if previous.width == board.width
+                            && previous.height == board.height
+                            && self.deltas_since_keyframe < KEYFRAME_INTERVAL =>
+                    {
+                        let changed = board.changed_rows(previous);
+                        out.push(TAG_DELTA);
+                        out.extend_from_slice(&changed.to_be_bytes());
+                        out.extend_from_slice(&board.score.to_be_bytes());
+                        let mut writer = BitWriter {
+                            out: &mut *out,
+                            bit: 0,
+                        };
+                        for y in (0..board.height).filter(|y| changed & (1 << y) != 0) {
+                            writer.push_row(board.rows[y], board.width);
+                        }
+                        self.deltas_since_keyframe += 1;
+                    }
+                    _ => {
+                        out.push(TAG_KEYFRAME);
+                        out.push(board.width as u8);
+                        out.push(board.height as u8);
+                        out.extend_from_slice(&board.score.to_be_bytes());
+                        let mut writer = BitWriter {
+                            out: &mut *out,
+                            bit: 0,
+                        };
+                        for y in 0..board.height {
+                            writer.push_row(board.rows[y], board.width);
+                        }
+                        self.deltas_since_keyframe = 0;
+                    }
+                }
+                self.previous = Some(board);
+            }
+            Tetris::Finished(_) => {
+                out.push(TAG_FINISHED);
+                self.previous = None;
+            }
+        }
+
+        out[length_at] = (out.len() - length_at - 1) as u8;
+    }
+}
+
+/// What a spectator currently knows about the game.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum View {
+    /// Nothing can be shown until the first keyframe arrives.
+    Waiting,
+    Playing(Board),
+    /// The game is over, with the last board seen.
+    Finished(Option<Board>),
+}
+
+/// Rebuilds the board on the spectator's side from a stream of messages.
+pub struct Decoder {
+    view: View,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder {
+            view: View::Waiting,
+        }
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+
+    /// Apply a single message, without its length prefix. Deltas that arrive before a keyframe
+    /// are skipped.
+    pub fn decode(&mut self, message: &[u8]) -> Result<(), String> {
+        let (&tag, body) = message
+            .split_first()
+            .ok_or_else(|| String::from("Empty message"))?;
+
+        match tag {
+            TAG_KEYFRAME => {
+                if body.len() < 6 {
+                    return Err(String::from("Keyframe too short"));
+                }
+                let (width, height) = (body[0] as usize, body[1] as usize);
+                if width > MAX_WIDTH || height > MAX_HEIGHT {
+                    return Err(format!("Board of {}x{} is too large", width, height));
+                }
+
+                let mut board = Board {
+                    width,
+                    height,
+                    rows: [0; MAX_HEIGHT],
+                    score: u32::from_be_bytes([body[2], body[3], body[4], body[5]]),
+                };
+                let mut reader = BitReader {
+                    data: &body[6..],
+                    bit: 0,
+                };
+                for y in 0..height {
+                    board.rows[y] = reader.read_row(width)?;
+                }
+                self.view = View::Playing(board);
+            }
+            TAG_DELTA => {
+                if body.len() < 8 {
+                    return Err(String::from("Delta too short"));
+                }
+                let board = match self.view {
+                    View::Playing(ref mut board) => board,
+                    _ => return Ok(()),
+                };
+
+                let changed = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+                board.score = u32::from_be_bytes([body[4], body[5], body[6], body[7]]);
+                let mut reader = BitReader {
+                    data: &body[8..],
+                    bit: 0,
+                };
+                for y in (0..board.height).filter(|y| changed & (1 << y) != 0) {
+                    board.rows[y] = reader.read_row(board.width)?;
+                }
+            }
+            TAG_FINISHED => {
+                let last = match core::mem::replace(&mut self.view, View::Waiting) {
+                    View::Playing(board) => Some(board),
+                    View::Finished(board) => board,
+                    View::Waiting => None,
+                };
+                self.view = View::Finished(last);
+            }
+            _ => return Err(format!("Unknown message tag {}", tag)),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::spectate::{Board, Decoder, Encoder, View, KEYFRAME_INTERVAL};
+    use crate::tetris::{KeyState, Tetris};
+    use alloc::vec::Vec;
+
+    /// Encode the game and feed every message to the decoder, returning the encoded lengths.
+    fn stream(encoder: &mut Encoder, decoder: &mut Decoder, tetris: &Tetris) -> usize {
+        let mut out = Vec::new();
+        encoder.encode(tetris, &mut out);
+        assert!(out[0] as usize == out.len() - 1);
+        decoder.decode(&out[1..]).unwrap();
+        out.len()
+    }
+
+    fn board_of(tetris: &Tetris) -> Board {
+        match tetris {
+            Tetris::Running(state) => Board::from_state(state),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    #[test]
+    fn spectator_follows_the_game() {
+        let mut tetris = Tetris::new();
+        let (mut encoder, mut decoder) = (Encoder::new(), Decoder::new());
+
+        for frame in 0..200 {
+            tetris.set_key_state(&KeyState {
+                left: frame % 3 == 0,
+                rotate: frame % 5 == 0,
+                ..KeyState::default()
+            });
+            tetris.update();
+            stream(&mut encoder, &mut decoder, &tetris);
+
+            if tetris.is_finished() {
+                assert!(matches!(decoder.view(), View::Finished(Some(_))));
+                return;
+            }
+            assert!(*decoder.view() == View::Playing(board_of(&tetris)));
+        }
+    }
+
+    #[test]
+    fn deltas_are_smaller_than_keyframes() {
+        let mut tetris = Tetris::new();
+        let (mut encoder, mut decoder) = (Encoder::new(), Decoder::new());
+
+        let keyframe = stream(&mut encoder, &mut decoder, &tetris);
+        tetris.update();
+        let delta = stream(&mut encoder, &mut decoder, &tetris);
+
+        assert!(delta < keyframe);
+    }
+
+    #[test]
+    fn keyframes_are_repeated() {
+        let tetris = Tetris::new();
+        let (mut encoder, mut decoder) = (Encoder::new(), Decoder::new());
+
+        let keyframe = stream(&mut encoder, &mut decoder, &tetris);
+        let lengths: Vec<usize> = (0..=KEYFRAME_INTERVAL)
+            .map(|_| stream(&mut encoder, &mut decoder, &tetris))
+            .collect();
+
+        assert!(lengths[..KEYFRAME_INTERVAL as usize]
+            .iter()
+            .all(|&len| len < keyframe));
+        assert!(lengths[KEYFRAME_INTERVAL as usize] == keyframe);
+    }
+
+    #[test]
+    fn deltas_before_a_keyframe_are_skipped() {
+        let tetris = Tetris::new();
+        let mut encoder = Encoder::new();
+        let mut out = Vec::new();
+        encoder.encode(&tetris, &mut out);
+        out.clear();
+        encoder.encode(&tetris, &mut out);
+
+        let mut decoder = Decoder::new();
+        decoder.decode(&out[1..]).unwrap();
+        assert!(*decoder.view() == View::Waiting);
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        let mut decoder = Decoder::new();
+        assert!(decoder.decode(&[]).is_err());
+        assert!(decoder.decode(b"K").is_err());
+        assert!(decoder.decode(&[b'K', 10, 20, 0, 0, 0, 0]).is_err());
+        assert!(decoder.decode(b"?").is_err());
+    }
+}
@@ -1,222 +1,3372 @@
+use crate::cascade;
+use crate::drought::Droughts;
+use crate::event::{Events, GameEvent};
+use crate::finesse::{self, Finesse};
 use crate::grid::Grid;
-use crate::piece::Piece;
-use itertools::iproduct;
-use rand::{SeedableRng, rngs::{SmallRng}};
+use crate::item::{self, Item, ROWS_FOR_ITEM, SLOW_DOWN_TICKS};
+use crate::metrics::Metrics;
+use crate::mode::GameMode;
+use crate::piece::{Piece, PieceSelector, Rotation, Turn};
+use crate::scoring::{LineClear, Scoring, ScoringPolicy, LINES_PER_LEVEL};
+use crate::sequence::PieceSequence;
+use crate::session::PlayStats;
+use core::fmt;
+use rand::{prng::XorShiftRng, Rng, SeedableRng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-const GRID_SIZE: (usize, usize) = (10, 20);
-const PIECE_START_LOCATION: (usize, usize) = (5, 19);
+/// Rows of the playfield that are shown.
+pub const VISIBLE_HEIGHT: usize = 20;
+/// Rows hidden above the shown playfield that pieces spawn into, as in the guideline. The stack
+/// can grow into them without the game ending.
+pub const BUFFER_ROWS: usize = 2;
+/// The width and height of the playfield, the hidden buffer rows included.
+pub const GRID_SIZE: (usize, usize) = (10, VISIBLE_HEIGHT + BUFFER_ROWS);
 
-// The minimum score for removing a single grid piece
-const BASE_SCORE_UNIT: usize = 1000;
+/// A piece of kind where pieces spawn: in the first of the buffer rows, horizontally centered or
+/// just left of center when it cannot be.
+pub(crate) fn spawn_piece(kind: PieceSelector) -> Piece {
+    spawn_piece_in(kind, (GRID_SIZE.0, VISIBLE_HEIGHT))
+}
 
-#[derive(Clone, Copy, Default)]
+/// A piece of kind where pieces spawn on a playfield width wide showing visible_height rows.
+fn spawn_piece_in(kind: PieceSelector, (width, visible_height): (usize, usize)) -> Piece {
+    let mut piece = kind.to_piece((0, visible_height));
+    piece.x = width.saturating_sub(piece.current_rotation().width) / 2;
+    piece
+}
+
+// Rotate presses remembered between updates, a fourth would bring the piece back round
+const MAX_BUFFERED_ROTATIONS: i8 = 3;
+
+/// The most pieces the rules can show coming up, the next piece included.
+pub const MAX_PREVIEWS: usize = 6;
+
+/// Whether piece placed at (x, y) overlaps the stack, its columns wrapping round the playfield in
+/// the wrap-around variant.
+fn collides(piece: &Grid, stack: &Grid, at: (usize, usize), wrap: bool) -> bool {
+    match wrap {
+        true => piece.collides_wrapping(stack, at),
+        false => piece.collides(stack, at),
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct KeyState {
     pub left: bool,
     pub right: bool,
+    /// Rotate clockwise.
     pub rotate: bool,
+    pub rotate_ccw: bool,
+    /// Turn halfway round, when the rules allow it.
+    pub rotate_180: bool,
+    pub hard_drop: bool,
+    /// Fall at least as fast as SOFT_DROP_GRAVITY for as long as this is held.
+    pub soft_drop: bool,
+    /// Swap the falling piece with the held piece, once per piece.
+    pub hold: bool,
+}
+
+impl KeyState {
+    /// A bit for each key, as sent over the network and saved in replays.
+    pub fn to_bits(&self) -> u8 {
+        [
+            self.left,
+            self.right,
+            self.rotate,
+            self.hard_drop,
+            self.hold,
+            self.soft_drop,
+            self.rotate_ccw,
+            self.rotate_180,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &held)| bits | (held as u8) << bit)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        KeyState {
+            left: bits & 1 != 0,
+            right: bits & 2 != 0,
+            rotate: bits & 4 != 0,
+            hard_drop: bits & 8 != 0,
+            hold: bits & 16 != 0,
+            soft_drop: bits & 32 != 0,
+            rotate_ccw: bits & 64 != 0,
+            rotate_180: bits & 128 != 0,
+        }
+    }
+}
+
+/// How fast pieces fall, in cells per update as a fixed point number with GRAVITY_ONE being one
+/// cell. Slower than one cell an update carries the fraction over between updates, faster drops
+/// the piece several cells at once, up to the whole playfield at TWENTY_G.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Gravity(pub u16);
+
+/// The fraction bits of Gravity.
+const GRAVITY_SHIFT: u32 = 8;
+
+/// How many times faster than at level zero pieces fall at each level with the speed curve, in
+/// 256ths. The ratios of the guideline's curve from its level 1, so that they suit any rate of
+/// updates. Levels past the end fall at TWENTY_G.
+const SPEED_CURVE: [u32; 15] = [
+    256, 323, 414, 542, 721, 977, 1350, 1900, 2727, 3991, 5957, 9072, 14102, 22379, 36268,
+];
+
+impl Gravity {
+    pub const ONE: Gravity = Gravity(1 << GRAVITY_SHIFT);
+    /// Pieces fall to the stack in the update they spawn.
+    pub const TWENTY_G: Gravity = Gravity(20 << GRAVITY_SHIFT);
+    /// The least a held soft drop pulls the piece down by.
+    pub const SOFT_DROP: Gravity = Gravity(2 << GRAVITY_SHIFT);
+
+    /// This gravity sped up for level along the speed curve, up to TWENTY_G unless it was
+    /// already faster.
+    pub fn at_level(self, level: usize) -> Gravity {
+        let Some(&speed) = SPEED_CURVE.get(level) else {
+            return self.max(Gravity::TWENTY_G);
+        };
+        let gravity = (self.0 as u32 * speed) >> GRAVITY_SHIFT;
+        self.max(Gravity(gravity.min(Gravity::TWENTY_G.0 as u32) as u16))
+    }
+
+    /// Updates between each cell the piece falls, rounded up, so one for a cell an update or
+    /// more. Frontends can pace their updates by it, u16::MAX for no gravity at all.
+    pub fn interval(self) -> u16 {
+        match self.0 {
+            0 => u16::MAX,
+            gravity => Gravity::ONE.0.div_ceil(gravity),
+        }
+    }
+
+    /// Gravity of cells every ticks updates, rounded down to the nearest 1/256th of a cell.
+    pub const fn from_ratio(cells: u16, ticks: u16) -> Gravity {
+        let gravity = ((cells as u32) << GRAVITY_SHIFT) / ticks as u32;
+        if gravity > u16::MAX as u32 {
+            Gravity(u16::MAX)
+        } else {
+            Gravity(gravity as u16)
+        }
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity::ONE
+    }
+}
+
+/// Moves of a piece resting on the stack that start its lock delay again under LockReset::Move.
+pub const MOVE_RESET_LIMIT: u8 = 15;
+
+/// What starts the lock delay of a resting piece again.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LockReset {
+    /// Only falling to a row lower than the piece has been before.
+    Step,
+    /// Falling lower, or up to MOVE_RESET_LIMIT moves and rotations since the piece last fell
+    /// lower.
+    #[default]
+    Move,
+    /// Falling lower or any move or rotation, so a piece kept moving never locks.
+    Infinite,
+}
+
+impl LockReset {
+    /// Whether a move or rotation starts the lock delay again after resets_used earlier ones.
+    fn allows(self, resets_used: u8) -> bool {
+        match self {
+            LockReset::Step => false,
+            LockReset::Move => resets_used < MOVE_RESET_LIMIT,
+            LockReset::Infinite => true,
+        }
+    }
+}
+
+/// What ends the game besides a piece coming into play with nowhere to go, which always does.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TopOutRule {
+    /// Nothing else (block out), the stack may sit in the hidden rows as long as pieces still
+    /// spawn.
+    #[default]
+    BlockOut,
+    /// Also locking a piece entirely in the hidden rows above the visible playfield (lock out),
+    /// as in the guideline.
+    LockOut,
+    /// Also lock out, and garbage pushing any of the stack up into the hidden rows.
+    Garbage,
+}
+
+impl TopOutRule {
+    /// The rule with name, as given on command lines: block, lock or garbage.
+    pub fn from_name(name: &str) -> Option<TopOutRule> {
+        Some(match name {
+            "block" => TopOutRule::BlockOut,
+            "lock" => TopOutRule::LockOut,
+            "garbage" => TopOutRule::Garbage,
+            _ => return None,
+        })
+    }
+}
+
+/// Timing rules that differ between modes, counted in updates.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+// Rules saved before a field was added read it as its default
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Rules {
+    /// Updates between a piece locking and the next one spawning (ARE), giving slow displays
+    /// time to show the lock. Rotate and hold held as the piece spawns still act on it.
+    pub entry_delay: u8,
+    /// Updates that complete rows stay on the playfield before they are removed and play goes
+    /// on, zero to remove them as the piece locks.
+    pub line_clear_delay: u8,
+    pub gravity: Gravity,
+    /// Updates a piece rests on the stack before locking, zero for it to lock when it is next
+    /// due to fall.
+    pub lock_delay: u8,
+    pub lock_reset: LockReset,
+    /// Updates left or right must be held after the piece first moves before it moves again
+    /// (delayed auto shift), zero for a held key to move the piece every update.
+    pub das: u8,
+    /// Updates between each move once the delayed auto shift has passed (auto repeat rate), zero
+    /// to slide the piece all the way to the wall at once.
+    pub arr: u8,
+    /// Earn an item for clearing ROWS_FOR_ITEM or more rows at once.
+    pub items: bool,
+    /// Allow the playfield and next piece to be edited, for practicing setups.
+    pub practice: bool,
+    /// Keep any kind of piece from going more than this many pieces without being dealt, by
+    /// dealing the kind that has waited longest when it is due. None for a purely random
+    /// sequence, and caps below 6 cannot be kept so deal the kinds in turn.
+    pub max_drought: Option<u8>,
+    /// Deal every piece flipped left to right, as a challenge.
+    pub mirror: bool,
+    /// Clear the stack instead of ending the game when it tops out, so play and scoring carry on
+    /// for as long as the player likes.
+    pub zen: bool,
+    /// An experimental variant where the playfield wraps round, a piece moved off one side
+    /// coming back on the other.
+    pub wrap: bool,
+    /// Let the piece turn halfway round in one go, which some rule sets do not allow.
+    pub rotate_180: bool,
+    /// After a clear let every group of joined blocks fall on its own until it lands, possibly
+    /// completing more rows in a chain, rather than only lowering the rows above.
+    pub cascade: bool,
+    /// Speed gravity up with the level along SPEED_CURVE, rather than keeping it the same all
+    /// game.
+    pub speed_curve: bool,
+    /// How points are awarded, unless the game has been given a scoring policy of its own.
+    pub scoring: Scoring,
+    /// The goal that ends the game early, if any.
+    pub mode: GameMode,
+    /// Updates before the first piece starts to fall, for frontends to count down to the start
+    /// of the game. Zero to start straight away.
+    pub countdown: u8,
+    /// Pieces shown coming up, from one for the next piece alone to MAX_PREVIEWS. Zero is read
+    /// as one.
+    pub previews: u8,
+    pub top_out: TopOutRule,
+}
+
+/// Saved in place of a max drought of None.
+const NO_MAX_DROUGHT: u8 = u8::MAX;
+
+impl Rules {
+    /// Bytes taken by the saved form from to_bytes.
+    pub const ENCODED_LEN: usize = 15;
+
+    /// The rules as little endian fields, for profiles and replays on platforms without serde.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let flags = [
+            self.items,
+            self.practice,
+            self.mirror,
+            self.zen,
+            self.wrap,
+            self.rotate_180,
+            self.cascade,
+            self.speed_curve,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0u8, |flags, (bit, &set)| flags | (set as u8) << bit);
+        let gravity = self.gravity.0.to_le_bytes();
+        [
+            self.entry_delay,
+            self.line_clear_delay,
+            gravity[0],
+            gravity[1],
+            self.lock_delay,
+            self.lock_reset as u8,
+            flags,
+            self.max_drought.unwrap_or(NO_MAX_DROUGHT),
+            self.scoring as u8,
+            self.das,
+            self.arr,
+            self.mode as u8,
+            self.countdown,
+            self.previews,
+            self.top_out as u8,
+        ]
+    }
+
+    /// Read saved rules. Settings saved by a later version that this one does not know are read
+    /// as their defaults.
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let flag = |bit: u8| bytes[6] & (1 << bit) != 0;
+        Rules {
+            entry_delay: bytes[0],
+            line_clear_delay: bytes[1],
+            gravity: Gravity(u16::from_le_bytes([bytes[2], bytes[3]])),
+            lock_delay: bytes[4],
+            lock_reset: match bytes[5] {
+                0 => LockReset::Step,
+                2 => LockReset::Infinite,
+                _ => LockReset::Move,
+            },
+            items: flag(0),
+            practice: flag(1),
+            mirror: flag(2),
+            zen: flag(3),
+            wrap: flag(4),
+            rotate_180: flag(5),
+            cascade: flag(6),
+            speed_curve: flag(7),
+            max_drought: Some(bytes[7]).filter(|&drought| drought != NO_MAX_DROUGHT),
+            scoring: match bytes[8] {
+                1 => Scoring::Guideline,
+                2 => Scoring::Nes,
+                _ => Scoring::Classic,
+            },
+            das: bytes[9],
+            arr: bytes[10],
+            mode: match bytes[11] {
+                1 => GameMode::Marathon,
+                2 => GameMode::Sprint,
+                3 => GameMode::Ultra,
+                _ => GameMode::Endless,
+            },
+            countdown: bytes[12],
+            previews: bytes[13],
+            top_out: match bytes[14] {
+                1 => TopOutRule::LockOut,
+                2 => TopOutRule::Garbage,
+                _ => TopOutRule::BlockOut,
+            },
+        }
+    }
+}
+
+/// Rows of garbage to start a game with, as a handicap or a challenge.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StartingGarbage {
+    pub rows: u8,
+    /// Percent chance that each row's hole is in a different column from the row below it, 0
+    /// for a single well to dig down and 100 for a hole that moves every row.
+    pub messiness: u8,
+}
+
+/// What the game is doing between updates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Phase {
+    /// The game has yet to start, the first piece starts to fall in this many updates.
+    Countdown { remaining: u8 },
+    /// A piece is falling and responds to input.
+    Falling,
+    /// A piece has locked and the next one spawns in this many updates.
+    Entry { remaining: u8 },
+    /// A piece has completed rows, which are removed in this many updates.
+    LineClear { remaining: u8 },
+}
+
+/// A broken invariant of the game state, found by TetrisState::validate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InvalidState {
+    /// The cells of the playfield or a piece do not match its width and height.
+    InconsistentGrid { width: usize, height: usize },
+    /// The falling piece at (x, y) sticks out of the side of the playfield or starts above it.
+    /// Only the top of the piece may be above the playfield, as it is when spawned.
+    PieceOutOfBounds {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    /// The falling piece overlaps the stack at this cell.
+    PieceOverlapsStack { x: usize, y: usize },
+    /// Row y is full but was not cleared.
+    CompleteRow { y: usize },
+    /// The score went down during an update.
+    ScoreDecreased { before: usize, after: usize },
+}
+
+impl fmt::Display for InvalidState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InconsistentGrid { width, height } => {
+                write!(
+                    f,
+                    "Grid cells do not match its size of {}x{}",
+                    width, height
+                )
+            }
+            Self::PieceOutOfBounds {
+                x,
+                y,
+                width,
+                height,
+            } => write!(
+                f,
+                "Piece of {}x{} at ({}, {}) is outside of the playfield",
+                width, height, x, y
+            ),
+            Self::PieceOverlapsStack { x, y } => {
+                write!(f, "Piece overlaps the stack at ({}, {})", x, y)
+            }
+            Self::CompleteRow { y } => write!(f, "Row {} is complete but was not cleared", y),
+            Self::ScoreDecreased { before, after } => {
+                write!(f, "Score went down from {} to {}", before, after)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidState {}
+
+/// Why an edit of the game was refused.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EditError {
+    /// Edits are only allowed when the rules allow practice.
+    NotPracticing,
+    /// The cell is outside the playfield.
+    OutOfBounds { x: usize, y: usize },
+    /// The cell is covered by the falling piece.
+    UnderPiece { x: usize, y: usize },
+    /// Filling the cell would complete its row, which only a locking piece may do.
+    CompletesRow { y: usize },
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Self::NotPracticing => write!(f, "The game can only be edited in practice"),
+            Self::OutOfBounds { x, y } => write!(f, "({}, {}) is outside the playfield", x, y),
+            Self::UnderPiece { x, y } => write!(f, "({}, {}) is under the falling piece", x, y),
+            Self::CompletesRow { y } => write!(f, "Filling row {} would complete it", y),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EditError {}
+
+/// Where a piece locked: its kind and rotation, and the bottom left of its grid in the stack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Placement {
+    pub kind: PieceSelector,
+    pub rotation: Rotation,
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Placement {
+    /// The cells of the piece in its rotation, mirrored as in mirror mode if mirror is set.
+    pub fn piece_grid(&self, mirror: bool) -> Grid {
+        let mut piece = self.kind.to_piece((0, 0));
+        while piece.rotation() != self.rotation {
+            piece.next_rotation();
+        }
+        if mirror {
+            piece.mirror();
+        }
+        piece.current_rotation().clone()
+    }
 }
 
+/// A game in progress. With serde the whole game can be saved and picked up again, the random
+/// generator included so that it goes on to deal the same pieces, except for a scoring policy
+/// given to the game, which has to be given again.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TetrisState {
     pub piece: Piece,
+    /// The first of the pieces coming up, the rest being queued.
     pub next_piece: Piece,
+    /// The piece put aside by the hold key, if any.
+    pub held_piece: Option<Piece>,
     pub grid: Grid,
     pub key_state: KeyState,
     pub score: usize,
-    rng: SmallRng,
+    pub rules: Rules,
+    /// During Entry and LineClear, piece is the piece that last locked and is not in play. During
+    /// the Countdown it is the first piece, yet to come into play.
+    pub phase: Phase,
+    /// The item earned in the item mode, waiting to be used.
+    pub item: Option<Item>,
+    /// Updates left of a slow down used on this board.
+    pub slowed: u16,
+    /// Rows cleared by the most recent update.
+    rows_cleared: usize,
+    /// Points scored for soft and hard drops by the most recent update.
+    drop_score: usize,
+    /// Whether the most recent update locked a piece into the stack.
+    piece_locked: bool,
+    /// Where the last piece to lock was placed.
+    last_placement: Option<Placement>,
+    /// Work done by the most recent update.
+    metrics: Metrics,
+    /// Whether the most recent update locked a T piece with a T-spin.
+    t_spin: bool,
+    /// Whether the piece that last locked did so with a T-spin, kept until its rows are
+    /// cleared and scored.
+    locked_t_spin: bool,
+    /// Whether the most recent update cleared rows and left the playfield empty.
+    perfect_clear: bool,
+    /// Scoring that overrides the scoring of the rules, for modes with their own.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    scoring_policy: Option<&'static dyn ScoringPolicy>,
+    /// Rows cleared so far, which sets the level.
+    lines: usize,
+    /// Clears in a row less one, None if the last piece cleared nothing.
+    combo: Option<usize>,
+    /// Whether the last clear was difficult, for a back to back.
+    last_clear_difficult: bool,
+    /// Presses made since the last update, applied by the next one even if the key has been
+    /// released by then. Rotations are counted clockwise, counter clockwise presses taking one
+    /// away.
+    buffered_rotations: i8,
+    buffered_hard_drop: bool,
+    buffered_hold: bool,
+    buffered_half_turn: bool,
+    /// Whether the falling piece came out of hold, it cannot be held again until it locks.
+    hold_used: bool,
+    /// The part of a cell gravity has moved the falling piece that it has yet to fall.
+    gravity_progress: u16,
+    /// Updates the falling piece has rested towards its lock delay, the moves that have started
+    /// the delay again and the lowest row it has reached, which falling below starts it again.
+    lock_timer: u8,
+    lock_resets: u8,
+    lowest_y: usize,
+    /// The way left or right was held at the last update, -1, 0 or 1, and the updates until the
+    /// piece moves that way again if it stays held.
+    shift_direction: i8,
+    shift_timer: u8,
+    /// Items earned so far, which picks the next one.
+    items_earned: usize,
+    pieces_dealt: usize,
+    /// The pieces dealt after next_piece, the first queued_len of them in the order they come.
+    /// Dealt ahead only as far as the rules show, so the sequence is the same however many are
+    /// shown.
+    queued: [PieceSelector; MAX_PREVIEWS - 1],
+    queued_len: usize,
+    droughts: Droughts,
+    /// Times the stack has been cleared after topping out in zen mode.
+    top_outs: usize,
+    /// Updates played, which time a sprint and end an ultra.
+    ticks: u32,
+    /// Pieces locked into the stack so far.
+    pieces_placed: usize,
+    stats: PlayStats,
+    /// Presses of the move and rotate keys since the last piece locked or was held.
+    piece_inputs: u32,
+    finesse: Finesse,
+    /// Whether the last move of the falling piece was a rotation rather than a move sideways.
+    rotated_last: bool,
+    /// What happened in the most recent update.
+    events: Events,
+    /// The pieces to deal in order instead of drawing them from rng, if given.
+    sequence: Option<PieceSequence>,
+    /// The generator behind SmallRng, named so that it can be saved with the game.
+    rng: XorShiftRng,
 }
 
 impl TetrisState {
-    fn respawn_piece(&mut self) {
-        core::mem::swap(&mut self.piece, &mut self.next_piece);
-        self.next_piece = Piece::random_piece(PIECE_START_LOCATION, &mut self.rng);
+    /// A game in progress from its parts, with the piece sequence continuing from rng.
+    #[cfg(feature = "proptest")]
+    pub(crate) fn from_parts(
+        grid: Grid,
+        piece: Piece,
+        next_piece: Piece,
+        score: usize,
+        rng: XorShiftRng,
+    ) -> Self {
+        TetrisState {
+            piece,
+            next_piece,
+            held_piece: None,
+            grid,
+            key_state: KeyState::default(),
+            score,
+            rules: Rules::default(),
+            phase: Phase::Falling,
+            item: None,
+            slowed: 0,
+            rows_cleared: 0,
+            drop_score: 0,
+            piece_locked: false,
+            last_placement: None,
+            metrics: Metrics::default(),
+            t_spin: false,
+            locked_t_spin: false,
+            perfect_clear: false,
+            scoring_policy: None,
+            lines: 0,
+            combo: None,
+            last_clear_difficult: false,
+            buffered_rotations: 0,
+            buffered_hard_drop: false,
+            buffered_hold: false,
+            buffered_half_turn: false,
+            hold_used: false,
+            gravity_progress: 0,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_y: VISIBLE_HEIGHT,
+            shift_direction: 0,
+            shift_timer: 0,
+            items_earned: 0,
+            pieces_dealt: 0,
+            queued: [PieceSelector::Line; MAX_PREVIEWS - 1],
+            queued_len: 0,
+            events: Events::default(),
+            droughts: Droughts::default(),
+            top_outs: 0,
+            ticks: 0,
+            pieces_placed: 0,
+            stats: PlayStats::default(),
+            piece_inputs: 0,
+            finesse: Finesse::default(),
+            rotated_last: false,
+            sequence: None,
+            rng,
+        }
     }
 
-    /// Removes any cleared rows from the game grid after a piece has been placed down.
-    fn remove_complete_rows(&mut self) {
-        let mut rows_cleared = 0;
+    /// Set the held keys, remembering any rotate, hard drop or hold press for the next update.
+    fn set_key_state(&mut self, key_state: &KeyState) {
+        if key_state.rotate && !self.key_state.rotate {
+            self.buffered_rotations = (self.buffered_rotations + 1).min(MAX_BUFFERED_ROTATIONS);
+        }
+        if key_state.rotate_ccw && !self.key_state.rotate_ccw {
+            self.buffered_rotations = (self.buffered_rotations - 1).max(-MAX_BUFFERED_ROTATIONS);
+        }
+        if key_state.hard_drop && !self.key_state.hard_drop {
+            self.buffered_hard_drop = true;
+        }
+        if key_state.hold && !self.key_state.hold {
+            self.buffered_hold = true;
+        }
+        if key_state.rotate_180 && !self.key_state.rotate_180 {
+            self.buffered_half_turn = true;
+        }
+        let presses = [
+            (key_state.left, self.key_state.left),
+            (key_state.right, self.key_state.right),
+            (key_state.rotate, self.key_state.rotate),
+            (key_state.rotate_ccw, self.key_state.rotate_ccw),
+            (
+                key_state.rotate_180 && self.rules.rotate_180,
+                self.key_state.rotate_180,
+            ),
+        ];
+        self.piece_inputs += presses.iter().filter(|&&(now, was)| now && !was).count() as u32;
+        self.key_state = *key_state;
+    }
 
-        for y in 0..self.grid.height {
-            if (0..self.grid.width).find(|&x| !self.grid[(x, y)]).is_none() {
-                (0..self.grid.width).for_each(|x| self.grid[(x, y)] = false);
-                rows_cleared += 1;
+    /// Turn the falling piece about the bottom left of its grid, or failing that kick it to the
+    /// first of the piece's kick offsets that stays inside and does not collide with the grid.
+    /// Returns whether it rotated.
+    fn try_rotate(&mut self, turn: Turn) -> bool {
+        let rotated_grid = self.piece.peek_turn(turn);
+        let wrap = self.rules.wrap;
+        let width = self.grid.width;
+        let (kind, rotation) = (self.piece.kind(), self.piece.rotation());
+        // The kicks are from where the piece would turn to in its rotation box
+        let (turn_x, turn_y) = kind.turn_offset(rotation, turn);
+        let mut kicks = (kind.kicks(rotation, turn)).map(|(dx, dy)| (turn_x + dx, turn_y + dy));
+        let kicked = kicks.find_map(|(dx, dy)| {
+            let x = match wrap {
+                true => (self.piece.x + width).wrapping_add_signed(dx) % width,
+                false => self.piece.x.checked_add_signed(dx)?,
+            };
+            let y = self.piece.y.checked_add_signed(dy)?;
+            let fits = (wrap || x + rotated_grid.width <= width)
+                && y < self.grid.height
+                && !collides(rotated_grid, &self.grid, (x, y), wrap);
+            fits.then_some((x, y))
+        });
+        if let Some((x, y)) = kicked {
+            self.piece.turn(turn);
+            (self.piece.x, self.piece.y) = (x, y);
+            self.rotated_last = true;
+        }
+        kicked.is_some()
+    }
+
+    /// Put the falling piece into hold and bring out the piece held before, or the next piece if
+    /// nothing was held. The piece brought out starts again from the top.
+    fn swap_hold(&mut self) {
+        let kind = self.piece.kind();
+        match self.held_piece.take() {
+            Some(held) => self.piece = held,
+            None => self.take_next_piece(),
+        }
+        self.held_piece = Some(self.new_piece(kind));
+        self.events.push(GameEvent::PieceHeld(kind));
+        self.piece_inputs = 0;
+        self.hold_used = true;
+        self.gravity_progress = 0;
+        self.reset_lock_delay();
+    }
+
+    /// Start the lock delay afresh for a piece brought in at the top.
+    fn reset_lock_delay(&mut self) {
+        self.lock_timer = 0;
+        self.lock_resets = 0;
+        self.lowest_y = self.piece.y;
+    }
+
+    fn take_next_piece(&mut self) {
+        core::mem::swap(&mut self.piece, &mut self.next_piece);
+        let kind = match self.queued_len {
+            0 => self.deal_piece(),
+            _ => {
+                let kind = self.queued[0];
+                self.queued.copy_within(1..self.queued_len, 0);
+                self.queued_len -= 1;
+                kind
+            }
+        };
+        self.next_piece = self.new_piece(kind);
+        self.fill_queue();
+        self.pieces_dealt += 1;
+        self.rotated_last = false;
+    }
+
+    /// Draw the next piece of the sequence, protected from droughts if the rules cap them. A
+    /// sequence given to the game is dealt as it is.
+    fn deal_piece(&mut self) -> PieceSelector {
+        if let Some(ref mut sequence) = self.sequence {
+            let kind = sequence.deal();
+            self.droughts.deal(kind);
+            return kind;
+        }
+        let mut kind = self.rng.gen::<PieceSelector>();
+        if let Some(max_drought) = self.rules.max_drought {
+            if self.droughts.is_due(u32::from(max_drought)) {
+                kind = self.droughts.longest_waiting().0;
             }
         }
+        self.droughts.deal(kind);
+        kind
+    }
 
-        // Combo by squaring rows_cleared, you double the base row score for each additional
-        // row you clear.
-        self.score += (rows_cleared * rows_cleared) * self.grid.width * BASE_SCORE_UNIT;
+    /// Pieces the rules show coming up.
+    fn preview_len(&self) -> usize {
+        (self.rules.previews as usize).clamp(1, MAX_PREVIEWS)
     }
 
-    /// Calls set_output (x + x_off, y + y_off, true|false) for every pixel in a scaled
-    /// tetris grid.
-    pub fn draw_game_grid<F: FnMut(usize, usize, bool)>(
-        &self,
-        mut set_output: F,
-        (x_off, y_off): (usize, usize),
-        (scale_x, scale_y): (usize, usize),
-    ) {
-        let (piece_x_offset, piece_y_offset) = (self.piece.x, self.piece.y);
-        let piece_grid = self.piece.current_rotation();
+    /// Deal pieces into the queue until there are as many coming up as the rules show. Pieces
+    /// already dealt stay queued if the rules come to show fewer.
+    fn fill_queue(&mut self) {
+        while 1 + self.queued_len < self.preview_len() {
+            self.queued[self.queued_len] = self.deal_piece();
+            self.queued_len += 1;
+        }
+    }
 
-        for (x, y) in iproduct!(0..self.grid.width, (0..self.grid.height).rev()) {
-            let in_piece = {
-                if x >= piece_x_offset
-                    && (x - piece_x_offset < piece_grid.width)
-                    && y >= piece_y_offset
-                    && (y - piece_y_offset < piece_grid.height)
-                {
-                    piece_grid[(x - piece_x_offset, y - piece_y_offset)]
-                } else {
-                    false
-                }
-            };
+    /// The pieces coming up in the order they come, from the next piece, as many as the rules
+    /// show.
+    pub fn previews(&self) -> impl Iterator<Item = Piece> + '_ {
+        let queued = self.queued[..self.queued_len].iter();
+        core::iter::once(self.next_piece.clone())
+            .chain(queued.map(|&kind| self.new_piece(kind)))
+            .take(self.preview_len())
+    }
+
+    /// A piece of kind at the top of the playfield, mirrored in mirror mode.
+    fn new_piece(&self, kind: PieceSelector) -> Piece {
+        let mut piece = spawn_piece_in(kind, (self.grid.width, self.visible_height()));
+        if self.rules.mirror {
+            piece.mirror();
+        }
+        piece
+    }
+
+    /// Mirror the pieces already dealt, as mirror mode is turned on or off.
+    fn mirror_pieces(&mut self) {
+        for piece in [&mut self.piece, &mut self.next_piece]
+            .into_iter()
+            .chain(self.held_piece.as_mut())
+        {
+            piece.mirror();
+        }
+    }
+
+    /// Pieces taken from next so far, the first piece of the game not included.
+    pub fn pieces_dealt(&self) -> usize {
+        self.pieces_dealt
+    }
+
+    /// How long each kind of piece has gone without being dealt, counting the pieces coming up
+    /// as dealt.
+    pub fn droughts(&self) -> &Droughts {
+        &self.droughts
+    }
+
+    /// Whether the most recent update locked a T piece with a T-spin: rotated as its last move
+    /// before locking, with three of the four cells diagonal to its centre filled or outside the
+    /// playfield.
+    pub fn t_spin(&self) -> bool {
+        self.t_spin
+    }
 
-            let is_set = self.grid[(x, y)] || in_piece;
-            let (canvas_x, canvas_y) = (x, ((self.grid.height - 1- y)));
-            let (canvas_x, canvas_y) = ((canvas_x * scale_x) + x_off, (canvas_y * scale_y) + y_off);
+    /// Whether the most recent update cleared rows and left the playfield empty, a perfect clear.
+    pub fn perfect_clear(&self) -> bool {
+        self.perfect_clear
+    }
 
-            for x in 0..(scale_x) {
-                for y in 0..(scale_y) {
-                    (set_output)(canvas_x + x, canvas_y + y, is_set);
+    fn is_t_spin(&self, piece: &Grid, (x, y): (usize, usize)) -> bool {
+        if self.piece.kind() != PieceSelector::T || !self.rotated_last {
+            return false;
+        }
+        // The centre is the cell of the T next to the three others
+        let filled =
+            |px: usize, py: usize| px < piece.width && py < piece.height && piece[(px, py)];
+        let Some((cx, cy)) = (0..piece.height)
+            .flat_map(|py| (0..piece.width).map(move |px| (px, py)))
+            .find(|&(px, py)| {
+                filled(px, py)
+                    && [(px + 1, py), (px, py + 1)]
+                        .into_iter()
+                        .chain(px.checked_sub(1).map(|px| (px, py)))
+                        .chain(py.checked_sub(1).map(|py| (px, py)))
+                        .filter(|&(px, py)| filled(px, py))
+                        .count()
+                        == 3
+            })
+        else {
+            return false;
+        };
+        let (cx, cy) = (x + cx, y + cy);
+        [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .into_iter()
+            .filter(|&(dx, dy): &(isize, isize)| {
+                let cx = match self.rules.wrap {
+                    true => Some((cx + self.grid.width).wrapping_add_signed(dx) % self.grid.width),
+                    false => cx.checked_add_signed(dx),
+                };
+                match (cx, cy.checked_add_signed(dy)) {
+                    (Some(cx), Some(cy)) if cx < self.grid.width && cy < self.grid.height => {
+                        self.grid[(cx, cy)]
+                    }
+                    _ => true,
                 }
+            })
+            .count()
+            >= 3
+    }
+
+    /// Bring in the next piece after the last one locked. Hold and rotate are looked at as it
+    /// spawns, so holding them swaps the new piece straight into hold or has it enter the field
+    /// already rotated (initial hold and initial rotation in arcade games).
+    fn respawn_piece(&mut self) {
+        self.take_next_piece();
+        self.metrics.pieces_spawned += 1;
+        self.events.push(GameEvent::PieceSpawned(self.piece.kind()));
+        self.hold_used = false;
+        self.gravity_progress = 0;
+        self.reset_lock_delay();
+        if self.key_state.hold {
+            self.swap_hold();
+        }
+        match (self.key_state.rotate, self.key_state.rotate_ccw) {
+            _ if self.key_state.rotate_180 && self.rules.rotate_180 => {
+                _ = self.try_rotate(Turn::Half)
             }
+            (true, false) => _ = self.try_rotate(Turn::Clockwise),
+            (false, true) => _ = self.try_rotate(Turn::CounterClockwise),
+            // Both held cancel out
+            _ => {}
         }
     }
-}
-
-pub enum Tetris {
-    Running(TetrisState),
-    Finished,
-}
 
-impl Tetris {
-    pub fn new() -> Self {
-        let mut rng = SmallRng::seed_from_u64(/* TODO: Supply with OS entropy when creating Tetris */ 31203103120);
-        let piece = Piece::random_piece(PIECE_START_LOCATION, &mut rng);
-        let next_piece = Piece::random_piece(PIECE_START_LOCATION, &mut rng);
-        Self::Running(TetrisState {
-            grid: Grid::new(GRID_SIZE),
-            piece,
-            next_piece,
-            key_state: KeyState::default(),
-            score: 0,
-            rng,
+    /// True if the falling piece overlaps the stack, which ends the game when a piece is brought
+    /// in.
+    fn piece_collides(&self) -> bool {
+        self.piece_in_play().is_some_and(|piece| {
+            collides(
+                piece.current_rotation(),
+                &self.grid,
+                (piece.x, piece.y),
+                self.rules.wrap,
+            )
         })
     }
 
-    /// Set the current state of all inputs to the game, to be considered on all subsequent
-    /// updates until the next call to set_key_state.
-    pub fn set_key_state(&mut self, key_state: &KeyState) {
-        match self {
-            Self::Running(state) => state.key_state = *key_state,
-            Self::Finished => {}
+    /// The falling piece, or None between a piece locking and the next spawning and before the
+    /// countdown to the start is over.
+    pub fn piece_in_play(&self) -> Option<&Piece> {
+        match self.phase {
+            Phase::Falling => Some(&self.piece),
+            Phase::Countdown { .. } | Phase::Entry { .. } | Phase::LineClear { .. } => None,
         }
     }
 
-    /// Perform a single update of the game, first applying and input moves or rotations if legal,
-    /// then attempting to lower the piece by one tile. If the lowered piece collides with an
-    /// existing tile or the floor of the game grid then the piece is placed into the grid,
-    /// complete rows are considered and the piece is respawned. Upon respawn if the piece
-    /// immediately collides with the grid then the player has lost and the game is over.
-    ///
-    /// This function should be called with a frequency that matches your desired game speed,
-    /// calling it more frequently will make the game faster and more difficult.
-    pub fn update(&mut self) {
-        match self {
-            Self::Running(state) => {
-                // Apply rotation if rotate key is pressed and the rotation would not collide with
-                // the grid.
-                if state.key_state.rotate {
-                    let rotated_grid = state.piece.peek_next_rotation();
-                    if !rotated_grid.collides(&state.grid, (state.piece.x, state.piece.y)) {
-                        state.piece.next_rotation();
-                    }
-                }
+    /// The complete rows waiting out the line clear delay before they are removed, from the
+    /// bottom up, for frontends to flash or animate. None outside of a line clear.
+    pub fn clearing_rows(&self) -> impl Iterator<Item = usize> + '_ {
+        let clearing = matches!(self.phase, Phase::LineClear { .. });
+        (0..self.grid.height)
+            .filter(move |&y| clearing && self.grid.row(y).iter().all(|&cell| cell))
+    }
 
-                // Apply any left / right move before lowering y. Do not do the move if it creates
-                // a collision.
-                match (state.key_state.left, state.key_state.right) {
-                    (false, false) | (true, true) => {
-                        // We do nothing if both keys are pushed as they net out.
-                    }
-                    (true, false) => {
-                        if state.piece.x > 0
-                            && !state
-                                .piece
-                                .current_rotation()
-                                .collides(&state.grid, (state.piece.x - 1, state.piece.y))
-                        {
-                            state.piece.x -= 1;
-                        }
-                    }
-                    (false, true) => {
-                        if (state.piece.x + state.piece.current_rotation().width) < state.grid.width
-                            && !state
-                                .piece
-                                .current_rotation()
-                                .collides(&state.grid, (state.piece.x + 1, state.piece.y))
-                        {
-                            state.piece.x += 1;
-                        }
-                    }
-                }
+    /// Rows of the playfield frontends should draw, from the bottom. The hidden buffer rows
+    /// above them are left out.
+    pub fn visible_height(&self) -> usize {
+        self.grid.height.saturating_sub(BUFFER_ROWS)
+    }
+
+    /// Updates left before the first piece starts to fall, None once the game has started.
+    pub fn countdown(&self) -> Option<u8> {
+        match self.phase {
+            Phase::Countdown { remaining } => Some(remaining),
+            _ => None,
+        }
+    }
 
-                if state.piece.y == 0
-                    || state
-                        .piece
-                        .current_rotation()
-                        .collides(&state.grid, (state.piece.x, state.piece.y - 1))
-                {
-                    state
-                        .piece
-                        .current_rotation()
-                        .copy_into(&mut state.grid, (state.piece.x, state.piece.y));
+    /// The level, going up by one every LINES_PER_LEVEL lines cleared, from zero.
+    pub fn level(&self) -> usize {
+        self.lines / LINES_PER_LEVEL
+    }
 
-                    state.remove_complete_rows();
+    /// The gravity the next update falls by: that of the rules, sped up for the level with the
+    /// speed curve, halved while slowed down and at least SOFT_DROP while a soft drop is held.
+    /// Its interval is how often a frontend needs to update for the piece to fall a cell.
+    pub fn gravity(&self) -> Gravity {
+        let mut gravity = match self.rules.speed_curve {
+            true => self.rules.gravity.at_level(self.level()),
+            false => self.rules.gravity,
+        };
+        if self.slowed > 0 {
+            gravity.0 /= 2;
+        }
+        if self.key_state.soft_drop {
+            gravity = gravity.max(Gravity::SOFT_DROP);
+        }
+        gravity
+    }
 
-                    state.respawn_piece();
+    /// How far the falling piece has dropped towards the row below, in 256ths of a cell, once
+    /// elapsed 256ths of the time to the next update have passed. Frontends drawing between
+    /// updates lower the piece by this so it falls smoothly rather than a cell at a time. Zero
+    /// while the piece rests on the stack or none is falling.
+    pub fn fall_offset(&self, elapsed: u16) -> u16 {
+        let Some(piece) = self.piece_in_play() else {
+            return 0;
+        };
+        let below = piece.y.checked_sub(1);
+        let resting = below.is_none_or(|y| {
+            collides(
+                piece.current_rotation(),
+                &self.grid,
+                (piece.x, y),
+                self.rules.wrap,
+            )
+        });
+        if resting {
+            return 0;
+        }
 
-                    // If a spawned piece immediately collides with the world then the game is lost
-                    if state
-                        .piece
-                        .current_rotation()
-                        .collides(&state.grid, (state.piece.x, state.piece.y))
-                    {
-                        *self = Self::Finished;
-                    }
-                } else {
-                    state.piece.y -= 1;
-                }
+        // The gravity the next update falls by
+        let gravity = self.gravity();
+        let one = 1 << GRAVITY_SHIFT;
+        let elapsed = (elapsed as u32).min(one);
+        let fall = self.gravity_progress as u32 + gravity.0 as u32 * elapsed / one;
+        fall.min(one - 1) as u16
+    }
+
+    /// Move on after a piece has locked and any complete rows are gone, waiting out the entry
+    /// delay or bringing in the next piece straight away. Returns false if the next piece has
+    /// nowhere to go.
+    fn start_next_piece(&mut self) -> bool {
+        if self.rules.entry_delay > 0 {
+            self.phase = Phase::Entry {
+                remaining: self.rules.entry_delay,
+            };
+            return true;
+        }
+        self.spawn_next_piece()
+    }
+
+    fn spawn_next_piece(&mut self) -> bool {
+        self.phase = Phase::Falling;
+        self.respawn_piece();
+        !self.piece_collides() || self.forgive_top_out()
+    }
+
+    /// In zen mode clear the stack after it tops out so that play can go on. Returns whether it
+    /// was cleared.
+    fn forgive_top_out(&mut self) -> bool {
+        if self.rules.zen {
+            self.grid.data.fill(false);
+            self.metrics.cells_touched += (self.grid.width * self.grid.height) as u32;
+            self.top_outs += 1;
+        }
+        self.rules.zen
+    }
+
+    /// Times the stack has topped out and been cleared in zen mode.
+    pub fn top_outs(&self) -> usize {
+        self.top_outs
+    }
+
+    /// Updates played so far.
+    pub fn ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// How the game has gone so far, as it is kept once the game tops out.
+    pub fn summary(&self, top_out: TopOut) -> GameSummary {
+        GameSummary {
+            score: self.score,
+            lines: self.lines,
+            level: self.level(),
+            pieces: self.pieces_placed,
+            ticks: self.ticks,
+            top_out,
+        }
+    }
+
+    /// Whether the game has reached the goal of its mode, such as the lines of a sprint. The
+    /// update that completes it finishes the game, with TopOut::GoalReached as how it ended.
+    pub fn is_complete(&self) -> bool {
+        self.rules.mode.is_complete(self.lines, self.ticks)
+    }
+
+    /// Count down the delay of a phase without a falling piece, returning true once it is over.
+    fn count_down(remaining: &mut u8) -> bool {
+        if *remaining > 1 {
+            *remaining -= 1;
+            false
+        } else {
+            true
+        }
+    }
+
+    fn has_complete_rows(&mut self) -> bool {
+        self.metrics.rows_scanned += self.grid.height as u32;
+        (0..self.grid.height).any(|y| self.grid.row(y).iter().all(|&cell| cell))
+    }
+
+    /// Removes any cleared rows from the game grid after a piece has been placed down, the rows
+    /// above each falling to take its place. With cascade gravity every group of blocks left then
+    /// falls on its own, and the rows they complete clear as the next step of the combo.
+    pub(crate) fn remove_complete_rows(&mut self) {
+        let mut cleared = self.take_complete_rows();
+        self.score_clear(cleared);
+        self.rows_cleared = cleared;
+        while self.rules.cascade && cleared > 0 {
+            cascade::settle(&mut self.grid, self.rules.wrap);
+            cleared = self.take_complete_rows();
+            if cleared > 0 {
+                self.score_clear(cleared);
+                self.rows_cleared += cleared;
             }
-            Self::Finished => {}
         }
     }
 
-    pub fn is_finished(&self) -> bool {
-        match self {
-            Self::Running(_) => false,
-            Self::Finished => true,
+    /// Remove the complete rows, returning how many there were.
+    fn take_complete_rows(&mut self) -> usize {
+        let mut rows_cleared = 0;
+        let mut rows = 0;
+
+        // From the top down, so the rows moved down by a removal have already been looked at
+        let (width, height) = (self.grid.width, self.grid.height);
+        for y in (0..height).rev() {
+            if self.grid.row(y).iter().all(|&cell| cell) {
+                self.grid.remove_row(y);
+                self.metrics.cells_touched += ((height - y) * width) as u32;
+                rows_cleared += 1;
+                rows |= 1 << y;
+            }
         }
+        self.metrics.rows_scanned += height as u32;
+        if rows_cleared > 0 {
+            self.events.push(GameEvent::LinesCleared {
+                lines: rows_cleared,
+                rows,
+            });
+        }
+        rows_cleared
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::tetris::Tetris;
+    /// Score clearing rows_cleared rows at once, which may be none.
+    fn score_clear(&mut self, rows_cleared: usize) {
+        let combo = match rows_cleared {
+            0 => None,
+            _ => Some(self.combo.map_or(0, |combo| combo + 1)),
+        };
+        let mut clear = LineClear {
+            rows: rows_cleared,
+            t_spin: self.locked_t_spin,
+            level: self.level(),
+            combo: combo.unwrap_or(0),
+            back_to_back: false,
+            perfect_clear: false,
+            width: self.grid.width,
+        };
+        // Pieces that clear nothing neither keep nor break a back to back
+        if rows_cleared > 0 {
+            let height = self.grid.height;
+            clear.perfect_clear = (0..height).all(|y| !self.grid.row(y).contains(&true));
+            self.perfect_clear = clear.perfect_clear;
+            clear.back_to_back = clear.is_difficult() && self.last_clear_difficult;
+            self.last_clear_difficult = clear.is_difficult();
+        }
+        self.score += self.scoring_policy().clear(&clear);
+        self.combo = combo;
+        let level = self.level();
+        self.lines += rows_cleared;
+        self.stats.add_clear(rows_cleared);
+        if self.level() > level {
+            self.events.push(GameEvent::LevelUp(self.level()));
+        }
+        self.locked_t_spin = false;
 
-    #[test]
-    fn new_tetris_instance() {
-        let _tetris = Tetris::new();
+        if self.rules.items && rows_cleared >= ROWS_FOR_ITEM && self.item.is_none() {
+            self.item = Some(Item::ALL[self.items_earned % Item::ALL.len()]);
+            self.items_earned += 1;
+        }
     }
 
-    #[test]
-    fn doing_nothing_for_a_million_updates_creates_a_finished_game() {
-        let mut tetris = Tetris::new();
+    fn check_practicing(&self) -> Result<(), EditError> {
+        match self.rules.practice {
+            true => Ok(()),
+            false => Err(EditError::NotPracticing),
+        }
+    }
 
-        for _ in 0..100_000 {
-            tetris.update();
+    /// Fill or empty a cell of the stack in practice.
+    pub fn set_cell(&mut self, (x, y): (usize, usize), filled: bool) -> Result<(), EditError> {
+        self.check_practicing()?;
+        if x >= self.grid.width || y >= self.grid.height {
+            return Err(EditError::OutOfBounds { x, y });
+        }
+        let under_piece = self.piece_in_play().is_some_and(|piece| {
+            let grid = piece.current_rotation();
+            x.checked_sub(piece.x)
+                .zip(y.checked_sub(piece.y))
+                .is_some_and(|(px, py)| px < grid.width && py < grid.height && grid[(px, py)])
+        });
+        if under_piece {
+            return Err(EditError::UnderPiece { x, y });
         }
+        let row = self.grid.row(y);
+        if filled && (0..row.len()).all(|cx| cx == x || row[cx]) {
+            return Err(EditError::CompletesRow { y });
+        }
+        self.grid[(x, y)] = filled;
+        Ok(())
+    }
 
-        assert!(tetris.is_finished());
+    /// Pick the next piece in practice.
+    pub fn set_next_piece(&mut self, kind: PieceSelector) -> Result<(), EditError> {
+        self.check_practicing()?;
+        self.next_piece = self.new_piece(kind);
+        Ok(())
+    }
+
+    /// Apply an item to this board. A line shift that would push the stack into the falling
+    /// piece is undone.
+    fn apply_item(&mut self, item: Item) {
+        match item {
+            Item::Bomb => item::explode(&mut self.grid),
+            Item::SlowDown => self.slowed = SLOW_DOWN_TICKS,
+            Item::LineShift => {
+                item::shift_rows(&mut self.grid, false);
+                if self.piece_collides() {
+                    item::shift_rows(&mut self.grid, true);
+                }
+            }
+        }
+    }
+
+    /// How points are awarded: the policy given to the game, or else the one its rules pick.
+    pub fn scoring_policy(&self) -> &'static dyn ScoringPolicy {
+        self.scoring_policy
+            .unwrap_or_else(|| self.rules.scoring.policy())
+    }
+
+    /// Score the rest of the game by policy instead of the scoring of the rules.
+    pub fn set_scoring_policy(&mut self, policy: &'static dyn ScoringPolicy) {
+        self.scoring_policy = Some(policy);
+    }
+
+    /// Pieces in a row before the last one that cleared rows, None if the last piece cleared
+    /// nothing.
+    pub fn combo(&self) -> Option<usize> {
+        self.combo
+    }
+
+    /// The number of rows cleared by the most recent update, zero unless it placed a piece.
+    pub fn rows_cleared(&self) -> usize {
+        self.rows_cleared
+    }
+
+    /// Points scored by the most recent update for the distance a soft or hard drop moved the
+    /// piece, already added to the score.
+    pub fn drop_score(&self) -> usize {
+        self.drop_score
+    }
+
+    /// Whether the most recent update locked a piece into the stack.
+    pub fn piece_locked(&self) -> bool {
+        self.piece_locked
+    }
+
+    /// Pieces placed of each kind, lines and clears of each size so far, for stats panels.
+    pub fn stats(&self) -> &PlayStats {
+        &self.stats
+    }
+
+    /// The inputs made placing each piece against the fewest that would have done.
+    pub fn finesse(&self) -> &Finesse {
+        &self.finesse
+    }
+
+    /// What happened in the most recent update, in the order it happened.
+    pub fn events(&self) -> impl Iterator<Item = GameEvent> + '_ {
+        self.events.iter()
+    }
+
+    /// Work done by the most recent update.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Where the last piece to lock this game was placed, None before the first locks.
+    pub fn last_placement(&self) -> Option<Placement> {
+        self.last_placement
+    }
+
+    /// Push the stack up by rows of garbage, each filled except for the cell in column hole,
+    /// or the last column if hole is past it. Returns false if this pushed the stack out of the
+    /// top of the grid, into the hidden rows under TopOutRule::Garbage, or into the falling
+    /// piece and zen mode did not clear it.
+    fn add_garbage(&mut self, rows: usize, hole: usize) -> bool {
+        let (width, height) = (self.grid.width, self.grid.height);
+        let hole = hole.min(width - 1);
+        let rows = rows.min(height);
+
+        let top = match self.rules.top_out {
+            TopOutRule::Garbage => self.visible_height(),
+            TopOutRule::BlockOut | TopOutRule::LockOut => height,
+        };
+        let pushed_out =
+            (top.saturating_sub(rows)..height).any(|y| self.grid.row(y).contains(&true));
+        // Every row is moved up or filled with garbage
+        self.metrics.cells_touched += (width * height) as u32;
+        self.grid
+            .data
+            .copy_within(0..(height - rows) * width, rows * width);
+        for y in 0..rows {
+            let row = self.grid.row_mut(y);
+            row.fill(true);
+            row[hole] = false;
+        }
+
+        (!pushed_out && !self.piece_collides()) || self.forgive_top_out()
+    }
+
+    /// Check the invariants that hold between updates: every grid matches its size, the falling
+    /// piece is inside the playfield (or above it) without overlapping the stack, and no complete
+    /// rows are left uncleared.
+    pub fn validate(&self) -> Result<(), InvalidState> {
+        for grid in [
+            &self.grid,
+            self.piece.current_rotation(),
+            self.next_piece.current_rotation(),
+        ] {
+            if !grid.is_consistent() {
+                return Err(InvalidState::InconsistentGrid {
+                    width: grid.width,
+                    height: grid.height,
+                });
+            }
+        }
+
+        if let Some(piece) = self.piece_in_play() {
+            self.validate_piece(piece)?;
+        }
+
+        // Complete rows are left on the playfield during the line clear delay
+        if let Phase::LineClear { .. } = self.phase {
+            return Ok(());
+        }
+        match (0..self.grid.height).find(|&y| self.grid.row(y).iter().all(|&cell| cell)) {
+            Some(y) => Err(InvalidState::CompleteRow { y }),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_piece(&self, piece: &Piece) -> Result<(), InvalidState> {
+        let (x, y) = (piece.x, piece.y);
+        let piece = piece.current_rotation();
+        // A piece in the wrap-around variant only has to start inside the playfield
+        let width = match self.rules.wrap {
+            true => x + 1,
+            false => x + piece.width,
+        };
+        if width > self.grid.width || y >= self.grid.height {
+            return Err(InvalidState::PieceOutOfBounds {
+                x,
+                y,
+                width: piece.width,
+                height: piece.height,
+            });
+        }
+
+        // Rows of the piece above the playfield cannot overlap anything
+        for piece_y in 0..piece.height.min(self.grid.height - y) {
+            for piece_x in 0..piece.width {
+                let grid_x = (x + piece_x) % self.grid.width;
+                if piece[(piece_x, piece_y)] && self.grid[(grid_x, y + piece_y)] {
+                    return Err(InvalidState::PieceOverlapsStack {
+                        x: grid_x,
+                        y: y + piece_y,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the state after an update, also checking that the score has not gone down from
+    /// score_before.
+    pub fn validate_update(&self, score_before: usize) -> Result<(), InvalidState> {
+        if self.score < score_before {
+            return Err(InvalidState::ScoreDecreased {
+                before: score_before,
+                after: self.score,
+            });
+        }
+        self.validate()
+    }
+
+    /// Calls set_output (x + x_off, y + y_off, true|false) for every pixel in a scaled
+    /// tetris grid. Only the visible rows are drawn.
+    pub fn draw_game_grid<F: FnMut(usize, usize, bool)>(
+        &self,
+        mut set_output: F,
+        (x_off, y_off): (usize, usize),
+        (scale_x, scale_y): (usize, usize),
+    ) {
+        let (piece_x_offset, piece_y_offset) = (self.piece.x, self.piece.y);
+        let piece_grid = self.piece.current_rotation();
+        let in_play = self.piece_in_play().is_some();
+
+        let height = self.visible_height();
+        for y in (0..height).rev() {
+            // The row of the piece that overlaps this row of the grid, if any
+            let piece_row = y
+                .checked_sub(piece_y_offset)
+                .filter(|_| in_play)
+                .filter(|&piece_y| piece_y < piece_grid.height)
+                .map(|piece_y| piece_grid.row(piece_y));
+            let canvas_y = ((height - 1 - y) * scale_y) + y_off;
+
+            for (x, &filled) in self.grid.row(y).iter().enumerate() {
+                let piece_x = match self.rules.wrap {
+                    true => Some((x + self.grid.width - piece_x_offset) % self.grid.width),
+                    false => x.checked_sub(piece_x_offset),
+                };
+                let in_piece = piece_row
+                    .zip(piece_x)
+                    .and_then(|(piece_row, piece_x)| piece_row.get(piece_x).copied())
+                    .unwrap_or(false);
+
+                let is_set = filled || in_piece;
+                let canvas_x = (x * scale_x) + x_off;
+
+                for x in 0..(scale_x) {
+                    for y in 0..(scale_y) {
+                        (set_output)(canvas_x + x, canvas_y + y, is_set);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Empty the playfield and make it width by height, the buffer rows included, with the
+    /// pieces dealt so far brought to its top. For playfields other than GRID_SIZE, such as the
+    /// half size one that big mode draws with every cell doubled.
+    pub fn resize_playfield(&mut self, (width, height): (usize, usize)) {
+        self.grid = Grid::new((width, height));
+        self.piece = self.new_piece(self.piece.kind());
+        self.next_piece = self.new_piece(self.next_piece.kind());
+        if let Some(ref held) = self.held_piece {
+            self.held_piece = Some(self.new_piece(held.kind()));
+        }
+        self.reset_lock_delay();
+    }
+}
+
+/// A source of randomness for seeding the piece generator, such as OS entropy or hardware noise
+/// on embedded targets.
+pub trait EntropySource {
+    fn next_seed(&mut self) -> u64;
+}
+
+/// The seed of games created by Tetris::new without the standard library.
+#[cfg(not(feature = "std"))]
+const DEFAULT_SEED: u64 = 31203103120;
+
+/// Seeds from the operating system's randomness, through the random keys the standard library
+/// gives each HashMap.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Default)]
+pub struct OsEntropy;
+
+#[cfg(feature = "std")]
+impl EntropySource for OsEntropy {
+    fn next_seed(&mut self) -> u64 {
+        use std::collections::hash_map::RandomState;
+        use std::hash::{BuildHasher, Hasher};
+        RandomState::new().build_hasher().finish()
+    }
+}
+
+/// How a game topped out, or that it ended by reaching its goal instead.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TopOut {
+    /// A piece coming into play, from next or from hold, had nowhere to go.
+    #[default]
+    BlockOut,
+    /// A piece locked entirely above the visible playfield, under TopOutRule::LockOut or
+    /// TopOutRule::Garbage.
+    LockOut,
+    /// Garbage pushed the stack out of the top of the playfield or into the falling piece, or
+    /// into the hidden rows under TopOutRule::Garbage.
+    Garbage,
+    /// Not a top out, the game reached the goal of its mode such as the lines of a sprint.
+    GoalReached,
+}
+
+impl fmt::Display for TopOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BlockOut => write!(f, "Blocked out"),
+            Self::LockOut => write!(f, "Locked out"),
+            Self::Garbage => write!(f, "Pushed out by garbage"),
+            Self::GoalReached => write!(f, "Reached the goal"),
+        }
+    }
+}
+
+/// How a game went, kept once it is over for frontends to show the results.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GameSummary {
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    /// Pieces locked into the stack.
+    pub pieces: usize,
+    /// Updates played, the countdown to the start not included.
+    pub ticks: u32,
+    pub top_out: TopOut,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Tetris {
+    Running(TetrisState),
+    /// A game held where it is by pause, left alone by updates and input until it is resumed.
+    Paused(TetrisState),
+    /// A game that has topped out or reached its goal, and how it went.
+    Finished(GameSummary),
+}
+
+impl Default for Tetris {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tetris {
+    /// Create a new game seeded from the operating system, so that every game is different.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        Self::new_with_entropy(&mut OsEntropy)
+    }
+
+    /// Create a new game. Without the standard library there is no entropy to seed it from, so
+    /// every game is dealt the same pieces; use new_with_entropy or new_with_seed for others.
+    #[cfg(not(feature = "std"))]
+    pub fn new() -> Self {
+        Self::new_with_seed(DEFAULT_SEED)
+    }
+
+    /// Create a new game with the piece sequence seeded from the given entropy source, so that
+    /// every game is different.
+    pub fn new_with_entropy<E: EntropySource>(entropy: &mut E) -> Self {
+        Self::new_with_seed(entropy.next_seed())
+    }
+
+    /// Create a new game whose piece sequence is decided by seed, so that games started with the
+    /// same seed are dealt the same pieces.
+    pub fn new_with_seed(seed: u64) -> Self {
+        Self::with_rng(XorShiftRng::seed_from_u64(seed))
+    }
+
+    /// Play a game again from the seed it was started with under rules, given each of inputs
+    /// as the key state of an update in turn. A game replayed from the same seed, rules and
+    /// inputs always ends the same, on any platform.
+    pub fn replay<I: IntoIterator<Item = KeyState>>(seed: u64, rules: Rules, inputs: I) -> Self {
+        let mut tetris = Self::new_with_seed(seed);
+        tetris.set_rules(rules);
+        for key_state in inputs {
+            tetris.set_key_state(&key_state);
+            tetris.update();
+        }
+        tetris
+    }
+
+    /// Create a new game dealt the pieces of sequence in order, over and over, so that openings
+    /// and setups can be drilled. The game cannot be replayed from a seed.
+    pub fn with_sequence(sequence: PieceSequence) -> Self {
+        let mut tetris = Self::new_with_seed(0);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.sequence = Some(sequence);
+            state.droughts = Droughts::default();
+            state.piece = spawn_piece(state.deal_piece());
+            state.next_piece = spawn_piece(state.deal_piece());
+        }
+        tetris
+    }
+
+    fn with_rng(mut rng: XorShiftRng) -> Self {
+        let piece = spawn_piece(rng.gen());
+        let next_piece = spawn_piece(rng.gen());
+        let mut droughts = Droughts::default();
+        droughts.deal(piece.kind());
+        droughts.deal(next_piece.kind());
+        Self::Running(TetrisState {
+            grid: Grid::new(GRID_SIZE),
+            piece,
+            next_piece,
+            held_piece: None,
+            key_state: KeyState::default(),
+            score: 0,
+            rules: Rules::default(),
+            phase: Phase::Falling,
+            item: None,
+            slowed: 0,
+            rows_cleared: 0,
+            drop_score: 0,
+            piece_locked: false,
+            last_placement: None,
+            metrics: Metrics::default(),
+            t_spin: false,
+            locked_t_spin: false,
+            perfect_clear: false,
+            scoring_policy: None,
+            lines: 0,
+            combo: None,
+            last_clear_difficult: false,
+            buffered_rotations: 0,
+            buffered_hard_drop: false,
+            buffered_hold: false,
+            buffered_half_turn: false,
+            hold_used: false,
+            gravity_progress: 0,
+            lock_timer: 0,
+            lock_resets: 0,
+            lowest_y: VISIBLE_HEIGHT,
+            shift_direction: 0,
+            shift_timer: 0,
+            items_earned: 0,
+            pieces_dealt: 0,
+            queued: [PieceSelector::Line; MAX_PREVIEWS - 1],
+            queued_len: 0,
+            events: Events::default(),
+            droughts,
+            top_outs: 0,
+            ticks: 0,
+            pieces_placed: 0,
+            stats: PlayStats::default(),
+            piece_inputs: 0,
+            finesse: Finesse::default(),
+            rotated_last: false,
+            sequence: None,
+            rng,
+        })
+    }
+
+    /// Set the current state of all inputs to the game, to be considered on all subsequent
+    /// updates until the next call to set_key_state.
+    ///
+    /// Rotate and hard drop presses are also remembered until the next update, so a key that is
+    /// pressed and released between two updates still takes effect. Each press between updates
+    /// rotates the piece once more.
+    pub fn set_key_state(&mut self, key_state: &KeyState) {
+        match self {
+            Self::Running(state) => state.set_key_state(key_state),
+            Self::Paused(_) | Self::Finished(_) => {}
+        }
+    }
+
+    /// Perform a single update of the game, first applying and input moves or rotations if legal,
+    /// then attempting to lower the piece by one tile. If the lowered piece collides with an
+    /// existing tile or the floor of the game grid then the piece is placed into the grid,
+    /// complete rows are considered and the piece is respawned. Upon respawn if the piece
+    /// immediately collides with the grid then the player has lost and the game is over. A game
+    /// that has completed its mode is over too, and updates leave it as it is.
+    ///
+    /// This function should be called with a frequency that matches your desired game speed,
+    /// calling it more frequently will make the game faster and more difficult.
+    ///
+    /// Debug builds validate the state after every update and panic if it has been corrupted.
+    pub fn update(&mut self) {
+        self.update_n(1);
+    }
+
+    /// Update once like update, calling listener with each event of the update in the order
+    /// they happened. The events are also left for TetrisState::events, except for GameOver,
+    /// which only the listener is told of.
+    pub fn update_with<L: FnMut(GameEvent)>(&mut self, listener: L) {
+        self.run_ticks(1, |_| None, listener);
+    }
+
+    /// Update once, recording how long the update took by clock, such as a microsecond timer,
+    /// in the metrics of the game.
+    pub fn update_timed<C: FnMut() -> u32>(&mut self, mut clock: C) {
+        let start = clock();
+        self.update();
+        let elapsed = clock().wrapping_sub(start);
+        if let Self::Running(state) = self {
+            state.metrics.elapsed = Some(elapsed);
+        }
+    }
+
+    /// Perform up to ticks updates in a row with the current key state, for seeking through
+    /// replays and headless simulation. Stops early once the game is over and returns the number
+    /// of updates performed.
+    ///
+    /// Debug builds validate the state once at the end rather than after every update.
+    pub fn update_n(&mut self, ticks: usize) -> usize {
+        self.run_ticks(ticks, |_| None, |_| {})
+    }
+
+    /// Like update_n, with the key state for each update given by input, which is called with
+    /// the number of the update starting from zero.
+    pub fn update_n_with<F: FnMut(usize) -> KeyState>(
+        &mut self,
+        ticks: usize,
+        mut input: F,
+    ) -> usize {
+        self.run_ticks(ticks, |tick| Some(input(tick)), |_| {})
+    }
+
+    fn run_ticks<F: FnMut(usize) -> Option<KeyState>, L: FnMut(GameEvent)>(
+        &mut self,
+        ticks: usize,
+        mut input: F,
+        mut listener: L,
+    ) -> usize {
+        #[cfg(debug_assertions)]
+        let score_before = match self {
+            Self::Running(state) => Some(state.score),
+            Self::Paused(_) | Self::Finished(_) => None,
+        };
+
+        let mut performed = 0;
+        while performed < ticks {
+            let Self::Running(state) = self else {
+                break;
+            };
+            // Only reached by a game whose rules were changed to a mode it has already completed
+            if state.is_complete() {
+                *self = Self::Finished(state.summary(TopOut::GoalReached));
+                break;
+            }
+            if let Some(key_state) = input(performed) {
+                state.set_key_state(&key_state);
+            }
+            let top_out = self.step();
+            performed += 1;
+
+            let Self::Running(state) = self else {
+                break;
+            };
+            let complete = state.is_complete();
+            if complete {
+                state.events.push(GameEvent::GoalReached);
+            }
+            state.events.iter().for_each(&mut listener);
+            // Reaching the goal ends the game before a piece spawned after it can top out
+            if complete {
+                *self = Self::Finished(state.summary(TopOut::GoalReached));
+            } else if let Some(top_out) = top_out {
+                listener(GameEvent::GameOver(top_out));
+                *self = Self::Finished(state.summary(top_out));
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        if let (Self::Running(state), Some(score_before)) = (&*self, score_before) {
+            if let Err(error) = state.validate_update(score_before) {
+                panic!("Invalid game state after update: {}", error);
+            }
+        }
+
+        performed
+    }
+
+    /// Update a running game once, returning how it topped out if it did. The game is left for
+    /// the caller to end, so that the events of the update can be reported first.
+    fn step(&mut self) -> Option<TopOut> {
+        match self {
+            Self::Running(state) => {
+                state.events.clear();
+                state.rows_cleared = 0;
+                state.drop_score = 0;
+                state.piece_locked = false;
+                state.t_spin = false;
+                state.perfect_clear = false;
+                state.metrics = Metrics::default();
+
+                // Between pieces nothing responds to input, presses stay buffered for the next
+                // piece and held keys are read as it spawns.
+                let next_piece_fits = match state.phase {
+                    // Nor before the game starts, the countdown not being part of the time played
+                    Phase::Countdown { ref mut remaining } => {
+                        if TetrisState::count_down(remaining) {
+                            state.phase = Phase::Falling;
+                        }
+                        return None;
+                    }
+                    Phase::Falling => None,
+                    Phase::Entry { ref mut remaining } => {
+                        Some(!TetrisState::count_down(remaining) || state.spawn_next_piece())
+                    }
+                    Phase::LineClear { ref mut remaining } => {
+                        Some(if TetrisState::count_down(remaining) {
+                            state.remove_complete_rows();
+                            state.start_next_piece()
+                        } else {
+                            true
+                        })
+                    }
+                };
+                state.ticks += 1;
+                state.stats.ticks += 1;
+                match next_piece_fits {
+                    None => {}
+                    Some(true) => return None,
+                    Some(false) => {
+                        return Some(TopOut::BlockOut);
+                    }
+                }
+
+                // Presses since the last update are applied now, a held key acts once per
+                // update.
+                let rotations = match state.buffered_rotations {
+                    0 => state.key_state.rotate as i8 - state.key_state.rotate_ccw as i8,
+                    buffered => buffered,
+                };
+                let hard_drop = state.key_state.hard_drop || state.buffered_hard_drop;
+                let hold = state.key_state.hold || state.buffered_hold;
+                let half_turn = (state.key_state.rotate_180 || state.buffered_half_turn)
+                    && state.rules.rotate_180;
+                state.buffered_rotations = 0;
+                state.buffered_hard_drop = false;
+                state.buffered_hold = false;
+                state.buffered_half_turn = false;
+
+                if hold && !state.hold_used {
+                    state.swap_hold();
+                    if state.piece_collides() && !state.forgive_top_out() {
+                        return Some(TopOut::BlockOut);
+                    }
+                }
+
+                // Whether the piece moved or rotated, which can start its lock delay again
+                let mut moved = false;
+                if half_turn {
+                    moved |= state.try_rotate(Turn::Half);
+                }
+                let turn = match rotations > 0 {
+                    true => Turn::Clockwise,
+                    false => Turn::CounterClockwise,
+                };
+                for _ in 0..rotations.unsigned_abs() {
+                    moved |= state.try_rotate(turn);
+                }
+
+                // The rotation is fixed from here on, so the piece is moved with its grid
+                // looked up once and its position written back at the end.
+                let piece = state.piece.current_rotation();
+                let (mut x, mut y) = (state.piece.x, state.piece.y);
+                let (width, wrap) = (state.grid.width, state.rules.wrap);
+
+                // Apply any left / right move before lowering y. Do not do the move if it creates
+                // a collision. We do nothing if both keys are pushed as they net out.
+                let direction = match (state.key_state.left, state.key_state.right) {
+                    (true, false) => -1,
+                    (false, true) => 1,
+                    _ => 0,
+                };
+                // A new press moves the piece once, holding it moves the piece again after the
+                // delayed auto shift and then at the auto repeat rate.
+                let shifts = if direction != state.shift_direction {
+                    state.shift_timer = state.rules.das;
+                    1
+                } else if state.rules.das == 0 {
+                    1
+                } else {
+                    state.shift_timer = state.shift_timer.saturating_sub(1);
+                    match (state.shift_timer, state.rules.arr) {
+                        (0, 0) => width,
+                        (0, arr) => {
+                            state.shift_timer = arr;
+                            1
+                        }
+                        _ => 0,
+                    }
+                };
+                state.shift_direction = direction;
+                for _ in 0..shifts {
+                    let shifted = match (direction, wrap) {
+                        (0, _) => None,
+                        (-1, true) => Some((x + width - 1) % width),
+                        (-1, false) => x.checked_sub(1),
+                        (_, true) => Some((x + 1) % width),
+                        (_, false) => Some(x + 1).filter(|&right| right + piece.width <= width),
+                    };
+                    match shifted.filter(|&to| !collides(piece, &state.grid, (to, y), wrap)) {
+                        Some(to) => {
+                            x = to;
+                            state.rotated_last = false;
+                            moved = true;
+                        }
+                        None => break,
+                    }
+                }
+
+                let resting = |y: usize| y == 0 || collides(piece, &state.grid, (x, y - 1), wrap);
+
+                // Gravity moves the piece whole cells at a time. A piece already resting on the
+                // stack when it is due to fall locks, one that lands only locks once it is due
+                // to fall again, leaving time to slide it.
+                let gravity = state.gravity();
+                state.slowed = state.slowed.saturating_sub(1);
+                let start_y = y;
+                let fall = state.gravity_progress as usize + gravity.0 as usize;
+                state.gravity_progress = (fall & ((1 << GRAVITY_SHIFT) - 1)) as u16;
+                let mut cells = fall >> GRAVITY_SHIFT;
+                let locks = cells > 0 && resting(y);
+                while cells > 0 && !resting(y) {
+                    y -= 1;
+                    cells -= 1;
+                }
+
+                // A hard drop lowers the piece as far as it will go and places it during this
+                // update.
+                if hard_drop {
+                    while !resting(y) {
+                        y -= 1;
+                    }
+                }
+
+                // With a lock delay a piece locks once it has rested that many updates instead,
+                // falling lower or moving as the rules allow starting the count again.
+                let locks = match state.rules.lock_delay {
+                    0 => locks,
+                    delay => {
+                        if y < state.lowest_y {
+                            state.lowest_y = y;
+                            state.lock_timer = 0;
+                            state.lock_resets = 0;
+                        } else if moved && state.rules.lock_reset.allows(state.lock_resets) {
+                            state.lock_timer = 0;
+                            state.lock_resets = state.lock_resets.saturating_add(1);
+                        }
+                        resting(y) && {
+                            state.lock_timer += 1;
+                            state.lock_timer >= delay
+                        }
+                    }
+                };
+
+                if hard_drop || state.key_state.soft_drop {
+                    state.drop_score = state.scoring_policy().drop(start_y - y, hard_drop);
+                }
+                state.score += state.drop_score;
+
+                if locks || hard_drop {
+                    // The corners are checked against the stack before the piece joins it
+                    state.t_spin = state.is_t_spin(piece, (x, y));
+                    state.locked_t_spin = state.t_spin;
+                    match wrap {
+                        true => piece.copy_into_wrapping(&mut state.grid, (x, y)),
+                        false => piece.copy_into(&mut state.grid, (x, y)),
+                    }
+                    state.metrics.cells_touched +=
+                        piece.data.iter().filter(|&&cell| cell).count() as u32;
+                    state.piece_locked = true;
+                    state.pieces_placed += 1;
+                    state.stats.piece_counts.add(state.piece.kind());
+                    let placement = Placement {
+                        kind: state.piece.kind(),
+                        rotation: state.piece.rotation(),
+                        x,
+                        y,
+                    };
+                    state.last_placement = Some(placement);
+                    state.events.push(GameEvent::PieceLocked(placement));
+                    let width = state.grid.width;
+                    let fewest = finesse::fewest_inputs(placement, width, state.rules.rotate_180);
+                    if let Some(fewest) = fewest {
+                        state.finesse.record(state.piece_inputs, fewest);
+                    }
+                    state.piece_inputs = 0;
+
+                    // The piece never came into view, so it counts as topping out even if it
+                    // completes a row up there
+                    let locked_out =
+                        state.rules.top_out != TopOutRule::BlockOut && y >= state.visible_height();
+                    if locked_out && !state.forgive_top_out() {
+                        return Some(TopOut::LockOut);
+                    }
+
+                    if state.rules.line_clear_delay > 0 && state.has_complete_rows() {
+                        state.phase = Phase::LineClear {
+                            remaining: state.rules.line_clear_delay,
+                        };
+                        return None;
+                    }
+
+                    state.remove_complete_rows();
+
+                    // If a spawned piece immediately collides with the world then the game is lost
+                    if !state.start_next_piece() {
+                        return Some(TopOut::BlockOut);
+                    }
+                } else {
+                    state.piece.x = x;
+                    state.piece.y = y;
+                }
+                None
+            }
+            Self::Paused(_) | Self::Finished(_) => None,
+        }
+    }
+
+    /// Set the rules for the rest of the game, which can be done while it is paused. A countdown
+    /// only applies to a game that has yet to start.
+    pub fn set_rules(&mut self, rules: Rules) {
+        match self {
+            Self::Running(state) | Self::Paused(state) => {
+                if rules.mirror != state.rules.mirror {
+                    state.mirror_pieces();
+                }
+                if rules.countdown != state.rules.countdown && state.ticks == 0 {
+                    state.phase = match rules.countdown {
+                        0 => Phase::Falling,
+                        remaining => Phase::Countdown { remaining },
+                    };
+                }
+                state.rules = rules;
+                state.fill_queue();
+            }
+            Self::Finished(_) => {}
+        }
+    }
+
+    /// Push the stack up by rows of garbage with an empty cell in column hole, as sent by an
+    /// opponent. A hole past the edge of the playfield is kept to its last column rather than
+    /// trusted, as the garbage may come from another device. The game is lost if the stack is
+    /// pushed out of the top of the grid or into the falling piece, unless zen mode clears it.
+    pub fn add_garbage(&mut self, rows: usize, hole: usize) {
+        if let Self::Running(state) = self {
+            if !state.add_garbage(rows, hole) {
+                *self = Self::Finished(state.summary(TopOut::Garbage));
+            }
+        }
+    }
+
+    /// Fill the bottom of the stack with rows of garbage, their holes placed by rng. The game is
+    /// lost if there are too many rows for the falling piece to fit above them.
+    pub fn add_starting_garbage<R: Rng>(&mut self, garbage: StartingGarbage, rng: &mut R) {
+        let Self::Running(ref state) = self else {
+            return;
+        };
+        let width = state.grid.width;
+        let mut hole = rng.gen_range(0, width);
+        for _ in 0..garbage.rows {
+            self.add_garbage(1, hole);
+            if rng.gen_range(0, 100) < garbage.messiness {
+                hole = (hole + rng.gen_range(1, width)) % width;
+            }
+        }
+    }
+
+    /// Use the held item, applying it straight away if it acts on this board. Returns the item
+    /// used so that one for an opponent can be passed to their apply_item.
+    pub fn use_item(&mut self) -> Option<Item> {
+        let Self::Running(state) = self else {
+            return None;
+        };
+        let item = state.item.take()?;
+        if item.target() == item::Target::Own {
+            state.apply_item(item);
+        }
+        Some(item)
+    }
+
+    /// Apply an item used by an opponent.
+    pub fn apply_item(&mut self, item: Item) {
+        if let Self::Running(state) = self {
+            state.apply_item(item);
+        }
+    }
+
+    /// Hold a running game where it is. Updates, input, garbage and items leave it alone until
+    /// it is resumed.
+    pub fn pause(&mut self) {
+        *self = match core::mem::replace(self, Self::Finished(GameSummary::default())) {
+            Self::Running(state) => Self::Paused(state),
+            other => other,
+        };
+    }
+
+    /// Carry on with a paused game from where it was paused.
+    pub fn resume(&mut self) {
+        *self = match core::mem::replace(self, Self::Finished(GameSummary::default())) {
+            Self::Paused(state) => Self::Running(state),
+            other => other,
+        };
+    }
+
+    pub fn is_paused(&self) -> bool {
+        matches!(self, Self::Paused(_))
+    }
+
+    /// How the game went, once it has topped out or reached its goal.
+    pub fn summary(&self) -> Option<&GameSummary> {
+        match self {
+            Self::Finished(summary) => Some(summary),
+            Self::Running(_) | Self::Paused(_) => None,
+        }
+    }
+
+    /// Whether the game is over, by topping out or by completing its mode.
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Finished(_))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::event::GameEvent;
+    use crate::item::{Item, SLOW_DOWN_TICKS};
+    use crate::mode::{GameMode, SPRINT_LINES, ULTRA_TICKS};
+    use crate::piece::{Piece, PieceSelector, Rotation};
+    use crate::scoring::{LineClear, Scoring, ScoringPolicy};
+    use crate::sequence::PieceSequence;
+    #[cfg(feature = "std")]
+    use crate::tetris::MAX_PREVIEWS;
+    use crate::tetris::{
+        spawn_piece, EntropySource, Gravity, InvalidState, KeyState, LockReset, Phase, Placement,
+        Rules, StartingGarbage, Tetris, TetrisState, TopOut, TopOutRule, BUFFER_ROWS, GRID_SIZE,
+        MOVE_RESET_LIMIT, VISIBLE_HEIGHT,
+    };
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    #[test]
+    fn new_tetris_instance() {
+        let _tetris = Tetris::new();
+    }
+
+    #[test]
+    fn doing_nothing_for_a_million_updates_creates_a_finished_game() {
+        let mut tetris = Tetris::new();
+
+        for _ in 0..100_000 {
+            tetris.update();
+        }
+
+        assert!(tetris.is_finished());
+    }
+
+    #[test]
+    fn zen_mode_clears_the_stack_instead_of_ending_the_game() {
+        let mut tetris = Tetris::new();
+        tetris.set_rules(Rules {
+            zen: true,
+            ..Rules::default()
+        });
+
+        for _ in 0..100_000 {
+            tetris.update();
+        }
+
+        let state = running(tetris);
+        assert!(state.top_outs() > 0);
+        assert!(state.validate() == Ok(()));
+    }
+
+    #[test]
+    fn a_game_that_completes_its_mode_is_finished_with_its_summary() {
+        let mut tetris = Tetris::new();
+        tetris.set_rules(Rules {
+            zen: true,
+            mode: GameMode::Ultra,
+            ..Rules::default()
+        });
+
+        assert!(tetris.update_n(100_000) == ULTRA_TICKS as usize);
+        assert!(tetris.is_finished());
+        let summary = tetris.summary().unwrap();
+        assert!(summary.top_out == TopOut::GoalReached && summary.ticks == ULTRA_TICKS);
+    }
+
+    #[test]
+    fn a_sprint_is_finished_by_the_line_that_reaches_its_goal() {
+        let mut state = running(Tetris::new_with_seed(4));
+        state.rules.mode = GameMode::Sprint;
+        state.lines = SPRINT_LINES - 1;
+        state.grid.row_mut(0).fill(true);
+        state.grid.row_mut(0)[3..7].fill(false);
+        state.piece = PieceSelector::Line.to_piece((3, 10));
+        let mut tetris = Tetris::Running(state);
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+
+        let mut last = None;
+        tetris.update_with(|event| last = Some(event));
+        assert!(last == Some(GameEvent::GoalReached));
+        let summary = tetris.summary().unwrap();
+        assert!(summary.top_out == TopOut::GoalReached && summary.lines == SPRINT_LINES);
+    }
+
+    #[test]
+    fn a_paused_game_is_left_alone_until_it_is_resumed() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.pause();
+        assert!(tetris.is_paused() && !tetris.is_finished());
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        assert!(tetris.update_n(10) == 0);
+        tetris.add_garbage(4, 0);
+
+        tetris.resume();
+        assert!(!tetris.is_paused());
+        assert!(same_game(&tetris, &Tetris::new_with_seed(4)));
+        tetris.update();
+        // The hard drop pressed while paused was ignored
+        assert!(!running_ref(&tetris).piece_locked());
+    }
+
+    struct Counter(u64);
+
+    impl EntropySource for Counter {
+        fn next_seed(&mut self) -> u64 {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    #[test]
+    fn new_tetris_instance_with_entropy() {
+        let mut entropy = Counter(0);
+        let tetris = Tetris::new_with_entropy(&mut entropy);
+        assert!(!tetris.is_finished());
+        assert!(entropy.0 == 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn new_games_are_dealt_different_pieces() {
+        let rules = Rules {
+            previews: MAX_PREVIEWS as u8,
+            ..Rules::default()
+        };
+        let (mut first, mut second) = (Tetris::new(), Tetris::new());
+        first.set_rules(rules);
+        second.set_rules(rules);
+        let (first, second) = (running_ref(&first), running_ref(&second));
+        // Seven pieces alike by chance is about one game pair in a million
+        fn dealt(state: &TetrisState) -> impl Iterator<Item = PieceSelector> + '_ {
+            core::iter::once(state.piece.kind()).chain(state.previews().map(|piece| piece.kind()))
+        }
+        assert!(!dealt(first).eq(dealt(second)));
+    }
+
+    #[test]
+    fn hard_drop_places_the_piece_on_the_floor() {
+        let mut tetris = Tetris::new();
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+
+        match tetris {
+            Tetris::Running(state) => {
+                assert!((0..state.grid.width).any(|x| state.grid[(x, 0)]));
+            }
+            Tetris::Paused(_) | Tetris::Finished(_) => {
+                panic!("A single hard drop should not end the game")
+            }
+        }
+    }
+
+    fn running(tetris: Tetris) -> TetrisState {
+        match tetris {
+            Tetris::Running(state) => state,
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    #[test]
+    fn a_new_game_is_valid() {
+        assert!(running(Tetris::new()).validate() == Ok(()));
+    }
+
+    #[test]
+    fn corrupted_states_are_reported() {
+        let mut state = running(Tetris::new());
+        state.grid.row_mut(3).fill(true);
+        assert!(state.validate() == Err(InvalidState::CompleteRow { y: 3 }));
+
+        let mut state = running(Tetris::new());
+        state.piece.x = state.grid.width;
+        assert!(matches!(
+            state.validate(),
+            Err(InvalidState::PieceOutOfBounds { .. })
+        ));
+
+        let mut state = running(Tetris::new());
+        state.piece.y = 0;
+        state.grid.row_mut(0).fill(true);
+        state.grid[(0, 0)] = false;
+        assert!(matches!(
+            state.validate(),
+            Err(InvalidState::PieceOverlapsStack { y: 0, .. })
+        ));
+
+        let state = running(Tetris::new());
+        assert!(matches!(
+            state.validate_update(state.score + 1),
+            Err(InvalidState::ScoreDecreased { .. })
+        ));
+    }
+
+    fn running_ref(tetris: &Tetris) -> &TetrisState {
+        match tetris {
+            Tetris::Running(state) => state,
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    fn same_game(first: &Tetris, second: &Tetris) -> bool {
+        match (first, second) {
+            (Tetris::Running(first), Tetris::Running(second)) => {
+                first.grid == second.grid
+                    && first.score == second.score
+                    && first.piece.kind() == second.piece.kind()
+                    && first.piece.rotation() == second.piece.rotation()
+                    && (first.piece.x, first.piece.y) == (second.piece.x, second.piece.y)
+                    && first.next_piece.kind() == second.next_piece.kind()
+            }
+            (Tetris::Finished(_), Tetris::Finished(_)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn update_n_matches_repeated_updates() {
+        let input = |tick: usize| KeyState {
+            left: tick % 3 == 0,
+            rotate: tick % 7 == 0,
+            ..KeyState::default()
+        };
+
+        let (mut stepped, mut batched) = (Tetris::new_with_seed(3), Tetris::new_with_seed(3));
+        for tick in 0..60 {
+            stepped.set_key_state(&input(tick));
+            stepped.update();
+        }
+        assert!(batched.update_n_with(60, input) == 60);
+        assert!(same_game(&stepped, &batched));
+
+        stepped.update_n(10);
+        for _ in 0..10 {
+            batched.update();
+        }
+        assert!(same_game(&stepped, &batched));
+    }
+
+    #[test]
+    fn a_game_replays_from_its_seed_and_inputs() {
+        let rules = Rules {
+            previews: 3,
+            ..Rules::default()
+        };
+        let keys = [
+            KeyState {
+                right: true,
+                ..KeyState::default()
+            },
+            KeyState {
+                rotate: true,
+                ..KeyState::default()
+            },
+            KeyState::default(),
+            KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            },
+        ];
+        let input = |tick: usize| keys[tick % keys.len()];
+
+        let mut played = Tetris::new_with_seed(8);
+        played.set_rules(rules);
+        played.update_n_with(24, input);
+        let replayed = Tetris::replay(8, rules, (0..24).map(input));
+        assert!(!replayed.is_finished() && same_game(&played, &replayed));
+        assert!(!same_game(
+            &played,
+            &Tetris::replay(9, rules, (0..24).map(input))
+        ));
+    }
+
+    #[test]
+    fn a_sequence_is_dealt_in_order_over_and_over() {
+        let sequence = PieceSequence::from_letters("TSZ").unwrap();
+        let mut tetris = Tetris::with_sequence(sequence);
+        // Drought protection gives way to the sequence, which never deals a line piece
+        tetris.set_rules(Rules {
+            previews: 4,
+            max_drought: Some(2),
+            ..Rules::default()
+        });
+        let state = running_ref(&tetris);
+        let dealt = core::iter::once(state.piece.clone()).chain(state.previews());
+        assert!(dealt.map(|piece| piece.kind()).eq([
+            PieceSelector::T,
+            PieceSelector::S,
+            PieceSelector::Z,
+            PieceSelector::T,
+            PieceSelector::S,
+        ]));
+    }
+
+    #[test]
+    fn update_n_stops_when_the_game_is_over() {
+        let mut tetris = Tetris::new();
+        let performed = tetris.update_n(100_000);
+        assert!(tetris.is_finished());
+        assert!(performed < 100_000);
+        assert!(tetris.update_n(10) == 0);
+    }
+
+    #[test]
+    fn a_topped_out_game_keeps_its_summary() {
+        let mut tetris = Tetris::new();
+        assert!(tetris.summary().is_none());
+        let performed = tetris.update_n(100_000);
+
+        let summary = *tetris.summary().unwrap();
+        assert!(summary.top_out == TopOut::BlockOut);
+        assert!(summary.ticks == performed as u32);
+        assert!(summary.pieces > 0 && summary.lines == 0 && summary.level == 0);
+    }
+
+    #[test]
+    fn presses_between_updates_are_not_lost() {
+        let (mut tapped, mut held) = (Tetris::new_with_seed(5), Tetris::new_with_seed(5));
+        let rotate = KeyState {
+            rotate: true,
+            ..KeyState::default()
+        };
+
+        // Pressed and released before the update
+        tapped.set_key_state(&rotate);
+        tapped.set_key_state(&KeyState::default());
+        tapped.update();
+        held.set_key_state(&rotate);
+        held.update();
+        assert!(same_game(&tapped, &held));
+
+        // Two taps rotate twice
+        for _ in 0..2 {
+            tapped.set_key_state(&rotate);
+            tapped.set_key_state(&KeyState::default());
+        }
+        tapped.update();
+        held.update();
+        let rotation = |tetris: &Tetris| running_ref(tetris).piece.rotation();
+        assert!(rotation(&tapped) == rotation(&held).next());
+    }
+
+    #[test]
+    fn a_tapped_hard_drop_is_not_lost() {
+        let mut tetris = Tetris::new();
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.set_key_state(&KeyState::default());
+        tetris.update();
+
+        let state = running_ref(&tetris);
+        assert!((0..state.grid.width).any(|x| state.grid[(x, 0)]));
+    }
+
+    const HOLD: KeyState = KeyState {
+        left: false,
+        right: false,
+        rotate: false,
+        rotate_ccw: false,
+        rotate_180: false,
+        hard_drop: false,
+        soft_drop: false,
+        hold: true,
+    };
+
+    #[test]
+    fn hold_swaps_the_piece_once_until_it_locks() {
+        let mut tetris = Tetris::new_with_seed(9);
+        let (first, second) = {
+            let state = running_ref(&tetris);
+            (state.piece.kind(), state.next_piece.kind())
+        };
+
+        // The first hold brings out the next piece
+        tetris.set_key_state(&HOLD);
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.held_piece.as_ref().map(|held| held.kind()) == Some(first));
+        assert!(state.piece.kind() == second);
+
+        // Holding again does nothing until the piece locks
+        tetris.set_key_state(&KeyState::default());
+        tetris.set_key_state(&HOLD);
+        tetris.update();
+        assert!(running_ref(&tetris).piece.kind() == second);
+    }
+
+    /// Hard drop the falling piece with keys held as the next piece spawns, without them
+    /// acting on the piece being dropped.
+    fn drop_with_keys_held(tetris: &mut Tetris, key_state: KeyState) {
+        if let Tetris::Running(ref mut state) = tetris {
+            state.key_state = KeyState {
+                hard_drop: true,
+                ..key_state
+            };
+            state.hold_used = true;
+        }
+        tetris.update();
+    }
+
+    #[test]
+    fn hold_held_as_a_piece_spawns_swaps_it_straight_away() {
+        let mut plain = Tetris::new_with_seed(9);
+        drop_with_keys_held(&mut plain, KeyState::default());
+        let mut with_hold = Tetris::new_with_seed(9);
+        drop_with_keys_held(&mut with_hold, HOLD);
+
+        let (plain, with_hold) = (running_ref(&plain), running_ref(&with_hold));
+        assert!(with_hold.held_piece.as_ref().unwrap().kind() == plain.piece.kind());
+        assert!(with_hold.piece.kind() == plain.next_piece.kind());
+        assert!(with_hold.grid == plain.grid);
+    }
+
+    #[test]
+    fn rotate_held_as_a_piece_spawns_enters_rotated() {
+        let mut tetris = Tetris::new_with_seed(9);
+        drop_with_keys_held(
+            &mut tetris,
+            KeyState {
+                rotate: true,
+                ..KeyState::default()
+            },
+        );
+        assert!(running_ref(&tetris).piece.rotation() == Rotation::R90);
+    }
+
+    #[test]
+    fn the_next_piece_waits_for_the_entry_delay() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            entry_delay: 3,
+            ..Rules::default()
+        });
+        let next = running_ref(&tetris).next_piece.kind();
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        tetris.set_key_state(&KeyState::default());
+
+        for remaining in [3, 2, 1] {
+            let state = running_ref(&tetris);
+            assert!(state.phase == Phase::Entry { remaining });
+            assert!(state.piece_in_play().is_none());
+            assert!(state.validate() == Ok(()));
+            tetris.update();
+        }
+
+        let state = running_ref(&tetris);
+        assert!(state.phase == Phase::Falling);
+        assert!(state.piece_in_play().map(|piece| piece.kind()) == Some(next));
+    }
+
+    #[test]
+    fn the_first_piece_waits_for_the_countdown() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            countdown: 3,
+            ..Rules::default()
+        });
+        let first = running_ref(&tetris).piece.clone();
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.set_key_state(&KeyState::default());
+
+        for remaining in [3, 2, 1] {
+            let state = running_ref(&tetris);
+            assert!(state.countdown() == Some(remaining));
+            assert!(state.piece_in_play().is_none());
+            assert!(state.validate() == Ok(()));
+            tetris.update();
+        }
+
+        // The countdown is not part of the time played, and the drop pressed during it is kept
+        let state = running_ref(&tetris);
+        assert!(state.countdown().is_none() && state.ticks() == 0);
+        assert!(state.piece.x == first.x && state.piece.y == first.y);
+        tetris.update();
+        assert!(running_ref(&tetris).piece_locked());
+
+        // Once the game has started the countdown has no effect
+        tetris.set_rules(Rules {
+            countdown: 5,
+            ..Rules::default()
+        });
+        assert!(running_ref(&tetris).countdown().is_none());
+    }
+
+    #[test]
+    fn presses_during_the_entry_delay_act_on_the_next_piece() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            entry_delay: 2,
+            ..Rules::default()
+        });
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        tetris.set_key_state(&KeyState::default());
+
+        // A tap of rotate between pieces
+        tetris.set_key_state(&KeyState {
+            rotate: true,
+            ..KeyState::default()
+        });
+        tetris.set_key_state(&KeyState::default());
+        tetris.update_n(2);
+        assert!(running_ref(&tetris).piece.rotation() == Rotation::R0);
+        tetris.update();
+        assert!(running_ref(&tetris).piece.rotation() == Rotation::R90);
+    }
+
+    #[test]
+    fn complete_rows_stay_for_the_line_clear_delay() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            line_clear_delay: 2,
+            entry_delay: 1,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..2 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        tetris.set_key_state(&KeyState::default());
+
+        let state = running_ref(&tetris);
+        assert!(state.phase == Phase::LineClear { remaining: 2 });
+        assert!(state.clearing_rows().eq([0, 1]));
+        assert!(state.validate() == Ok(()));
+        assert!(state.score == state.drop_score());
+
+        tetris.update();
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.phase == Phase::Entry { remaining: 1 });
+        assert!(state.rows_cleared() == 2);
+        assert!(state.grid.row(0).iter().all(|&cell| !cell));
+        assert!(state.clearing_rows().next().is_none());
+
+        tetris.update();
+        assert!(running_ref(&tetris).phase == Phase::Falling);
+    }
+
+    #[test]
+    fn the_drop_score_is_reported_for_the_update_that_made_it() {
+        let mut tetris = Tetris::new_with_seed(4);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        assert!(running_ref(&tetris).drop_score() == 20);
+        assert!(running_ref(&tetris).score == 20);
+
+        tetris.set_key_state(&KeyState::default());
+        tetris.update();
+        assert!(running_ref(&tetris).drop_score() == 0);
+        assert!(running_ref(&tetris).score == 20);
+    }
+
+    #[test]
+    fn clearing_two_rows_earns_an_item_in_the_item_mode() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            items: true,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..3 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        assert!(running_ref(&tetris).item == Some(Item::Bomb));
+
+        // The bomb acts on this board, clearing around the top of the stack, the row left after
+        // the clear having fallen to the floor
+        assert!(tetris.use_item() == Some(Item::Bomb));
+        let state = running_ref(&tetris);
+        assert!(state.item.is_none());
+        assert!(!state.grid[(2, 0)] && !state.grid[(3, 0)] && state.grid[(4, 0)]);
+    }
+
+    #[test]
+    fn pieces_spawn_at_the_top_of_a_resized_playfield_and_land_on_its_floor() {
+        let mut state = running(Tetris::new_with_seed(4));
+        state.resize_playfield((5, 12));
+        assert!(state.grid.width == 5 && state.visible_height() == 10);
+        assert!(state.piece.y == 10 && state.next_piece.y == 10);
+        let width = state.piece.current_rotation().width;
+        assert!(state.piece.x == (5 - width) / 2);
+        assert!(state.validate().is_ok());
+
+        let mut tetris = Tetris::Running(state);
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.piece_locked());
+        assert!(state.grid.row(0).iter().any(|&cell| cell));
+        assert!(state.piece.y == 10);
+    }
+
+    #[test]
+    fn a_slow_down_halves_gravity_while_it_lasts() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.apply_item(Item::SlowDown);
+        let start = running_ref(&tetris).piece.y;
+        tetris.update_n(4);
+        assert!(running_ref(&tetris).piece.y == start - 2);
+        assert!(running_ref(&tetris).slowed == SLOW_DOWN_TICKS - 4);
+    }
+
+    /// A T pointing right standing in the slot of a T-spin double, rotated into it by the next
+    /// update. With a ledge over the slot three corners of the T are covered.
+    fn t_in_slot(ledge: bool) -> Tetris {
+        let mut tetris = Tetris::new_with_seed(4);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.grid.row_mut(0).fill(true);
+            state.grid.row_mut(0)[3] = false;
+            state.grid.row_mut(1)[..2].fill(true);
+            state.grid.row_mut(1)[5..].fill(true);
+            state.grid[(4, 2)] = ledge;
+            state.piece = PieceSelector::T.to_piece((3, 0));
+            state.piece.next_rotation();
+        }
+        tetris.set_key_state(&KeyState {
+            rotate: true,
+            ..KeyState::default()
+        });
+        tetris
+    }
+
+    #[test]
+    fn rotating_a_t_into_a_covered_slot_is_a_t_spin() {
+        let mut tetris = t_in_slot(true);
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.piece_locked() && state.t_spin());
+        assert!(state.rows_cleared() == 2);
+    }
+
+    #[test]
+    fn clearing_everything_left_is_a_perfect_clear() {
+        let clear_row = |leftover: bool| {
+            let mut state = running(Tetris::new_with_seed(4));
+            state.grid.row_mut(0).fill(true);
+            state.grid.row_mut(0)[3..7].fill(false);
+            state.grid[(0, 1)] = leftover;
+            state.piece = PieceSelector::Line.to_piece((3, 10));
+            let mut tetris = Tetris::Running(state);
+            tetris.set_key_state(&KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            });
+            tetris.update();
+            tetris
+        };
+        let mut tetris = clear_row(false);
+        let state = running_ref(&tetris);
+        assert!(state.rows_cleared() == 1 && state.perfect_clear());
+        tetris.update();
+        assert!(!running_ref(&tetris).perfect_clear());
+
+        let state = running(clear_row(true));
+        assert!(state.rows_cleared() == 1 && !state.perfect_clear());
+    }
+
+    #[test]
+    fn an_open_slot_is_not_a_t_spin() {
+        let mut tetris = t_in_slot(false);
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.piece_locked() && !state.t_spin());
+    }
+
+    #[test]
+    fn the_scoring_of_the_rules_scores_t_spins_after_the_line_clear_delay() {
+        let mut tetris = t_in_slot(true);
+        tetris.set_rules(Rules {
+            scoring: Scoring::Guideline,
+            line_clear_delay: 2,
+            ..Rules::default()
+        });
+        tetris.update();
+        assert!(running_ref(&tetris).score == 0);
+        tetris.update_n(2);
+        let state = running_ref(&tetris);
+        assert!(state.rows_cleared() == 2);
+        assert!(state.score == 1200);
+    }
+
+    #[derive(Debug)]
+    struct PerRow;
+
+    impl ScoringPolicy for PerRow {
+        fn clear(&self, clear: &LineClear) -> usize {
+            clear.rows
+        }
+
+        fn drop(&self, _cells: usize, _hard_drop: bool) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn a_scoring_policy_given_to_the_game_overrides_the_rules() {
+        let mut tetris = t_in_slot(true);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.set_scoring_policy(&PerRow);
+        }
+        tetris.update();
+        assert!(running_ref(&tetris).score == 2);
+    }
+
+    #[test]
+    fn pieces_moved_off_one_side_come_back_on_the_other_in_wrap_mode() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            wrap: true,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+        }
+        tetris.set_key_state(&KeyState {
+            left: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        assert!(running_ref(&tetris).piece.x == GRID_SIZE.0 - 1);
+
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.grid[(GRID_SIZE.0 - 1, 0)] && state.grid[(0, 0)]);
+        assert!(!state.grid[(1, 0)]);
+    }
+
+    fn with_gravity(gravity: Gravity) -> Tetris {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            gravity,
+            ..Rules::default()
+        });
+        tetris
+    }
+
+    #[test]
+    fn the_falling_piece_is_lowered_between_updates() {
+        let mut tetris = with_gravity(Gravity::from_ratio(1, 4));
+        tetris.update();
+        let mut state = running(tetris);
+        assert!(state.fall_offset(0) == 64);
+        assert!(state.fall_offset(128) == 96);
+        assert!(state.fall_offset(256) == 128);
+
+        state.piece.y = 0;
+        assert!(state.fall_offset(128) == 0);
+    }
+
+    #[test]
+    fn fractional_gravity_carries_over_between_updates() {
+        let mut tetris = with_gravity(Gravity::from_ratio(1, 3));
+        let start = running_ref(&tetris).piece.y;
+        tetris.update_n(2);
+        assert!(running_ref(&tetris).piece.y == start);
+        // Three thirds of a cell, rounded down, fall short of a whole cell
+        tetris.update();
+        assert!(running_ref(&tetris).piece.y == start);
+        tetris.update();
+        assert!(running_ref(&tetris).piece.y == start - 1);
+    }
+
+    #[test]
+    fn gravity_above_one_drops_several_cells_an_update() {
+        let mut tetris = with_gravity(Gravity::from_ratio(3, 1));
+        let start = running_ref(&tetris).piece.y;
+        tetris.update();
+        assert!(running_ref(&tetris).piece.y == start - 3);
+    }
+
+    #[test]
+    fn twenty_g_lands_at_once_and_locks_on_the_next_update() {
+        let mut tetris = with_gravity(Gravity::TWENTY_G);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = spawn_piece(PieceSelector::O);
+        }
+        tetris.update();
+        let state = running_ref(&tetris);
+        assert!(state.piece.kind() == PieceSelector::O && state.piece.y == 0);
+
+        // Still time to slide along the floor before it locks
+        tetris.set_key_state(&KeyState {
+            left: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        let state = running_ref(&tetris);
+        let x = spawn_piece(PieceSelector::O).x;
+        assert!(state.grid.row(0)[x - 1] && !state.grid.row(0)[x + 1]);
+        assert!(state.piece.y > 0);
+    }
+
+    /// The update an O piece resting on the floor locks on while slid back and forth, with a
+    /// lock delay of two updates.
+    fn update_locked_while_sliding(lock_reset: LockReset) -> Option<usize> {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            lock_delay: 2,
+            lock_reset,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((4, 0));
+        }
+        let mut left = false;
+        (1..=100).find(|_| {
+            left = !left;
+            tetris.set_key_state(&KeyState {
+                left,
+                right: !left,
+                ..KeyState::default()
+            });
+            tetris.update();
+            running_ref(&tetris).piece_locked()
+        })
+    }
+
+    #[test]
+    fn the_lock_reset_policy_decides_which_moves_delay_the_lock() {
+        assert!(update_locked_while_sliding(LockReset::Step) == Some(2));
+        // The first update lands the piece, then each reset adds an update
+        assert!(
+            update_locked_while_sliding(LockReset::Move) == Some(2 + MOVE_RESET_LIMIT as usize)
+        );
+        assert!(update_locked_while_sliding(LockReset::Infinite).is_none());
+    }
+
+    /// The column an O piece is in after each of the first updates right is held from column 0.
+    fn columns_while_holding_right(das: u8, arr: u8) -> [usize; 6] {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            das,
+            arr,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 15));
+        }
+        tetris.set_key_state(&KeyState {
+            right: true,
+            ..KeyState::default()
+        });
+        [0; 6].map(|_| {
+            tetris.update();
+            running_ref(&tetris).piece.x
+        })
+    }
+
+    #[test]
+    fn holding_a_direction_moves_once_then_repeats_after_the_delay() {
+        assert!(columns_while_holding_right(0, 0) == [1, 2, 3, 4, 5, 6]);
+        assert!(columns_while_holding_right(3, 2) == [1, 1, 1, 2, 2, 3]);
+        // With no repeat delay the piece slides to the wall as soon as the shift delay passes
+        assert!(columns_while_holding_right(2, 0) == [1, 1, 8, 8, 8, 8]);
+    }
+
+    #[test]
+    fn gravity_speeds_up_with_the_level_along_the_curve() {
+        assert!(Gravity::ONE.at_level(0) == Gravity::ONE);
+        assert!(Gravity::ONE.at_level(1) > Gravity::ONE);
+        // Level 14 is over a hundred times as fast, and no faster than TWENTY_G
+        assert!(Gravity::from_ratio(1, 60).at_level(14) > Gravity::ONE);
+        assert!(Gravity::from_ratio(1, 60).at_level(14) < Gravity::TWENTY_G);
+        assert!(Gravity::ONE.at_level(14) == Gravity::TWENTY_G);
+        assert!(Gravity::ONE.at_level(15) == Gravity::TWENTY_G);
+        assert!(Gravity(0).at_level(5) == Gravity(0));
+        assert!(Gravity(u16::MAX).at_level(20) == Gravity(u16::MAX));
+
+        assert!(Gravity::ONE.interval() == 1);
+        assert!(Gravity::TWENTY_G.interval() == 1);
+        assert!(Gravity::from_ratio(1, 3).interval() == 4);
+        assert!(Gravity(0).interval() == u16::MAX);
+    }
+
+    #[test]
+    fn the_level_sets_the_gravity_with_the_speed_curve() {
+        let slow = Gravity::from_ratio(1, 60);
+        let mut state = running(Tetris::new_with_seed(1));
+        state.rules.gravity = slow;
+        state.lines = 25;
+        assert!(state.level() == 2);
+        assert!(state.gravity() == slow);
+
+        state.rules.speed_curve = true;
+        assert!(state.gravity() == slow.at_level(2));
+        assert!(state.gravity().interval() < slow.interval());
+    }
+
+    #[test]
+    fn mirror_mode_flips_the_pieces_dealt() {
+        let mut tetris = Tetris::new_with_seed(4);
+        let mirrored = |piece: &Piece| {
+            let mut grid = piece.kind().to_piece((0, 0)).current_rotation().clone();
+            grid.mirror();
+            grid == *piece.current_rotation()
+        };
+        tetris.set_rules(Rules {
+            mirror: true,
+            ..Rules::default()
+        });
+        let state = running_ref(&tetris);
+        assert!(mirrored(&state.piece) && mirrored(&state.next_piece));
+
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        assert!(mirrored(&running_ref(&tetris).next_piece));
+    }
+
+    #[test]
+    fn the_previews_show_the_pieces_to_come_in_order() {
+        assert!(running_ref(&Tetris::new_with_seed(4)).previews().count() == 1);
+
+        let mut shown = Tetris::new_with_seed(4);
+        shown.set_rules(Rules {
+            previews: 5,
+            ..Rules::default()
+        });
+        let mut coming = [PieceSelector::Line; 5];
+        for (kind, piece) in coming.iter_mut().zip(running_ref(&shown).previews()) {
+            *kind = piece.kind();
+        }
+        assert!(running_ref(&shown).previews().count() == 5);
+
+        // Dealing ahead leaves the sequence as it would have been
+        let mut plain = Tetris::new_with_seed(4);
+        let drop = KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        };
+        for kind in coming {
+            for tetris in [&mut shown, &mut plain] {
+                tetris.set_key_state(&drop);
+                tetris.update();
+                tetris.set_key_state(&KeyState::default());
+                assert!(running_ref(tetris).piece.kind() == kind);
+            }
+        }
+        assert!(running_ref(&shown).previews().count() == 5);
+    }
+
+    #[test]
+    fn pieces_spawn_centered_in_the_hidden_rows() {
+        for (kind, x) in [
+            (PieceSelector::Line, 3),
+            (PieceSelector::T, 3),
+            (PieceSelector::O, 4),
+        ] {
+            let piece = spawn_piece(kind);
+            assert!((piece.x, piece.y) == (x, VISIBLE_HEIGHT));
+        }
+
+        let state = running(Tetris::new_with_seed(4));
+        assert!(state.grid.height == VISIBLE_HEIGHT + BUFFER_ROWS);
+        assert!(state.visible_height() == VISIBLE_HEIGHT);
+        let mut drawn = 0;
+        state.draw_game_grid(|_, y, _| drawn = drawn.max(y + 1), (0, 0), (1, 1));
+        assert!(drawn == VISIBLE_HEIGHT);
+    }
+
+    #[test]
+    fn updates_count_the_work_they_do() {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.update();
+        let metrics = *running_ref(&tetris).metrics();
+        assert!(metrics.cells_touched == 0 && metrics.pieces_spawned == 0);
+
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        let mut now = 0;
+        tetris.update_timed(|| {
+            now += 3;
+            now
+        });
+        let metrics = *running_ref(&tetris).metrics();
+        assert!(metrics.cells_touched == 4);
+        assert!(metrics.rows_scanned == GRID_SIZE.1 as u32);
+        assert!(metrics.pieces_spawned == 1);
+        assert!(metrics.elapsed == Some(3));
+    }
+
+    #[test]
+    fn games_with_the_same_seed_are_dealt_the_same_pieces() {
+        let (first, second) = (
+            running(Tetris::new_with_seed(7)),
+            running(Tetris::new_with_seed(7)),
+        );
+        assert!(first.piece.kind() == second.piece.kind());
+        assert!(first.next_piece.kind() == second.next_piece.kind());
+    }
+
+    fn longest_drought_dealing(max_drought: Option<u8>) -> u32 {
+        let mut state = running(Tetris::new_with_seed(7));
+        state.rules.max_drought = max_drought;
+        for _ in 0..10_000 {
+            state.take_next_piece();
+        }
+        enum_iterator::all::<PieceSelector>()
+            .map(|kind| state.droughts().longest(kind))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn drought_protection_caps_the_longest_drought() {
+        assert!(longest_drought_dealing(None) > 12);
+        assert!(longest_drought_dealing(Some(12)) <= 12);
+    }
+
+    #[test]
+    fn garbage_pushes_the_stack_up() {
+        let mut tetris = Tetris::new();
+        if let Tetris::Running(ref mut state) = tetris {
+            state.grid[(4, 0)] = true;
+        }
+        tetris.add_garbage(2, 3);
+
+        let state = running(tetris);
+        for y in 0..2 {
+            assert!((0..state.grid.width).all(|x| state.grid[(x, y)] == (x != 3)));
+        }
+        assert!(state.grid[(4, 2)]);
+        assert!(state.validate() == Ok(()));
+    }
+
+    #[test]
+    fn garbage_with_a_hole_past_the_edge_keeps_it_in_the_last_column() {
+        let mut tetris = Tetris::new();
+        tetris.add_garbage(1, 200);
+
+        let state = running(tetris);
+        let last = state.grid.width - 1;
+        assert!((0..state.grid.width).all(|x| state.grid[(x, 0)] == (x != last)));
+    }
+
+    #[test]
+    fn garbage_pushing_the_stack_out_ends_the_game() {
+        let mut tetris = Tetris::new();
+        if let Tetris::Running(ref mut state) = tetris {
+            let top = state.grid.height - 1;
+            state.grid[(0, top)] = true;
+        }
+        tetris.add_garbage(1, 0);
+        assert!(tetris.is_finished());
+
+        let mut tetris = Tetris::new();
+        tetris.add_garbage(GRID_SIZE.1, 0);
+        assert!(tetris.summary().map(|summary| summary.top_out) == Some(TopOut::Garbage));
+    }
+
+    /// A game with the visible rows stacked to the top but for the first column, so nothing
+    /// clears and a dropped piece stays in the hidden rows.
+    fn stacked_to_the_top(top_out: TopOutRule) -> Tetris {
+        let mut tetris = Tetris::new_with_seed(4);
+        tetris.set_rules(Rules {
+            top_out,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = tetris {
+            for y in 0..VISIBLE_HEIGHT {
+                state.grid.row_mut(y)[1..].fill(true);
+            }
+        }
+        tetris
+    }
+
+    #[test]
+    fn the_top_out_rule_picks_what_ends_the_game() {
+        let drop = KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        };
+        let top_out_of = |top_out: TopOutRule| {
+            let mut tetris = stacked_to_the_top(top_out);
+            tetris.set_key_state(&drop);
+            tetris.update();
+            tetris.summary().map(|summary| summary.top_out)
+        };
+        // Under a block out alone the game ends once the next piece has nowhere to go
+        assert!(top_out_of(TopOutRule::BlockOut) == Some(TopOut::BlockOut));
+        assert!(top_out_of(TopOutRule::LockOut) == Some(TopOut::LockOut));
+        assert!(top_out_of(TopOutRule::Garbage) == Some(TopOut::LockOut));
+
+        // Garbage pushing the stack into the hidden rows ends the game only under its own rule
+        for (top_out, finished) in [(TopOutRule::LockOut, false), (TopOutRule::Garbage, true)] {
+            let mut tetris = Tetris::new();
+            tetris.set_rules(Rules {
+                top_out,
+                ..Rules::default()
+            });
+            if let Tetris::Running(ref mut state) = tetris {
+                state.grid[(0, VISIBLE_HEIGHT - 1)] = true;
+            }
+            tetris.add_garbage(1, 1);
+            assert!(tetris.is_finished() == finished);
+        }
+        assert!(TopOutRule::from_name("lock") == Some(TopOutRule::LockOut));
+    }
+
+    #[test]
+    fn updates_report_what_happened() {
+        let mut tetris = Tetris::new_with_seed(4);
+        let next = running_ref(&tetris).next_piece.kind();
+        if let Tetris::Running(ref mut state) = tetris {
+            state.grid.row_mut(0)[..3].fill(true);
+            state.grid.row_mut(0)[7..].fill(true);
+            state.piece = spawn_piece(PieceSelector::Line);
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        let locked = Placement {
+            kind: PieceSelector::Line,
+            rotation: Rotation::R0,
+            x: 3,
+            y: 0,
+        };
+        assert!(running_ref(&tetris).events().eq([
+            GameEvent::PieceLocked(locked),
+            GameEvent::LinesCleared { lines: 1, rows: 1 },
+            GameEvent::PieceSpawned(next),
+        ]));
+
+        // Nothing happens while the piece falls
+        tetris.set_key_state(&KeyState::default());
+        tetris.update();
+        assert!(running_ref(&tetris).events().next().is_none());
+
+        // The listener hears of the game ending after the lock that ended it
+        let mut tetris = stacked_to_the_top(TopOutRule::LockOut);
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        let (mut heard, mut last) = (0, None);
+        tetris.update_with(|event| {
+            heard += 1;
+            last = Some(event);
+        });
+        assert!(heard == 2 && last == Some(GameEvent::GameOver(TopOut::LockOut)));
+    }
+
+    #[test]
+    fn the_game_keeps_its_own_stats() {
+        let mut tetris = Tetris::new_with_seed(4);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..2 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        tetris.set_key_state(&KeyState::default());
+        tetris.update_n(3);
+
+        let stats = running_ref(&tetris).stats();
+        assert!(stats.piece_counts.count(PieceSelector::O) == 1 && stats.pieces() == 1);
+        assert!(stats.lines == 2 && stats.clears == [0, 1, 0, 0]);
+        assert!(stats.clears_of(2) == 1 && stats.clears_of(0) == 0);
+        assert!(stats.ticks == 4 && stats.pieces_per_update() == 0.25);
+    }
+
+    #[test]
+    fn pieces_placed_with_inputs_to_spare_are_finesse_faults() {
+        let mut tetris = Tetris::new_with_seed(4);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = spawn_piece(PieceSelector::O);
+        }
+        // Three taps left, where sliding to the wall and tapping back right takes two
+        for _ in 0..3 {
+            for left in [true, false] {
+                tetris.set_key_state(&KeyState {
+                    left,
+                    ..KeyState::default()
+                });
+                tetris.update();
+            }
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+
+        let state = running_ref(&tetris);
+        assert!(state
+            .last_placement()
+            .is_some_and(|placement| placement.x == 1));
+        let finesse = state.finesse();
+        assert!(finesse.pieces == 1 && finesse.inputs == 3 && finesse.wasted == 1);
+        assert!(finesse.last == Some((3, 2)) && finesse.score() == 0);
+    }
+
+    /// The hole in each of the bottom eight rows, which must have exactly one.
+    fn holes(tetris: Tetris) -> [usize; 8] {
+        let state = running(tetris);
+        core::array::from_fn(|y| {
+            let row = state.grid.row(y);
+            assert!(row.iter().filter(|&&cell| !cell).count() == 1);
+            row.iter().position(|&cell| !cell).unwrap()
+        })
+    }
+
+    #[test]
+    fn starting_garbage_is_as_messy_as_asked() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for (messiness, moves) in [(0, false), (100, true)] {
+            let mut tetris = Tetris::new_with_seed(1);
+            tetris.add_starting_garbage(StartingGarbage { rows: 8, messiness }, &mut rng);
+            let holes = holes(tetris);
+            assert!(holes.windows(2).all(|pair| (pair[0] != pair[1]) == moves));
+        }
+    }
+
+    #[test]
+    fn rotating_against_the_right_wall_keeps_the_piece_inside() {
+        let mut tetris = Tetris::new();
+
+        // Hold right and keep rotating, every update is validated in debug builds
+        for _ in 0..1_000 {
+            tetris.set_key_state(&KeyState {
+                right: true,
+                rotate: true,
+                ..KeyState::default()
+            });
+            tetris.update();
+            if let Tetris::Running(ref state) = tetris {
+                let width = state.piece.current_rotation().width;
+                assert!(state.piece.x + width <= state.grid.width);
+            }
+        }
     }
 }
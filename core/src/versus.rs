@@ -0,0 +1,891 @@
+//! Battles between any number of boards. Every player is dealt the same pieces, clearing rows
+//! sends garbage to an opponent picked by the player's targeting strategy, and players are
+//! knocked out one by one until a single board is left.
+//!
+//! Garbage sent to a player waits in their garbage meter until they next lock a piece without
+//! clearing rows, and rows they clear before then cancel it out instead of being sent on. With a
+//! garbage delay it also waits that many updates before it can land, giving the player longer to
+//! cancel it.
+//!
+//! The holes of the garbage a player receives come from a stream seeded the same for every
+//! player, so each is dealt garbage placed the same way and none is luckier with it than another.
+//!
+//! A battle between two devices over a network is a Duel on each of them instead, each running
+//! only its own board with the garbage carried between them by the frontends.
+
+use crate::item::{Item, Target};
+use crate::tetris::{EntropySource, KeyState, Rules, StartingGarbage, Tetris};
+use alloc::vec::Vec;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+/// Rows of garbage sent for clearing 0, 1, 2, 3 or 4 rows at once.
+pub const GARBAGE_FOR_ROWS_CLEARED: [usize; 5] = [0, 0, 1, 2, 4];
+
+/// How a player picks the opponent their garbage is sent to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Targeting {
+    /// The opponent with the highest score, the earliest player on a tie.
+    Leader,
+    /// Any opponent still playing, picked at random for each attack.
+    Random,
+    /// Each opponent still playing in turn.
+    RoundRobin,
+}
+
+/// Garbage sent during an update, queued for the player it was sent to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Attack {
+    pub from: usize,
+    pub to: usize,
+    pub rows: usize,
+    pub hole: usize,
+}
+
+/// Rows of garbage sent to or from the other device of a Duel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Garbage {
+    pub rows: usize,
+    pub hole: usize,
+}
+
+/// Garbage sent to a player that has yet to land, the oldest first. Each lot waits the delay in
+/// updates of the player's board before it can land, and rows cleared cancel it until it does.
+#[derive(Clone, Default, Debug)]
+pub struct GarbageMeter {
+    /// The garbage waiting, each with the updates left before it can land.
+    queue: Vec<(Garbage, u16)>,
+    delay: u16,
+}
+
+impl GarbageMeter {
+    pub fn new(delay: u16) -> Self {
+        GarbageMeter {
+            queue: Vec::new(),
+            delay,
+        }
+    }
+
+    /// Updates garbage sent from now on waits before it can land.
+    pub fn set_delay(&mut self, delay: u16) {
+        self.delay = delay;
+    }
+
+    pub fn push(&mut self, garbage: Garbage) {
+        self.queue.push((garbage, self.delay));
+    }
+
+    /// Rows of garbage waiting, whether or not they can land yet.
+    pub fn rows(&self) -> usize {
+        self.queue.iter().map(|(garbage, _)| garbage.rows).sum()
+    }
+
+    /// Rows of garbage whose delay has passed, which land with the next piece to lock without
+    /// clearing rows.
+    pub fn ready_rows(&self) -> usize {
+        self.queue
+            .iter()
+            .filter(|&&(_, wait)| wait == 0)
+            .map(|(garbage, _)| garbage.rows)
+            .sum()
+    }
+
+    /// Cancel the garbage with rows, the oldest first, returning the rows left over.
+    pub fn cancel(&mut self, mut rows: usize) -> usize {
+        while let Some((garbage, _)) = self.queue.first_mut() {
+            if rows == 0 {
+                break;
+            }
+            let cancelled = rows.min(garbage.rows);
+            garbage.rows -= cancelled;
+            rows -= cancelled;
+            if garbage.rows == 0 {
+                self.queue.remove(0);
+            }
+        }
+        rows
+    }
+
+    /// Count an update of the board off the delay of the garbage waiting.
+    pub fn tick(&mut self) {
+        for (_, wait) in &mut self.queue {
+            *wait = wait.saturating_sub(1);
+        }
+    }
+
+    /// Take the garbage whose delay has passed, to land on the board.
+    pub fn take_ready(&mut self) -> Vec<Garbage> {
+        let ready = self
+            .queue
+            .iter()
+            .filter(|&&(_, wait)| wait == 0)
+            .map(|&(garbage, _)| garbage)
+            .collect();
+        self.queue.retain(|&(_, wait)| wait > 0);
+        ready
+    }
+}
+
+/// The holes of the garbage for one player, picked one after another from a seeded stream.
+#[derive(Clone, Debug)]
+pub struct GarbageHoles {
+    rng: SmallRng,
+}
+
+impl GarbageHoles {
+    pub fn new(seed: u64) -> Self {
+        GarbageHoles {
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    /// The hole of the next garbage, in a playfield width cells wide.
+    pub fn next_hole(&mut self, width: usize) -> usize {
+        self.rng.gen_range(0, width)
+    }
+}
+
+/// An item used on an opponent during an update.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ItemUse {
+    pub from: usize,
+    pub to: usize,
+    pub item: Item,
+}
+
+struct Player {
+    tetris: Tetris,
+    targeting: Targeting,
+    last_target: Option<usize>,
+    /// Garbage sent to this player that has yet to land.
+    incoming: GarbageMeter,
+    /// Where the holes of the garbage sent to this player go.
+    holes: GarbageHoles,
+}
+
+pub struct Versus {
+    players: Vec<Player>,
+    /// Players in the order they were knocked out.
+    knocked_out: Vec<usize>,
+    garbage_seed: u64,
+    /// Picks random targets and starting garbage, separate from the piece sequence.
+    rng: SmallRng,
+    /// Items used on opponents by the most recent update.
+    items_used: Vec<ItemUse>,
+}
+
+impl Versus {
+    /// Start a battle between players boards, all of them targeting the same way.
+    pub fn new<E: EntropySource>(players: usize, targeting: Targeting, entropy: &mut E) -> Self {
+        let (seed, garbage_seed) = (entropy.next_seed(), entropy.next_seed());
+        Self::with_seeds(players, targeting, seed, garbage_seed, entropy)
+    }
+
+    /// Start a battle whose pieces are dealt from seed and whose garbage holes are picked from
+    /// garbage_seed, such as seeds agreed with other devices.
+    pub fn with_seeds<E: EntropySource>(
+        players: usize,
+        targeting: Targeting,
+        seed: u64,
+        garbage_seed: u64,
+        entropy: &mut E,
+    ) -> Self {
+        assert!(players >= 2, "A battle needs at least two players");
+        Versus {
+            players: (0..players)
+                .map(|_| Player {
+                    tetris: Tetris::new_with_seed(seed),
+                    targeting,
+                    last_target: None,
+                    incoming: GarbageMeter::default(),
+                    holes: GarbageHoles::new(garbage_seed),
+                })
+                .collect(),
+            knocked_out: Vec::new(),
+            garbage_seed,
+            rng: SmallRng::seed_from_u64(entropy.next_seed()),
+            items_used: Vec::new(),
+        }
+    }
+
+    /// The seed every player's garbage holes are picked from.
+    pub fn garbage_seed(&self) -> u64 {
+        self.garbage_seed
+    }
+
+    pub fn players(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn tetris(&self, player: usize) -> &Tetris {
+        &self.players[player].tetris
+    }
+
+    pub fn set_key_state(&mut self, player: usize, key_state: &KeyState) {
+        self.players[player].tetris.set_key_state(key_state);
+    }
+
+    /// Set the rules for every board, such as turning on the item mode.
+    pub fn set_rules(&mut self, rules: Rules) {
+        for player in &mut self.players {
+            player.tetris.set_rules(rules);
+        }
+    }
+
+    /// Handicap a player with rows of garbage, before the battle starts.
+    pub fn add_starting_garbage(&mut self, player: usize, garbage: StartingGarbage) {
+        self.players[player]
+            .tetris
+            .add_starting_garbage(garbage, &mut self.rng);
+        self.knock_out_if_finished(player);
+    }
+
+    /// Items used on opponents by the most recent update, picked by the same targeting as
+    /// garbage.
+    pub fn items_used(&self) -> &[ItemUse] {
+        &self.items_used
+    }
+
+    pub fn set_targeting(&mut self, player: usize, targeting: Targeting) {
+        self.players[player].targeting = targeting;
+    }
+
+    /// Make garbage sent from now on wait updates of its target's board before it can land.
+    pub fn set_garbage_delay(&mut self, updates: u16) {
+        for player in &mut self.players {
+            player.incoming.set_delay(updates);
+        }
+    }
+
+    /// Rows of garbage waiting to land on player, for frontends to show as a meter.
+    pub fn incoming_garbage(&self, player: usize) -> usize {
+        self.players[player].incoming.rows()
+    }
+
+    /// The garbage meter of player, for frontends that show the garbage yet to be ready apart.
+    pub fn garbage_meter(&self, player: usize) -> &GarbageMeter {
+        &self.players[player].incoming
+    }
+
+    pub fn is_playing(&self, player: usize) -> bool {
+        !self.players[player].tetris.is_finished()
+    }
+
+    /// Players in the order they were knocked out. Players knocked out by the same update are
+    /// ordered by their final score, the lowest first, then by player number.
+    pub fn knocked_out(&self) -> &[usize] {
+        &self.knocked_out
+    }
+
+    /// True once at most one player is left.
+    pub fn is_finished(&self) -> bool {
+        self.knocked_out.len() + 1 >= self.players.len()
+    }
+
+    /// The winner, once every other player has been knocked out. None if the last players were
+    /// knocked out by the same update, which is a draw.
+    pub fn winner(&self) -> Option<usize> {
+        match self.is_finished() {
+            true => (0..self.players.len()).find(|&player| self.is_playing(player)),
+            false => None,
+        }
+    }
+
+    /// Whether the battle ended with the last players knocked out together, leaving no winner.
+    pub fn is_draw(&self) -> bool {
+        self.knocked_out.len() == self.players.len()
+    }
+
+    /// Every player from first place to last: those still playing by player number, then those
+    /// knocked out, the last to go first. Players knocked out together, as in a draw, are placed
+    /// by their final score.
+    pub fn standings(&self) -> Vec<usize> {
+        (0..self.players.len())
+            .filter(|&player| self.is_playing(player))
+            .chain(self.knocked_out.iter().rev().copied())
+            .collect()
+    }
+
+    fn score(&self, player: usize) -> usize {
+        match self.players[player].tetris {
+            Tetris::Running(ref state) | Tetris::Paused(ref state) => state.score,
+            Tetris::Finished(_) => 0,
+        }
+    }
+
+    /// The score player finished with, or has so far if still playing.
+    fn final_score(&self, player: usize) -> usize {
+        match self.players[player].tetris {
+            Tetris::Finished(summary) => summary.score,
+            _ => self.score(player),
+        }
+    }
+
+    /// The opponent attacker's garbage goes to, if any are still playing.
+    fn choose_target(&mut self, attacker: usize) -> Option<usize> {
+        let opponents: Vec<usize> = (0..self.players.len())
+            .filter(|&player| player != attacker && self.is_playing(player))
+            .collect();
+        if opponents.is_empty() {
+            return None;
+        }
+
+        let target = match self.players[attacker].targeting {
+            Targeting::Leader => opponents
+                .iter()
+                .copied()
+                .rev()
+                .max_by_key(|&player| self.score(player))?,
+            Targeting::Random => opponents[self.rng.gen_range(0, opponents.len())],
+            Targeting::RoundRobin => {
+                let after = self.players[attacker].last_target;
+                opponents
+                    .iter()
+                    .copied()
+                    .find(|&player| after.is_some_and(|after| player > after))
+                    .unwrap_or(opponents[0])
+            }
+        };
+        self.players[attacker].last_target = Some(target);
+        Some(target)
+    }
+
+    fn knock_out_if_finished(&mut self, player: usize) {
+        if !self.is_playing(player) && !self.knocked_out.contains(&player) {
+            self.knocked_out.push(player);
+        }
+    }
+
+    /// Update every board still playing, then send garbage for the rows each cleared that did
+    /// not cancel garbage of their own. Items earned are used straight away. Returns the attacks
+    /// made, for frontends to show or to send to remote players.
+    pub fn update(&mut self) -> Vec<Attack> {
+        let mut attackers = Vec::new();
+        let already_out = self.knocked_out.len();
+        self.items_used.clear();
+        for player in 0..self.players.len() {
+            if !self.is_playing(player) {
+                continue;
+            }
+            self.players[player].tetris.update();
+            self.players[player].incoming.tick();
+            self.knock_out_if_finished(player);
+
+            let (rows_cleared, locked) = match self.players[player].tetris {
+                Tetris::Running(ref state) => (state.rows_cleared(), state.piece_locked()),
+                Tetris::Paused(_) | Tetris::Finished(_) => (0, false),
+            };
+            let rows = self.players[player]
+                .incoming
+                .cancel(GARBAGE_FOR_ROWS_CLEARED[rows_cleared.min(4)]);
+            if rows > 0 {
+                attackers.push((player, rows));
+            }
+            if locked && rows_cleared == 0 {
+                self.land_garbage(player);
+            }
+
+            match self.players[player].tetris.use_item() {
+                Some(item) if item.target() == Target::Opponent => {
+                    if let Some(to) = self.choose_target(player) {
+                        self.players[to].tetris.apply_item(item);
+                        self.items_used.push(ItemUse {
+                            from: player,
+                            to,
+                            item,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut attacks = Vec::new();
+        for (from, rows) in attackers {
+            let Some(to) = self.choose_target(from) else {
+                break;
+            };
+            let width = match self.players[to].tetris {
+                Tetris::Running(ref state) | Tetris::Paused(ref state) => state.grid.width,
+                Tetris::Finished(_) => unreachable!(),
+            };
+            let hole = self.players[to].holes.next_hole(width);
+            let attack = Attack {
+                from,
+                to,
+                rows,
+                hole,
+            };
+            self.players[to].incoming.push(Garbage { rows, hole });
+            attacks.push(attack);
+        }
+
+        // Stable, so players knocked out with the same score stay in player order
+        let mut knocked_out = core::mem::take(&mut self.knocked_out);
+        knocked_out[already_out..].sort_by_key(|&player| self.final_score(player));
+        self.knocked_out = knocked_out;
+        attacks
+    }
+
+    /// Push player's stack up by their incoming garbage that is ready to land.
+    fn land_garbage(&mut self, player: usize) {
+        let target = &mut self.players[player];
+        for garbage in target.incoming.take_ready() {
+            target.tetris.add_garbage(garbage.rows, garbage.hole);
+        }
+        self.knock_out_if_finished(player);
+    }
+}
+
+/// The garbage of one side of a battle between two devices. The frontend passes its board to
+/// update after every update of the game and sends what it returns to the other device, whose
+/// garbage it hands to receive.
+pub struct Duel {
+    /// Garbage from the other device that has yet to land.
+    incoming: GarbageMeter,
+    /// Where the holes of the garbage sent go. Both devices seed it the same, so the garbage
+    /// each sends is placed as the other's would have been.
+    holes: GarbageHoles,
+}
+
+impl Duel {
+    pub fn new(garbage_seed: u64) -> Self {
+        Duel {
+            incoming: GarbageMeter::default(),
+            holes: GarbageHoles::new(garbage_seed),
+        }
+    }
+
+    pub fn receive(&mut self, garbage: Garbage) {
+        self.incoming.push(garbage);
+    }
+
+    /// Make garbage received from now on wait updates before it can land.
+    pub fn set_garbage_delay(&mut self, updates: u16) {
+        self.incoming.set_delay(updates);
+    }
+
+    /// Rows of garbage waiting to land, for frontends to show as a meter.
+    pub fn incoming_garbage(&self) -> usize {
+        self.incoming.rows()
+    }
+
+    /// The garbage waiting to land, for frontends that show the garbage yet to be ready apart.
+    pub fn garbage_meter(&self) -> &GarbageMeter {
+        &self.incoming
+    }
+
+    /// Land the incoming garbage that is ready if tetris just locked a piece without clearing
+    /// rows, and return the garbage to send for any rows it cleared that did not cancel incoming
+    /// garbage.
+    pub fn update(&mut self, tetris: &mut Tetris) -> Option<Garbage> {
+        let (rows_cleared, locked, width) = match tetris {
+            Tetris::Running(ref state) => {
+                (state.rows_cleared(), state.piece_locked(), state.grid.width)
+            }
+            Tetris::Paused(_) | Tetris::Finished(_) => return None,
+        };
+
+        self.incoming.tick();
+        let rows = self
+            .incoming
+            .cancel(GARBAGE_FOR_ROWS_CLEARED[rows_cleared.min(4)]);
+        if locked && rows_cleared == 0 {
+            for garbage in self.incoming.take_ready() {
+                tetris.add_garbage(garbage.rows, garbage.hole);
+            }
+        }
+
+        (rows > 0).then(|| Garbage {
+            rows,
+            hole: self.holes.next_hole(width),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::item::Item;
+    use crate::piece::PieceSelector;
+    use crate::tetris::{
+        EntropySource, GameSummary, KeyState, Rules, StartingGarbage, Tetris, GRID_SIZE,
+        VISIBLE_HEIGHT,
+    };
+    use crate::versus::{
+        Duel, Garbage, GarbageHoles, GarbageMeter, ItemUse, Targeting, Versus,
+        GARBAGE_FOR_ROWS_CLEARED,
+    };
+    use alloc::vec::Vec;
+
+    struct Counter(u64);
+
+    impl EntropySource for Counter {
+        fn next_seed(&mut self) -> u64 {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    #[test]
+    fn every_player_is_dealt_the_same_pieces() {
+        let versus = Versus::new(4, Targeting::Random, &mut Counter(0));
+        let kinds: Vec<_> = (0..4)
+            .map(|player| match versus.tetris(player) {
+                Tetris::Running(state) => (state.piece.kind(), state.next_piece.kind()),
+                Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+            })
+            .collect();
+        assert!(kinds.iter().all(|&kind| kind == kinds[0]));
+    }
+
+    #[test]
+    fn the_leader_is_targeted() {
+        let mut versus = Versus::new(4, Targeting::Leader, &mut Counter(0));
+        if let Tetris::Running(ref mut state) = versus.players[2].tetris {
+            state.score = 5000;
+        }
+        assert!(versus.choose_target(0) == Some(2));
+        // A leader cannot target themselves
+        assert!(versus.choose_target(2) == Some(0));
+    }
+
+    #[test]
+    fn round_robin_skips_knocked_out_players() {
+        let mut versus = Versus::new(4, Targeting::RoundRobin, &mut Counter(0));
+        versus.players[2].tetris = Tetris::Finished(GameSummary::default());
+
+        let targets: Vec<_> = (0..4).map(|_| versus.choose_target(0).unwrap()).collect();
+        assert!(targets == [1, 3, 1, 3]);
+    }
+
+    #[test]
+    fn random_targets_are_opponents_still_playing() {
+        let mut versus = Versus::new(4, Targeting::Random, &mut Counter(0));
+        versus.players[1].tetris = Tetris::Finished(GameSummary::default());
+
+        for _ in 0..100 {
+            let target = versus.choose_target(0).unwrap();
+            assert!(target == 2 || target == 3);
+        }
+    }
+
+    #[test]
+    fn a_battle_ends_with_one_winner() {
+        let mut versus = Versus::new(4, Targeting::RoundRobin, &mut Counter(0));
+
+        let mut frame = 0;
+        while !versus.is_finished() {
+            for player in 0..versus.players() {
+                versus.set_key_state(
+                    player,
+                    &KeyState {
+                        left: (frame + player) % 4 == 0,
+                        right: (frame + player) % 3 == 0,
+                        hard_drop: frame % 2 == 0,
+                        ..KeyState::default()
+                    },
+                );
+            }
+            versus.update();
+            frame += 1;
+            assert!(frame < 100_000);
+        }
+
+        // The last two players can go out in the same update, leaving nobody to win
+        let standings = versus.standings();
+        match versus.winner() {
+            Some(winner) => {
+                assert!(versus.knocked_out().len() == 3 && !versus.is_draw());
+                assert!(!versus.knocked_out().contains(&winner));
+                assert!(standings[0] == winner);
+            }
+            None => assert!(versus.knocked_out().len() == 4 && versus.is_draw()),
+        }
+        assert!(standings.len() == 4 && standings[3] == versus.knocked_out()[0]);
+    }
+
+    #[test]
+    fn players_knocked_out_together_draw_and_are_placed_by_score() {
+        let mut versus = Versus::new(3, Targeting::RoundRobin, &mut Counter(0));
+        // Player 0 is ahead when all three are stacked to the top
+        for (player, score) in [(0, 500), (1, 100), (2, 100)] {
+            if let Tetris::Running(ref mut state) = versus.players[player].tetris {
+                for y in 0..VISIBLE_HEIGHT {
+                    state.grid.row_mut(y)[..GRID_SIZE.0 - 1].fill(true);
+                }
+                state.score = score;
+            }
+            versus.set_key_state(
+                player,
+                &KeyState {
+                    hard_drop: true,
+                    ..KeyState::default()
+                },
+            );
+        }
+        for _ in 0..10 {
+            if !versus.is_finished() {
+                versus.update();
+            }
+        }
+
+        assert!(versus.winner().is_none() && versus.is_draw());
+        assert!(versus.knocked_out() == [1, 2, 0]);
+        assert!(versus.standings() == [0, 2, 1]);
+    }
+
+    #[test]
+    fn garbage_is_sent_for_cleared_rows() {
+        let mut versus = Versus::new(2, Targeting::Leader, &mut Counter(0));
+        // Two rows with a gap on the left that a dropped O piece fills
+        if let Tetris::Running(ref mut state) = versus.players[0].tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..2 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        versus.set_key_state(
+            0,
+            &KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            },
+        );
+
+        let attacks = versus.update();
+        assert!(attacks.len() == 1);
+        assert!(attacks[0].from == 0 && attacks[0].to == 1);
+        assert!(attacks[0].rows == GARBAGE_FOR_ROWS_CLEARED[2]);
+        assert!(versus.incoming_garbage(1) == attacks[0].rows);
+    }
+
+    fn bottom_row(versus: &Versus, player: usize) -> Vec<bool> {
+        match versus.tetris(player) {
+            Tetris::Running(state) => state.grid.row(0).to_vec(),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    #[test]
+    fn incoming_garbage_lands_when_a_piece_locks_without_clearing() {
+        let mut versus = Versus::new(2, Targeting::Leader, &mut Counter(0));
+        versus.players[1]
+            .incoming
+            .push(Garbage { rows: 1, hole: 3 });
+        versus.update();
+        assert!(versus.incoming_garbage(1) == 1);
+        assert!(!bottom_row(&versus, 1).contains(&true));
+
+        versus.set_key_state(
+            1,
+            &KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            },
+        );
+        versus.update();
+        assert!(versus.incoming_garbage(1) == 0);
+        let row = bottom_row(&versus, 1);
+        assert!((0..row.len()).all(|x| row[x] == (x != 3)));
+    }
+
+    #[test]
+    fn delayed_garbage_waits_before_it_can_land() {
+        let mut meter = GarbageMeter::new(2);
+        meter.push(Garbage { rows: 2, hole: 1 });
+        meter.tick();
+        meter.push(Garbage { rows: 3, hole: 4 });
+        assert!(meter.rows() == 5 && meter.ready_rows() == 0);
+        assert!(meter.take_ready().is_empty());
+
+        meter.tick();
+        assert!(meter.ready_rows() == 2);
+        // Cancelling takes the oldest first, whether or not it is ready
+        assert!(meter.cancel(3) == 0);
+        assert!(meter.rows() == 2 && meter.ready_rows() == 0);
+        meter.tick();
+        assert!(meter.take_ready() == [Garbage { rows: 2, hole: 4 }]);
+        assert!(meter.rows() == 0);
+    }
+
+    #[test]
+    fn a_piece_locking_before_the_garbage_delay_passes_leaves_it_waiting() {
+        let mut versus = Versus::new(2, Targeting::Leader, &mut Counter(0));
+        versus.set_garbage_delay(100);
+        versus.players[1]
+            .incoming
+            .push(Garbage { rows: 1, hole: 3 });
+        versus.set_key_state(
+            1,
+            &KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            },
+        );
+        versus.update();
+        assert!(versus.incoming_garbage(1) == 1);
+        assert!(versus.garbage_meter(1).ready_rows() == 0);
+        // Only the piece that locked is in the stack
+        let Tetris::Running(state) = versus.tetris(1) else {
+            panic!("Expected a running game");
+        };
+        let filled = (0..state.grid.height)
+            .map(|y| state.grid.row(y).iter().filter(|&&cell| cell).count())
+            .sum::<usize>();
+        assert!(state.piece_locked() && filled == 4);
+    }
+
+    #[test]
+    fn cleared_rows_cancel_incoming_garbage() {
+        let mut versus = Versus::new(2, Targeting::Leader, &mut Counter(0));
+        versus.players[0]
+            .incoming
+            .push(Garbage { rows: 3, hole: 0 });
+        if let Tetris::Running(ref mut state) = versus.players[0].tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..2 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        versus.set_key_state(
+            0,
+            &KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            },
+        );
+
+        assert!(versus.update().is_empty());
+        assert!(versus.incoming_garbage(0) == 3 - GARBAGE_FOR_ROWS_CLEARED[2]);
+    }
+
+    #[test]
+    fn a_handicapped_player_starts_with_garbage() {
+        let mut versus = Versus::new(2, Targeting::Leader, &mut Counter(0));
+        versus.add_starting_garbage(
+            1,
+            StartingGarbage {
+                rows: 4,
+                messiness: 50,
+            },
+        );
+        let filled_rows = |player| match versus.tetris(player) {
+            Tetris::Running(state) => (0..state.grid.height)
+                .filter(|&y| state.grid.row(y).contains(&true))
+                .count(),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        };
+        assert!(filled_rows(0) == 0 && filled_rows(1) == 4);
+    }
+
+    #[test]
+    fn items_for_opponents_are_used_on_them() {
+        let mut versus = Versus::new(2, Targeting::Leader, &mut Counter(0));
+        versus.set_rules(Rules {
+            items: true,
+            ..Rules::default()
+        });
+        if let Tetris::Running(ref mut state) = versus.players[0].tetris {
+            state.item = Some(Item::SlowDown);
+        }
+
+        versus.update();
+        assert!(
+            versus.items_used()
+                == [ItemUse {
+                    from: 0,
+                    to: 1,
+                    item: Item::SlowDown
+                }]
+        );
+        match versus.tetris(1) {
+            Tetris::Running(state) => assert!(state.slowed > 0),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        }
+    }
+
+    #[test]
+    fn a_duel_sends_garbage_for_cleared_rows_less_what_it_cancels() {
+        let mut tetris = Tetris::new_with_seed(1);
+        if let Tetris::Running(ref mut state) = tetris {
+            state.piece = PieceSelector::O.to_piece((0, 10));
+            for y in 0..2 {
+                state.grid.row_mut(y)[2..].fill(true);
+            }
+        }
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+
+        let mut cancelled = Duel::new(1);
+        cancelled.receive(Garbage { rows: 3, hole: 0 });
+        let mut sent = Duel::new(1);
+        assert!(sent.update(&mut tetris.clone()).map(|garbage| garbage.rows) == Some(1));
+        assert!(cancelled.update(&mut tetris).is_none());
+        assert!(cancelled.incoming_garbage() == 2);
+    }
+
+    #[test]
+    fn every_player_is_sent_garbage_with_the_same_holes() {
+        let mut versus = Versus::new(3, Targeting::RoundRobin, &mut Counter(0));
+        let width = 10;
+        let holes = |player: usize, versus: &mut Versus| -> Vec<usize> {
+            (0..20)
+                .map(|_| versus.players[player].holes.next_hole(width))
+                .collect()
+        };
+        let first = holes(0, &mut versus);
+        assert!(holes(1, &mut versus) == first);
+        assert!(holes(2, &mut versus) == first);
+        // The stream is the one a remote device would seed from the same garbage seed
+        let mut remote = GarbageHoles::new(versus.garbage_seed());
+        assert!((0..20).all(|index| remote.next_hole(width) == first[index]));
+    }
+
+    #[test]
+    fn duels_with_the_same_seed_send_the_same_holes() {
+        let cleared = || {
+            let mut tetris = Tetris::new_with_seed(1);
+            if let Tetris::Running(ref mut state) = tetris {
+                state.piece = PieceSelector::O.to_piece((0, 10));
+                for y in 0..2 {
+                    state.grid.row_mut(y)[2..].fill(true);
+                }
+            }
+            tetris.set_key_state(&KeyState {
+                hard_drop: true,
+                ..KeyState::default()
+            });
+            tetris.update();
+            tetris
+        };
+        let (mut host, mut guest) = (Duel::new(7), Duel::new(7));
+        for _ in 0..10 {
+            assert!(host.update(&mut cleared()) == guest.update(&mut cleared()));
+        }
+    }
+
+    #[test]
+    fn a_duel_lands_garbage_when_a_piece_locks_without_clearing() {
+        let mut tetris = Tetris::new_with_seed(1);
+        let mut duel = Duel::new(1);
+        duel.receive(Garbage { rows: 1, hole: 3 });
+        tetris.update();
+        assert!(duel.update(&mut tetris).is_none());
+        assert!(duel.incoming_garbage() == 1);
+
+        tetris.set_key_state(&KeyState {
+            hard_drop: true,
+            ..KeyState::default()
+        });
+        tetris.update();
+        duel.update(&mut tetris);
+        assert!(duel.incoming_garbage() == 0);
+        let Tetris::Running(ref state) = tetris else {
+            panic!("Expected a running game");
+        };
+        let row = state.grid.row(0);
+        assert!((0..row.len()).all(|x| row[x] == (x != 3)));
+    }
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "proptest")]
+
+use proptest::prelude::*;
+use tetris_core::grid::Grid;
+use tetris_core::spectate::{Board, Decoder, Encoder, View};
+use tetris_core::tetris::{KeyState, Tetris, TetrisState};
+
+/// Whether a placed at offset overlaps b, checked one cell at a time.
+fn overlaps(a: &Grid, b: &Grid, (offset_x, offset_y): (usize, usize)) -> bool {
+    (0..a.width).any(|x| {
+        (0..a.height).any(|y| {
+            let (target_x, target_y) = (x + offset_x, y + offset_y);
+            a[(x, y)] && target_x < b.width && target_y < b.height && b[(target_x, target_y)]
+        })
+    })
+}
+
+/// Whether any set cell of a lands inside b when placed at offset.
+fn lands_inside(a: &Grid, b: &Grid, (offset_x, offset_y): (usize, usize)) -> bool {
+    (0..a.width).any(|x| {
+        (0..a.height).any(|y| a[(x, y)] && x + offset_x < b.width && y + offset_y < b.height)
+    })
+}
+
+proptest! {
+    #[test]
+    fn generated_states_are_valid(state in any::<TetrisState>()) {
+        prop_assert_eq!(state.validate(), Ok(()));
+    }
+
+    #[test]
+    fn collides_matches_checking_every_cell(
+        a in any::<Grid>(),
+        b in any::<Grid>(),
+        offset in (0..20usize, 0..20usize),
+    ) {
+        prop_assert_eq!(a.collides(&b, offset), overlaps(&a, &b, offset));
+    }
+
+    #[test]
+    fn a_copied_grid_collides_where_it_was_copied(
+        a in any::<Grid>(),
+        mut b in any::<Grid>(),
+        offset in (0..20usize, 0..20usize),
+    ) {
+        a.copy_into(&mut b, offset);
+        prop_assert_eq!(a.collides(&b, offset), lands_inside(&a, &b, offset));
+    }
+
+    #[test]
+    fn updates_keep_the_state_valid(
+        tetris in any::<Tetris>(),
+        keys in proptest::collection::vec(any::<KeyState>(), 1..50),
+    ) {
+        let mut tetris = tetris;
+        for key_state in keys {
+            let score_before = match tetris {
+                Tetris::Running(ref state) => state.score,
+                Tetris::Paused(_) | Tetris::Finished(_) => break,
+            };
+            tetris.set_key_state(&key_state);
+            tetris.update();
+            if let Tetris::Running(ref state) = tetris {
+                prop_assert_eq!(state.validate_update(score_before), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn spectators_see_the_same_board(tetris in any::<Tetris>()) {
+        let mut message = Vec::new();
+        Encoder::new().encode(&tetris, &mut message);
+        let mut decoder = Decoder::new();
+        decoder.decode(&message[1..]).unwrap();
+
+        match tetris {
+            Tetris::Running(ref state) => {
+                prop_assert_eq!(decoder.view(), &View::Playing(Board::from_state(state)));
+            }
+            Tetris::Paused(_) | Tetris::Finished(_) => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,434 @@
+#![cfg(feature = "alloc")]
+
+use tetris_core::piece::PieceSelector;
+use tetris_core::simulation::{ScriptError, ScriptStep::*, Simulation};
+use tetris_core::tetris::{Gravity, KeyState, Rules};
+
+const NOTHING: KeyState = KeyState {
+    left: false,
+    right: false,
+    rotate: false,
+    rotate_ccw: false,
+    rotate_180: false,
+    hard_drop: false,
+    soft_drop: false,
+    hold: false,
+};
+
+const RIGHT: KeyState = KeyState {
+    right: true,
+    ..NOTHING
+};
+
+const ROTATE: KeyState = KeyState {
+    rotate: true,
+    ..NOTHING
+};
+
+const ROTATE_CCW: KeyState = KeyState {
+    rotate_ccw: true,
+    ..NOTHING
+};
+
+const ROTATE_180: KeyState = KeyState {
+    rotate_180: true,
+    ..NOTHING
+};
+
+const HARD_DROP: KeyState = KeyState {
+    hard_drop: true,
+    ..NOTHING
+};
+
+const SOFT_DROP: KeyState = KeyState {
+    soft_drop: true,
+    ..NOTHING
+};
+
+const LEFT: KeyState = KeyState {
+    left: true,
+    ..NOTHING
+};
+
+#[test]
+fn pieces_fall_a_row_each_tick_and_lock_on_the_floor() {
+    Simulation::run(&[
+        Spawn(PieceSelector::O, (0, 5)),
+        Tick(5),
+        ExpectBoard(&["@@........", "@@........"]),
+        Tick(1),
+        ExpectBoard(&["##........", "##........"]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn hard_drop_locks_the_piece_in_one_tick() {
+    Simulation::run(&[
+        SetStack(&["#########."]),
+        Spawn(PieceSelector::O, (3, 15)),
+        Hold(HARD_DROP),
+        Tick(1),
+        ExpectBoard(&["...##.....", "...##.....", "#########."]),
+        // Two points for each of the 14 cells dropped
+        ExpectScore(28),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn the_entry_delay_holds_back_the_next_piece() {
+    Simulation::run(&[
+        SetRules(Rules {
+            entry_delay: 2,
+            ..Rules::default()
+        }),
+        SetStack(&["#########."]),
+        Spawn(PieceSelector::O, (3, 15)),
+        Hold(HARD_DROP),
+        // Locks, then two updates without a piece to drop
+        Tick(3),
+        ExpectBoard(&["..........", "...##.....", "...##.....", "#########."]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn the_lock_delay_lets_a_resting_piece_slide() {
+    Simulation::run(&[
+        SetRules(Rules {
+            lock_delay: 3,
+            ..Rules::default()
+        }),
+        Spawn(PieceSelector::O, (0, 0)),
+        Tick(2),
+        // Each move starts the delay again
+        Hold(RIGHT),
+        Tick(2),
+        Hold(NOTHING),
+        Tick(1),
+        ExpectBoard(&["..@@......", "..@@......"]),
+        Tick(1),
+        ExpectBoard(&["..##......", "..##......"]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn soft_drop_scores_a_point_a_cell() {
+    Simulation::run(&[
+        Spawn(PieceSelector::O, (0, 10)),
+        Hold(SOFT_DROP),
+        Tick(2),
+        ExpectBoard(&[
+            "@@........",
+            "@@........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+        ]),
+        // Two cells an update at the soft drop speed
+        ExpectScore(4),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn completing_a_row_clears_it() {
+    Simulation::run(&[
+        SetStack(&["###....###"]),
+        Spawn(PieceSelector::Line, (3, 10)),
+        Hold(HARD_DROP),
+        Tick(1),
+        ExpectBoard(&["..........", ".........."]),
+        // Doubled as it left the playfield empty, a perfect clear
+        ExpectScore(2 * 10_000 + 2 * 10),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn rows_above_a_clear_fall_to_take_its_place() {
+    Simulation::run(&[
+        SetStack(&[".#........", "####.#####", "####.#####", "#.##.#####"]),
+        Spawn(PieceSelector::Line, (2, 10)),
+        Hold(ROTATE),
+        Tick(1),
+        Hold(HARD_DROP),
+        Tick(1),
+        // The two middle rows go, the row above them landing on the bottom one
+        ExpectBoard(&["..........", "..........", ".#..#.....", "#.########"]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn cascade_gravity_drops_loose_blocks_into_a_chain() {
+    let stack = SetStack(&["#.........", "###....###", ".#########"]);
+    Simulation::run(&[
+        stack,
+        Spawn(PieceSelector::Line, (3, 10)),
+        Hold(HARD_DROP),
+        Tick(1),
+        ExpectBoard(&["#.........", ".#########"]),
+    ])
+    .unwrap();
+
+    // The block left over the hole falls into it, completing the bottom row as well
+    Simulation::run(&[
+        SetRules(Rules {
+            cascade: true,
+            ..Rules::default()
+        }),
+        stack,
+        Spawn(PieceSelector::Line, (3, 10)),
+        Hold(HARD_DROP),
+        Tick(1),
+        ExpectBoard(&[".........."; 3]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn the_line_clear_delay_leaves_complete_rows_in_place() {
+    Simulation::run(&[
+        SetRules(Rules {
+            line_clear_delay: 2,
+            ..Rules::default()
+        }),
+        SetStack(&["###....###"]),
+        Spawn(PieceSelector::Line, (3, 10)),
+        Hold(HARD_DROP),
+        Tick(2),
+        ExpectBoard(&["##########"]),
+        ExpectScore(2 * 10),
+        Tick(1),
+        ExpectScore(2 * 10_000 + 2 * 10),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn clearing_four_rows_at_once_scores_the_square() {
+    Simulation::run(&[
+        SetStack(&["#.########", "#.########", "#.########", "#.########"]),
+        Spawn(PieceSelector::Line, (0, 10)),
+        Hold(ROTATE),
+        Tick(1),
+        Hold(LEFT),
+        Tick(1),
+        Hold(HARD_DROP),
+        Tick(1),
+        ExpectBoard(&[".........."; 4]),
+        ExpectScore(2 * 160_000 + 2 * 6),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn rotation_against_the_wall_kicks_off_it() {
+    Simulation::run(&[
+        Spawn(PieceSelector::Line, (0, 15)),
+        Hold(ROTATE),
+        Tick(1),
+        Hold(RIGHT),
+        Tick(9),
+        Hold(ROTATE),
+        Tick(1),
+        ExpectBoard(&["......@@@@", "..........", "..........", ".........."]),
+    ])
+    .unwrap();
+}
+
+/// Rules under which pieces hang where they are put, to watch them turn.
+fn no_gravity() -> Rules {
+    Rules {
+        gravity: Gravity(0),
+        ..Rules::default()
+    }
+}
+
+/// Turn a piece clockwise four times from spawning with its rotation box on the floor, checking
+/// the board after each turn.
+fn turn_clockwise(kind: PieceSelector, boards: [&[&str]; 4]) {
+    let mut steps = vec![SetRules(no_gravity()), Spawn(kind, (4, 1))];
+    for board in boards {
+        steps.extend([
+            Hold(ROTATE),
+            Tick(1),
+            ExpectBoard(board),
+            Hold(NOTHING),
+            Tick(1),
+        ]);
+    }
+    Simulation::run(&steps).unwrap();
+}
+
+#[test]
+fn a_j_turns_about_the_middle_of_its_box() {
+    turn_clockwise(
+        PieceSelector::J,
+        [
+            &[".....@@...", ".....@....", ".....@...."],
+            &["..........", "....@@@...", "......@..."],
+            &[".....@....", ".....@....", "....@@...."],
+            &["....@.....", "....@@@...", ".........."],
+        ],
+    );
+}
+
+#[test]
+fn an_l_turns_about_the_middle_of_its_box() {
+    turn_clockwise(
+        PieceSelector::L,
+        [
+            &[".....@....", ".....@....", ".....@@..."],
+            &["..........", "....@@@...", "....@....."],
+            &["....@@....", ".....@....", ".....@...."],
+            &["......@...", "....@@@...", ".........."],
+        ],
+    );
+}
+
+#[test]
+fn a_t_turns_about_the_middle_of_its_box() {
+    turn_clockwise(
+        PieceSelector::T,
+        [
+            &[".....@....", ".....@@...", ".....@...."],
+            &["..........", "....@@@...", ".....@...."],
+            &[".....@....", "....@@....", ".....@...."],
+            &[".....@....", "....@@@...", ".........."],
+        ],
+    );
+}
+
+#[test]
+fn an_s_turns_about_the_middle_of_its_box() {
+    turn_clockwise(
+        PieceSelector::S,
+        [
+            &[".....@....", ".....@@...", "......@..."],
+            &["..........", ".....@@...", "....@@...."],
+            &["....@.....", "....@@....", ".....@...."],
+            &[".....@@...", "....@@....", ".........."],
+        ],
+    );
+}
+
+/// Turn a piece clockwise, slide it to the left wall and turn it again, where its box overhangs
+/// the wall so it has to kick off it.
+fn kick_off_the_left_wall(kind: PieceSelector, against: &[&str], kicked: &[&str]) {
+    Simulation::run(&[
+        SetRules(no_gravity()),
+        Spawn(kind, (4, 1)),
+        Hold(ROTATE),
+        Tick(1),
+        Hold(LEFT),
+        Tick(10),
+        ExpectBoard(against),
+        Hold(ROTATE),
+        Tick(1),
+        ExpectBoard(kicked),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn pieces_turned_against_the_wall_kick_off_it() {
+    kick_off_the_left_wall(
+        PieceSelector::J,
+        &["@@........", "@.........", "@........."],
+        &["..........", "@@@.......", "..@......."],
+    );
+    kick_off_the_left_wall(
+        PieceSelector::L,
+        &["@.........", "@.........", "@@........"],
+        &["..........", "@@@.......", "@........."],
+    );
+    kick_off_the_left_wall(
+        PieceSelector::T,
+        &["@.........", "@@........", "@........."],
+        &["..........", "@@@.......", ".@........"],
+    );
+    kick_off_the_left_wall(
+        PieceSelector::S,
+        &["@.........", "@@........", ".@........"],
+        &["..........", ".@@.......", "@@........"],
+    );
+}
+
+#[test]
+fn rotating_counter_clockwise_turns_the_other_way() {
+    Simulation::run(&[
+        Spawn(PieceSelector::T, (4, 5)),
+        Hold(ROTATE_CCW),
+        Tick(1),
+        // Pointing left, where turning clockwise would point it right
+        ExpectBoard(&[
+            "..........",
+            ".....@....",
+            "....@@....",
+            ".....@....",
+            "..........",
+            "..........",
+            "..........",
+        ]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn turning_halfway_round_needs_the_rules_to_allow_it() {
+    Simulation::run(&[
+        Spawn(PieceSelector::T, (4, 5)),
+        Hold(ROTATE_180),
+        Tick(1),
+        ExpectBoard(&[
+            ".....@....",
+            "....@@@...",
+            "..........",
+            "..........",
+            "..........",
+            "..........",
+        ]),
+        SetRules(Rules {
+            rotate_180: true,
+            ..Rules::default()
+        }),
+        Spawn(PieceSelector::T, (4, 5)),
+        Hold(ROTATE_180),
+        Tick(1),
+        // Pointing down, in one update
+        ExpectBoard(&[
+            "....@@@...",
+            ".....@....",
+            "..........",
+            "..........",
+            "..........",
+        ]),
+    ])
+    .unwrap();
+}
+
+#[test]
+fn stacking_to_the_top_finishes_the_game() {
+    Simulation::run(&[Hold(HARD_DROP), Tick(60), ExpectFinished]).unwrap();
+}
+
+#[test]
+fn a_failed_step_is_reported() {
+    let result = Simulation::run(&[Tick(1), ExpectScore(5)]);
+    assert_eq!(
+        result.err(),
+        Some(ScriptError {
+            step: 1,
+            message: String::from("Expected a score of 5 but found 0"),
+        })
+    );
+
+    assert!(Simulation::run(&[SetStack(&["#"])]).is_err());
+}
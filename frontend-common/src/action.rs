@@ -0,0 +1,138 @@
+use tetris_core::tetris::KeyState;
+
+/// Something the player can ask the app to do, independent of how the platform reads input.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Left,
+    Right,
+    /// Rotate clockwise.
+    Rotate,
+    RotateCcw,
+    /// Turn halfway round, when the rules allow it.
+    Rotate180,
+    HardDrop,
+    SoftDrop,
+    Hold,
+    /// Pause or resume a running game.
+    Pause,
+    /// Start a game from the menu, or leave the game over screen.
+    Confirm,
+    Quit,
+}
+
+impl Action {
+    /// The action with name, as written in settings files: left, right, rotate, rotate_ccw,
+    /// rotate_180, hard_drop, soft_drop, hold, pause, confirm or quit.
+    pub fn from_name(name: &str) -> Option<Action> {
+        Some(match name {
+            "left" => Action::Left,
+            "right" => Action::Right,
+            "rotate" => Action::Rotate,
+            "rotate_ccw" => Action::RotateCcw,
+            "rotate_180" => Action::Rotate180,
+            "hard_drop" => Action::HardDrop,
+            "soft_drop" => Action::SoftDrop,
+            "hold" => Action::Hold,
+            "pause" => Action::Pause,
+            "confirm" => Action::Confirm,
+            "quit" => Action::Quit,
+            _ => return None,
+        })
+    }
+
+    fn mask(self) -> u16 {
+        1 << (self as u8)
+    }
+}
+
+/// The set of actions requested during a single tick.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Actions(u16);
+
+impl Actions {
+    pub fn set(&mut self, action: Action, requested: bool) {
+        if requested {
+            self.0 |= action.mask();
+        } else {
+            self.0 &= !action.mask();
+        }
+    }
+
+    pub fn contains(&self, action: Action) -> bool {
+        self.0 & action.mask() != 0
+    }
+
+    /// The inputs to the game for these actions.
+    pub fn key_state(&self) -> KeyState {
+        KeyState {
+            left: self.contains(Action::Left),
+            right: self.contains(Action::Right),
+            rotate: self.contains(Action::Rotate),
+            rotate_ccw: self.contains(Action::RotateCcw),
+            rotate_180: self.contains(Action::Rotate180),
+            hard_drop: self.contains(Action::HardDrop),
+            soft_drop: self.contains(Action::SoftDrop),
+            hold: self.contains(Action::Hold),
+        }
+    }
+}
+
+/// Maps a platform's keys or buttons to actions through a table of bindings. A key may be bound
+/// to more than one action and an action to more than one key.
+pub struct ActionMapper<'a, K> {
+    bindings: &'a [(K, Action)],
+}
+
+impl<'a, K: PartialEq> ActionMapper<'a, K> {
+    pub const fn new(bindings: &'a [(K, Action)]) -> Self {
+        ActionMapper { bindings }
+    }
+
+    /// Add every action bound to key to actions.
+    pub fn apply(&self, key: &K, actions: &mut Actions) {
+        self.bindings
+            .iter()
+            .filter(|(bound, _)| bound == key)
+            .for_each(|(_, action)| actions.set(*action, true));
+    }
+
+    /// The actions for all of the given keys.
+    pub fn map<I: IntoIterator<Item = K>>(&self, keys: I) -> Actions {
+        let mut actions = Actions::default();
+        for key in keys {
+            self.apply(&key, &mut actions);
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::action::{Action, ActionMapper};
+
+    #[test]
+    fn keys_map_to_their_bound_actions() {
+        let mapper = ActionMapper::new(&[
+            ('a', Action::Left),
+            ('d', Action::Right),
+            (' ', Action::Rotate),
+            (' ', Action::Confirm),
+        ]);
+
+        let actions = mapper.map(['a', ' ', 'x']);
+        assert!(actions.contains(Action::Left));
+        assert!(actions.contains(Action::Rotate));
+        assert!(actions.contains(Action::Confirm));
+        assert!(!actions.contains(Action::Right));
+
+        let key_state = actions.key_state();
+        assert!(key_state.left && key_state.rotate && !key_state.right && !key_state.hard_drop);
+    }
+
+    #[test]
+    fn actions_are_found_by_name() {
+        assert!(Action::from_name("hard_drop") == Some(Action::HardDrop));
+        assert!(Action::from_name("rotate_ccw") == Some(Action::RotateCcw));
+        assert!(Action::from_name("Hard Drop").is_none());
+    }
+}
@@ -0,0 +1,479 @@
+use crate::action::{Action, ActionMapper, Actions};
+use crate::tick::TickScheduler;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use tetris_core::achievement::Achievements;
+#[cfg(feature = "alloc")]
+use tetris_core::analysis::Replay;
+use tetris_core::daily::Date;
+use tetris_core::grade::{Grade, Grading};
+use tetris_core::sequence::PieceSequence;
+use tetris_core::session::{GameStats, Session};
+#[cfg(feature = "alloc")]
+use tetris_core::tetris::KeyState;
+use tetris_core::tetris::{EntropySource, Rules, Tetris};
+
+/// Which screen the app is showing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AppState {
+    /// Waiting for the player to start a game.
+    Menu,
+    Playing,
+    Paused,
+    /// The last game has ended with this score, and the grade it earned when grading is on.
+    GameOver {
+        score: usize,
+        grade: Option<Grade>,
+    },
+}
+
+/// The flow from the menu through a game to the game over screen and back, shared by every
+/// frontend.
+pub struct App<E: EntropySource> {
+    state: AppState,
+    tetris: Tetris,
+    /// The seed the game was started with and the key state of each update, so that it can be
+    /// replayed.
+    seed: u64,
+    #[cfg(feature = "alloc")]
+    inputs: Vec<KeyState>,
+    entropy: E,
+    quit: bool,
+    /// The game being played, added to the session when it ends.
+    stats: GameStats,
+    session: Session,
+    achievements: Achievements,
+    /// Achievements unlocked since the frontend last took them to announce.
+    unlocked: Achievements,
+    grading: Option<Grading>,
+    /// Rules each new game is played by.
+    rules: Rules,
+    /// The day whose challenge is played instead of a random game.
+    daily: Option<Date>,
+    /// Pieces dealt in order instead of at random, for drilling.
+    sequence: Option<PieceSequence>,
+}
+
+impl<E: EntropySource> App<E> {
+    pub fn new(mut entropy: E) -> Self {
+        let seed = entropy.next_seed();
+        App {
+            state: AppState::Menu,
+            tetris: Tetris::new_with_seed(seed),
+            seed,
+            #[cfg(feature = "alloc")]
+            inputs: Vec::new(),
+            entropy,
+            quit: false,
+            stats: GameStats::default(),
+            session: Session::new(),
+            achievements: Achievements::default(),
+            unlocked: Achievements::default(),
+            grading: None,
+            rules: Rules::default(),
+            daily: None,
+            sequence: None,
+        }
+    }
+
+    pub fn state(&self) -> AppState {
+        self.state
+    }
+
+    pub fn tetris(&self) -> &Tetris {
+        &self.tetris
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The game so far, or the last one once it is over, to be played again.
+    #[cfg(feature = "alloc")]
+    pub fn replay(&self) -> Replay {
+        Replay {
+            seed: self.seed,
+            rules: self.rules,
+            inputs: self.inputs.clone(),
+        }
+    }
+
+    /// Statistics of the game being played, or of the last one once it is over.
+    pub fn stats(&self) -> &GameStats {
+        &self.stats
+    }
+
+    /// Every game finished so far, for lifetime stats.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Carry on a session saved by an earlier run.
+    pub fn set_session(&mut self, session: Session) {
+        self.session = session;
+    }
+
+    /// Every achievement unlocked so far, for saving with the session.
+    pub fn achievements(&self) -> &Achievements {
+        &self.achievements
+    }
+
+    /// Carry on from achievements unlocked in an earlier run, so they are not announced again.
+    pub fn set_achievements(&mut self, achievements: Achievements) {
+        self.achievements = achievements;
+    }
+
+    /// The achievements unlocked since this was last called, for the frontend to announce.
+    pub fn take_unlocked(&mut self) -> Achievements {
+        core::mem::take(&mut self.unlocked)
+    }
+
+    /// Grade each game as it ends, or stop grading with None.
+    pub fn set_grading(&mut self, grading: Option<Grading>) {
+        self.grading = grading;
+    }
+
+    /// Play the games started from now on by rules, such as zen mode.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// The rules new games are played by.
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    /// Play the daily challenge for date, the same pieces as everyone else playing that day, or
+    /// random games again with None.
+    pub fn set_daily(&mut self, date: Option<Date>) {
+        self.daily = date;
+    }
+
+    pub fn daily(&self) -> Option<Date> {
+        self.daily
+    }
+
+    /// Deal the pieces of sequence in order in the games started from now on, or random pieces
+    /// again with None. Games played from a sequence cannot be reviewed from their replay.
+    pub fn set_sequence(&mut self, sequence: Option<PieceSequence>) {
+        self.sequence = sequence;
+    }
+
+    /// True once the player has asked to quit.
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// Advance the app by one tick with the actions requested since the last one. The game is
+    /// only updated while it is being played.
+    pub fn update(&mut self, actions: Actions) {
+        if actions.contains(Action::Quit) {
+            self.quit = true;
+            return;
+        }
+
+        self.state = match self.state {
+            AppState::Menu if actions.contains(Action::Confirm) => {
+                self.seed = match self.daily {
+                    Some(date) => date.seed(),
+                    None => self.entropy.next_seed(),
+                };
+                self.tetris = match self.sequence {
+                    Some(sequence) => Tetris::with_sequence(sequence),
+                    None => Tetris::new_with_seed(self.seed),
+                };
+                self.tetris.set_rules(self.rules);
+                #[cfg(feature = "alloc")]
+                self.inputs.clear();
+                self.stats = GameStats::default();
+                AppState::Playing
+            }
+            AppState::Playing if actions.contains(Action::Pause) => {
+                self.tetris.pause();
+                AppState::Paused
+            }
+            AppState::Playing => {
+                let key_state = actions.key_state();
+                self.tetris.set_key_state(&key_state);
+                self.tetris.update();
+                #[cfg(feature = "alloc")]
+                self.inputs.push(key_state);
+                self.stats.observe(&self.tetris);
+                let unlocked = self.achievements.observe(&self.tetris, &self.stats);
+                self.unlocked = self.unlocked.union(unlocked);
+                if self.tetris.is_finished() {
+                    self.session.record(&self.stats);
+                    let score = self.tetris.summary().map_or(0, |summary| summary.score);
+                    AppState::GameOver {
+                        score,
+                        grade: self.grading.map(|grading| grading.grade(&self.stats)),
+                    }
+                } else {
+                    AppState::Playing
+                }
+            }
+            AppState::Paused
+                if actions.contains(Action::Pause) || actions.contains(Action::Confirm) =>
+            {
+                self.tetris.resume();
+                AppState::Playing
+            }
+            AppState::GameOver { .. } if actions.contains(Action::Confirm) => AppState::Menu,
+            state => state,
+        };
+    }
+}
+
+/// What a frontend provides to run the app: input, drawing and time.
+pub trait Platform {
+    /// A key or button as the platform reports it.
+    type Key: PartialEq;
+
+    /// Call on_key with every key pressed or held since the last frame.
+    fn poll_keys(&mut self, on_key: &mut dyn FnMut(Self::Key));
+
+    /// Clear the screen and draw the app in its current state, with the statistics of the game
+    /// for platforms that show them alongside it.
+    fn draw(&mut self, state: AppState, tetris: &Tetris, stats: &GameStats);
+
+    /// Milliseconds since an arbitrary fixed point, such as when the platform started.
+    fn now_ms(&self) -> u64;
+
+    fn sleep_ms(&mut self, ms: u64);
+}
+
+/// Run a single frame of the app: read input, update, draw and then wait for the next tick.
+pub fn run_frame<P: Platform, E: EntropySource>(
+    platform: &mut P,
+    app: &mut App<E>,
+    mapper: &ActionMapper<P::Key>,
+    scheduler: &mut TickScheduler,
+) {
+    let mut actions = Actions::default();
+    platform.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+
+    app.update(actions);
+    platform.draw(app.state(), app.tetris(), app.stats());
+
+    let delay = scheduler.next_delay(platform.now_ms());
+    platform.sleep_ms(delay);
+}
+
+/// Run frames until the player quits.
+pub fn run<P: Platform, E: EntropySource>(
+    platform: &mut P,
+    app: &mut App<E>,
+    mapper: &ActionMapper<P::Key>,
+    scheduler: &mut TickScheduler,
+) {
+    while !app.has_quit() {
+        run_frame(platform, app, mapper, scheduler);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::action::{Action, Actions};
+    use crate::app::{App, AppState};
+    use tetris_core::achievement::Achievement;
+    use tetris_core::daily::Date;
+    use tetris_core::grade::{Grade, Grading};
+    use tetris_core::mode::{GameMode, ULTRA_TICKS};
+    use tetris_core::piece::PieceSelector;
+    use tetris_core::sequence::PieceSequence;
+    use tetris_core::tetris::{EntropySource, Rules, Tetris};
+
+    struct FixedSeed;
+
+    impl EntropySource for FixedSeed {
+        fn next_seed(&mut self) -> u64 {
+            4
+        }
+    }
+
+    fn only(action: Action) -> Actions {
+        let mut actions = Actions::default();
+        actions.set(action, true);
+        actions
+    }
+
+    #[test]
+    fn a_game_runs_from_the_menu_to_game_over_and_back() {
+        let mut app = App::new(FixedSeed);
+        assert_eq!(app.state(), AppState::Menu);
+
+        app.update(Actions::default());
+        assert_eq!(app.state(), AppState::Menu);
+
+        app.update(only(Action::Confirm));
+        assert_eq!(app.state(), AppState::Playing);
+
+        for _ in 0..10_000 {
+            app.update(only(Action::HardDrop));
+            if app.state() != AppState::Playing {
+                break;
+            }
+        }
+        assert!(matches!(app.state(), AppState::GameOver { .. }));
+        assert!(app.tetris().is_finished());
+        assert_eq!(app.session().games_played, 1);
+        assert!(app.session().total_pieces > 0);
+        assert_eq!(app.stats().pieces, app.session().total_pieces);
+
+        app.update(only(Action::Confirm));
+        assert_eq!(app.state(), AppState::Menu);
+        app.update(only(Action::Confirm));
+        assert!(!app.tetris().is_finished());
+    }
+
+    #[test]
+    fn games_are_graded_when_grading_is_on() {
+        let mut app = App::new(FixedSeed);
+        app.set_grading(Some(Grading::new(4)));
+        app.update(only(Action::Confirm));
+        while app.state() == AppState::Playing {
+            app.update(only(Action::HardDrop));
+        }
+        assert!(matches!(
+            app.state(),
+            AppState::GameOver {
+                grade: Some(Grade::Nine),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn zen_games_do_not_end() {
+        let mut app = App::new(FixedSeed);
+        app.set_rules(Rules {
+            zen: true,
+            ..Rules::default()
+        });
+        app.update(only(Action::Confirm));
+        for _ in 0..10_000 {
+            app.update(only(Action::HardDrop));
+        }
+        assert_eq!(app.state(), AppState::Playing);
+    }
+
+    #[test]
+    fn an_ultra_game_ends_when_its_time_is_up() {
+        let mut app = App::new(FixedSeed);
+        app.set_rules(Rules {
+            zen: true,
+            mode: GameMode::Ultra,
+            ..Rules::default()
+        });
+        app.update(only(Action::Confirm));
+        for _ in 0..ULTRA_TICKS {
+            assert_eq!(app.state(), AppState::Playing);
+            app.update(only(Action::HardDrop));
+        }
+        assert!(matches!(app.state(), AppState::GameOver { .. }));
+        assert_eq!(app.stats().ticks, ULTRA_TICKS);
+    }
+
+    #[test]
+    fn the_daily_challenge_is_seeded_by_the_date() {
+        let date = Date {
+            year: 2026,
+            month: 10,
+            day: 16,
+        };
+        let mut app = App::new(FixedSeed);
+        app.set_daily(Some(date));
+        app.update(only(Action::Confirm));
+        assert_eq!(app.seed(), date.seed());
+
+        let kinds = |tetris: &Tetris| match tetris {
+            Tetris::Running(state) => (state.piece.kind(), state.next_piece.kind()),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        };
+        assert_eq!(
+            kinds(app.tetris()),
+            kinds(&Tetris::new_with_seed(date.seed()))
+        );
+    }
+
+    #[test]
+    fn games_can_be_dealt_a_sequence() {
+        let mut app = App::new(FixedSeed);
+        app.set_sequence(PieceSequence::from_letters("OT"));
+        app.update(only(Action::Confirm));
+        let Tetris::Running(state) = app.tetris() else {
+            panic!("Expected a running game");
+        };
+        assert_eq!(state.piece.kind(), PieceSelector::O);
+        assert_eq!(state.next_piece.kind(), PieceSelector::T);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn the_game_is_replayed_by_its_seed_and_inputs() {
+        let mut app = App::new(FixedSeed);
+        app.update(only(Action::Confirm));
+        for action in [
+            Action::Left,
+            Action::Rotate,
+            Action::HardDrop,
+            Action::Right,
+        ] {
+            app.update(only(action));
+        }
+        app.update(only(Action::Pause));
+        app.update(only(Action::HardDrop));
+        let replay = app.replay();
+        assert_eq!(replay.inputs.len(), 4);
+
+        let tetris = Tetris::replay(replay.seed, replay.rules, replay.inputs);
+        let grid = |tetris: &Tetris| match tetris {
+            Tetris::Running(state) => (state.grid.clone(), state.piece.x, state.piece.y),
+            Tetris::Paused(_) | Tetris::Finished(_) => panic!("Expected a running game"),
+        };
+        assert_eq!(grid(&tetris), grid(app.tetris()));
+    }
+
+    #[test]
+    fn achievements_are_announced_once() {
+        let mut app = App::new(FixedSeed);
+        app.set_rules(Rules {
+            zen: true,
+            ..Rules::default()
+        });
+        app.update(only(Action::Confirm));
+        for _ in 0..10_000 {
+            app.update(only(Action::HardDrop));
+        }
+        // Hard drops alone score past a hundred thousand points
+        let unlocked = app.take_unlocked();
+        assert!(unlocked.contains(Achievement::HundredThousand));
+        assert!(app.achievements().contains(Achievement::HundredThousand));
+        assert!(app.take_unlocked().is_empty());
+    }
+
+    #[test]
+    fn a_paused_game_does_not_advance() {
+        let mut app = App::new(FixedSeed);
+        app.update(only(Action::Confirm));
+        app.update(only(Action::Pause));
+        assert_eq!(app.state(), AppState::Paused);
+        assert!(app.tetris().is_paused());
+
+        for _ in 0..10_000 {
+            app.update(only(Action::HardDrop));
+        }
+        assert_eq!(app.state(), AppState::Paused);
+
+        app.update(only(Action::Pause));
+        assert_eq!(app.state(), AppState::Playing);
+        assert!(!app.tetris().is_paused());
+    }
+
+    #[test]
+    fn quit_is_honoured_from_any_state() {
+        let mut app = App::new(FixedSeed);
+        app.update(only(Action::Quit));
+        assert!(app.has_quit());
+    }
+}
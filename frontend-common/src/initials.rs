@@ -0,0 +1,111 @@
+//! Arcade style entry of a player's initials for the high score table: up and down cycle the
+//! letter under the cursor, left and right move the cursor and confirming the last letter
+//! finishes.
+
+/// The characters initials are made of, in the order they are cycled through.
+const CHARACTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct InitialsEntry {
+    initials: [u8; 3],
+    cursor: usize,
+}
+
+impl InitialsEntry {
+    /// Start from initials, such as those the player last entered. Characters that cannot be
+    /// entered are replaced with A.
+    pub fn new(initials: [u8; 3]) -> Self {
+        InitialsEntry {
+            initials: initials.map(|initial| match CHARACTERS.contains(&initial) {
+                true => initial,
+                false => CHARACTERS[0],
+            }),
+            cursor: 0,
+        }
+    }
+
+    pub fn initials(&self) -> [u8; 3] {
+        self.initials
+    }
+
+    /// The initials as text.
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.initials).unwrap_or("???")
+    }
+
+    /// Which initial is being changed, from zero.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    fn cycle(&mut self, forward: bool) {
+        let initial = &mut self.initials[self.cursor];
+        let index = CHARACTERS
+            .iter()
+            .position(|character| character == initial)
+            .unwrap_or(0);
+        let index = match forward {
+            true => (index + 1) % CHARACTERS.len(),
+            false => (index + CHARACTERS.len() - 1) % CHARACTERS.len(),
+        };
+        *initial = CHARACTERS[index];
+    }
+
+    /// The next character for the initial under the cursor, wrapping from 9 back to A.
+    pub fn up(&mut self) {
+        self.cycle(true);
+    }
+
+    pub fn down(&mut self) {
+        self.cycle(false);
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.initials.len() - 1);
+    }
+
+    /// Keep the initial under the cursor and move on to the next, returning the initials once
+    /// the last has been confirmed.
+    pub fn confirm(&mut self) -> Option<[u8; 3]> {
+        if self.cursor + 1 == self.initials.len() {
+            return Some(self.initials);
+        }
+        self.cursor += 1;
+        None
+    }
+}
+
+impl Default for InitialsEntry {
+    fn default() -> Self {
+        InitialsEntry::new(*b"AAA")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::initials::InitialsEntry;
+
+    #[test]
+    fn initials_are_entered_a_letter_at_a_time() {
+        let mut entry = InitialsEntry::default();
+        entry.up();
+        entry.up();
+        assert_eq!(entry.confirm(), None);
+        entry.down();
+        assert_eq!(entry.as_str(), "C9A");
+        assert_eq!(entry.confirm(), None);
+        entry.left();
+        entry.up();
+        entry.right();
+        assert_eq!(entry.confirm(), Some(*b"CAA"));
+    }
+
+    #[test]
+    fn initials_that_cannot_be_entered_start_as_a() {
+        assert_eq!(InitialsEntry::new(*b"j-Z").as_str(), "AAZ");
+    }
+}
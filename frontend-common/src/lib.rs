@@ -0,0 +1,13 @@
+#![no_std]
+
+//! The parts of a frontend that do not depend on the platform: the app state machine, mapping
+//! keys or buttons to actions and scheduling ticks. Each frontend supplies the platform through
+//! the Platform trait.
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod action;
+pub mod app;
+pub mod initials;
+pub mod tick;
@@ -0,0 +1,53 @@
+/// Spaces ticks a fixed period apart. Each tick is scheduled from the previous one rather than
+/// from when the frame finished, so time spent updating and drawing does not slow the game down.
+pub struct TickScheduler {
+    period_ms: u64,
+    next_tick_ms: Option<u64>,
+}
+
+impl TickScheduler {
+    pub const fn new(period_ms: u64) -> Self {
+        TickScheduler {
+            period_ms,
+            next_tick_ms: None,
+        }
+    }
+
+    pub fn period_ms(&self) -> u64 {
+        self.period_ms
+    }
+
+    /// Called at the end of a frame with the current time, returning how long to wait before
+    /// the next one. A frame that overran starts the next one immediately and the schedule
+    /// continues from there, rather than running several ticks back to back to catch up.
+    pub fn next_delay(&mut self, now_ms: u64) -> u64 {
+        let next_tick_ms = match self.next_tick_ms {
+            Some(next_tick_ms) if next_tick_ms >= now_ms => next_tick_ms,
+            Some(_) => now_ms,
+            None => now_ms + self.period_ms,
+        };
+        self.next_tick_ms = Some(next_tick_ms + self.period_ms);
+        next_tick_ms - now_ms
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tick::TickScheduler;
+
+    #[test]
+    fn time_spent_in_a_frame_is_subtracted_from_the_delay() {
+        let mut scheduler = TickScheduler::new(100);
+        assert_eq!(scheduler.next_delay(0), 100);
+        assert_eq!(scheduler.next_delay(130), 70);
+        assert_eq!(scheduler.next_delay(200), 100);
+    }
+
+    #[test]
+    fn an_overrun_does_not_cause_a_burst_of_ticks() {
+        let mut scheduler = TickScheduler::new(100);
+        assert_eq!(scheduler.next_delay(0), 100);
+        assert_eq!(scheduler.next_delay(450), 0);
+        assert_eq!(scheduler.next_delay(460), 90);
+    }
+}
@@ -0,0 +1,280 @@
+//! The playfield drawn as an image for terminals that show graphics, with the stack and pieces
+//! in color and the falling piece lowered smoothly between updates. Terminals without either
+//! protocol get the braille playfield instead.
+
+use std::env;
+use tetris_core::piece::PieceSelector;
+use tetris_core::tetris::TetrisState;
+
+/// Indices into PALETTE.
+const BACKGROUND: usize = 0;
+const STACK: usize = 1;
+const CLEARING: usize = 9;
+/// Colors the image is drawn in as red, green and blue, the same as on the handheld.
+const PALETTE: [[u8; 3]; 10] = [
+    [0, 0, 0],
+    [96, 96, 96],
+    // The pieces, indexed by piece_color
+    [0, 255, 255],
+    [0, 0, 255],
+    [255, 96, 0],
+    [255, 255, 0],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    // Complete rows waiting to be removed
+    [255, 255, 255],
+];
+
+/// Pixels a cell of the playfield is drawn with when the size of the terminal's cells is not
+/// known.
+const DEFAULT_CELL_PIXELS: usize = 8;
+/// Base64 bytes sent in each chunk of a Kitty image, the most the protocol allows.
+const KITTY_CHUNK: usize = 4096;
+
+fn piece_color(kind: PieceSelector) -> usize {
+    match kind {
+        PieceSelector::Line => 2,
+        PieceSelector::J => 3,
+        PieceSelector::L => 4,
+        PieceSelector::O => 5,
+        PieceSelector::S => 6,
+        PieceSelector::T => 7,
+        PieceSelector::Z => 8,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Graphics {
+    /// The Kitty graphics protocol, also spoken by WezTerm and Konsole.
+    Kitty,
+    /// DEC sixels, spoken by xterm, foot, mlterm and others.
+    Sixel,
+}
+
+impl Graphics {
+    /// The protocol the terminal is known to speak from its environment, None if it speaks
+    /// neither or cannot be told apart. Asking the terminal itself would race the input thread
+    /// for its answer.
+    pub fn detect() -> Option<Self> {
+        let term = env::var("TERM").unwrap_or_default();
+        let program = env::var("TERM_PROGRAM").unwrap_or_default();
+        let kitty = env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty";
+        if kitty || program == "WezTerm" || env::var_os("KONSOLE_VERSION").is_some() {
+            Some(Graphics::Kitty)
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            Some(Graphics::Sixel)
+        } else {
+            None
+        }
+    }
+
+    /// Parse the --graphics flag, where text asks for the braille playfield.
+    pub fn from_name(name: &str) -> Result<Option<Self>, String> {
+        match name {
+            "kitty" => Ok(Some(Graphics::Kitty)),
+            "sixel" => Ok(Some(Graphics::Sixel)),
+            "text" => Ok(None),
+            "auto" => Ok(Self::detect()),
+            _ => Err(format!(
+                "Unknown graphics {}, expected kitty, sixel, text or auto",
+                name
+            )),
+        }
+    }
+
+    /// The escape sequence drawing state into columns by rows cells from the cursor, the
+    /// falling piece lowered by fall_offset 256ths of a cell.
+    pub fn draw(
+        &self,
+        state: &TetrisState,
+        fall_offset: u16,
+        (columns, rows): (u16, u16),
+    ) -> String {
+        match self {
+            // Kitty scales the image to the cells itself
+            Graphics::Kitty => {
+                let image = Image::playfield(state, fall_offset, DEFAULT_CELL_PIXELS);
+                kitty(&image, (columns, rows))
+            }
+            Graphics::Sixel => {
+                let sizes = (termion::terminal_size_pixels(), termion::terminal_size());
+                let cell = match sizes {
+                    (Ok((width, height)), Ok((term_columns, term_rows))) if width > 0 => {
+                        let cell_width = (width / term_columns) as usize * columns as usize;
+                        let cell_height = (height / term_rows) as usize * rows as usize;
+                        let (grid_width, grid_height) = (state.grid.width, state.visible_height());
+                        (cell_width / grid_width).min(cell_height / grid_height)
+                    }
+                    _ => DEFAULT_CELL_PIXELS,
+                };
+                sixel(&Image::playfield(state, fall_offset, cell.max(1)))
+            }
+        }
+    }
+
+    /// The escape sequence removing the image from the screen. Sixels are part of the text and
+    /// go when it is cleared.
+    pub fn clear(&self) -> &'static str {
+        match self {
+            Graphics::Kitty => "\x1b_Ga=d,d=i,i=1,q=2\x1b\\",
+            Graphics::Sixel => "",
+        }
+    }
+}
+
+/// Pixels as indices into PALETTE, row by row from the top.
+struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<usize>,
+}
+
+impl Image {
+    /// The visible rows of the playfield, the hidden buffer rows above them left out.
+    fn playfield(state: &TetrisState, fall_offset: u16, cell: usize) -> Self {
+        let grid = &state.grid;
+        let height = state.visible_height();
+        let mut image = Image {
+            width: grid.width * cell,
+            height: height * cell,
+            pixels: vec![BACKGROUND; grid.width * cell * height * cell],
+        };
+        for x in 0..grid.width {
+            for y in 0..height {
+                if grid[(x, y)] {
+                    image.fill_cell((x, height - 1 - y), 0, cell, STACK);
+                }
+            }
+        }
+        for y in state.clearing_rows().filter(|&y| y < height) {
+            for x in 0..grid.width {
+                image.fill_cell((x, height - 1 - y), 0, cell, CLEARING);
+            }
+        }
+
+        if let Some(falling) = state.piece_in_play() {
+            let offset = fall_offset as usize * cell / 256;
+            let piece = falling.current_rotation();
+            let color = piece_color(falling.kind());
+            for x in 0..piece.width {
+                for y in 0..piece.height {
+                    let (mut grid_x, grid_y) = (falling.x + x, falling.y + y);
+                    if state.rules.wrap {
+                        grid_x %= grid.width;
+                    }
+                    if piece[(x, y)] && grid_x < grid.width && grid_y < height {
+                        image.fill_cell((grid_x, height - 1 - grid_y), offset, cell, color);
+                    }
+                }
+            }
+        }
+        image
+    }
+
+    /// Fill the cell at column x and row y from the top, lowered by offset pixels.
+    fn fill_cell(&mut self, (x, y): (usize, usize), offset: usize, cell: usize, color: usize) {
+        let top = y * cell + offset;
+        for row in top..(top + cell).min(self.height) {
+            let start = row * self.width + x * cell;
+            self.pixels[start..start + cell].fill(color);
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, &byte)| {
+            bits | (byte as u32) << (16 - index * 8)
+        });
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => out.push(ALPHABET[(bits >> (18 - index * 6)) as usize & 63] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+/// The image as RGB pixels in chunks, replacing the last one drawn and scaled to fit the
+/// cells. Kitty is asked not to answer so that no reply turns up as input.
+fn kitty(image: &Image, (columns, rows): (u16, u16)) -> String {
+    let rgb: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|&color| PALETTE[color])
+        .collect();
+    let data = base64(&rgb);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    let mut out = String::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let more = (index + 1 < chunks.len()) as u8;
+        match index {
+            0 => out.push_str(&format!(
+                "\x1b_Ga=T,f=24,s={},v={},i=1,p=1,c={},r={},C=1,q=2,m={};",
+                image.width, image.height, columns, rows, more
+            )),
+            _ => out.push_str(&format!("\x1b_Gm={};", more)),
+        }
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push_str("\x1b\\");
+    }
+    out
+}
+
+/// The image as sixels: bands six pixels tall, each drawn once per color in it with runs of
+/// the same sixel compressed.
+fn sixel(image: &Image) -> String {
+    let mut out = format!("\x1bPq\"1;1;{};{}", image.width, image.height);
+    for (index, [red, green, blue]) in PALETTE.iter().enumerate() {
+        let percent = |value: &u8| *value as u32 * 100 / 255;
+        out.push_str(&format!(
+            "#{};2;{};{};{}",
+            index,
+            percent(red),
+            percent(green),
+            percent(blue)
+        ));
+    }
+
+    for band in (0..image.height).step_by(6) {
+        let rows = band..(band + 6).min(image.height);
+        let sixels = |color: usize| -> Vec<u8> {
+            (0..image.width)
+                .map(|x| {
+                    rows.clone()
+                        .filter(|&y| image.pixels[y * image.width + x] == color)
+                        .fold(0, |bits, y| bits | 1 << (y - band))
+                })
+                .collect()
+        };
+        for color in 0..PALETTE.len() {
+            let sixels = sixels(color);
+            if sixels.iter().all(|&bits| bits == 0) {
+                continue;
+            }
+            out.push_str(&format!("#{}", color));
+            let mut x = 0;
+            while x < sixels.len() {
+                let run = sixels[x..]
+                    .iter()
+                    .take_while(|&&bits| bits == sixels[x])
+                    .count();
+                let sixel = (63 + sixels[x]) as char;
+                match run {
+                    1..=3 => out.extend(std::iter::repeat_n(sixel, run)),
+                    _ => out.push_str(&format!("!{}{}", run, sixel)),
+                }
+                x += run;
+            }
+            // Back to the start of the band for the next color
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    out
+}
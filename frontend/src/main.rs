@@ -1,26 +1,67 @@
+use frontend_common::action::{Action, ActionMapper, Actions};
+use frontend_common::app::{run_frame, App, AppState, Platform};
+use frontend_common::initials::InitialsEntry;
+use frontend_common::tick::TickScheduler;
+use serde_json::json;
 use std::{
-    io::{stdin, stdout, Write},
-    sync::mpsc::channel,
+    collections::VecDeque,
+    env, fs,
+    io::{self, stdin, stdout, Read, Stdout, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{channel, Receiver},
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use termion::{
     clear,
-    event::Key,
+    event::{Event, Key, MouseButton, MouseEvent},
+    input::MouseTerminal,
     input::TermRead,
     raw::{IntoRawMode, RawTerminal},
 };
-use tetris_core::tetris::{KeyState, Tetris};
+use tetris_core::analysis::{analyse, hint, PieceAnalysis};
+use tetris_core::daily::Date;
+use tetris_core::event::GameEvent;
+use tetris_core::grade::Grading;
+use tetris_core::grid::Grid;
+use tetris_core::high_score::{HighScore, HighScores};
+use tetris_core::mode::GameMode;
+use tetris_core::piece::PieceSelector;
+use tetris_core::profile::Profile;
+use tetris_core::puzzle::{Outcome, Puzzle, PuzzleGame, PUZZLES};
+use tetris_core::replay::{Mode, ReplayFile};
+use tetris_core::scoring::Scoring;
+use tetris_core::sequence::{PieceSequence, MAX_SEQUENCE};
+use tetris_core::session::{GameStats, PlayStats};
+use tetris_core::spectate::{Board, Decoder, Encoder, View};
+use tetris_core::tetris::{
+    EntropySource, OsEntropy, Phase, Rules, Tetris, TetrisState, TopOutRule, BUFFER_ROWS,
+};
+use tetris_core::versus::{Duel, Targeting, Versus};
+use tetris_net::frame::{encode_framed, Deframer, MAX_MESSAGE_LEN};
+use tetris_net::lobby::{Lobby, MatchConfig};
+use tetris_net::{DecodeError, Message, NETPLAY_PORT, PROTOCOL_VERSION, SPECTATE_PORT};
 
 use drawille::Canvas;
+use graphics::Graphics;
 
-fn draw_tetris<W: Write>(terminal: &mut RawTerminal<W>, tetris: &Tetris) {
-    let mut canvas = Canvas::new(30, 30);
+mod graphics;
 
+/// Updates left of the countdown to the start of a game, None once it has started.
+fn countdown_of(tetris: &Tetris) -> Option<u8> {
     match tetris {
-        Tetris::Running(state) => {
-            write!(terminal, "{}", termion::cursor::Goto(1 as u16, 1 as u16)).unwrap();
+        Tetris::Running(state) | Tetris::Paused(state) => state.countdown(),
+        Tetris::Finished(_) => None,
+    }
+}
+
+/// The playfield drawn as lines of braille, or Finished once the game is over. With hints the
+/// corners of the cells where the evaluator would place the falling piece are marked.
+fn tetris_lines(tetris: &Tetris, hints: bool) -> Vec<String> {
+    let mut canvas = Canvas::new(30, 30);
 
+    match tetris {
+        Tetris::Running(state) | Tetris::Paused(state) => {
             state.draw_game_grid(
                 |x, y, state| {
                     if state {
@@ -32,77 +73,1476 @@ fn draw_tetris<W: Write>(terminal: &mut RawTerminal<W>, tetris: &Tetris) {
                 (0, 0),
                 (4, 4),
             );
-            for (idx, line) in canvas.frame().lines().enumerate() {
-                write!(
-                    terminal,
-                    "{}",
-                    termion::cursor::Goto(1 as u16, 1 + idx as u16)
-                )
-                .unwrap();
-                write!(terminal, "{}\n", line,).unwrap();
+            // Complete rows blink, left empty every other update while they wait to be removed
+            if let Phase::LineClear { remaining } = state.phase {
+                for y in state.clearing_rows().filter(|_| remaining % 2 == 0) {
+                    let canvas_y = (state.visible_height() - 1 - y) * 4;
+                    for x in 0..state.grid.width * 4 {
+                        for py in 0..4 {
+                            canvas.unset(x as u32, (canvas_y + py) as u32);
+                        }
+                    }
+                }
             }
+            if let Some(placement) = hints.then(|| hint(state)).flatten() {
+                let piece = placement.piece_grid(state.rules.mirror);
+                for x in 0..piece.width {
+                    for y in 0..piece.height {
+                        if piece[(x, y)] && placement.y + y < state.visible_height() {
+                            let canvas_x = (placement.x + x) * 4;
+                            let canvas_y = (state.visible_height() - 1 - (placement.y + y)) * 4;
+                            for (px, py) in [(0, 0), (3, 0), (0, 3), (3, 3)] {
+                                canvas.set((canvas_x + px) as u32, (canvas_y + py) as u32);
+                            }
+                        }
+                    }
+                }
+            }
+            canvas.frame().lines().map(String::from).collect()
         }
-        Tetris::Finished => write!(terminal, "Finished").unwrap(),
+        Tetris::Finished(_) => vec!["Finished".to_string()],
     }
 }
 
-fn main() {
+/// The pieces of each kind placed so far, a line for each with a bar as long as its share, then
+/// the clears of each size and how fast pieces are being placed.
+fn stats_lines(stats: &PlayStats) -> Vec<String> {
+    let counts = &stats.piece_counts;
+    let total = counts.total().max(1);
+    let mut lines: Vec<String> = counts
+        .iter()
+        .map(|(kind, count)| {
+            let bar = "#".repeat((count * PIECE_STATS_BAR / total) as usize);
+            format!("{} {:>4} {}", kind.letter(), count, bar)
+        })
+        .collect();
+    lines.push(String::new());
+    lines.push(format!("Lines    {:>4}", stats.lines));
+    for (rows, name) in [
+        (1, "Singles"),
+        (2, "Doubles"),
+        (3, "Triples"),
+        (4, "Tetrises"),
+    ] {
+        lines.push(format!("{:<8} {:>4}", name, stats.clears_of(rows)));
+    }
+    let pps = stats.pieces_per_update() * 1000.0 / TICK_MS as f32;
+    lines.push(format!("{:.2} pieces a second", pps));
+    lines
+}
+
+/// lines with side drawn to the right of them, lines padded to the same width.
+fn beside(mut lines: Vec<String>, side: Vec<String>) -> Vec<String> {
+    let width = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+    if lines.len() < side.len() {
+        lines.resize(side.len(), String::new());
+    }
+    for (line, side) in lines.iter_mut().zip(side) {
+        *line = format!("{:<width$}  {}", line, side, width = width);
+    }
+    lines
+}
+
+/// A stack drawn as lines of braille the same size as the playfield of tetris_lines, leaving out
+/// the hidden buffer rows.
+fn grid_lines(grid: &Grid) -> Vec<String> {
+    let mut canvas = Canvas::new(30, 30);
+    let height = grid.height.saturating_sub(BUFFER_ROWS);
+    for x in 0..grid.width {
+        for y in 0..height {
+            if grid[(x, y)] {
+                let canvas_y = height - 1 - y;
+                for px in 0..4 {
+                    for py in 0..4 {
+                        canvas.set((x * 4 + px) as u32, (canvas_y * 4 + py) as u32);
+                    }
+                }
+            }
+        }
+    }
+    canvas.frame().lines().map(String::from).collect()
+}
+
+/// A spectated board drawn as lines of braille the same size as the playfield of
+/// tetris_lines.
+fn board_lines(board: &Board) -> Vec<String> {
+    let mut canvas = Canvas::new(30, 30);
+    for x in 0..board.width {
+        for y in 0..board.height {
+            if board.get(x, y) {
+                let canvas_y = board.height - 1 - y;
+                for px in 0..4 {
+                    for py in 0..4 {
+                        canvas.set((x * 4 + px) as u32, (canvas_y * 4 + py) as u32);
+                    }
+                }
+            }
+        }
+    }
+    canvas.frame().lines().map(String::from).collect()
+}
+
+/// What happened between two views of a spectated game that is worth telling the audience.
+fn spectate_event(before: &View, after: &View) -> Option<String> {
+    match (before, after) {
+        (View::Playing(before), View::Playing(after)) => {
+            let points = after.score.saturating_sub(before.score);
+            (points >= SPECTATE_CLEAR_POINTS).then(|| format!("+{} points", points))
+        }
+        (_, View::Playing(_)) => Some("New game".to_string()),
+        (View::Finished(_), View::Finished(_)) | (_, View::Waiting) => None,
+        (_, View::Finished(board)) => Some(match board {
+            Some(board) => format!("Game over with {} points", board.score),
+            None => "Game over".to_string(),
+        }),
+    }
+}
+
+/// address with port added if it has none, so a Pico W can be reached by its host name alone.
+fn with_default_port(address: &str, port: u16) -> String {
+    match address.rsplit_once(':') {
+        Some((_, given)) if given.parse::<u16>().is_ok() => address.to_string(),
+        _ => format!("{}:{}", address, port),
+    }
+}
+
+/// Watch a game streamed from the Pico W at address, a host with an optional port, until the
+/// connection closes. The board and score are drawn with what has happened lately below them.
+fn spectate(address: &str) {
+    let address = with_default_port(address, SPECTATE_PORT);
+    let mut stream = match TcpStream::connect(&address) {
+        Ok(stream) => stream,
+        Err(error) => {
+            println!("Could not connect to {}: {}", address, error);
+            return;
+        }
+    };
     let mut terminal = stdout().into_raw_mode().unwrap();
-    let mut tetris = Tetris::new();
+    let mut deframer = Deframer::new();
+    let mut decoder = Decoder::new();
+    let mut events = VecDeque::new();
 
-    let (key_tx, key_rx) = channel();
+    'stream: while let Ok(read) = stream.read(deframer.space()) {
+        if read == 0 {
+            break;
+        }
+        deframer.filled(read);
 
-    thread::spawn(move || {
-        let keys = stdin().keys();
-        for key in keys {
-            key_tx.send(key.unwrap()).unwrap();
+        while let Some(message) = deframer.next_message() {
+            let error = match message {
+                Ok(Message::Hello { version, .. }) if version != PROTOCOL_VERSION => {
+                    format!("Unsupported protocol version {}", version)
+                }
+                Ok(Message::Spectate(message)) => {
+                    let before = decoder.view().clone();
+                    match decoder.decode(message) {
+                        Ok(()) => {
+                            events.extend(spectate_event(&before, decoder.view()));
+                            if events.len() > SPECTATE_EVENTS {
+                                events.pop_front();
+                            }
+                            draw_spectated(&mut terminal, decoder.view(), &events);
+                            continue;
+                        }
+                        Err(error) => format!("Bad spectate message from the game: {}", error),
+                    }
+                }
+                Ok(_) => continue,
+                Err(error) => format!("Bad message from the game: {}", error),
+            };
+            write!(terminal, "{}{}", clear::All, error).unwrap();
+            break 'stream;
         }
-    });
+    }
+
+    println!("END");
+}
+
+fn draw_spectated<W: Write>(terminal: &mut RawTerminal<W>, view: &View, events: &VecDeque<String>) {
+    let mut lines = match view {
+        View::Waiting => vec!["Waiting for the game".to_string()],
+        View::Playing(board) => {
+            let mut lines = board_lines(board);
+            lines.push(format!("Score: {}", board.score));
+            lines
+        }
+        View::Finished(Some(board)) => {
+            let mut lines = board_lines(board);
+            lines.push(format!("Finished with {} points", board.score));
+            lines
+        }
+        View::Finished(None) => vec!["Finished".to_string()],
+    };
+    lines.extend(events.iter().cloned());
+
+    write!(terminal, "{}", clear::All).unwrap();
+    for (row, line) in lines.iter().enumerate() {
+        write!(
+            terminal,
+            "{}{}",
+            termion::cursor::Goto(1, 1 + row as u16),
+            line
+        )
+        .unwrap();
+    }
+    terminal.flush().unwrap();
+}
+
+/// Milliseconds between game updates.
+const TICK_MS: u64 = 250;
+/// How long an achievement being unlocked or a perfect clear is announced for.
+const TOAST_MS: u64 = 3000;
+/// Things that happened in a spectated game shown below it, older ones scroll off.
+const SPECTATE_EVENTS: usize = 5;
+/// Score jumps smaller than this are drops rather than clears and are not announced.
+const SPECTATE_CLEAR_POINTS: u32 = 40;
+/// Characters in the bar of a kind of piece that made up every piece placed.
+const PIECE_STATS_BAR: u32 = 20;
+/// The cells the braille playfield takes up, which the playfield image is scaled to.
+const PLAYFIELD_COLUMNS: u16 = 20;
+const PLAYFIELD_ROWS: u16 = 20;
+/// Milliseconds between redraws of the playfield image while waiting for the next update.
+const SMOOTH_FRAME_MS: u64 = 40;
+
+/// Input read from the terminal: keys, and clicks of the left mouse button anywhere on the
+/// screen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Input {
+    Key(Key),
+    Click,
+}
+
+const BINDINGS: &[(Input, Action)] = &[
+    (Input::Key(Key::Char('a')), Action::Left),
+    (Input::Key(Key::Char('d')), Action::Right),
+    (Input::Key(Key::Char(' ')), Action::Rotate),
+    (Input::Key(Key::Char('z')), Action::RotateCcw),
+    (Input::Key(Key::Char('e')), Action::Rotate180),
+    (Input::Key(Key::Char('s')), Action::HardDrop),
+    (Input::Key(Key::Char('x')), Action::SoftDrop),
+    (Input::Key(Key::Char('c')), Action::Hold),
+    (Input::Key(Key::Char('p')), Action::Pause),
+    (Input::Key(Key::Char('\n')), Action::Confirm),
+    // Menus have a single choice, so clicking anywhere picks it
+    (Input::Click, Action::Confirm),
+    (Input::Key(Key::Char('q')), Action::Quit),
+    (Input::Key(Key::Ctrl('c')), Action::Quit),
+];
+
+/// Opens the review of a game from its game over screen.
+const REVIEW_KEY: Input = Input::Key(Key::Char('v'));
+
+/// Keys of the initials entry screen, up and down being read as rotate and soft drop.
+const INITIALS_BINDINGS: &[(Input, Action)] = &[
+    (Input::Key(Key::Left), Action::Left),
+    (Input::Key(Key::Char('a')), Action::Left),
+    (Input::Key(Key::Right), Action::Right),
+    (Input::Key(Key::Char('d')), Action::Right),
+    (Input::Key(Key::Up), Action::Rotate),
+    (Input::Key(Key::Char('w')), Action::Rotate),
+    (Input::Key(Key::Down), Action::SoftDrop),
+    (Input::Key(Key::Char('s')), Action::SoftDrop),
+    (Input::Key(Key::Char('\n')), Action::Confirm),
+    (Input::Click, Action::Confirm),
+];
+
+/// The second player's keys in a battle on one keyboard, the first uses BINDINGS.
+const PLAYER_TWO_BINDINGS: &[(Input, Action)] = &[
+    (Input::Key(Key::Left), Action::Left),
+    (Input::Key(Key::Right), Action::Right),
+    (Input::Key(Key::Up), Action::Rotate),
+    (Input::Key(Key::Char(',')), Action::RotateCcw),
+    (Input::Key(Key::Down), Action::HardDrop),
+    (Input::Key(Key::Char('/')), Action::SoftDrop),
+    (Input::Key(Key::Char('.')), Action::Hold),
+];
+
+/// The input called name in a bindings file: a single character, space, enter, esc, tab, an
+/// arrow (left, right, up or down), ctrl- and a character, or click for the mouse.
+fn input_from_name(name: &str) -> Option<Input> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => return Some(Input::Key(Key::Char(c))),
+        (None, _) => return None,
+        _ => {}
+    }
+    if name == "click" {
+        return Some(Input::Click);
+    }
+    Some(Input::Key(match name {
+        "space" => Key::Char(' '),
+        "enter" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "esc" => Key::Esc,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        _ => {
+            let mut chars = name.strip_prefix("ctrl-")?.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Key::Ctrl(c),
+                _ => return None,
+            }
+        }
+    }))
+}
+
+/// Read bindings written one to a line as `<key> = <action>`, with blank lines and lines
+/// starting with # ignored. Ctrl-c always quits so a bad file cannot trap the player.
+fn parse_bindings(text: &str) -> Result<Vec<(Input, Action)>, String> {
+    let mut bindings = vec![(Input::Key(Key::Ctrl('c')), Action::Quit)];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let binding = line.split_once('=').and_then(|(key, action)| {
+            Some((
+                input_from_name(key.trim())?,
+                Action::from_name(action.trim())?,
+            ))
+        });
+        match binding {
+            Some(binding) => bindings.push(binding),
+            None => return Err(format!("line {}: expected <key> = <action>", number + 1)),
+        }
+    }
+    Ok(bindings)
+}
+
+/// Key bindings kept in a file, read again whenever the file changes.
+struct BindingsFile {
+    path: String,
+    modified: Option<SystemTime>,
+    bindings: Vec<(Input, Action)>,
+}
+
+impl BindingsFile {
+    fn new(path: String) -> Self {
+        BindingsFile {
+            path,
+            modified: None,
+            bindings: BINDINGS.to_vec(),
+        }
+    }
+
+    /// Read the file again if it has changed since it was last read. Returns a message saying
+    /// what happened if it was read, a file that cannot be read or parsed leaves the bindings as
+    /// they were.
+    fn reload(&mut self) -> Option<String> {
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        let modified = match modified {
+            Ok(modified) if Some(modified) == self.modified => return None,
+            Ok(modified) => modified,
+            Err(_) if self.modified.is_none() => return None,
+            Err(error) => {
+                self.modified = None;
+                return Some(format!("Could not read {}: {}", self.path, error));
+            }
+        };
+        self.modified = Some(modified);
+        let parsed = fs::read_to_string(&self.path)
+            .map_err(|error| error.to_string())
+            .and_then(|text| parse_bindings(&text));
+        Some(match parsed {
+            Ok(bindings) => {
+                self.bindings = bindings;
+                format!("Loaded key bindings from {}", self.path)
+            }
+            Err(error) => format!("Kept the old key bindings, {} {}", self.path, error),
+        })
+    }
+}
 
-    'game_loop: while !tetris.is_finished() {
-        write!(&mut terminal, "{}", clear::All).unwrap();
+/// The terminal, with input read on a separate thread so the game is not held up waiting for
+/// it.
+struct Terminal {
+    terminal: MouseTerminal<RawTerminal<Stdout>>,
+    inputs: Receiver<Input>,
+    started: Instant,
+    /// The day whose challenge is being played, shown on the menu.
+    daily: Option<Date>,
+    /// A message for the player shown on every screen but the game, such as the bindings file
+    /// being reloaded.
+    notice: Option<String>,
+    /// The lines on screen, so that a frame only rewrites the lines that changed. Clearing the
+    /// screen for every frame flickers on slow terminals.
+    shown: Vec<String>,
+    /// Whether REVIEW_KEY was pressed during the last frame.
+    review_asked: bool,
+    /// Show the work done by each update below the playfield.
+    hud: bool,
+    /// Beginner mode, marking where the falling piece would best go.
+    hints: bool,
+    /// An announcement shown under the game until the time in milliseconds from now_ms.
+    toast: Option<(String, u64)>,
+    /// The protocol the playfield is drawn as an image with, None for braille.
+    graphics: Option<Graphics>,
+    /// The game being drawn as an image and when it was last updated, so that the falling
+    /// piece can be lowered between updates.
+    falling: Option<(TetrisState, u64)>,
+    /// The image on screen, so that it is only sent again when it changes.
+    image: String,
+}
+
+impl Terminal {
+    /// Show lines from the top of the screen, writing nothing at all if they are already shown.
+    fn show(&mut self, lines: Vec<String>) {
+        let mut changed = false;
+        for (row, line) in lines.iter().enumerate() {
+            if self.shown.get(row) != Some(line) {
+                let goto = termion::cursor::Goto(1, 1 + row as u16);
+                write!(self.terminal, "{}{}{}", goto, line, clear::UntilNewline).unwrap();
+                changed = true;
+            }
+        }
+        for row in lines.len()..self.shown.len() {
+            let goto = termion::cursor::Goto(1, 1 + row as u16);
+            write!(self.terminal, "{}{}", goto, clear::CurrentLine).unwrap();
+            changed = true;
+        }
+        if changed {
+            self.terminal.flush().unwrap();
+        }
+        self.shown = lines;
+    }
 
-        while let Ok(key) = key_rx.try_recv() {
-            match key {
-                Key::Char('a') => {
-                    tetris.set_key_state(&KeyState {
-                        left: true,
-                        right: false,
-                        rotate: false,
-                    });
+    /// Draw the playfield image over the space left for it, or take it down once the game is
+    /// no longer shown.
+    fn draw_image(&mut self) {
+        let Some(graphics) = self.graphics else {
+            return;
+        };
+        let image = match self.falling {
+            Some((ref state, updated)) => {
+                let elapsed = ((self.now_ms() - updated) * 256 / TICK_MS).min(256) as u16;
+                format!(
+                    "{}{}",
+                    termion::cursor::Goto(1, 1),
+                    graphics.draw(
+                        state,
+                        state.fall_offset(elapsed),
+                        (PLAYFIELD_COLUMNS, PLAYFIELD_ROWS)
+                    )
+                )
+            }
+            None => graphics.clear().to_string(),
+        };
+        if image != self.image {
+            write!(self.terminal, "{}", image).unwrap();
+            self.terminal.flush().unwrap();
+            self.image = image;
+        }
+    }
+}
+
+impl Platform for Terminal {
+    type Key = Input;
+
+    fn poll_keys(&mut self, on_key: &mut dyn FnMut(Input)) {
+        while let Ok(input) = self.inputs.try_recv() {
+            self.review_asked |= input == REVIEW_KEY;
+            on_key(input);
+        }
+    }
+
+    fn draw(&mut self, state: AppState, tetris: &Tetris, _stats: &GameStats) {
+        self.falling = None;
+        let mut lines = match state {
+            AppState::Menu => {
+                let daily = self
+                    .daily
+                    .map(|date| format!("Daily challenge for {}. ", date));
+                vec![format!(
+                    "{}Press enter or click to start, q to quit",
+                    daily.unwrap_or_default()
+                )]
+            }
+            AppState::Playing => {
+                // Hints are only marked on the braille playfield
+                let playfield = match (self.graphics, tetris) {
+                    (Some(_), Tetris::Running(state)) if !self.hints => {
+                        self.falling = Some((state.clone(), self.now_ms()));
+                        let blank = " ".repeat(PLAYFIELD_COLUMNS as usize);
+                        vec![blank; PLAYFIELD_ROWS as usize]
+                    }
+                    _ => tetris_lines(tetris, self.hints),
+                };
+                let side = match tetris {
+                    Tetris::Running(state) => {
+                        let finesse = state.finesse();
+                        let mut side = stats_lines(state.stats());
+                        side.push(format!(
+                            "Finesse {}%, {} inputs wasted",
+                            finesse.score(),
+                            finesse.wasted
+                        ));
+                        side
+                    }
+                    _ => Vec::new(),
+                };
+                let mut lines = beside(playfield, side);
+                if let Tetris::Running(state) = tetris {
+                    let coming: Vec<String> = state
+                        .previews()
+                        .map(|piece| piece.kind().letter().to_string())
+                        .collect();
+                    lines.push(format!("Next: {}", coming.join(" ")));
                 }
-                Key::Char('d') => {
-                    tetris.set_key_state(&KeyState {
-                        left: false,
-                        right: true,
-                        rotate: false,
-                    });
+                if let (true, Tetris::Running(state)) = (self.hud, tetris) {
+                    let metrics = state.metrics();
+                    lines.push(format!(
+                        "{} cells touched, {} rows scanned, {} pieces spawned",
+                        metrics.cells_touched, metrics.rows_scanned, metrics.pieces_spawned
+                    ));
                 }
-                Key::Char(' ') => {
-                    tetris.set_key_state(&KeyState {
-                        left: false,
-                        right: false,
-                        rotate: true,
-                    });
+                if let Some(remaining) = countdown_of(tetris) {
+                    let seconds = (u64::from(remaining) * TICK_MS).div_ceil(1000);
+                    lines.push(format!("Ready... {}", seconds));
                 }
-                Key::Ctrl('c') => {
-                    println!("Exit on SIGINT");
-                    break 'game_loop;
+                lines
+            }
+            AppState::Paused => vec!["Paused, press p or click to resume".to_string()],
+            AppState::GameOver { score, grade } => {
+                let mut line = format!("Game over with {} points", score);
+                if let Some(grade) = grade {
+                    line.push_str(&format!(", grade {}", grade));
                 }
-                _ => {}
+                line.push_str(", press enter or click to continue, v to review the game");
+                let mut lines = vec![line];
+                if let Tetris::Finished(summary) = tetris {
+                    lines.push(format!(
+                        "{} after {} lines at level {}, {} pieces in {} seconds",
+                        summary.top_out,
+                        summary.lines,
+                        summary.level,
+                        summary.pieces,
+                        u64::from(summary.ticks) * TICK_MS / 1000
+                    ));
+                }
+                lines
             }
+        };
+        match self.toast {
+            Some((ref toast, until)) if self.now_ms() < until => lines.push(toast.clone()),
+            _ => self.toast = None,
+        }
+        if let (Some(notice), false) = (&self.notice, state == AppState::Playing) {
+            lines.resize(2, String::new());
+            lines.push(notice.clone());
+        }
+        self.show(lines);
+        // Rewritten lines cover sixels, so the image is sent again after each update
+        if self.falling.is_some() {
+            self.image.clear();
+        }
+        self.draw_image();
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    /// The playfield image is redrawn while waiting, lowering the falling piece as the next
+    /// update draws near.
+    fn sleep_ms(&mut self, ms: u64) {
+        let until = self.now_ms() + ms;
+        while self.falling.is_some() && self.now_ms() + SMOOTH_FRAME_MS < until {
+            thread::sleep(Duration::from_millis(SMOOTH_FRAME_MS));
+            self.draw_image();
+        }
+        thread::sleep(Duration::from_millis(until.saturating_sub(self.now_ms())));
+    }
+}
+
+/// Play a puzzle from the built in pack until it is solved, failed or the player quits.
+fn play_puzzle(terminal: &mut Terminal, puzzle: &Puzzle) {
+    let mut game = PuzzleGame::new(puzzle);
+    let mapper = ActionMapper::new(BINDINGS);
+    let mut scheduler = TickScheduler::new(TICK_MS);
+
+    while game.outcome() == Outcome::Playing {
+        let mut actions = Actions::default();
+        terminal.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+        if actions.contains(Action::Quit) {
+            return;
         }
+        game.set_key_state(&actions.key_state());
+        game.update();
+        let mut lines = tetris_lines(game.tetris(), terminal.hints);
+        lines.push(puzzle.name.clone());
+        terminal.show(lines);
+
+        let delay = scheduler.next_delay(terminal.now_ms());
+        terminal.sleep_ms(delay);
+    }
+
+    match game.outcome() {
+        Outcome::Solved => println!("Solved {}", puzzle.name),
+        _ => println!("Failed {}", puzzle.name),
+    }
+}
+
+/// Play a saved replay back at the speed it was recorded, until it ends or the player quits.
+fn watch_replay(terminal: &mut Terminal, file: &ReplayFile) {
+    let mapper = ActionMapper::new(BINDINGS);
+    let mut scheduler = TickScheduler::new(TICK_MS);
+    let replay = &file.replay;
+    let title = match file.mode {
+        Mode::Marathon => "Replay".to_string(),
+        Mode::Daily(date) => format!("Replay of the daily challenge for {}", date),
+    };
+    // Checked before it starts so that the player knows not to trust what they are shown
+    let warning = (!file.verify())
+        .then(|| "This replay plays out differently than when it was recorded".to_string());
 
-        draw_tetris(&mut terminal, &tetris);
+    let mut tetris = Tetris::new_with_seed(replay.seed);
+    tetris.set_rules(replay.rules);
+    for (update, keys) in replay.inputs.iter().enumerate() {
+        let mut actions = Actions::default();
+        terminal.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+        if actions.contains(Action::Quit) {
+            return;
+        }
+        tetris.set_key_state(keys);
         tetris.update();
-        tetris.set_key_state(&KeyState {
-            left: false,
-            right: false,
-            rotate: false,
+        let mut lines = tetris_lines(&tetris, false);
+        lines.push(format!(
+            "{}, update {} of {}, q to quit",
+            title,
+            update + 1,
+            replay.inputs.len()
+        ));
+        lines.extend(warning.clone());
+        terminal.show(lines);
+
+        let delay = scheduler.next_delay(terminal.now_ms());
+        terminal.sleep_ms(delay);
+    }
+}
+
+/// The replay saved at path, or why it could not be read.
+fn load_replay(path: &str) -> Result<ReplayFile, String> {
+    let bytes = fs::read(path).map_err(|error| error.to_string())?;
+    ReplayFile::from_bytes(&bytes).map_err(|error| error.to_string())
+}
+
+/// What the review says about a piece, below the stack it left.
+fn analysis_lines(analyses: &[PieceAnalysis], index: usize) -> Vec<String> {
+    let Some(analysis) = analyses.get(index) else {
+        return vec!["No pieces were placed, press enter to go back".to_string()];
+    };
+    let placement = analysis.placement;
+    let mut lines = grid_lines(&analysis.stack);
+    lines.push(format!(
+        "Piece {} of {}: {:?} turned {} times to column {}",
+        index + 1,
+        analyses.len(),
+        placement.kind,
+        placement.rotation as usize,
+        placement.x + 1
+    ));
+    lines.push(format!(
+        "{} holes created, {} finesse faults",
+        analysis.holes_created, analysis.finesse_faults
+    ));
+    lines.push(match analysis.better {
+        Some(better) => format!(
+            "Better: turned {} times to column {}",
+            better.rotation as usize,
+            better.x + 1
+        ),
+        None => "No better placement found".to_string(),
+    });
+    lines.push("a and d step through the pieces, enter to go back".to_string());
+    lines
+}
+
+/// Step through the pieces of the game just played with the review of each, until the player
+/// goes back to the game over screen.
+fn review(terminal: &mut Terminal, analyses: &[PieceAnalysis]) {
+    let mapper = ActionMapper::new(BINDINGS);
+    let mut scheduler = TickScheduler::new(TICK_MS);
+    let mut index: usize = 0;
+
+    loop {
+        let mut actions = Actions::default();
+        terminal.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+        if actions.contains(Action::Confirm) || actions.contains(Action::Quit) {
+            return;
+        }
+        if actions.contains(Action::Left) {
+            index = index.saturating_sub(1);
+        }
+        if actions.contains(Action::Right) && index + 1 < analyses.len() {
+            index += 1;
+        }
+        terminal.show(analysis_lines(analyses, index));
+
+        let delay = scheduler.next_delay(terminal.now_ms());
+        terminal.sleep_ms(delay);
+    }
+}
+
+/// Ask the player who just made the high score table for their initials, starting from
+/// initials.
+fn enter_initials(terminal: &mut Terminal, initials: [u8; 3]) -> [u8; 3] {
+    let mapper = ActionMapper::new(INITIALS_BINDINGS);
+    let mut scheduler = TickScheduler::new(TICK_MS);
+    let mut entry = InitialsEntry::new(initials);
+
+    loop {
+        let mut actions = Actions::default();
+        terminal.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+        if actions.contains(Action::Left) {
+            entry.left();
+        }
+        if actions.contains(Action::Right) {
+            entry.right();
+        }
+        if actions.contains(Action::Rotate) {
+            entry.up();
+        }
+        if actions.contains(Action::SoftDrop) {
+            entry.down();
+        }
+        if actions.contains(Action::Confirm) {
+            if let Some(initials) = entry.confirm() {
+                return initials;
+            }
+        }
+
+        let letters: Vec<String> = entry.as_str().chars().map(String::from).collect();
+        terminal.show(vec![
+            "New high score! Enter your initials".to_string(),
+            String::new(),
+            format!("  {}", letters.join(" ")),
+            format!("  {}^", "  ".repeat(entry.cursor())),
+            String::new(),
+            "Up and down change the letter, enter keeps it".to_string(),
+        ]);
+
+        let delay = scheduler.next_delay(terminal.now_ms());
+        terminal.sleep_ms(delay);
+    }
+}
+
+/// The high score table saved at path, or an empty one if there is none.
+fn load_high_scores(path: &str) -> HighScores {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_default()
+}
+
+fn save_high_scores(path: &str, scores: &HighScores) -> io::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(scores)?)
+}
+
+/// Every board of a battle drawn side by side, each with a meter beside it filling up from the
+/// bottom a cell for each row of garbage on its way.
+fn versus_lines(versus: &Versus) -> Vec<String> {
+    let mut canvas = Canvas::new(30, 30);
+    let scale = 4;
+
+    for player in 0..versus.players() {
+        let Tetris::Running(ref state) = *versus.tetris(player) else {
+            continue;
+        };
+        // Two cells between boards, one of them for the meter
+        let x_off = player * (state.grid.width + 2) * scale;
+        state.draw_game_grid(
+            |x, y, state| {
+                if state {
+                    canvas.set(x as u32, y as u32);
+                } else {
+                    canvas.unset(x as u32, y as u32);
+                }
+            },
+            (x_off, 0),
+            (scale, scale),
+        );
+
+        let meter_x = x_off + (state.grid.width * scale) + 1;
+        let height = state.visible_height();
+        let rows = versus.incoming_garbage(player).min(height);
+        for y in (height - rows) * scale..height * scale {
+            for x in meter_x..meter_x + scale - 2 {
+                canvas.set(x as u32, y as u32);
+            }
+        }
+    }
+
+    canvas.frame().lines().map(String::from).collect()
+}
+
+/// Play a battle between two players on one keyboard until one of them is knocked out or
+/// either quits, garbage waiting garbage_delay updates before it can land.
+fn play_versus(terminal: &mut Terminal, garbage_delay: u16) {
+    let mut versus = Versus::new(2, Targeting::Leader, &mut OsEntropy);
+    versus.set_garbage_delay(garbage_delay);
+    let mappers = [
+        ActionMapper::new(BINDINGS),
+        ActionMapper::new(PLAYER_TWO_BINDINGS),
+    ];
+    let mut scheduler = TickScheduler::new(TICK_MS);
+
+    while !versus.is_finished() {
+        let mut actions = [Actions::default(); 2];
+        terminal.poll_keys(&mut |input| {
+            for (mapper, actions) in mappers.iter().zip(&mut actions) {
+                mapper.apply(&input, actions);
+            }
+        });
+        if actions.iter().any(|actions| actions.contains(Action::Quit)) {
+            return;
+        }
+        for (player, actions) in actions.iter().enumerate() {
+            versus.set_key_state(player, &actions.key_state());
+        }
+        versus.update();
+
+        terminal.show(versus_lines(&versus));
+
+        let delay = scheduler.next_delay(terminal.now_ms());
+        terminal.sleep_ms(delay);
+    }
+
+    match versus.winner() {
+        Some(winner) => println!("Player {} wins", winner + 1),
+        None => println!("Nobody wins"),
+    }
+}
+
+/// Which side of a match over the network this terminal plays.
+enum Online {
+    /// Wait for a player to join on NETPLAY_PORT.
+    Host,
+    /// Join the match hosted at an address, a host with an optional port.
+    Join(String),
+}
+
+fn send_message(stream: &mut TcpStream, message: &Message) -> io::Result<()> {
+    let mut buffer = [0; MAX_MESSAGE_LEN + 1];
+    let len = encode_framed(message, &mut buffer)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error.to_string()))?;
+    stream.write_all(&buffer[..len])
+}
+
+/// Pass each message that has arrived from the other player to on_message, without waiting for
+/// more. An error once the connection has closed.
+fn receive_messages(
+    stream: &mut TcpStream,
+    deframer: &mut Deframer,
+    on_message: &mut dyn FnMut(Result<Message, DecodeError>),
+) -> io::Result<()> {
+    loop {
+        match stream.read(deframer.space()) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(read) => {
+                deframer.filled(read);
+                while let Some(message) = deframer.next_message() {
+                    on_message(message);
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Open the connection to the other player of a match, the host offering to play by rules.
+fn connect(
+    terminal: &mut Terminal,
+    online: Online,
+    rules: Rules,
+) -> io::Result<(TcpStream, Lobby)> {
+    match online {
+        Online::Host => {
+            let listener = TcpListener::bind(("0.0.0.0", NETPLAY_PORT))?;
+            terminal.show(vec![format!(
+                "Waiting for a player to join on port {}",
+                NETPLAY_PORT
+            )]);
+            let (stream, _) = listener.accept()?;
+            let seed = OsEntropy.next_seed();
+            Ok((stream, Lobby::host(MatchConfig::new(rules), seed)))
+        }
+        Online::Join(address) => {
+            let address = with_default_port(&address, NETPLAY_PORT);
+            terminal.show(vec![format!("Joining the match at {}", address)]);
+            Ok((TcpStream::connect(address)?, Lobby::join()))
+        }
+    }
+}
+
+/// The other player's board drawn as lines of braille, with how their game is going.
+fn opponent_lines(view: &View) -> Vec<String> {
+    match view {
+        View::Waiting => vec!["Waiting for the other player".to_string()],
+        View::Playing(board) => {
+            let mut lines = board_lines(board);
+            lines.push(format!("Them: {}", board.score));
+            lines
+        }
+        View::Finished(Some(board)) => {
+            let mut lines = board_lines(board);
+            lines.push(format!("Them: {}, knocked out", board.score));
+            lines
+        }
+        View::Finished(None) => vec!["Knocked out".to_string()],
+    }
+}
+
+/// Play a match against another terminal or a Pico W over TCP until one player is knocked out
+/// and the player quits. Each side runs its own board, streams it to the other as spectate
+/// messages and sends garbage for the rows it clears.
+fn play_online(terminal: &mut Terminal, online: Online, rules: Rules, garbage_delay: u16) {
+    let result = connect(terminal, online, rules).and_then(|(mut stream, mut lobby)| {
+        stream.set_nonblocking(true)?;
+        send_message(&mut stream, &lobby.hello())?;
+        play_match(terminal, stream, &mut lobby, garbage_delay)
+    });
+    match result {
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            terminal.show(vec!["The other player left".to_string()]);
+        }
+        Err(error) => terminal.show(vec![format!("The match ended early: {}", error)]),
+        Ok(()) => {}
+    }
+}
+
+fn play_match(
+    terminal: &mut Terminal,
+    mut stream: TcpStream,
+    lobby: &mut Lobby,
+    garbage_delay: u16,
+) -> io::Result<()> {
+    let mapper = ActionMapper::new(BINDINGS);
+    let mut scheduler = TickScheduler::new(TICK_MS);
+    let mut deframer = Deframer::new();
+
+    // Both players agree on the match before it starts
+    let agreed = loop {
+        let mut actions = Actions::default();
+        terminal.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+        if actions.contains(Action::Quit) {
+            return Ok(());
+        }
+        let (mut agreed, mut replies) = (Ok(None), Vec::new());
+        receive_messages(&mut stream, &mut deframer, &mut |message| {
+            if let (Ok(message), Ok(None)) = (message, &agreed) {
+                agreed = lobby.receive(&message, &mut |reply| replies.push(reply));
+            }
+        })?;
+        for reply in &replies {
+            send_message(&mut stream, reply)?;
+        }
+        let agreed = agreed
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+        if let Some(agreed) = agreed {
+            break agreed;
+        }
+        terminal.sleep_ms(scheduler.next_delay(terminal.now_ms()));
+    };
+
+    let mut tetris = Tetris::new_with_seed(agreed.seed);
+    tetris.set_rules(agreed.config.rules);
+    let mut duel = Duel::new(agreed.garbage_seed());
+    duel.set_garbage_delay(garbage_delay);
+    let mut encoder = Encoder::new();
+    let mut decoder = Decoder::new();
+    let mut encoded = Vec::new();
+    let mut tick: u32 = 0;
+
+    loop {
+        let mut actions = Actions::default();
+        terminal.poll_keys(&mut |key| mapper.apply(&key, &mut actions));
+        if actions.contains(Action::Quit) {
+            return Ok(());
+        }
+
+        let mut decode_error = None;
+        receive_messages(&mut stream, &mut deframer, &mut |message| match message {
+            // Garbage that does not fit the playfield ends the match like any malformed message
+            Ok(Message::Garbage { rows, hole, .. }) => match agreed.config.garbage(rows, hole) {
+                Ok(garbage) => duel.receive(garbage),
+                Err(error) => decode_error = Some(error.to_string()),
+            },
+            Ok(Message::Spectate(message)) => {
+                if let Err(error) = decoder.decode(message) {
+                    decode_error = Some(error);
+                }
+            }
+            Ok(_) => {}
+            Err(error) => decode_error = Some(error.to_string()),
+        })?;
+        if let Some(error) = decode_error {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+        }
+
+        // Play stops once either player is knocked out, the boards staying up until they quit
+        let opponent_out = matches!(decoder.view(), View::Finished(_));
+        if !opponent_out {
+            tetris.set_key_state(&actions.key_state());
+            tetris.update();
+            if let Some(garbage) = duel.update(&mut tetris) {
+                send_message(
+                    &mut stream,
+                    &Message::Garbage {
+                        tick,
+                        rows: garbage.rows as u8,
+                        hole: garbage.hole as u8,
+                    },
+                )?;
+            }
+        }
+        encoded.clear();
+        encoder.encode(&tetris, &mut encoded);
+        // The encoder's length prefix is replaced by the frame's own
+        send_message(&mut stream, &Message::Spectate(&encoded[1..]))?;
+        tick += 1;
+
+        let score = match tetris {
+            Tetris::Running(ref state) | Tetris::Paused(ref state) => state.score,
+            Tetris::Finished(summary) => summary.score,
+        };
+        let mut lines = beside(tetris_lines(&tetris, false), opponent_lines(decoder.view()));
+        lines.push(match (tetris.is_finished(), opponent_out) {
+            (true, _) => "You were knocked out, press q to leave".to_string(),
+            (false, true) => "You win, press q to leave".to_string(),
+            (false, false) => format!(
+                "You: {}, {} rows of garbage incoming",
+                score,
+                duel.incoming_garbage()
+            ),
         });
+        terminal.show(lines);
 
-        thread::sleep(Duration::from_millis(250));
+        terminal.sleep_ms(scheduler.next_delay(terminal.now_ms()));
+    }
+}
+
+/// Write the summary of the game that just ended to path for other tools to read, as CSV if
+/// the path ends in .csv and as JSON otherwise.
+fn write_summary(path: &str, app: &App<OsEntropy>) -> io::Result<()> {
+    let AppState::GameOver { score, grade } = app.state() else {
+        return Ok(());
+    };
+    let stats = app.stats();
+    let mode = app.rules().mode.name();
+    let daily = app.daily().is_some();
+    let date = app.daily().map(|date| date.to_string());
+    let grade = grade.map(|grade| grade.to_string());
+    let duration_ms = u64::from(stats.ticks) * TICK_MS;
+    let longest_line_drought = stats.droughts.longest(PieceSelector::Line);
+
+    let summary = if path.ends_with(".csv") {
+        format!(
+            "mode,daily,date,seed,score,grade,lines,pieces,level,ticks,duration_ms,\
+             longest_line_drought\n\
+             {},{},{},{},{},{},{},{},{},{},{},{}\n",
+            mode,
+            daily,
+            date.unwrap_or_default(),
+            app.seed(),
+            score,
+            grade.unwrap_or_default(),
+            stats.lines,
+            stats.pieces,
+            stats.level(),
+            stats.ticks,
+            duration_ms,
+            longest_line_drought,
+        )
+    } else {
+        let summary = json!({
+            "mode": mode,
+            "daily": daily,
+            "date": date,
+            "seed": app.seed(),
+            "score": score,
+            "grade": grade,
+            "stats": {
+                "lines": stats.lines,
+                "pieces": stats.pieces,
+                "level": stats.level(),
+                "ticks": stats.ticks,
+                "longest_line_drought": longest_line_drought,
+            },
+            "duration_ms": duration_ms,
+        });
+        format!("{:#}\n", summary)
+    };
+    fs::write(path, summary)
+}
+
+/// Where the profile of the player called name is kept.
+fn profile_path(name: &str) -> String {
+    format!("{}.profile.json", name)
+}
+
+/// The saved profile of the player called name, or a new one if they have none.
+fn load_profile(name: &str) -> Profile {
+    fs::read_to_string(profile_path(name))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or_else(|| Profile::new(name))
+}
+
+fn save_profile(name: &str, profile: &Profile) -> io::Result<()> {
+    fs::write(profile_path(name), serde_json::to_string_pretty(profile)?)
+}
+
+/// Today's date in UTC.
+fn today() -> Date {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    Date::from_days_since_epoch((since_epoch.as_secs() / (24 * 60 * 60)) as u32)
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut puzzle = None;
+    let mut daily = None;
+    let mut stats_out = None;
+    let mut bindings_file = None;
+    let mut versus = false;
+    let mut hud = false;
+    let mut hints = false;
+    let mut wrap = false;
+    let mut rotate_180 = false;
+    let mut cascade = false;
+    let mut speed_curve = false;
+    let mut lock_delay = None;
+    let mut line_clear_delay = None;
+    let mut previews = None;
+    let mut garbage_delay = 0;
+    let mut scoring = None;
+    let mut mode = None;
+    let mut countdown = None;
+    let mut top_out = None;
+    let mut sequence = None;
+    let mut profile_name = None;
+    let mut scores_path = None;
+    let mut record_path = None;
+    let mut replay = None;
+    let mut online = None;
+    let mut graphics = Graphics::detect();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--spectate" => {
+                if let Some(address) = args.next() {
+                    spectate(&address);
+                }
+                return;
+            }
+            // Puzzles are numbered from one
+            "--puzzle" => {
+                let number = args.next().and_then(|number| number.parse::<usize>().ok());
+                let Some(text) = number
+                    .and_then(|number| number.checked_sub(1))
+                    .and_then(|index| PUZZLES.get(index))
+                else {
+                    println!("There are {} puzzles, numbered from 1", PUZZLES.len());
+                    return;
+                };
+                puzzle = Some(Puzzle::parse(text).unwrap());
+            }
+            "--replay" => {
+                let path = args.next().unwrap_or_default();
+                match load_replay(&path) {
+                    Ok(file) => replay = Some(file),
+                    Err(error) => {
+                        println!("Could not read the replay {}: {}", path, error);
+                        return;
+                    }
+                }
+            }
+            "--record" => record_path = args.next(),
+            "--daily" => daily = Some(today()),
+            "--versus" => versus = true,
+            "--host" => online = Some(Online::Host),
+            "--join" => online = args.next().map(Online::Join),
+            "--hud" => hud = true,
+            "--hints" => hints = true,
+            "--graphics" => match Graphics::from_name(&args.next().unwrap_or_default()) {
+                Ok(named) => graphics = named,
+                Err(error) => {
+                    println!("{}", error);
+                    return;
+                }
+            },
+            "--wrap" => wrap = true,
+            "--rotate-180" => rotate_180 = true,
+            "--cascade" => cascade = true,
+            "--speed-curve" => speed_curve = true,
+            "--scoring" => {
+                let name = args.next().unwrap_or_default();
+                match Scoring::from_name(&name) {
+                    Some(named) => scoring = Some(named),
+                    None => {
+                        println!(
+                            "Unknown scoring {}, expected classic, guideline or nes",
+                            name
+                        );
+                        return;
+                    }
+                }
+            }
+            "--mode" => {
+                let name = args.next().unwrap_or_default();
+                match GameMode::from_name(&name) {
+                    Some(named) => mode = Some(named),
+                    None => {
+                        println!(
+                            "Unknown mode {}, expected endless, marathon, sprint or ultra",
+                            name
+                        );
+                        return;
+                    }
+                }
+            }
+            "--top-out" => {
+                let name = args.next().unwrap_or_default();
+                match TopOutRule::from_name(&name) {
+                    Some(named) => top_out = Some(named),
+                    None => {
+                        println!(
+                            "Unknown top out rule {}, expected block, lock or garbage",
+                            name
+                        );
+                        return;
+                    }
+                }
+            }
+            "--sequence" => {
+                let letters = args.next().unwrap_or_default();
+                match PieceSequence::from_letters(&letters) {
+                    Some(given) => sequence = Some(given),
+                    None => {
+                        println!(
+                            "Unknown sequence {}, expected up to {} of the letters IJLOSTZ",
+                            letters, MAX_SEQUENCE
+                        );
+                        return;
+                    }
+                }
+            }
+            // Counted down in seconds, rather than the updates the rules count in
+            "--countdown" => {
+                countdown = args
+                    .next()
+                    .and_then(|seconds| seconds.parse::<u64>().ok())
+                    .map(|seconds| (seconds * 1000 / TICK_MS).min(u8::MAX as u64) as u8)
+            }
+            "--garbage-delay" => {
+                garbage_delay = args
+                    .next()
+                    .and_then(|delay| delay.parse::<u16>().ok())
+                    .unwrap_or_default()
+            }
+            "--lock-delay" => lock_delay = args.next().and_then(|delay| delay.parse::<u8>().ok()),
+            "--previews" => previews = args.next().and_then(|count| count.parse::<u8>().ok()),
+            "--line-clear-delay" => {
+                line_clear_delay = args.next().and_then(|delay| delay.parse::<u8>().ok())
+            }
+            "--profile" => profile_name = args.next(),
+            "--scores" => scores_path = args.next(),
+            "--stats-out" => stats_out = args.next(),
+            "--bindings" => bindings_file = args.next().map(BindingsFile::new),
+            _ => {}
+        }
+    }
+
+    let (input_tx, input_rx) = channel();
+
+    thread::spawn(move || {
+        for event in stdin().events() {
+            let input = match event.unwrap() {
+                Event::Key(key) => Input::Key(key),
+                Event::Mouse(MouseEvent::Press(MouseButton::Left, _, _)) => Input::Click,
+                _ => continue,
+            };
+            input_tx.send(input).unwrap();
+        }
+    });
+
+    let mut terminal = Terminal {
+        terminal: MouseTerminal::from(stdout().into_raw_mode().unwrap()),
+        inputs: input_rx,
+        started: Instant::now(),
+        daily,
+        notice: None,
+        shown: Vec::new(),
+        review_asked: false,
+        hud,
+        hints,
+        toast: None,
+        graphics,
+        falling: None,
+        image: String::new(),
+    };
+    // Cleared once, from here on frames only rewrite what changed
+    write!(terminal.terminal, "{}", clear::All).unwrap();
+
+    if let Some(puzzle) = puzzle {
+        play_puzzle(&mut terminal, &puzzle);
+        return;
+    }
+    if versus {
+        play_versus(&mut terminal, garbage_delay);
+        return;
+    }
+    if let Some(file) = replay {
+        watch_replay(&mut terminal, &file);
+        return;
+    }
+
+    // The rules of a profile are its player's preferences, flags only change them for this run
+    let mut profile = profile_name.as_deref().map(load_profile);
+    let mut rules = profile.map_or_else(Rules::default, |profile| profile.rules);
+    rules.wrap |= wrap;
+    rules.rotate_180 |= rotate_180;
+    rules.cascade |= cascade;
+    rules.speed_curve |= speed_curve;
+    if let Some(delay) = lock_delay {
+        rules.lock_delay = delay;
+    }
+    if let Some(delay) = line_clear_delay {
+        rules.line_clear_delay = delay;
+    }
+    if let Some(previews) = previews {
+        rules.previews = previews;
+    }
+    if let Some(scoring) = scoring {
+        rules.scoring = scoring;
+    }
+    if let Some(mode) = mode {
+        rules.mode = mode;
+    }
+    if let Some(countdown) = countdown {
+        rules.countdown = countdown;
+    }
+    if let Some(top_out) = top_out {
+        rules.top_out = top_out;
+    }
+    if let Some(online) = online {
+        play_online(&mut terminal, online, rules, garbage_delay);
+        return;
+    }
+
+    let mut app = App::new(OsEntropy);
+    app.set_grading(Some(Grading::new((1000 / TICK_MS) as u32)));
+    app.set_daily(daily);
+    app.set_sequence(sequence);
+    app.set_rules(rules);
+    if let Some(ref profile) = profile {
+        app.set_session(profile.session);
+        app.set_achievements(profile.achievements);
+    }
+
+    let mut high_scores = scores_path.as_deref().map(load_high_scores);
+
+    let mut scheduler = TickScheduler::new(TICK_MS);
+    let mut summary_error = None;
+    while !app.has_quit() {
+        let was_playing = app.state() == AppState::Playing;
+        // Changed bindings are picked up between games or while paused, never mid game
+        if let (Some(file), false) = (&mut bindings_file, was_playing) {
+            if let Some(notice) = file.reload() {
+                terminal.notice = Some(notice);
+            }
+        }
+        let bindings = bindings_file
+            .as_ref()
+            .map_or(BINDINGS, |file| &file.bindings);
+        terminal.review_asked = false;
+        let counting_down = countdown_of(app.tetris()).is_some();
+        run_frame(
+            &mut terminal,
+            &mut app,
+            &ActionMapper::new(bindings),
+            &mut scheduler,
+        );
+        if counting_down && countdown_of(app.tetris()).is_none() {
+            let until = terminal.now_ms() + TOAST_MS;
+            terminal.toast = Some((String::from("Go!"), until));
+        }
+        if let Tetris::Running(ref state) = app.tetris() {
+            if state.perfect_clear() {
+                let until = terminal.now_ms() + TOAST_MS;
+                terminal.toast = Some((String::from("Perfect clear!"), until));
+            }
+            for event in state.events() {
+                if let GameEvent::LevelUp(level) = event {
+                    let until = terminal.now_ms() + TOAST_MS;
+                    terminal.toast = Some((format!("Level {}!", level), until));
+                }
+            }
+        }
+        for achievement in app.take_unlocked().iter() {
+            let until = terminal.now_ms() + TOAST_MS;
+            terminal.toast = Some((format!("Achievement unlocked: {}", achievement), until));
+        }
+        if let (true, AppState::GameOver { score, .. }, Some(scores), Some(path)) =
+            (was_playing, app.state(), &mut high_scores, &scores_path)
+        {
+            if scores.qualifies(score as u32) {
+                let last = profile.map_or(*b"AAA", |profile| profile.initials);
+                let initials = enter_initials(&mut terminal, last);
+                if let Some(ref mut profile) = profile {
+                    profile.initials = initials;
+                }
+                scores.insert(HighScore {
+                    initials,
+                    score: score as u32,
+                    date: Some(today()),
+                    uptime: None,
+                });
+                if let Err(error) = save_high_scores(path, scores) {
+                    terminal.notice = Some(format!("Could not save the high scores, {}", error));
+                }
+            }
+        }
+        // Replays and reviews deal the pieces from the seed, so games of a sequence have none
+        let replayable = sequence.is_none();
+        if let (true, AppState::GameOver { .. }, Some(path)) =
+            (was_playing && replayable, app.state(), &record_path)
+        {
+            let mode = app.daily().map_or(Mode::Marathon, Mode::Daily);
+            let file = ReplayFile::new(mode, app.replay());
+            if let Err(error) = fs::write(path, file.to_bytes()) {
+                terminal.notice = Some(format!("Could not save the replay, {}", error));
+            }
+        }
+        if terminal.review_asked && replayable && matches!(app.state(), AppState::GameOver { .. }) {
+            review(&mut terminal, &analyse(&app.replay()));
+        }
+        if let (true, Some(path)) = (was_playing, &stats_out) {
+            if let Err(error) = write_summary(path, &app) {
+                summary_error = Some(error);
+            }
+        }
     }
 
     println!("END");
+    if let (Some(path), Some(error)) = (stats_out, summary_error) {
+        println!("Failed to write the game summary to {}: {}", path, error);
+    }
+    if let (Some(name), Some(profile)) = (profile_name, &mut profile) {
+        profile.session = *app.session();
+        profile.achievements = *app.achievements();
+        if let Err(error) = save_profile(&name, profile) {
+            println!(
+                "Failed to save the profile to {}: {}",
+                profile_path(&name),
+                error
+            );
+        }
+    }
 }
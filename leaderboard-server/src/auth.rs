@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::{fs, io};
+
+/// The devices allowed to submit scores. Each has a fixed token that it sends as
+/// `Authorization: Bearer <token>`, which needs nothing more than a header from the Pico's HTTP
+/// client. The token decides the name the score is recorded under.
+pub struct Tokens {
+    players: HashMap<String, String>,
+}
+
+impl Tokens {
+    /// Parse a tokens file with one device per line as `<player> <token>`. Blank lines and lines
+    /// starting with '#' are ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut players = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                [player, token] => {
+                    players.insert(token.to_string(), player.to_string());
+                }
+                _ => return Err(format!("Line {} is not \"<player> <token>\"", number + 1)),
+            }
+        }
+        Ok(Tokens { players })
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+
+    /// The player for the value of an Authorization header, if it holds a known token.
+    pub fn player(&self, authorization: &str) -> Option<&str> {
+        let token = authorization.strip_prefix("Bearer ")?.trim();
+        self.players.get(token).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::auth::Tokens;
+
+    #[test]
+    fn tokens_identify_their_player() {
+        let tokens = Tokens::parse("# Devices\nhandheld 1234abcd\n\ndesktop ffff0000\n").unwrap();
+        assert_eq!(tokens.player("Bearer 1234abcd"), Some("handheld"));
+        assert_eq!(tokens.player("Bearer ffff0000"), Some("desktop"));
+        assert_eq!(tokens.player("Bearer 00000000"), None);
+        assert_eq!(tokens.player("1234abcd"), None);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        assert!(Tokens::parse("handheld").is_err());
+        assert!(Tokens::parse("handheld 1234 extra").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! A leaderboard for the handheld and the desktop frontend. Scores are submitted with the seed
+//! and a hash of the replay so that a disputed score can be checked by replaying it, and top
+//! lists are served as JSON or as plain text lines for the Pico's HTTP client.
+//!
+//! Usage: leaderboard-server <address> <tokens file> [scores file]
+//!
+//! - `POST /scores` with a JSON body of `mode`, `score`, `seed` and `replay_hash`, and an
+//!   `Authorization: Bearer <token>` header (see auth::Tokens). Daily challenge scores also send
+//!   the `date` of the challenge as `YYYY-MM-DD`.
+//! - `GET /scores/<mode>?limit=10&format=text&date=2026-10-16` for the best scores in a mode,
+//!   `format=text` gives one `<player> <score>` line per entry instead of JSON and `date` keeps
+//!   only the scores for that day's challenge.
+
+mod auth;
+mod store;
+
+use auth::Tokens;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use store::{Entry, Store, SubmitError};
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Clone)]
+struct AppState {
+    store: Arc<Mutex<Store>>,
+    tokens: Arc<Tokens>,
+}
+
+#[derive(Deserialize)]
+struct Submission {
+    mode: String,
+    score: u64,
+    seed: u64,
+    replay_hash: String,
+    date: Option<String>,
+}
+
+/// Modes and replay hashes end up in URLs and on small displays, so both are kept short and
+/// plain.
+fn is_valid_mode(mode: &str) -> bool {
+    !mode.is_empty()
+        && mode.len() <= 32
+        && mode
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Dates are YYYY-MM-DD, so that they compare and filter as plain strings.
+fn is_valid_date(date: &str) -> bool {
+    date.len() == 10
+        && date.char_indices().all(|(index, c)| match index {
+            4 | 7 => c == '-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+fn is_valid_replay_hash(hash: &str) -> bool {
+    (8..=64).contains(&hash.len()) && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+async fn submit(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Json(submission): Json<Submission>,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    let player = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| app.tokens.player(value))
+        .ok_or((StatusCode::UNAUTHORIZED, "Unknown token"))?;
+
+    if !is_valid_mode(&submission.mode) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid mode"));
+    }
+    if !is_valid_replay_hash(&submission.replay_hash) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid replay hash"));
+    }
+    if !submission.date.as_deref().is_none_or(is_valid_date) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid date"));
+    }
+
+    let entry = Entry {
+        player: player.to_string(),
+        mode: submission.mode,
+        score: submission.score,
+        seed: submission.seed,
+        replay_hash: submission.replay_hash.to_ascii_lowercase(),
+        date: submission.date,
+        submitted_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs()),
+    };
+
+    match app.store.lock().unwrap().submit(entry) {
+        Ok(()) => Ok(StatusCode::CREATED),
+        Err(SubmitError::Duplicate) => Err((StatusCode::CONFLICT, "Game already submitted")),
+        Err(SubmitError::Io(error)) => {
+            eprintln!("Failed to save a score: {}", error);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to save the score",
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TopQuery {
+    limit: Option<usize>,
+    format: Option<String>,
+    date: Option<String>,
+}
+
+async fn top(
+    State(app): State<AppState>,
+    Path(mode): Path<String>,
+    Query(query): Query<TopQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let store = app.store.lock().unwrap();
+    let entries = store.top(&mode, query.date.as_deref(), limit);
+
+    match query.format.as_deref() {
+        Some("text") => entries
+            .iter()
+            .map(|entry| format!("{} {}\n", entry.player, entry.score))
+            .collect::<String>()
+            .into_response(),
+        _ => Json(entries).into_response(),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (address, tokens_path, scores_path) = match args.as_slice() {
+        [_, address, tokens] => (address, tokens, None),
+        [_, address, tokens, scores] => (address, tokens, Some(scores)),
+        _ => {
+            eprintln!("Usage: leaderboard-server <address> <tokens file> [scores file]");
+            return;
+        }
+    };
+
+    let tokens = Tokens::load(tokens_path).expect("Failed to load the tokens file");
+    let store = match scores_path {
+        Some(path) => Store::open(path).expect("Failed to open the scores file"),
+        None => Store::in_memory(),
+    };
+
+    let app = Router::new()
+        .route("/scores", post(submit))
+        .route("/scores/:mode", get(top))
+        .with_state(AppState {
+            store: Arc::new(Mutex::new(store)),
+            tokens: Arc::new(tokens),
+        });
+
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .expect("Failed to bind the address");
+    axum::serve(listener, app).await.unwrap();
+}
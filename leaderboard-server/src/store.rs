@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+
+/// A submitted score. The seed and replay hash identify the game exactly, so a score can be
+/// checked by replaying it and the same game cannot be submitted twice.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub player: String,
+    pub mode: String,
+    pub score: u64,
+    pub seed: u64,
+    pub replay_hash: String,
+    /// The day of a daily challenge score, YYYY-MM-DD.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub submitted_at: u64,
+}
+
+#[derive(Debug)]
+pub enum SubmitError {
+    /// A score for the same game has already been submitted.
+    Duplicate,
+    Io(io::Error),
+}
+
+/// Scores for every mode, best first, optionally kept in a file with one JSON entry per line.
+#[derive(Default)]
+pub struct Store {
+    modes: HashMap<String, Vec<Entry>>,
+    file: Option<File>,
+}
+
+impl Store {
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Load the scores in path, creating it if needed, and append new scores to it.
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        let mut store = Store::default();
+        for line in BufReader::new(&file).lines() {
+            let entry: Entry = serde_json::from_str(&line?)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            store.insert(entry);
+        }
+        store.file = Some(file);
+        Ok(store)
+    }
+
+    fn insert(&mut self, entry: Entry) {
+        let entries = self.modes.entry(entry.mode.clone()).or_default();
+        // Ties keep the earlier score first
+        let position = entries.partition_point(|existing| existing.score >= entry.score);
+        entries.insert(position, entry);
+    }
+
+    pub fn submit(&mut self, entry: Entry) -> Result<(), SubmitError> {
+        let duplicate = self.modes.get(&entry.mode).is_some_and(|entries| {
+            entries.iter().any(|existing| {
+                existing.seed == entry.seed && existing.replay_hash == entry.replay_hash
+            })
+        });
+        if duplicate {
+            return Err(SubmitError::Duplicate);
+        }
+
+        if let Some(ref mut file) = self.file {
+            let line = serde_json::to_string(&entry).expect("Entries always serialize");
+            writeln!(file, "{}", line).map_err(SubmitError::Io)?;
+        }
+        self.insert(entry);
+        Ok(())
+    }
+
+    /// The best limit scores for mode, only those for the daily challenge of date if given.
+    pub fn top(&self, mode: &str, date: Option<&str>, limit: usize) -> Vec<&Entry> {
+        self.modes.get(mode).map_or(Vec::new(), |entries| {
+            entries
+                .iter()
+                .filter(|entry| date.is_none() || entry.date.as_deref() == date)
+                .take(limit)
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::store::{Entry, Store, SubmitError};
+
+    fn entry(player: &str, score: u64, seed: u64) -> Entry {
+        Entry {
+            player: player.to_string(),
+            mode: "marathon".to_string(),
+            score,
+            seed,
+            replay_hash: format!("{:08x}", seed),
+            date: None,
+            submitted_at: 0,
+        }
+    }
+
+    #[test]
+    fn scores_are_ranked_best_first() {
+        let mut store = Store::in_memory();
+        store.submit(entry("a", 1000, 1)).unwrap();
+        store.submit(entry("b", 5000, 2)).unwrap();
+        store.submit(entry("c", 1000, 3)).unwrap();
+
+        let players: Vec<&str> = store
+            .top("marathon", None, 10)
+            .iter()
+            .map(|entry| entry.player.as_str())
+            .collect();
+        assert_eq!(players, ["b", "a", "c"]);
+        assert_eq!(store.top("marathon", None, 1).len(), 1);
+        assert!(store.top("sprint", None, 10).is_empty());
+    }
+
+    #[test]
+    fn daily_scores_can_be_listed_by_day() {
+        let mut store = Store::in_memory();
+        for (player, score, date) in [("a", 1000, "2026-10-16"), ("b", 5000, "2026-10-15")] {
+            store
+                .submit(Entry {
+                    date: Some(date.to_string()),
+                    ..entry(player, score, score)
+                })
+                .unwrap();
+        }
+
+        let top = store.top("marathon", Some("2026-10-16"), 10);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].player, "a");
+        assert_eq!(store.top("marathon", None, 10).len(), 2);
+    }
+
+    #[test]
+    fn the_same_game_is_only_accepted_once() {
+        let mut store = Store::in_memory();
+        store.submit(entry("a", 1000, 1)).unwrap();
+        assert!(matches!(
+            store.submit(entry("b", 9000, 1)),
+            Err(SubmitError::Duplicate)
+        ));
+    }
+}
@@ -1,8 +1,11 @@
 #![no_std]
 #![no_main]
 
+extern crate alloc;
+
 use crate::hal::gpio::bank0::*;
-use crate::hal::gpio::PushPullOutput;
+#[cfg(feature = "wifi")]
+use core::{cell::RefCell, pin::pin};
 use cortex_m_rt::entry;
 use defmt_rtt as _;
 use embedded_alloc::Heap;
@@ -15,23 +18,126 @@ use embedded_graphics::{
 use embedded_hal::digital::v2::{InputPin, OutputPin};
 use embedded_time::fixed_point::FixedPoint;
 use embedded_time::rate::Extensions;
+use frontend_common::tick::TickScheduler;
 use hal::{
     clocks::{init_clocks_and_plls, Clock},
     i2c::I2C,
     pac,
     watchdog::Watchdog,
-    Sio,
+    Sio, Timer,
 };
-use panic_probe as _;
 use rp2040_hal as hal;
 use rp2040_hal::gpio::Pin;
 use rp2040_hal::gpio::PullDownInput;
 use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CDisplayInterface, Ssd1306};
-use tetris_core::tetris::{KeyState, Tetris};
+
+mod audio;
+#[cfg(not(feature = "i2s"))]
+mod buzzer;
+mod clock;
+mod crash;
+mod diagnostics;
+mod entropy;
+mod flash;
+mod game;
+#[cfg(feature = "hub75")]
+mod hub75;
+mod i2c;
+#[cfg(feature = "i2s")]
+mod i2s;
+mod input;
+mod launcher;
+mod led;
+#[cfg(feature = "matrix")]
+mod matrix;
+#[cfg(feature = "max7219")]
+mod max7219;
+#[cfg(feature = "wifi")]
+mod mdns;
+mod music;
+#[cfg(feature = "wifi")]
+mod net;
+#[cfg(feature = "wifi")]
+mod netplay;
+#[cfg(feature = "pcd8544")]
+mod pcd8544;
+#[cfg(feature = "wifi")]
+mod remote;
+mod settings;
+mod snake;
+#[cfg(feature = "wifi")]
+mod sntp;
+#[cfg(feature = "wifi")]
+mod spectate;
+#[cfg(feature = "wifi")]
+mod stack;
+mod storage;
+mod tetris_game;
+mod text;
+#[cfg(feature = "touch")]
+mod touch;
+#[cfg(feature = "wifi")]
+mod websocket;
+#[cfg(feature = "wifi")]
+mod wifi;
+#[cfg(feature = "wifi")]
+mod wireless;
+
+use audio::Audio;
+#[cfg(not(feature = "i2s"))]
+use buzzer::Buzzer;
+use clock::WallClock;
+use crash::{CrashReport, Snapshot};
+#[cfg(feature = "wifi")]
+use crash::{CrashReporter, CrashServer, CRASH_PORT};
+use diagnostics::{stack_headroom, Diagnostics, Report};
+use entropy::RoscEntropy;
+use game::{Canvas, Console, Display, Displays, Game};
+use i2c::I2cDevices;
+#[cfg(feature = "i2s")]
+use i2s::I2sAudio;
+#[cfg(not(any(feature = "touch", feature = "matrix")))]
+use input::Button;
+use input::{ButtonState, InputTracker};
+use launcher::{GameId, Launcher, Selection};
+#[cfg(feature = "wifi")]
+use led::Cyw43Led;
+use led::{blink_fault, Fault, StatusLed};
+#[cfg(feature = "matrix")]
+use matrix::ButtonMatrix;
+#[cfg(feature = "wifi")]
+use mdns::{Outcome, Pairing, MDNS_GROUP, MDNS_PORT};
+#[cfg(feature = "wifi")]
+use netplay::{NetplayHost, NETPLAY_PORT};
+#[cfg(feature = "wifi")]
+use remote::{RemoteControl, RemoteInput, HTTP_PORT};
+use snake::SnakeGame;
+#[cfg(feature = "wifi")]
+use sntp::SNTP_PORT;
+#[cfg(feature = "wifi")]
+use spectate::{SpectatorStream, SPECTATE_PORT};
+#[cfg(feature = "wifi")]
+use stack::{Buffers, Network};
+use storage::Storage;
+#[cfg(feature = "wifi")]
+use tetris_core::tetris::EntropySource;
+use tetris_game::TetrisGame;
+#[cfg(feature = "touch")]
+use touch::{TouchCalibrator, TouchPads};
+#[cfg(feature = "wifi")]
+use wifi::WIFI_STATUS;
+#[cfg(feature = "wifi")]
+use wireless::{Cyw43, PowerPin, Spi, Task};
 
 #[global_allocator]
 static HEAP: Heap = Heap::empty();
 
+/// Ten frames a second, the speed the games are tuned for.
+const FRAME_MS: u64 = 100;
+
+/// How often the buttons are sampled while waiting for the next frame.
+const INPUT_SAMPLE_MS: u64 = 5;
+
 #[link_section = ".boot2"]
 #[used]
 pub static BOOT2: [u8; 256] = rp2040_boot2::BOOT_LOADER_W25Q080;
@@ -88,6 +194,39 @@ impl<'a, DI: WriteOnlyDataCommand, SIZE: ssd1306::prelude::DisplaySize>
     }
 }
 
+impl<'a, DI: WriteOnlyDataCommand, SIZE: ssd1306::prelude::DisplaySize> Canvas
+    for Screen<'a, DI, SIZE, BufferedGraphicsMode<SIZE>, BinaryColor>
+{
+    fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        self.display.set_pixel(x, y, on);
+    }
+
+    fn draw_rect(&mut self, min: (u32, u32), max: (u32, u32)) {
+        Screen::draw_rect(self, min, max);
+    }
+
+    fn text(&mut self, text: &str, point: Point) {
+        Screen::text(self, text, point);
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.dim.width, self.dim.height)
+    }
+}
+
+impl<'a, DI: WriteOnlyDataCommand, SIZE: ssd1306::prelude::DisplaySize> Display
+    for Screen<'a, DI, SIZE, BufferedGraphicsMode<SIZE>, BinaryColor>
+{
+    fn clear(&mut self) {
+        Screen::clear(self);
+    }
+
+    fn flush(&mut self) {
+        Screen::flush(self);
+    }
+}
+
+#[cfg(not(any(feature = "touch", feature = "matrix")))]
 struct Buttons {
     // Left = Gpio22
     // Right = 19 and 18 (Hardware bug, fix)
@@ -100,6 +239,7 @@ struct Buttons {
     pub b: Pin<Gpio21, PullDownInput>,
 }
 
+#[cfg(not(any(feature = "touch", feature = "matrix")))]
 impl Buttons {
     pub fn a_pressed(&self) -> bool {
         self.a.is_high().unwrap()
@@ -127,88 +267,28 @@ impl Buttons {
     pub fn right_pressed(&self) -> bool {
         self.right.is_high().unwrap()
     }
-}
-
-fn print_buttons<'a, DI: WriteOnlyDataCommand, SIZE: ssd1306::prelude::DisplaySize>(
-    screen: &mut Screen<'a, DI, SIZE, BufferedGraphicsMode<SIZE>, BinaryColor>,
-    buttons: &Buttons,
-    led_pin: &mut Pin<Gpio25, PushPullOutput>,
-) {
-    const CHR_SZ_X: i32 = 4;
-    let mut btn = false;
-    if buttons.left_pressed() {
-        screen.text("L", Point::new(screen.dim.width as i32 - CHR_SZ_X, 0));
-        btn = true;
-    }
-
-    if buttons.right_pressed() {
-        screen.text("R", Point::new(screen.dim.width as i32 - (CHR_SZ_X * 2), 0));
-        btn = true;
-    }
-
-    if buttons.up_pressed() {
-        screen.text("U", Point::new(screen.dim.width as i32 - (CHR_SZ_X * 3), 0));
-        btn = true;
-    }
-
-    if buttons.down_pressed() {
-        screen.text("D", Point::new(screen.dim.width as i32 - (CHR_SZ_X * 4), 0));
-        btn = true;
-    }
-
-    if buttons.a_pressed() {
-        screen.text("A", Point::new(screen.dim.width as i32 - (CHR_SZ_X * 5), 0));
-        btn = true;
-    }
-
-    if buttons.b_pressed() {
-        screen.text("B", Point::new(screen.dim.width as i32 - (CHR_SZ_X * 6), 0));
-        btn = true;
-    }
 
-    if btn {
-        led_pin.set_high().unwrap();
-    } else {
-        led_pin.set_low().unwrap();
+    /// Sample every button into a single snapshot for the current frame.
+    pub fn state(&self) -> ButtonState {
+        let mut state = ButtonState::default();
+        state.set(Button::Up, self.up_pressed());
+        state.set(Button::Down, self.down_pressed());
+        state.set(Button::Left, self.left_pressed());
+        state.set(Button::Right, self.right_pressed());
+        state.set(Button::A, self.a_pressed());
+        state.set(Button::B, self.b_pressed());
+        state
     }
 }
 
-fn print_tetris<'a, DI: WriteOnlyDataCommand, SIZE: ssd1306::prelude::DisplaySize>(
-    screen: &mut Screen<'a, DI, SIZE, BufferedGraphicsMode<SIZE>, BinaryColor>,
-    tetris: &Tetris,
-) {
-    screen.draw_rect((1, 9), (43, 50));
-    match tetris {
-        Tetris::Running(ref state) => {
-            state.draw_game_grid(
-                |x, y, v| {
-                    screen.display.set_pixel(x as u32, y as u32, v);
-                },
-                (2, 10),
-                (4, 2),
-            );
-        }
-        Tetris::Finished => {}
-    }
-}
-
-fn update(tetris: &mut Tetris, buttons: &Buttons) {
-    let key_state = KeyState {
-        left: buttons.left_pressed(),
-        right: buttons.right_pressed(),
-        rotate: buttons.a_pressed(),
-    };
-
-    tetris.set_key_state(&key_state);
-    tetris.update();
-
-    match tetris {
-        Tetris::Running(ref _state) => {}
-        Tetris::Finished => {
-            if buttons.b_pressed() {
-                *tetris = Tetris::new();
-            }
-        }
+fn game_for<'a>(
+    id: GameId,
+    tetris: &'a mut TetrisGame,
+    snake: &'a mut SnakeGame,
+) -> &'a mut dyn Game {
+    match id {
+        GameId::Tetris => tetris,
+        GameId::Snake => snake,
     }
 }
 
@@ -217,11 +297,18 @@ fn main() -> ! {
     //Allocator
     {
         use core::mem::MaybeUninit;
-        const HEAP_SIZE: usize = 1024;
+        // The heap holds the playfield and piece grids, and with Wi-Fi the messages for a
+        // spectator and for the other player of a match, each under 256 bytes. Resizing the
+        // playfield and growing a message allocate while the old buffer is still held, so this
+        // is a few times their total. What is left is shown on the diagnostics screen.
+        const HEAP_SIZE: usize = 4096;
         static mut HEAP_MEM: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE];
         unsafe { HEAP.init(HEAP_MEM.as_ptr() as usize, HEAP_SIZE) }
     }
 
+    // Taken before core1 is started, as reading it erases it from flash
+    let crash_report: Option<CrashReport> = crash::take_report();
+
     let mut pac = pac::Peripherals::take().unwrap();
     let core = pac::CorePeripherals::take().unwrap();
     let mut watchdog = Watchdog::new(pac.WATCHDOG);
@@ -246,11 +333,76 @@ fn main() -> ! {
         sio.gpio_bank0,
         &mut pac.RESETS,
     );
+    // Started before the wireless chip, whose driver times itself off the same timer. Only set
+    // over Wi-Fi
+    #[cfg(feature = "wifi")]
+    let mut clock = WallClock::new(Timer::new(pac.TIMER, &mut pac.RESETS));
+    #[cfg(not(feature = "wifi"))]
+    let clock = WallClock::new(Timer::new(pac.TIMER, &mut pac.RESETS));
+    // Seeds the network stack before it is handed to Tetris
+    #[cfg(feature = "wifi")]
+    let mut entropy = RoscEntropy::new(pac.ROSC);
+    #[cfg(not(feature = "wifi"))]
+    let entropy = RoscEntropy::new(pac.ROSC);
+
+    // The LED of the plain Pico. On the Pico W this pin selects the wireless chip instead
+    #[cfg(not(feature = "wifi"))]
     let mut led_pin = pins.gpio25.into_push_pull_output();
 
+    // The wireless chip is started first, as the LED of the Pico W hangs off it. Its driver is
+    // polled from here on whenever the buttons are sampled
+    #[cfg(feature = "wifi")]
+    let mut cyw43_state = cyw43::State::new();
+    #[cfg(feature = "wifi")]
+    let (net_driver, mut control, runner) = wireless::start(
+        &mut cyw43_state,
+        PowerPin(pins.gpio23.into_push_pull_output()),
+        Spi::new(
+            pins.gpio24.into_push_pull_output(),
+            pins.gpio25.into_push_pull_output(),
+            pins.gpio29.into_push_pull_output(),
+        ),
+    );
+    #[cfg(feature = "wifi")]
+    let mut runner = pin!(runner.run());
+    #[cfg(feature = "wifi")]
+    wireless::init(runner.as_mut(), &mut control);
+    #[cfg(feature = "wifi")]
+    let mut buffers = Buffers::new();
+    #[cfg(feature = "wifi")]
+    let network = Network::new(net_driver, &mut buffers, entropy.next_seed());
+    #[cfg(feature = "wifi")]
+    let mut cyw43_task = pin!(wireless::run(runner, control, &network));
+    #[cfg(feature = "wifi")]
+    let cyw43_task: Task = RefCell::new(cyw43_task.as_mut());
+    #[cfg(feature = "wifi")]
+    let cyw43 = Cyw43::new(&cyw43_task);
+    #[cfg(feature = "wifi")]
+    let mut led_pin = Cyw43Led::new(cyw43);
+    #[cfg(feature = "wifi")]
+    let mut sntp = network.udp(SNTP_PORT, sntp::SERVER, sntp::TIMEOUT_US, cyw43);
+    // Read without waiting, as pairing is polled once a frame
+    #[cfg(feature = "wifi")]
+    let mut mdns = network.udp(MDNS_PORT, MDNS_GROUP, 0, cyw43);
+    // When to next ask the time server, while the clock has not been set
+    #[cfg(feature = "wifi")]
+    let mut next_sync_ms = 0;
+    #[cfg(feature = "wifi")]
+    let mut spectators = SpectatorStream::new(network.listen(SPECTATE_PORT));
+    #[cfg(feature = "wifi")]
+    let mut netplay = NetplayHost::new(
+        network.listen(NETPLAY_PORT),
+        tetris_game::rules(),
+        entropy.next_seed(),
+    );
+
+    // The matrix drives its own rows, leaving GPIO0 free
+    #[cfg(not(feature = "matrix"))]
     let mut btn_pwr = pins.gpio0.into_push_pull_output();
+    #[cfg(not(feature = "matrix"))]
     btn_pwr.set_high().unwrap();
 
+    #[cfg(not(any(feature = "touch", feature = "matrix")))]
     let buttons = Buttons {
         up: pins.gpio18.into_pull_down_input(),
         left: pins.gpio22.into_pull_down_input(),
@@ -260,49 +412,429 @@ fn main() -> ! {
         b: pins.gpio21.into_pull_down_input(),
     };
 
-    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().integer());
-
-    let i2c = I2C::i2c1(
-        pac.I2C1,
-        pins.gpio10.into_mode(), // I2C 1 SDA
-        pins.gpio11.into_mode(), // I2C 1 SCL
-        400_u32.kHz(),
-        &mut pac.RESETS,
-        clocks.system_clock.freq(),
+    // Touch pads on the button GPIOs, indexed by button, charged from the button power pin
+    #[cfg(feature = "touch")]
+    let mut touch_pads = TouchPads::new([
+        pins.gpio18.into(), // Up
+        pins.gpio19.into(), // Down
+        pins.gpio22.into(), // Left
+        pins.gpio17.into(), // Right
+        pins.gpio16.into(), // A
+        pins.gpio21.into(), // B
+    ]);
+
+    // Buttons wired as a matrix, see matrix::LAYOUT for which button joins each row and column.
+    // GPIO22 is left free
+    #[cfg(feature = "matrix")]
+    let mut buttons = ButtonMatrix::new(
+        [
+            pins.gpio16.into(), // Row 0
+            pins.gpio17.into(), // Row 1
+        ],
+        [
+            pins.gpio18.into(), // Column 0
+            pins.gpio19.into(), // Column 1
+            pins.gpio21.into(), // Column 2
+        ],
     );
 
-    let interface = I2CDisplayInterface::new(i2c);
-    let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate180)
-        .into_buffered_graphics_mode();
+    #[cfg(not(feature = "i2s"))]
+    let mut audio = {
+        let pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+        let mut buzzer_pwm = pwm_slices.pwm7;
+        buzzer_pwm.channel_b.output_to(pins.gpio15);
+        Buzzer::new(buzzer_pwm, clocks.system_clock.freq().integer())
+    };
+
+    // An I2S DAC in place of the buzzer, the pins here must match the constants in the i2s
+    // module
+    #[cfg(feature = "i2s")]
+    let mut audio = {
+        use hal::gpio::FunctionPio1;
+
+        let _bclk = pins.gpio26.into_mode::<FunctionPio1>();
+        let _lrclk = pins.gpio27.into_mode::<FunctionPio1>();
+        let _data = pins.gpio28.into_mode::<FunctionPio1>();
+        I2sAudio::new(pac.PIO1, &mut pac.RESETS, clocks.system_clock.freq().integer())
+    };
 
-    led_pin.set_high().unwrap();
+    let mut delay = cortex_m::delay::Delay::new(core.SYST, clocks.system_clock.freq().integer());
 
-    display.init().unwrap();
+    led_pin.set(true);
 
     let text_style = MonoTextStyleBuilder::new()
         .font(&FONT_6X10)
         .text_color(BinaryColor::On)
         .build();
 
-    let mut screen = Screen {
-        display,
-        dim: Dim2 {
-            width: 128,
-            height: 64,
-        },
-        text_style,
+    #[cfg(not(any(feature = "max7219", feature = "hub75", feature = "pcd8544")))]
+    let mut screen = {
+        let mut i2c = I2C::i2c1(
+            pac.I2C1,
+            pins.gpio10.into_mode(), // I2C 1 SDA
+            pins.gpio11.into_mode(), // I2C 1 SCL
+            400_u32.kHz(),
+            &mut pac.RESETS,
+            clocks.system_clock.freq(),
+        );
+
+        // Without a display there is nothing to show an error on, so it is logged and blinked
+        let address = I2cDevices::scan(&mut i2c)
+            .display_address()
+            .unwrap_or_else(|| {
+                defmt::error!("No SSD1306 at 0x3C or 0x3D on I2C1, check the display wiring");
+                blink_fault(&mut led_pin, Fault::NoDisplay, &mut delay)
+            });
+        let interface = I2CDisplayInterface::new_custom_address(i2c, address);
+        let mut display = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate180)
+            .into_buffered_graphics_mode();
+
+        if display.init().is_err() {
+            defmt::error!("The SSD1306 at {=u8:#x} on I2C1 did not start", address);
+            blink_fault(&mut led_pin, Fault::NoDisplay, &mut delay);
+        }
+
+        Screen {
+            display,
+            dim: Dim2 {
+                width: 128,
+                height: 64,
+            },
+            text_style,
+        }
+    };
+
+    // A 16x32 panel of eight chained LED matrices on SPI0, one LED per playfield cell
+    #[cfg(feature = "max7219")]
+    let mut screen = {
+        let _sck = pins.gpio2.into_mode::<hal::gpio::FunctionSpi>();
+        let _mosi = pins.gpio3.into_mode::<hal::gpio::FunctionSpi>();
+        let mut cs = pins.gpio1.into_push_pull_output();
+        cs.set_high().unwrap();
+
+        let spi = hal::spi::Spi::<_, _, 8>::new(pac.SPI0).init(
+            &mut pac.RESETS,
+            clocks.peripheral_clock.freq(),
+            1_000_000u32.Hz(),
+            &embedded_hal::spi::MODE_0,
+        );
+
+        let mut matrix = max7219::Max7219::<_, _, 8, 2>::new(spi, cs);
+        matrix.init(2);
+        matrix
+    };
+
+    // A 64x32 RGB panel, the pins here must match the constants in the hub75 module
+    #[cfg(feature = "hub75")]
+    let mut screen = {
+        use hal::gpio::FunctionPio0;
+
+        let _r1 = pins.gpio6.into_mode::<FunctionPio0>();
+        let _g1 = pins.gpio7.into_mode::<FunctionPio0>();
+        let _b1 = pins.gpio8.into_mode::<FunctionPio0>();
+        let _r2 = pins.gpio9.into_mode::<FunctionPio0>();
+        let _g2 = pins.gpio10.into_mode::<FunctionPio0>();
+        let _b2 = pins.gpio11.into_mode::<FunctionPio0>();
+        let _clk = pins.gpio12.into_mode::<FunctionPio0>();
+        let _lat = pins.gpio13.into_push_pull_output();
+        let mut oe = pins.gpio14.into_push_pull_output();
+        oe.set_high().unwrap();
+        let _a = pins.gpio2.into_push_pull_output();
+        let _b = pins.gpio3.into_push_pull_output();
+        let _c = pins.gpio26.into_push_pull_output();
+        let _d = pins.gpio27.into_push_pull_output();
+
+        hub75::Hub75::new(
+            pac.PIO0,
+            &mut pac.PSM,
+            &mut pac.PPB,
+            sio.fifo,
+            &mut pac.RESETS,
+        )
+    };
+
+    // A Nokia 5110 LCD on SPI0, using the compact layout
+    #[cfg(feature = "pcd8544")]
+    let mut screen = {
+        let _sck = pins.gpio2.into_mode::<hal::gpio::FunctionSpi>();
+        let _mosi = pins.gpio3.into_mode::<hal::gpio::FunctionSpi>();
+        let mut cs = pins.gpio1.into_push_pull_output();
+        cs.set_high().unwrap();
+        let dc = pins.gpio6.into_push_pull_output();
+        let rst = pins.gpio7.into_push_pull_output();
+
+        let spi = hal::spi::Spi::<_, _, 8>::new(pac.SPI0).init(
+            &mut pac.RESETS,
+            clocks.peripheral_clock.freq(),
+            4_000_000u32.Hz(),
+            &embedded_hal::spi::MODE_0,
+        );
+
+        let mut lcd = pcd8544::Pcd8544::new(spi, cs, dc, rst);
+        lcd.init(0x3F, &mut delay);
+        lcd
     };
 
-    let mut tetris = Tetris::new();
+    // An optional second display on I2C0 shows secondary information such as the next piece,
+    // leaving the main display for a larger playfield.
+    let mut side_i2c = I2C::i2c0(
+        pac.I2C0,
+        pins.gpio4.into_mode(), // I2C 0 SDA
+        pins.gpio5.into_mode(), // I2C 0 SCL
+        400_u32.kHz(),
+        &mut pac.RESETS,
+        clocks.system_clock.freq(),
+    );
+    // Also listed by the diagnostics screen, the bus belongs to the display from here on
+    let i2c0_devices = I2cDevices::scan(&mut side_i2c);
+
+    let mut side_screen = i2c0_devices.display_address().and_then(|address| {
+        let mut side_display = Ssd1306::new(
+            I2CDisplayInterface::new_custom_address(side_i2c, address),
+            DisplaySize128x64,
+            DisplayRotation::Rotate180,
+        )
+        .into_buffered_graphics_mode();
+        side_display.init().ok()?;
+        Some(Screen {
+            display: side_display,
+            dim: Dim2 {
+                width: 128,
+                height: 64,
+            },
+            text_style,
+        })
+    });
+
+    let (mut storage, mut settings, mut high_scores) = Storage::load();
+    let mut launcher = Launcher::new();
+    let mut tetris = TetrisGame::new(entropy);
+    let mut snake = SnakeGame::new();
+    let mut active_game: Option<GameId> = None;
+    let mut input_tracker = InputTracker::default();
+    let mut frames = TickScheduler::new(FRAME_MS);
+    // Buttons seen while waiting for the frame, so a tap between two frames is not missed
+    #[cfg(not(feature = "touch"))]
+    let mut latched = ButtonState::default();
+    #[cfg(feature = "touch")]
+    let mut calibrator: Option<TouchCalibrator> = None;
+    let mut diagnostics: Option<Diagnostics> = None;
+    // The report of a crash on the last boot is shown until a button is pressed
+    let mut crash_screen = crash_report.is_some();
+    // Only listened for when there is a report to serve
+    #[cfg(feature = "wifi")]
+    let mut crash_server = crash_report
+        .as_ref()
+        .map(|_| CrashServer::new(network.listen(CRASH_PORT)));
+    #[cfg(feature = "wifi")]
+    let mut pairing: Option<Pairing> = None;
+    #[cfg(feature = "wifi")]
+    let mut remote = RemoteControl::new(network.listen(HTTP_PORT));
 
     loop {
-        update(&mut tetris, &buttons);
+        #[cfg(not(feature = "touch"))]
+        let held = buttons.state().union(&core::mem::take(&mut latched));
+        #[cfg(feature = "touch")]
+        let held = touch_pads.state(&settings);
+        // Buttons held on the remote control page count as held on the device
+        #[cfg(feature = "wifi")]
+        let held = held.union(&remote.poll());
+        let input = input_tracker.update(held, settings.long_press_frames);
+        audio.update();
+        crash::record(Snapshot {
+            game: active_game,
+            score: match active_game {
+                Some(GameId::Tetris) => tetris.score(),
+                _ => 0,
+            },
+            uptime_ms: clock.uptime_ms() as u32,
+            heap_free: HEAP.free() as u32,
+        });
+        #[cfg(feature = "wifi")]
+        if let (Some(report), Some(server)) = (&crash_report, crash_server.as_mut()) {
+            server.poll(report);
+        }
+
         screen.clear();
+        if let Some(ref mut side_screen) = side_screen {
+            side_screen.clear();
+        }
+
+        // Touch calibration takes over the screen from the launcher until it finishes
+        #[cfg(feature = "touch")]
+        let calibrating = match calibrator {
+            Some(ref mut active) => {
+                active.draw(&mut screen);
+                if active.update(touch_pads.readings(), &mut settings) {
+                    calibrator = None;
+                }
+                true
+            }
+            None => false,
+        };
+        #[cfg(not(feature = "touch"))]
+        let calibrating = false;
+
+        let reporting = match crash_report {
+            Some(ref report) if crash_screen => {
+                crash::draw_report(&mut screen, report);
+                crash_screen = input.pressed == ButtonState::default();
+                true
+            }
+            _ => false,
+        };
+
+        // The diagnostics screen takes over both displays from the launcher until it is left
+        let diagnosing = match diagnostics {
+            Some(ref mut active) => {
+                let report = Report {
+                    i2c0: i2c0_devices,
+                    heap_free: HEAP.free(),
+                    stack_free: stack_headroom(),
+                    #[cfg(feature = "wifi")]
+                    wifi: WIFI_STATUS.latest(),
+                };
+                active.draw(
+                    &mut Displays {
+                        main: &mut screen,
+                        side: side_screen
+                            .as_mut()
+                            .map(|side_screen| side_screen as &mut dyn Canvas),
+                    },
+                    held,
+                    &report,
+                );
+                if active.update(&input) {
+                    diagnostics = None;
+                }
+                true
+            }
+            None => false,
+        };
+
+        // Set the clock once the network gives an address, from the launcher as the request
+        // stalls until it is answered
+        #[cfg(feature = "wifi")]
+        if active_game.is_none()
+            && !clock.is_synchronised()
+            && network.address().is_some()
+            && clock.uptime_ms() >= next_sync_ms
+        {
+            next_sync_ms = clock.uptime_ms() + sntp::RETRY_MS;
+            let _ = sntp::sync(&mut sntp, &mut clock);
+        }
 
-        print_tetris(&mut screen, &mut tetris);
-        print_buttons(&mut screen, &buttons, &mut led_pin);
+        // The versus screen takes over the display from the launcher until it is left, or a match
+        // is started with the device found
+        #[cfg(feature = "wifi")]
+        let pairing_open = match pairing {
+            Some(ref mut active) => {
+                let _ = active.poll(&mut mdns);
+                active.draw(&mut screen);
+                match active.update(&input) {
+                    Some(Outcome::Play) => {
+                        // One of the pair connects, the other's netplay is listening for it
+                        if let Some(peer) = active.dial() {
+                            netplay.join(peer.address, peer.port);
+                        }
+                        game_for(GameId::Tetris, &mut tetris, &mut snake).start(&settings);
+                        active_game = Some(GameId::Tetris);
+                        pairing = None;
+                    }
+                    Some(Outcome::Back) => pairing = None,
+                    None => {}
+                }
+                true
+            }
+            None => false,
+        };
+        #[cfg(not(feature = "wifi"))]
+        let pairing_open = false;
+
+        match active_game {
+            None if calibrating || diagnosing || reporting || pairing_open => {}
+            None => {
+                match launcher.update(&input, &mut settings) {
+                    Some(Selection::Game(id)) => {
+                        game_for(id, &mut tetris, &mut snake).start(&settings);
+                        active_game = Some(id);
+                    }
+                    #[cfg(feature = "touch")]
+                    Some(Selection::CalibrateTouch) => calibrator = Some(TouchCalibrator::new()),
+                    Some(Selection::Diagnostics) => diagnostics = Some(Diagnostics::new()),
+                    // Needs an address to advertise, so does nothing until the network gives one
+                    #[cfg(feature = "wifi")]
+                    Some(Selection::Versus) => pairing = network.address().map(Pairing::new),
+                    // Not shown without Wi-Fi
+                    #[cfg(not(feature = "wifi"))]
+                    Some(Selection::Versus) => {}
+                    // Settings are changed by the launcher itself
+                    Some(
+                        Selection::Layout
+                        | Selection::Assist
+                        | Selection::PieceStats
+                        | Selection::Invisible
+                        | Selection::Big
+                        | Selection::TwentyG,
+                    )
+                    | None => {}
+                }
+                launcher.draw(&mut screen, &settings);
+                storage.save(&settings, &high_scores);
+            }
+            Some(id) => {
+                let game = game_for(id, &mut tetris, &mut snake);
+                let mut console = Console {
+                    settings: &mut settings,
+                    audio: &mut audio,
+                    clock: &clock,
+                    high_scores: &mut high_scores,
+                    #[cfg(feature = "wifi")]
+                    spectate: Some(&mut spectators),
+                    #[cfg(feature = "wifi")]
+                    netplay: Some(&mut netplay),
+                };
+                game.update(&input, &mut console);
+                game.draw(&mut Displays {
+                    main: &mut screen,
+                    side: side_screen
+                        .as_mut()
+                        .map(|side_screen| side_screen as &mut dyn Canvas),
+                });
+
+                if game.exited() {
+                    audio.silence();
+                    active_game = None;
+                }
+            }
+        }
+
+        led_pin.set(held != ButtonState::default());
+        #[cfg(feature = "wifi")]
+        wifi::draw_status(
+            &mut screen,
+            WIFI_STATUS.latest(),
+            (clock.uptime_ms() / FRAME_MS) as u32,
+        );
 
         screen.flush();
-        delay.delay_ms(100);
+        if let Some(ref mut side_screen) = side_screen {
+            side_screen.flush();
+        }
+        let mut wait_ms = frames.next_delay(clock.uptime_ms());
+        while wait_ms > 0 {
+            let slice_ms = wait_ms.min(INPUT_SAMPLE_MS);
+            delay.delay_ms(slice_ms as u32);
+            wait_ms -= slice_ms;
+            #[cfg(not(feature = "touch"))]
+            {
+                latched = latched.union(&buttons.state());
+            }
+            #[cfg(feature = "wifi")]
+            {
+                cyw43.poll();
+                network.poll();
+            }
+        }
     }
 }
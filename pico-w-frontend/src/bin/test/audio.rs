@@ -0,0 +1,23 @@
+/// Short sounds played by games over the music.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SoundEffect {
+    Rotate,
+    LineClear,
+    GameOver,
+}
+
+/// A sound output shared by the music and sound effects, implemented by the buzzer and the
+/// I2S DAC.
+pub trait Audio {
+    /// Play a music note at the given frequency in Hz, a frequency of zero is a rest.
+    fn tone(&mut self, freq: u32);
+
+    /// Stop the music and any sound effect.
+    fn silence(&mut self);
+
+    /// Start a sound effect, replacing any effect that is still playing.
+    fn effect(&mut self, effect: SoundEffect);
+
+    /// Advance by one frame of the main loop.
+    fn update(&mut self) {}
+}
@@ -0,0 +1,78 @@
+use crate::audio::{Audio, SoundEffect};
+use embedded_hal::PwmPin;
+use rp2040_hal::pwm::{FreeRunning, Pwm7, Slice};
+
+// Fixed clock divider for the buzzer slice. At 125MHz this leaves a 16 bit top value for
+// every frequency from ~30Hz upwards.
+const PWM_DIVIDER: u8 = 64;
+
+/// A piezo buzzer driven by a square wave on channel B of PWM slice 7 (GPIO15).
+pub struct Buzzer {
+    slice: Slice<Pwm7, FreeRunning>,
+    system_clock_hz: u32,
+    /// The music note to go back to once a sound effect finishes.
+    music_freq: u32,
+    effect_frames: u8,
+}
+
+impl Buzzer {
+    /// Takes ownership of a PWM slice whose channel B has already been routed to the buzzer pin.
+    pub fn new(mut slice: Slice<Pwm7, FreeRunning>, system_clock_hz: u32) -> Self {
+        slice.set_div_int(PWM_DIVIDER);
+        slice.channel_b.set_duty(0);
+        slice.enable();
+        Buzzer {
+            slice,
+            system_clock_hz,
+            music_freq: 0,
+            effect_frames: 0,
+        }
+    }
+
+    fn play(&mut self, freq: u32) {
+        if freq == 0 {
+            self.slice.channel_b.set_duty(0);
+            return;
+        }
+
+        let top = (self.system_clock_hz / PWM_DIVIDER as u32 / freq).clamp(2, u16::MAX as u32) - 1;
+        self.slice.set_top(top as u16);
+        self.slice.channel_b.set_duty(top as u16 / 2);
+    }
+}
+
+impl Audio for Buzzer {
+    fn tone(&mut self, freq: u32) {
+        self.music_freq = freq;
+        if self.effect_frames == 0 {
+            self.play(freq);
+        }
+    }
+
+    fn silence(&mut self) {
+        self.music_freq = 0;
+        self.effect_frames = 0;
+        self.play(0);
+    }
+
+    /// The buzzer can only play one tone, so effects are a single tone that interrupts the
+    /// music for a few frames.
+    fn effect(&mut self, effect: SoundEffect) {
+        let (freq, frames) = match effect {
+            SoundEffect::Rotate => (2093, 1),
+            SoundEffect::LineClear => (1568, 2),
+            SoundEffect::GameOver => (196, 6),
+        };
+        self.effect_frames = frames;
+        self.play(freq);
+    }
+
+    fn update(&mut self) {
+        if self.effect_frames > 0 {
+            self.effect_frames -= 1;
+            if self.effect_frames == 0 {
+                self.play(self.music_freq);
+            }
+        }
+    }
+}
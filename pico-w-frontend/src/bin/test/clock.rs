@@ -0,0 +1,55 @@
+use rp2040_hal::Timer;
+
+/// When something happened, as real time if the clock has been synchronised over the network
+/// or as time since boot otherwise.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    /// Seconds since the Unix epoch.
+    Unix(u32),
+    /// Seconds since the device booted, used while offline.
+    Uptime(u32),
+}
+
+/// A wall clock built on the free running microsecond timer. It reports uptime until it has been
+/// given a reference time (e.g. from SNTP) and real time afterwards.
+pub struct WallClock {
+    timer: Timer,
+    reference: Option<(u32, u64)>,
+}
+
+impl WallClock {
+    pub fn new(timer: Timer) -> Self {
+        WallClock {
+            timer,
+            reference: None,
+        }
+    }
+
+    fn uptime_us(&self) -> u64 {
+        self.timer.get_counter()
+    }
+
+    /// Milliseconds since boot, unaffected by synchronisation, used to schedule frames.
+    pub fn uptime_ms(&self) -> u64 {
+        self.uptime_us() / 1000
+    }
+
+    /// Record that it is currently unix_seconds, all later timestamps are derived from this.
+    pub fn set_unix_time(&mut self, unix_seconds: u32) {
+        self.reference = Some((unix_seconds, self.uptime_us()));
+    }
+
+    pub fn is_synchronised(&self) -> bool {
+        self.reference.is_some()
+    }
+
+    pub fn now(&self) -> Timestamp {
+        let uptime_us = self.uptime_us();
+        match self.reference {
+            Some((unix_seconds, at_us)) => {
+                Timestamp::Unix(unix_seconds + ((uptime_us - at_us) / 1_000_000) as u32)
+            }
+            None => Timestamp::Uptime((uptime_us / 1_000_000) as u32),
+        }
+    }
+}
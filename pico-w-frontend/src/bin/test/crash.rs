@@ -0,0 +1,296 @@
+//! Panics and hard faults are written to a sector near the end of flash before the handheld
+//! resets, so that a failure seen in the field can be read off the screen on the next boot, or
+//! fetched over Wi-Fi, rather than being lost with the reset. The sector is left out of the
+//! firmware's flash in memory.x.
+
+use crate::flash::{read_page, write_sector, RomFlash, FLASH_SIZE, PAGE_SIZE, SECTOR_SIZE};
+use crate::game::Canvas;
+use crate::launcher::GameId;
+#[cfg(feature = "wifi")]
+use crate::net::TcpConnection;
+use crate::text::TextBuffer;
+use core::cell::Cell;
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+use cortex_m::interrupt::{free, Mutex};
+use cortex_m_rt::{exception, ExceptionFrame};
+use embedded_graphics::prelude::Point;
+use rp2040_hal::pac;
+
+/// The crash sector's offset into the flash, just before the settings in the last sector.
+const CRASH_OFFSET: u32 = FLASH_SIZE - 2 * SECTOR_SIZE;
+
+/// Marks a sector holding a report, erased flash reads as 0xff.
+const MAGIC: &[u8; 4] = b"CRSH";
+/// Bytes of the panic message kept, the rest is cut off.
+const MESSAGE_LEN: usize = 128;
+/// Characters in a line of the crash screen.
+const LINE_CHARS: usize = 21;
+
+/// The port the last crash report is served on.
+#[cfg(feature = "wifi")]
+pub const CRASH_PORT: u16 = 7880;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CrashKind {
+    Panic = 1,
+    HardFault = 2,
+}
+
+/// What the handheld was doing, recorded by the main loop each frame for a crash to save.
+#[derive(Clone, Copy, Default)]
+pub struct Snapshot {
+    pub game: Option<GameId>,
+    /// The score of the Tetris game being played.
+    pub score: u32,
+    pub uptime_ms: u32,
+    pub heap_free: u32,
+}
+
+static SNAPSHOT: Mutex<Cell<Snapshot>> = Mutex::new(Cell::new(Snapshot {
+    game: None,
+    score: 0,
+    uptime_ms: 0,
+    heap_free: 0,
+}));
+
+/// Record the state a crash during this frame would save.
+pub fn record(snapshot: Snapshot) {
+    free(|cs| SNAPSHOT.borrow(cs).set(snapshot));
+}
+
+/// The panic message, cut off at MESSAGE_LEN bytes.
+#[derive(Clone, Copy)]
+struct Message {
+    bytes: [u8; MESSAGE_LEN],
+    len: usize,
+}
+
+impl fmt::Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let mut encoded = [0; 4];
+            let encoded = c.encode_utf8(&mut encoded).as_bytes();
+            if self.len + encoded.len() > MESSAGE_LEN {
+                break;
+            }
+            self.bytes[self.len..self.len + encoded.len()].copy_from_slice(encoded);
+            self.len += encoded.len();
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CrashReport {
+    pub kind: CrashKind,
+    /// Where a hard fault happened. A panic's location is in its message instead.
+    pub pc: Option<u32>,
+    pub snapshot: Snapshot,
+    message: Message,
+}
+
+impl CrashReport {
+    fn new(kind: CrashKind, pc: Option<u32>) -> Self {
+        CrashReport {
+            kind,
+            pc,
+            snapshot: free(|cs| SNAPSHOT.borrow(cs).get()),
+            message: Message {
+                bytes: [0; MESSAGE_LEN],
+                len: 0,
+            },
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        core::str::from_utf8(&self.message.bytes[..self.message.len]).unwrap_or("")
+    }
+
+    /// The report as it is laid out in flash: the magic, kind, message length, program counter,
+    /// game, score, uptime and free heap, then the message.
+    fn to_page(&self) -> [u8; PAGE_SIZE] {
+        let snapshot = &self.snapshot;
+        let game = match snapshot.game {
+            None => 0,
+            Some(GameId::Tetris) => 1,
+            Some(GameId::Snake) => 2,
+        };
+        let mut page = [0xff; PAGE_SIZE];
+        page[..4].copy_from_slice(MAGIC);
+        page[4] = self.kind as u8;
+        page[5] = self.message.len as u8;
+        page[6] = game;
+        page[8..12].copy_from_slice(&self.pc.unwrap_or(0).to_le_bytes());
+        page[12..16].copy_from_slice(&snapshot.score.to_le_bytes());
+        page[16..20].copy_from_slice(&snapshot.uptime_ms.to_le_bytes());
+        page[20..24].copy_from_slice(&snapshot.heap_free.to_le_bytes());
+        page[24..24 + MESSAGE_LEN].copy_from_slice(&self.message.bytes);
+        page
+    }
+
+    fn from_page(page: &[u8; PAGE_SIZE]) -> Option<Self> {
+        if &page[..4] != MAGIC {
+            return None;
+        }
+        let word =
+            |at: usize| u32::from_le_bytes([page[at], page[at + 1], page[at + 2], page[at + 3]]);
+        let kind = match page[4] {
+            1 => CrashKind::Panic,
+            2 => CrashKind::HardFault,
+            _ => return None,
+        };
+        let game = match page[6] {
+            1 => Some(GameId::Tetris),
+            2 => Some(GameId::Snake),
+            _ => None,
+        };
+        let mut bytes = [0; MESSAGE_LEN];
+        bytes.copy_from_slice(&page[24..24 + MESSAGE_LEN]);
+        Some(CrashReport {
+            kind,
+            pc: match kind {
+                CrashKind::Panic => None,
+                CrashKind::HardFault => Some(word(8)),
+            },
+            snapshot: Snapshot {
+                game,
+                score: word(12),
+                uptime_ms: word(16),
+                heap_free: word(20),
+            },
+            message: Message {
+                bytes,
+                len: (page[5] as usize).min(MESSAGE_LEN),
+            },
+        })
+    }
+
+    /// The report as lines of text, for the screen and for sending over Wi-Fi. The game comes
+    /// before the message so that it is on screen however long the message is.
+    fn lines(&self, mut line: impl FnMut(&str)) {
+        let mut text = TextBuffer::new();
+        let _ = match (self.kind, self.pc) {
+            (CrashKind::HardFault, Some(pc)) => write!(text, "Hard fault {:08X}", pc),
+            _ => write!(text, "Panic"),
+        };
+        line(text.as_str());
+
+        let snapshot = &self.snapshot;
+        let mut game = TextBuffer::new();
+        let _ = match snapshot.game {
+            Some(GameId::Tetris) => write!(game, "Tetris score {}", snapshot.score),
+            Some(id) => write!(game, "{}", id.name()),
+            None => write!(game, "In the launcher"),
+        };
+        line(game.as_str());
+
+        // Wrapped to the width of the screen
+        for part in self.message().lines() {
+            let mut start = 0;
+            while start < part.len() {
+                let end = (part[start..].char_indices())
+                    .nth(LINE_CHARS)
+                    .map_or(part.len(), |(index, _)| start + index);
+                line(part[start..end].trim_end());
+                start = end;
+            }
+        }
+
+        let mut uptime = TextBuffer::new();
+        let seconds = snapshot.uptime_ms / 1000;
+        let _ = write!(uptime, "Up {}m {}s", seconds / 60, seconds % 60);
+        line(uptime.as_str());
+        let mut heap = TextBuffer::new();
+        let _ = write!(heap, "Heap {} free", snapshot.heap_free);
+        line(heap.as_str());
+    }
+}
+
+/// Draw the report under a heading, as much of it as fits on the canvas.
+pub fn draw_report(canvas: &mut dyn Canvas, report: &CrashReport) {
+    canvas.text("Previous crash", Point::new(0, 0));
+    let mut y = 12;
+    report.lines(|line| {
+        canvas.text(line, Point::new(0, y));
+        y += 10;
+    });
+}
+
+/// Somewhere the report of the last crash is sent for a developer to read.
+#[cfg(feature = "wifi")]
+pub trait CrashReporter {
+    /// Called once a frame while there is a report.
+    fn poll(&mut self, report: &CrashReport);
+}
+
+/// Serves the report of the last crash as text to anyone who connects.
+#[cfg(feature = "wifi")]
+pub struct CrashServer<C: TcpConnection> {
+    connection: C,
+}
+
+#[cfg(feature = "wifi")]
+impl<C: TcpConnection> CrashServer<C> {
+    pub fn new(connection: C) -> Self {
+        CrashServer { connection }
+    }
+}
+
+#[cfg(feature = "wifi")]
+impl<C: TcpConnection> CrashReporter for CrashServer<C> {
+    /// Send the report to a client that has connected, then close the connection so the next
+    /// one can.
+    fn poll(&mut self, report: &CrashReport) {
+        if !self.connection.is_connected() {
+            return;
+        }
+        // The client is dropped whether or not it got the whole report
+        report.lines(|line| {
+            let _ = self.connection.write(line.as_bytes());
+            let _ = self.connection.write(b"\n");
+        });
+        self.connection.close();
+    }
+}
+
+/// The report left by the last crash, if the last boot ended in one. It is erased as it is read
+/// so that it is only reported once, which must happen before core1 is started.
+pub fn take_report() -> Option<CrashReport> {
+    let report = CrashReport::from_page(read_page(CRASH_OFFSET))?;
+    let rom = RomFlash::lookup();
+    free(|_| unsafe { write_sector(&rom, CRASH_OFFSET, None) });
+    Some(report)
+}
+
+/// Write report to flash and reset, so that it is shown on the next boot.
+fn save(report: &CrashReport) -> ! {
+    cortex_m::interrupt::disable();
+    let rom = RomFlash::lookup();
+    unsafe {
+        // The other core may be running from flash, so it is stopped before the flash is taken
+        // from it
+        let psm = &*pac::PSM::ptr();
+        match (*pac::SIO::ptr()).cpuid.read().bits() {
+            0 => psm.frce_off.modify(|_, w| w.proc1().set_bit()),
+            _ => psm.frce_off.modify(|_, w| w.proc0().set_bit()),
+        }
+        write_sector(&rom, CRASH_OFFSET, Some(&report.to_page()));
+    }
+    cortex_m::peripheral::SCB::sys_reset()
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    defmt::error!("{}", defmt::Display2Format(info));
+    let mut report = CrashReport::new(CrashKind::Panic, None);
+    let _ = write!(report.message, "{}", info);
+    save(&report)
+}
+
+#[exception]
+unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+    let mut report = CrashReport::new(CrashKind::HardFault, Some(frame.pc()));
+    let _ = write!(report.message, "lr {:08X}", frame.lr());
+    save(&report)
+}
@@ -0,0 +1,159 @@
+//! The service screen for checking a newly assembled unit: every button shown as it is held,
+//! every pixel of each display lit in turn, and what the firmware found at boot. Hidden from the
+//! launcher until its code is entered there.
+
+use crate::game::{Canvas, Displays};
+use crate::i2c::I2cDevices;
+use crate::input::{Button, ButtonState, Chord, Input};
+use crate::text::TextBuffer;
+#[cfg(feature = "wifi")]
+use crate::wifi::WifiStatus;
+use core::fmt::Write;
+use embedded_graphics::prelude::Point;
+
+/// Frames each pixel pattern is shown for, a second of the main loop.
+const PATTERN_FRAMES: u32 = 10;
+/// Addresses listed on the screen, more than this is almost certainly a wiring fault.
+const SHOWN_DEVICES: usize = 5;
+
+/// What the firmware knows about the unit, for the system page.
+pub struct Report {
+    /// Devices on I2C0, where the side display lives.
+    pub i2c0: I2cDevices,
+    /// Bytes left on the heap and between the stack pointer and the end of static memory.
+    pub heap_free: usize,
+    pub stack_free: usize,
+    #[cfg(feature = "wifi")]
+    pub wifi: WifiStatus,
+}
+
+/// Bytes left for the stack to grow into from where it is now.
+pub fn stack_headroom() -> usize {
+    extern "C" {
+        // The end of static memory, placed by the cortex-m-rt linker script
+        static __sheap: u8;
+    }
+    let bottom = unsafe { core::ptr::addr_of!(__sheap) as usize };
+    (cortex_m::register::msp::read() as usize).saturating_sub(bottom)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Page {
+    Buttons,
+    Pixels,
+    System,
+}
+
+impl Page {
+    const ALL: [Page; 3] = [Page::Buttons, Page::Pixels, Page::System];
+}
+
+/// Up and Down together move to the next page and A and B together leave, so every button can
+/// be tested on its own.
+pub struct Diagnostics {
+    page: usize,
+    frames: u32,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Diagnostics { page: 0, frames: 0 }
+    }
+
+    /// Feed the input for this frame, returning true once the screen is left.
+    pub fn update(&mut self, input: &Input) -> bool {
+        self.frames = self.frames.wrapping_add(1);
+        match input.chord {
+            Some(Chord::AB) => return true,
+            Some(Chord::UpDown) => {
+                self.page = (self.page + 1) % Page::ALL.len();
+                self.frames = 0;
+            }
+            None => {}
+        }
+        false
+    }
+
+    /// Draw the current page. held is every button read this frame, chords included.
+    pub fn draw(&self, displays: &mut Displays, held: ButtonState, report: &Report) {
+        match Page::ALL[self.page] {
+            Page::Buttons => draw_buttons(&mut *displays.main, held),
+            Page::Pixels => {
+                let pattern = self.frames / PATTERN_FRAMES;
+                draw_pattern(&mut *displays.main, pattern);
+                if let Some(ref mut side) = displays.side {
+                    draw_pattern(&mut **side, pattern);
+                }
+            }
+            Page::System => draw_report(&mut *displays.main, displays.side.is_some(), report),
+        }
+    }
+}
+
+/// Each button where it sits on the handheld, boxed while it is held.
+fn draw_buttons(canvas: &mut dyn Canvas, held: ButtonState) {
+    canvas.text("Buttons", Point::new(0, 0));
+    for (button, name, (x, y)) in [
+        (Button::Up, "U", (20, 16)),
+        (Button::Left, "L", (8, 28)),
+        (Button::Right, "R", (32, 28)),
+        (Button::Down, "D", (20, 40)),
+        (Button::B, "B", (76, 34)),
+        (Button::A, "A", (96, 24)),
+    ] {
+        canvas.text(name, Point::new(x as i32, y as i32));
+        if held.held(button) {
+            canvas.draw_rect((x - 3, y - 2), (x + 8, y + 11));
+        }
+    }
+}
+
+/// Every pixel lit, then a checkerboard and its inverse, so that a dead pixel shows as a gap
+/// and a stuck one as a dot.
+fn draw_pattern(canvas: &mut dyn Canvas, pattern: u32) {
+    let (width, height) = canvas.size();
+    for x in 0..width {
+        for y in 0..height {
+            let on = match pattern % 3 {
+                0 => true,
+                1 => (x + y) % 2 == 0,
+                _ => (x + y) % 2 == 1,
+            };
+            canvas.set_pixel(x, y, on);
+        }
+    }
+}
+
+fn draw_report(canvas: &mut dyn Canvas, side_display: bool, report: &Report) {
+    let mut i2c = TextBuffer::new();
+    let _ = write!(i2c, "I2C0");
+    for address in report.i2c0.iter().take(SHOWN_DEVICES) {
+        let _ = write!(i2c, " {:02X}", address);
+    }
+    let mut heap = TextBuffer::new();
+    let _ = write!(heap, "Heap {} free", report.heap_free);
+    let mut stack = TextBuffer::new();
+    let _ = write!(stack, "Stack {} free", report.stack_free);
+    let mut wifi = TextBuffer::new();
+    #[cfg(feature = "wifi")]
+    let _ = match report.wifi {
+        WifiStatus::Connecting => write!(wifi, "Wi-Fi connecting"),
+        WifiStatus::Connected { rssi } => write!(wifi, "Wi-Fi {} dBm", rssi),
+        WifiStatus::Error => write!(wifi, "Wi-Fi error"),
+    };
+    #[cfg(not(feature = "wifi"))]
+    let _ = write!(wifi, "No Wi-Fi in build");
+
+    canvas.text(i2c.as_str(), Point::new(0, 0));
+    canvas.text(
+        if side_display {
+            "Side display ok"
+        } else {
+            "No side display"
+        },
+        Point::new(0, 12),
+    );
+    canvas.text(heap.as_str(), Point::new(0, 24));
+    canvas.text(stack.as_str(), Point::new(0, 36));
+    canvas.text(wifi.as_str(), Point::new(0, 48));
+}
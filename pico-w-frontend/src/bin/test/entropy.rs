@@ -0,0 +1,35 @@
+use rp2040_hal::pac::ROSC;
+use tetris_core::tetris::EntropySource;
+
+/// Harvests entropy from the jitter of the ring oscillator, which keeps running after the
+/// system clock is switched to the crystal.
+pub struct RoscEntropy {
+    rosc: ROSC,
+}
+
+impl RoscEntropy {
+    pub fn new(rosc: ROSC) -> Self {
+        RoscEntropy { rosc }
+    }
+
+    fn random_bit(&self) -> bool {
+        self.rosc.randombit.read().randombit().bit_is_set()
+    }
+
+    /// The raw ring oscillator bit is biased, so sample pairs of bits and keep the first of
+    /// each differing pair (von Neumann debiasing).
+    fn unbiased_bit(&self) -> bool {
+        loop {
+            let (first, second) = (self.random_bit(), self.random_bit());
+            if first != second {
+                return first;
+            }
+        }
+    }
+}
+
+impl EntropySource for RoscEntropy {
+    fn next_seed(&mut self) -> u64 {
+        (0..64).fold(0, |seed, _| (seed << 1) | self.unbiased_bit() as u64)
+    }
+}
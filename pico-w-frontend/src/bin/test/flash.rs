@@ -0,0 +1,78 @@
+//! Writing to the flash the firmware runs from, through the boot ROM, for the sectors kept back
+//! from the firmware in memory.x.
+
+/// Where the flash is mapped for execute in place.
+pub const XIP_BASE: u32 = 0x1000_0000;
+pub const FLASH_SIZE: u32 = 2048 * 1024;
+/// The smallest area of flash that can be erased.
+pub const SECTOR_SIZE: u32 = 4096;
+/// The smallest area of flash that can be programmed.
+pub const PAGE_SIZE: usize = 256;
+/// The erase the boot ROM is told to use where it can, a 64K block.
+const BLOCK_SIZE: u32 = 1 << 16;
+const BLOCK_ERASE_COMMAND: u8 = 0xd8;
+
+/// The first page of the sector at offset into the flash, as it reads through execute in place.
+pub fn read_page(offset: u32) -> &'static [u8; PAGE_SIZE] {
+    unsafe { &*((XIP_BASE + offset) as *const [u8; PAGE_SIZE]) }
+}
+
+/// The boot ROM's flash routines. They are looked up before the flash leaves execute in place,
+/// since the lookup runs from flash.
+pub struct RomFlash {
+    connect_internal_flash: unsafe extern "C" fn(),
+    flash_exit_xip: unsafe extern "C" fn(),
+    flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+    flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+    flash_flush_cache: unsafe extern "C" fn(),
+    /// A copy of the second stage bootloader, run to put the flash back into fast execute in
+    /// place. The ROM's own routine for it leaves the flash much slower.
+    boot2: [u32; 64],
+}
+
+impl RomFlash {
+    pub fn lookup() -> Self {
+        fn function<T>(code: &[u8; 2]) -> T {
+            unsafe {
+                // Pointers to the function table and the lookup function are kept at fixed
+                // addresses at the start of the ROM
+                let table = *(0x14 as *const u16) as *const u16;
+                let lookup: unsafe extern "C" fn(*const u16, u32) -> usize =
+                    core::mem::transmute(*(0x18 as *const u16) as usize);
+                let address = lookup(table, u16::from_le_bytes(*code) as u32);
+                core::mem::transmute_copy(&address)
+            }
+        }
+        RomFlash {
+            connect_internal_flash: function(b"IF"),
+            flash_exit_xip: function(b"EX"),
+            flash_range_erase: function(b"RE"),
+            flash_range_program: function(b"RP"),
+            flash_flush_cache: function(b"FC"),
+            boot2: unsafe { *(XIP_BASE as *const [u32; 64]) },
+        }
+    }
+}
+
+/// Erase the sector at offset into the flash and program page into its start if given. Run from
+/// RAM as the flash cannot be read while it is written, with interrupts off and the other core
+/// stopped or running from RAM for the same reason.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+pub unsafe fn write_sector(rom: &RomFlash, offset: u32, page: Option<&[u8; PAGE_SIZE]>) {
+    (rom.connect_internal_flash)();
+    (rom.flash_exit_xip)();
+    (rom.flash_range_erase)(
+        offset,
+        SECTOR_SIZE as usize,
+        BLOCK_SIZE,
+        BLOCK_ERASE_COMMAND,
+    );
+    if let Some(page) = page {
+        (rom.flash_range_program)(offset, page.as_ptr(), PAGE_SIZE);
+    }
+    (rom.flash_flush_cache)();
+    // Thumb code, so the lowest bit of the address is set
+    let enter_xip: unsafe extern "C" fn() = core::mem::transmute(rom.boot2.as_ptr() as usize + 1);
+    enter_xip();
+}
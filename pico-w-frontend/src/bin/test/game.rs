@@ -0,0 +1,68 @@
+use crate::audio::Audio;
+use crate::clock::WallClock;
+use crate::input::Input;
+#[cfg(feature = "wifi")]
+use crate::netplay::Netplay;
+use crate::settings::Settings;
+#[cfg(feature = "wifi")]
+use crate::spectate::Spectate;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::Point;
+use tetris_core::high_score::HighScores;
+
+/// The drawing surface shared by every game, implemented by each display backend.
+pub trait Canvas {
+    fn set_pixel(&mut self, x: u32, y: u32, on: bool);
+    fn draw_rect(&mut self, min: (u32, u32), max: (u32, u32));
+    fn text(&mut self, text: &str, point: Point);
+    /// Set the color that pixels are drawn in when turned on. Monochrome displays ignore it.
+    fn set_color(&mut self, _color: Rgb888) {}
+    /// The width and height of the canvas in pixels.
+    fn size(&self) -> (u32, u32);
+}
+
+/// A physical display that games draw into through its canvas once per frame.
+pub trait Display: Canvas {
+    fn clear(&mut self);
+    fn flush(&mut self);
+}
+
+/// The screens available to a game. The side display is an optional second OLED for secondary
+/// information such as the next piece and score, when it is missing games fit that information
+/// onto the main display.
+pub struct Displays<'a> {
+    pub main: &'a mut dyn Canvas,
+    pub side: Option<&'a mut dyn Canvas>,
+}
+
+/// The subsystems of the handheld that a running game may use.
+pub struct Console<'a> {
+    pub settings: &'a mut Settings,
+    /// The buzzer, or the I2S DAC on builds that have one.
+    pub audio: &'a mut dyn Audio,
+    /// Used to timestamp scores, real time once synchronised over Wi-Fi.
+    pub clock: &'a WallClock,
+    /// The best Tetris scores, saved to flash with the settings on the way back to the launcher.
+    pub high_scores: &'a mut HighScores,
+    /// Where to stream the game to while someone is watching over Wi-Fi.
+    #[cfg(feature = "wifi")]
+    pub spectate: Option<&'a mut dyn Spectate>,
+    /// Where a player who joins over Wi-Fi for a versus match is served.
+    #[cfg(feature = "wifi")]
+    pub netplay: Option<&'a mut dyn Netplay>,
+}
+
+/// A game that can be started from the launcher. Games are polled once per frame of the main
+/// loop with the current input, then drawn into a freshly cleared canvas.
+pub trait Game {
+    /// Reset the game to its initial state, called each time it is selected in the launcher
+    /// with the settings to start it under.
+    fn start(&mut self, settings: &Settings);
+
+    fn update(&mut self, input: &Input, console: &mut Console);
+
+    fn draw(&self, displays: &mut Displays);
+
+    /// True once the player has asked to return to the launcher.
+    fn exited(&self) -> bool;
+}
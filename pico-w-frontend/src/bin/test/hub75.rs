@@ -0,0 +1,270 @@
+use crate::game::{Canvas, Display};
+use core::convert::Infallible;
+use core::sync::atomic::{compiler_fence, Ordering};
+use embedded_graphics::{
+    mono_font::{ascii::FONT_4X6, MonoTextStyle},
+    pixelcolor::{BinaryColor, Rgb888},
+    prelude::*,
+    text::{Baseline, Text},
+};
+use rp2040_hal::multicore::{Multicore, Stack};
+use rp2040_hal::pac;
+use rp2040_hal::pio::{
+    PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, Tx, PIO, SM0,
+};
+use rp2040_hal::sio::{Sio, SioFifo};
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+/// The GPIO of R1, followed by G1 B1 R2 G2 B2 on the next five pins.
+pub const RGB_BASE_PIN: u8 = 6;
+pub const CLOCK_PIN: u8 = 12;
+pub const LATCH_PIN: u8 = 13;
+/// Active low, the panel is dark while this is high.
+pub const OUTPUT_ENABLE_PIN: u8 = 14;
+/// Row address lines A to D.
+pub const ADDRESS_PINS: [u8; 4] = [2, 3, 26, 27];
+
+/// The panel lights one row from each half at a time.
+const SCAN_ROWS: usize = HEIGHT / 2;
+/// Bits per color channel, shown with binary coded modulation.
+const PLANES: usize = 2;
+/// Each pixel of a row pair is packed into a byte, four to a word.
+const WORDS_PER_ROW: usize = WIDTH / 4;
+const FRAME_WORDS: usize = PLANES * SCAN_ROWS * WORDS_PER_ROW;
+
+/// How long the least significant plane of a row is lit for, each further plane is lit twice
+/// as long as the one before.
+const PLANE_CYCLES: u32 = 1000;
+/// Shifts pixels at 125MHz / 4 / 3 instructions per pixel, around 10MHz which common panels
+/// accept.
+const CLOCK_DIVISOR: f32 = 4.0;
+
+/// Two frames, one being scanned out by core1 while core0 draws the next into the other.
+static mut FRAMES: [[u32; FRAME_WORDS]; 2] = [[0; FRAME_WORDS]; 2];
+static mut CORE1_STACK: Stack<1024> = Stack::new();
+
+/// A 64x32 HUB75 RGB panel. Pixels are clocked out by PIO0 and the rows are scanned by core1,
+/// leaving core0 free to run the game and draw into the back buffer.
+pub struct Hub75 {
+    fifo: SioFifo,
+    back: usize,
+    /// The pen color as a level per channel, from 0 to (1 << PLANES) - 1.
+    color: [u8; 3],
+}
+
+impl Hub75 {
+    /// Start scanning the panel on core1. The color and clock pins must already be given to
+    /// PIO0 and the latch, output enable and address pins configured as outputs.
+    pub fn new(
+        pio: pac::PIO0,
+        psm: &mut pac::PSM,
+        ppb: &mut pac::PPB,
+        mut fifo: SioFifo,
+        resets: &mut pac::RESETS,
+    ) -> Self {
+        let program = pio_proc::pio_asm!(
+            ".side_set 1",
+            ".wrap_target",
+            "    out x, 32      side 0", // the number of pixels in the row, minus one
+            "pixel:",
+            "    out pins, 6    side 0",
+            "    out null, 2    side 1", // the panel samples the pixel on the rising edge
+            "    jmp x-- pixel  side 0",
+            "    irq wait 0     side 0", // hold until the CPU has latched the row
+            ".wrap",
+        );
+
+        let (mut pio, sm0, _, _, _) = pio.split(resets);
+        let installed = pio.install(&program.program).unwrap();
+        let (mut sm, _, tx) = PIOBuilder::from_program(installed)
+            .out_pins(RGB_BASE_PIN, 6)
+            .side_set_pin_base(CLOCK_PIN)
+            .out_shift_direction(ShiftDirection::Right)
+            .autopull(true)
+            .pull_threshold(32)
+            .clock_divisor(CLOCK_DIVISOR)
+            .build(sm0);
+        sm.set_pindirs(
+            (RGB_BASE_PIN..RGB_BASE_PIN + 6)
+                .chain([CLOCK_PIN])
+                .map(|pin| (pin, PinDir::Output)),
+        );
+        let sm = sm.start();
+
+        {
+            let mut multicore = Multicore::new(psm, ppb, &mut fifo);
+            let cores = multicore.cores();
+            cores[1]
+                .spawn(unsafe { &mut CORE1_STACK.mem }, move || scan(pio, sm, tx))
+                .unwrap();
+        }
+
+        Hub75 {
+            fifo,
+            back: 1,
+            color: [(1 << PLANES) - 1; 3],
+        }
+    }
+
+    fn back_buffer(&mut self) -> &mut [u32; FRAME_WORDS] {
+        // Core1 only reads the front buffer and flush waits for it to let go of the old one
+        unsafe { &mut FRAMES[self.back] }
+    }
+}
+
+fn push(tx: &mut Tx<(pac::PIO0, SM0)>, word: u32) {
+    while !tx.write(word) {}
+}
+
+/// Row scanning loop run on core1. The SIO GPIO registers are written directly so that the
+/// latch, output enable and address lines change together.
+fn scan(
+    pio: PIO<pac::PIO0>,
+    _sm: StateMachine<(pac::PIO0, SM0), Running>,
+    mut tx: Tx<(pac::PIO0, SM0)>,
+) -> ! {
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut fifo = Sio::new(pac.SIO).fifo;
+    let gpio = unsafe { &*pac::SIO::ptr() };
+
+    let address_mask = ADDRESS_PINS.iter().fold(0, |mask, pin| mask | 1 << pin);
+    let mut front = 0;
+
+    loop {
+        // Swap to a newly drawn frame only between frames, then tell core0 that the old one
+        // is free to draw into.
+        if let Some(index) = fifo.read() {
+            front = index as usize;
+            fifo.write(index);
+        }
+        let frame = unsafe { &FRAMES[front] };
+
+        for row in 0..SCAN_ROWS {
+            let address = ADDRESS_PINS
+                .iter()
+                .enumerate()
+                .fold(0, |bits, (bit, pin)| bits | ((row as u32 >> bit) & 1) << pin);
+
+            for plane in 0..PLANES {
+                let start = (plane * SCAN_ROWS + row) * WORDS_PER_ROW;
+                push(&mut tx, (WIDTH - 1) as u32);
+                for word in &frame[start..start + WORDS_PER_ROW] {
+                    push(&mut tx, *word);
+                }
+
+                // The state machine raises IRQ 0 once the last pixel is clocked in
+                while pio.get_irq_raw() & 1 == 0 {}
+
+                gpio.gpio_out_set
+                    .write(|w| unsafe { w.bits(1 << OUTPUT_ENABLE_PIN | 1 << LATCH_PIN) });
+                gpio.gpio_out_clr.write(|w| unsafe { w.bits(address_mask) });
+                gpio.gpio_out_set.write(|w| unsafe { w.bits(address) });
+                gpio.gpio_out_clr.write(|w| unsafe { w.bits(1 << LATCH_PIN) });
+                pio.clear_irq(1);
+
+                gpio.gpio_out_clr.write(|w| unsafe { w.bits(1 << OUTPUT_ENABLE_PIN) });
+                cortex_m::asm::delay(PLANE_CYCLES << plane);
+                gpio.gpio_out_set.write(|w| unsafe { w.bits(1 << OUTPUT_ENABLE_PIN) });
+            }
+        }
+    }
+}
+
+impl Canvas for Hub75 {
+    fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        let (x, y) = (x as usize, y as usize);
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+
+        // R G B of the top half in the low three bits of the pixel, the bottom half above
+        let shift = (x % 4) * 8 + if y >= SCAN_ROWS { 3 } else { 0 };
+        let color = self.color;
+        let frame = self.back_buffer();
+
+        for plane in 0..PLANES {
+            let bits = if on {
+                color.iter().enumerate().fold(0, |bits, (channel, level)| {
+                    bits | (((level >> plane) & 1) as u32) << channel
+                })
+            } else {
+                0
+            };
+            let word = &mut frame[(plane * SCAN_ROWS + y % SCAN_ROWS) * WORDS_PER_ROW + x / 4];
+            *word = (*word & !(0b111 << shift)) | (bits << shift);
+        }
+    }
+
+    fn draw_rect(&mut self, (min_x, min_y): (u32, u32), (max_x, max_y): (u32, u32)) {
+        for x in min_x..=max_x {
+            self.set_pixel(x, min_y, true);
+            self.set_pixel(x, max_y, true);
+        }
+        for y in min_y..=max_y {
+            self.set_pixel(min_x, y, true);
+            self.set_pixel(max_x, y, true);
+        }
+    }
+
+    fn text(&mut self, text: &str, point: Point) {
+        let _ = Text::with_baseline(
+            text,
+            point,
+            MonoTextStyle::new(&FONT_4X6, BinaryColor::On),
+            Baseline::Top,
+        )
+        .draw(self);
+    }
+
+    fn set_color(&mut self, color: Rgb888) {
+        // Keep the most significant bits of each channel, one per plane
+        self.color = [color.r(), color.g(), color.b()].map(|level| level >> (8 - PLANES));
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl Display for Hub75 {
+    fn clear(&mut self) {
+        *self.back_buffer() = [0; FRAME_WORDS];
+        self.set_color(Rgb888::WHITE);
+    }
+
+    /// Hand the finished frame to core1 and wait until it has stopped scanning the previous
+    /// one, which becomes the next back buffer.
+    fn flush(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+        self.fifo.write_blocking(self.back as u32);
+        self.fifo.read_blocking();
+        compiler_fence(Ordering::SeqCst);
+        self.back ^= 1;
+    }
+}
+
+/// Lets embedded-graphics render text into the frame in the current pen color.
+impl DrawTarget for Hub75 {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<BinaryColor>>>(
+        &mut self,
+        pixels: I,
+    ) -> Result<(), Self::Error> {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 {
+                self.set_pixel(point.x as u32, point.y as u32, color.is_on());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl OriginDimensions for Hub75 {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
@@ -0,0 +1,42 @@
+//! Finding devices on an I2C bus, so that displays are found at whichever address they are
+//! strapped to and the diagnostics screen can list what is wired up.
+
+use embedded_hal::blocking::i2c::Read;
+
+/// The 7-bit addresses a scan asks for, the rest are reserved.
+const ADDRESSES: core::ops::RangeInclusive<u8> = 0x08..=0x77;
+/// The addresses an SSD1306 can be strapped to, the usual one first.
+const SSD1306_ADDRESSES: [u8; 2] = [0x3C, 0x3D];
+
+/// The I2C devices that answered a scan of a bus, bit n set for address n.
+#[derive(Clone, Copy, Default)]
+pub struct I2cDevices(u128);
+
+impl I2cDevices {
+    /// Read a byte from every address, each device that acknowledges is counted as present.
+    /// Must be done before the bus is handed to a display driver.
+    pub fn scan<I: Read>(i2c: &mut I) -> Self {
+        let mut devices = I2cDevices::default();
+        for address in ADDRESSES {
+            if i2c.read(address, &mut [0]).is_ok() {
+                devices.0 |= 1 << address;
+            }
+        }
+        devices
+    }
+
+    pub fn contains(&self, address: u8) -> bool {
+        self.0 & (1 << address) != 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        ADDRESSES.filter(|&address| self.contains(address))
+    }
+
+    /// Where an SSD1306 display answered, if one did.
+    pub fn display_address(&self) -> Option<u8> {
+        SSD1306_ADDRESSES
+            .into_iter()
+            .find(|&address| self.contains(address))
+    }
+}
@@ -0,0 +1,178 @@
+use crate::audio::{Audio, SoundEffect};
+use core::cell::RefCell;
+use cortex_m::interrupt::{free, Mutex};
+use rp2040_hal::pac::{self, interrupt};
+use rp2040_hal::pio::{PIOBuilder, PIOExt, PinDir, ShiftDirection, Tx, SM0};
+
+pub const SAMPLE_RATE: u32 = 22_050;
+
+/// The GPIO of the bit clock, followed by the word select (LRCLK) on the next pin.
+pub const BCLK_PIN: u8 = 26;
+pub const DATA_PIN: u8 = 28;
+
+/// Effects are signed 8 bit mono PCM at half the output rate.
+const EFFECT_RATE_SHIFT: u32 = 1;
+const ROTATE: &[u8] = include_bytes!("../../../sounds/rotate.raw");
+const LINE_CLEAR: &[u8] = include_bytes!("../../../sounds/line_clear.raw");
+const GAME_OVER: &[u8] = include_bytes!("../../../sounds/game_over.raw");
+
+/// Notes start at full volume and decay towards the sustain level, by 1/2^DECAY_SHIFT of the
+/// remaining level each sample, so repeated notes are heard separately.
+const ENVELOPE_MAX: i32 = 1 << 12;
+const SUSTAIN: i32 = ENVELOPE_MAX / 4;
+const DECAY_SHIFT: u32 = 11;
+
+/// Generates the output samples, run from the PIO interrupt whenever the FIFO has room.
+struct Synth {
+    tx: Tx<(pac::PIO1, SM0)>,
+    /// Phase of the note as a fraction of a cycle and how far it advances each sample.
+    phase: u32,
+    phase_step: u32,
+    envelope: i32,
+    effect: &'static [u8],
+    effect_position: usize,
+}
+
+impl Synth {
+    /// Music is a triangle wave, which is much softer than the buzzer's square wave, with the
+    /// effect mixed on top.
+    fn next_sample(&mut self) -> i16 {
+        let mut sample = 0;
+
+        if self.phase_step != 0 {
+            self.phase = self.phase.wrapping_add(self.phase_step);
+            let position = (self.phase >> 16) as i32;
+            let triangle = if position < 0x8000 {
+                position * 2 - 0x8000
+            } else {
+                (0xFFFF - position) * 2 - 0x8000
+            };
+            if self.envelope > SUSTAIN {
+                self.envelope -= (self.envelope - SUSTAIN) >> DECAY_SHIFT;
+            }
+            sample += (triangle * self.envelope / ENVELOPE_MAX) / 4;
+        }
+
+        if let Some(&byte) = self.effect.get(self.effect_position >> EFFECT_RATE_SHIFT) {
+            self.effect_position += 1;
+            sample += (byte as i8 as i32) << 7;
+        }
+
+        sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+    }
+
+    fn fill(&mut self) {
+        while !self.tx.is_full() {
+            let sample = self.next_sample() as u16 as u32;
+            self.tx.write(sample << 16 | sample);
+        }
+    }
+}
+
+static SYNTH: Mutex<RefCell<Option<Synth>>> = Mutex::new(RefCell::new(None));
+
+/// A PCM5102 or MAX98357 DAC fed 16 bit stereo over I2S from PIO1. The bit clock and word
+/// select pins must already be given to PIO1.
+pub struct I2sAudio;
+
+impl I2sAudio {
+    pub fn new(pio: pac::PIO1, resets: &mut pac::RESETS, system_clock_hz: u32) -> Self {
+        // Two instructions per bit and 32 bits per stereo sample, with the word select
+        // switching one bit early as I2S expects.
+        let program = pio_proc::pio_asm!(
+            ".side_set 2", // word select, bit clock
+            ".wrap_target",
+            "    set x, 14         side 0b01",
+            "left:",
+            "    out pins, 1       side 0b00",
+            "    jmp x-- left      side 0b01",
+            "    out pins, 1       side 0b10",
+            "    set x, 14         side 0b11",
+            "right:",
+            "    out pins, 1       side 0b10",
+            "    jmp x-- right     side 0b11",
+            "    out pins, 1       side 0b00",
+            ".wrap",
+        );
+
+        let (mut pio, sm0, _, _, _) = pio.split(resets);
+        let installed = pio.install(&program.program).unwrap();
+        let (mut sm, _, tx) = PIOBuilder::from_program(installed)
+            .out_pins(DATA_PIN, 1)
+            .side_set_pin_base(BCLK_PIN)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(32)
+            .clock_divisor(system_clock_hz as f32 / (SAMPLE_RATE * 64) as f32)
+            .build(sm0);
+        sm.set_pindirs([
+            (DATA_PIN, PinDir::Output),
+            (BCLK_PIN, PinDir::Output),
+            (BCLK_PIN + 1, PinDir::Output),
+        ]);
+
+        free(|cs| {
+            SYNTH.borrow(cs).replace(Some(Synth {
+                tx,
+                phase: 0,
+                phase_step: 0,
+                envelope: 0,
+                effect: &[],
+                effect_position: 0,
+            }))
+        });
+
+        // Refill the FIFO from the interrupt whenever it has room
+        let pio1 = unsafe { &*pac::PIO1::ptr() };
+        pio1.sm_irq[0].irq_inte.modify(|_, w| w.sm0_txnfull().set_bit());
+        unsafe { pac::NVIC::unmask(pac::Interrupt::PIO1_IRQ_0) };
+        sm.start();
+
+        I2sAudio
+    }
+
+    fn with_synth(f: impl FnOnce(&mut Synth)) {
+        free(|cs| {
+            if let Some(ref mut synth) = *SYNTH.borrow(cs).borrow_mut() {
+                f(synth);
+            }
+        });
+    }
+}
+
+impl Audio for I2sAudio {
+    fn tone(&mut self, freq: u32) {
+        Self::with_synth(|synth| {
+            synth.phase_step = (((freq as u64) << 32) / SAMPLE_RATE as u64) as u32;
+            synth.envelope = ENVELOPE_MAX;
+        });
+    }
+
+    fn silence(&mut self) {
+        Self::with_synth(|synth| {
+            synth.phase_step = 0;
+            synth.effect = &[];
+        });
+    }
+
+    fn effect(&mut self, effect: SoundEffect) {
+        let samples = match effect {
+            SoundEffect::Rotate => ROTATE,
+            SoundEffect::LineClear => LINE_CLEAR,
+            SoundEffect::GameOver => GAME_OVER,
+        };
+        Self::with_synth(|synth| {
+            synth.effect = samples;
+            synth.effect_position = 0;
+        });
+    }
+}
+
+#[interrupt]
+fn PIO1_IRQ_0() {
+    free(|cs| {
+        if let Some(ref mut synth) = *SYNTH.borrow(cs).borrow_mut() {
+            synth.fill();
+        }
+    });
+}
@@ -0,0 +1,252 @@
+/// A physical button on the handheld.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+}
+
+impl Button {
+    pub const ALL: [Button; 6] = [
+        Button::Up,
+        Button::Down,
+        Button::Left,
+        Button::Right,
+        Button::A,
+        Button::B,
+    ];
+
+    fn mask(self) -> u8 {
+        1 << (self as u8)
+    }
+}
+
+/// A snapshot of which buttons are held during a single frame.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct ButtonState(u8);
+
+impl ButtonState {
+    pub fn set(&mut self, button: Button, held: bool) {
+        if held {
+            self.0 |= button.mask();
+        } else {
+            self.0 &= !button.mask();
+        }
+    }
+
+    pub fn held(&self, button: Button) -> bool {
+        self.0 & button.mask() != 0
+    }
+
+    /// A state from a bitmask with bit n set for Button::ALL[n] held, as sent by the remote
+    /// control page.
+    pub fn from_bits(bits: u8) -> Self {
+        ButtonState(bits & ((1 << Button::ALL.len()) - 1))
+    }
+
+    /// Buttons held in either state.
+    pub fn union(&self, other: &ButtonState) -> ButtonState {
+        ButtonState(self.0 | other.0)
+    }
+
+    /// Buttons held in both states.
+    pub fn intersection(&self, other: &ButtonState) -> ButtonState {
+        ButtonState(self.0 & other.0)
+    }
+
+    /// Buttons held in this state but not in other.
+    pub fn without(&self, other: &ButtonState) -> ButtonState {
+        ButtonState(self.0 & !other.0)
+    }
+
+    /// True if every button held in other is held in this state.
+    pub fn contains(&self, other: &ButtonState) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Buttons that are held in this frame but were not held in the previous one.
+    pub fn pressed_since(&self, previous: &ButtonState) -> ButtonState {
+        ButtonState(self.0 & !previous.0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Button> + '_ {
+        Button::ALL.into_iter().filter(|b| self.held(*b))
+    }
+}
+
+/// Matches a fixed sequence of button presses. Any press that does not continue the sequence
+/// resets progress (restarting from the first step if it matches that instead).
+pub struct SequenceMatcher {
+    sequence: &'static [Button],
+    progress: usize,
+}
+
+impl SequenceMatcher {
+    pub const fn new(sequence: &'static [Button]) -> Self {
+        SequenceMatcher {
+            sequence,
+            progress: 0,
+        }
+    }
+
+    /// Feed a newly pressed button to the matcher, returning true when it completes the sequence.
+    pub fn push(&mut self, button: Button) -> bool {
+        if self.sequence[self.progress] == button {
+            self.progress += 1;
+        } else if self.sequence[0] == button {
+            self.progress = 1;
+        } else {
+            self.progress = 0;
+        }
+
+        if self.progress == self.sequence.len() {
+            self.progress = 0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Up, Up, Down, Down, Left, Right, Left, Right, B, A.
+pub const KONAMI_CODE: &[Button] = &[
+    Button::Up,
+    Button::Up,
+    Button::Down,
+    Button::Down,
+    Button::Left,
+    Button::Right,
+    Button::Left,
+    Button::Right,
+    Button::B,
+    Button::A,
+];
+
+/// Down, Down, Up, Up, B, A, entered in the launcher to show the diagnostics screen.
+pub const SERVICE_CODE: &[Button] = &[
+    Button::Down,
+    Button::Down,
+    Button::Up,
+    Button::Up,
+    Button::B,
+    Button::A,
+];
+
+/// Two buttons pressed together, giving the handheld actions it has no button for.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Chord {
+    /// A and B, to go back to the menu.
+    AB,
+    /// Up and Down, to pause.
+    UpDown,
+}
+
+impl Chord {
+    pub const ALL: [Chord; 2] = [Chord::AB, Chord::UpDown];
+
+    pub fn buttons(self) -> ButtonState {
+        let (first, second) = match self {
+            Chord::AB => (Button::A, Button::B),
+            Chord::UpDown => (Button::Up, Button::Down),
+        };
+        ButtonState(first.mask() | second.mask())
+    }
+}
+
+/// The buttons held during a frame and how they changed since the last one. Buttons of a
+/// chord are left out of everything but the chord until they are released.
+#[derive(Clone, Copy, Default)]
+pub struct Input {
+    pub held: ButtonState,
+    /// Buttons that went down this frame.
+    pub pressed: ButtonState,
+    /// Buttons released this frame before they became a long press.
+    pub taps: ButtonState,
+    /// Buttons that have just been held for the long press duration. Each long press is reported
+    /// once and is not followed by a tap on release.
+    pub long_presses: ButtonState,
+    /// A chord completed this frame by its last button going down.
+    pub chord: Option<Chord>,
+}
+
+/// Tracks button state across frames to derive presses, taps, long presses and chords.
+#[derive(Default)]
+pub struct InputTracker {
+    previous: ButtonState,
+    held_frames: [u8; Button::ALL.len()],
+    /// Buttons of a chord that are still held.
+    chorded: ButtonState,
+}
+
+impl InputTracker {
+    pub fn update(&mut self, held: ButtonState, long_press_frames: u8) -> Input {
+        let chord = Chord::ALL.into_iter().find(|chord| {
+            held.contains(&chord.buttons()) && !self.previous.contains(&chord.buttons())
+        });
+        if let Some(chord) = chord {
+            self.chorded = self.chorded.union(&chord.buttons());
+        }
+
+        let mut input = Input {
+            held: held.without(&self.chorded),
+            pressed: held.pressed_since(&self.previous).without(&self.chorded),
+            chord,
+            ..Input::default()
+        };
+
+        for button in Button::ALL {
+            let frames = &mut self.held_frames[button as usize];
+            if self.chorded.held(button) {
+                // Neither a tap nor a long press once it is part of a chord
+                *frames = 0;
+            } else if held.held(button) {
+                *frames = frames.saturating_add(1);
+                if *frames == long_press_frames {
+                    input.long_presses.set(button, true);
+                }
+            } else {
+                if self.previous.held(button) && *frames < long_press_frames {
+                    input.taps.set(button, true);
+                }
+                *frames = 0;
+            }
+        }
+
+        self.chorded = self.chorded.intersection(&held);
+        self.previous = held;
+        input
+    }
+}
+
+/// Detects a button being pressed twice within a short window of frames.
+#[derive(Default)]
+pub struct DoubleTap {
+    frames_since_press: Option<u8>,
+}
+
+impl DoubleTap {
+    /// Feed whether the button was pressed this frame, returning true on the second press of a
+    /// double tap. A third press starts a new gesture rather than completing another one.
+    pub fn update(&mut self, pressed: bool, window_frames: u8) -> bool {
+        let within_window = self
+            .frames_since_press
+            .map_or(false, |frames| frames <= window_frames);
+
+        if pressed {
+            if within_window {
+                self.frames_since_press = None;
+                return true;
+            }
+            self.frames_since_press = Some(0);
+        } else if within_window {
+            self.frames_since_press = self.frames_since_press.map(|frames| frames + 1);
+        } else {
+            self.frames_since_press = None;
+        }
+
+        false
+    }
+}
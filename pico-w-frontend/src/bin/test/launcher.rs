@@ -0,0 +1,210 @@
+use crate::game::Canvas;
+use crate::input::{Button, Input, SequenceMatcher, KONAMI_CODE, SERVICE_CODE};
+use crate::settings::{Settings, Unlocks};
+use embedded_graphics::prelude::Point;
+
+/// Every game that can be started from the launcher.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GameId {
+    Tetris,
+    Snake,
+}
+
+impl GameId {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameId::Tetris => "Tetris",
+            GameId::Snake => "Snake",
+        }
+    }
+}
+
+/// An entry in the launcher, either a game, a setting or a setup screen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Game(GameId),
+    /// Pairing with another handheld for a match, only shown in builds with Wi-Fi.
+    Versus,
+    /// Changed in place by pressing A, rather than started.
+    Layout,
+    Assist,
+    PieceStats,
+    /// Hidden until the Konami code is entered, like the other cheats.
+    Invisible,
+    Big,
+    TwentyG,
+    #[cfg(feature = "touch")]
+    CalibrateTouch,
+    /// Hidden until the service code is entered.
+    Diagnostics,
+}
+
+impl Selection {
+    #[cfg(not(feature = "touch"))]
+    pub const ALL: &'static [Selection] = &[
+        Selection::Game(GameId::Tetris),
+        Selection::Game(GameId::Snake),
+        Selection::Versus,
+        Selection::Layout,
+        Selection::Assist,
+        Selection::PieceStats,
+        Selection::Invisible,
+        Selection::Big,
+        Selection::TwentyG,
+        Selection::Diagnostics,
+    ];
+    #[cfg(feature = "touch")]
+    pub const ALL: &'static [Selection] = &[
+        Selection::Game(GameId::Tetris),
+        Selection::Game(GameId::Snake),
+        Selection::Versus,
+        Selection::Layout,
+        Selection::Assist,
+        Selection::PieceStats,
+        Selection::Invisible,
+        Selection::Big,
+        Selection::TwentyG,
+        Selection::CalibrateTouch,
+        Selection::Diagnostics,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Selection::Game(id) => id.name(),
+            Selection::Versus => "Versus",
+            Selection::Layout => "Layout",
+            Selection::Assist => "Assist",
+            Selection::PieceStats => "Stats",
+            Selection::Invisible => "Hidden",
+            Selection::Big => "Big",
+            Selection::TwentyG => "20G",
+            #[cfg(feature = "touch")]
+            Selection::CalibrateTouch => "Touch setup",
+            Selection::Diagnostics => "Diagnostics",
+        }
+    }
+
+    /// The unlock that shows the entry, if it is hidden until then.
+    fn unlocked_by(&self) -> Option<u8> {
+        match self {
+            Selection::Invisible => Some(Unlocks::INVISIBLE),
+            Selection::Big => Some(Unlocks::BIG),
+            Selection::TwentyG => Some(Unlocks::TWENTY_G),
+            Selection::Diagnostics => Some(Unlocks::SERVICE),
+            _ => None,
+        }
+    }
+
+    /// Whether this build has what the entry needs.
+    fn is_available(&self) -> bool {
+        match self {
+            Selection::Versus => cfg!(feature = "wifi"),
+            _ => true,
+        }
+    }
+
+    /// The entries shown in the launcher with what has been unlocked.
+    fn shown(settings: &Settings) -> impl Iterator<Item = Selection> + '_ {
+        Selection::ALL.iter().copied().filter(move |selection| {
+            selection.is_available()
+                && selection
+                    .unlocked_by()
+                    .map_or(true, |flag| settings.unlocks.is_unlocked(flag))
+        })
+    }
+
+    /// Whether the entry is a setting turned on, for those that are turned on and off.
+    fn is_on(&self, settings: &Settings) -> Option<bool> {
+        match self {
+            Selection::Assist => Some(settings.assist),
+            Selection::PieceStats => Some(settings.piece_stats),
+            Selection::Invisible => Some(settings.invisible),
+            Selection::Big => Some(settings.big),
+            Selection::TwentyG => Some(settings.twenty_g),
+            _ => None,
+        }
+    }
+}
+
+/// Entries that fit on the display at once, the list scrolling to keep the selected one shown.
+const ROWS: usize = 7;
+
+fn on_off(setting: bool) -> &'static str {
+    if setting {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// The menu shown at boot and after leaving a game. Up and down select an entry and A starts it.
+/// Entering the Konami code here unlocks the hidden options, and the service code the
+/// diagnostics screen.
+pub struct Launcher {
+    selected: usize,
+    konami: SequenceMatcher,
+    service: SequenceMatcher,
+}
+
+impl Launcher {
+    pub fn new() -> Self {
+        Launcher {
+            selected: 0,
+            konami: SequenceMatcher::new(KONAMI_CODE),
+            service: SequenceMatcher::new(SERVICE_CODE),
+        }
+    }
+
+    /// Feed the buttons pressed this frame to the launcher, returning the entry to start if one
+    /// was chosen. Completing the Konami or service code unlocks what they hide rather than
+    /// starting a game.
+    pub fn update(&mut self, input: &Input, settings: &mut Settings) -> Option<Selection> {
+        let mut start = None;
+        for button in input.pressed.iter() {
+            let service = self.service.push(button);
+            if self.konami.push(button) {
+                settings.unlocks.unlock(Unlocks::CHEATS);
+                continue;
+            }
+            if service {
+                settings.unlocks.unlock(Unlocks::SERVICE);
+                continue;
+            }
+
+            let shown = Selection::shown(settings).count();
+            let selection = Selection::shown(settings).nth(self.selected);
+            match button {
+                Button::Up => self.selected = (self.selected + shown - 1) % shown,
+                Button::Down => self.selected = (self.selected + 1) % shown,
+                Button::A => match selection {
+                    Some(Selection::Layout) => settings.layout = settings.layout.next(),
+                    Some(Selection::Assist) => settings.assist = !settings.assist,
+                    Some(Selection::PieceStats) => settings.piece_stats = !settings.piece_stats,
+                    Some(Selection::Invisible) => settings.invisible = !settings.invisible,
+                    Some(Selection::Big) => settings.big = !settings.big,
+                    Some(Selection::TwentyG) => settings.twenty_g = !settings.twenty_g,
+                    selection => start = selection,
+                },
+                _ => {}
+            }
+        }
+        start
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas, settings: &Settings) {
+        let first = (self.selected + 1).saturating_sub(ROWS);
+        let entries = Selection::shown(settings).enumerate().skip(first);
+        for (row, (idx, entry)) in entries.take(ROWS).enumerate() {
+            let y = 8 + (row as i32 * 9);
+            if idx == self.selected {
+                canvas.text(">", Point::new(30, y));
+            }
+            canvas.text(entry.name(), Point::new(40, y));
+            if entry == Selection::Layout {
+                canvas.text(settings.layout.name(), Point::new(82, y));
+            } else if let Some(on) = entry.is_on(settings) {
+                canvas.text(on_off(on), Point::new(82, y));
+            }
+        }
+    }
+}
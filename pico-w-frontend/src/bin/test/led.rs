@@ -0,0 +1,73 @@
+use cortex_m::delay::Delay;
+use embedded_hal::digital::v2::OutputPin;
+use rp2040_hal::gpio::{bank0::Gpio25, Pin, PushPullOutput};
+
+/// The board status LED, which is wired differently on the Pico and the Pico W.
+pub trait StatusLed {
+    fn set(&mut self, on: bool);
+}
+
+/// On the plain Pico the LED hangs off GPIO25.
+impl StatusLed for Pin<Gpio25, PushPullOutput> {
+    fn set(&mut self, on: bool) {
+        if on {
+            self.set_high().unwrap();
+        } else {
+            self.set_low().unwrap();
+        }
+    }
+}
+
+/// How long each flash of a fault code and the gap after it last, and the pause between
+/// repeats of the code.
+const FLASH_MS: u32 = 200;
+const PAUSE_MS: u32 = 1500;
+
+/// Faults that stop the firmware before it has a display to report them on, told apart by how
+/// many times the LED flashes.
+#[derive(Clone, Copy)]
+pub enum Fault {
+    /// No SSD1306 answered at either of its addresses, or the one that did failed to start.
+    NoDisplay = 2,
+}
+
+/// Flash the code of fault over and over, never returning.
+pub fn blink_fault(led: &mut impl StatusLed, fault: Fault, delay: &mut Delay) -> ! {
+    loop {
+        for _ in 0..fault as u8 {
+            led.set(true);
+            delay.delay_ms(FLASH_MS);
+            led.set(false);
+            delay.delay_ms(FLASH_MS);
+        }
+        delay.delay_ms(PAUSE_MS);
+    }
+}
+
+/// Access to the GPIOs of the CYW43 wireless chip, implemented by its driver in wireless.rs.
+#[cfg(feature = "wifi")]
+pub trait WirelessGpio {
+    fn set_gpio(&mut self, gpio: u8, high: bool);
+}
+
+/// On the Pico W the LED is connected to GPIO 0 of the CYW43 rather than the RP2040.
+#[cfg(feature = "wifi")]
+pub struct Cyw43Led<C: WirelessGpio> {
+    control: C,
+}
+
+#[cfg(feature = "wifi")]
+impl<C: WirelessGpio> Cyw43Led<C> {
+    const LED_GPIO: u8 = 0;
+
+    pub fn new(control: C) -> Self {
+        Cyw43Led { control }
+    }
+}
+
+#[cfg(feature = "wifi")]
+impl<C: WirelessGpio> StatusLed for Cyw43Led<C> {
+    fn set(&mut self, on: bool) {
+        self.control.set_gpio(Self::LED_GPIO, on);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::input::{Button, ButtonState};
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use rp2040_hal::gpio::DynPin;
+
+pub const ROWS: usize = 2;
+pub const COLUMNS: usize = 3;
+
+/// The button at each crossing of a row and a column.
+const LAYOUT: [[Button; COLUMNS]; ROWS] = [
+    [Button::Up, Button::Down, Button::Left],
+    [Button::Right, Button::A, Button::B],
+];
+
+/// Cycles to wait after driving a row before reading the columns, so they have settled.
+const SETTLE_CYCLES: u32 = 100;
+
+/// The buttons wired as a matrix, each joining a row to a column, so that six buttons take five
+/// pins rather than seven. The rows are driven high one at a time and the columns, pulled down,
+/// read which buttons on that row are held.
+///
+/// Without a diode on each button, holding three buttons at the corners of a rectangle reads the
+/// fourth as held too, current flowing back through the others. A button that only appears on
+/// such a rectangle cannot be told from a ghost, so it is not reported until the rectangle is
+/// broken, while buttons already held stay held.
+pub struct ButtonMatrix {
+    rows: [DynPin; ROWS],
+    columns: [DynPin; COLUMNS],
+    /// The state reported by the last scan.
+    reported: ButtonState,
+}
+
+impl ButtonMatrix {
+    pub fn new(mut rows: [DynPin; ROWS], mut columns: [DynPin; COLUMNS]) -> Self {
+        // Rows not being scanned float, so that two buttons held on one column do not short the
+        // driven row to another
+        for row in rows.iter_mut() {
+            row.into_floating_input();
+        }
+        for column in columns.iter_mut() {
+            column.into_pull_down_input();
+        }
+        ButtonMatrix {
+            rows,
+            columns,
+            reported: ButtonState::default(),
+        }
+    }
+
+    /// Drive each row in turn, returning the columns read high for each as a bitmask.
+    fn scan(&mut self) -> [u8; ROWS] {
+        let mut closed = [0; ROWS];
+        for (row, closed) in self.rows.iter_mut().zip(closed.iter_mut()) {
+            row.into_push_pull_output();
+            let _ = row.set_high();
+            cortex_m::asm::delay(SETTLE_CYCLES);
+            for (index, column) in self.columns.iter().enumerate() {
+                if column.is_high().unwrap_or(false) {
+                    *closed |= 1 << index;
+                }
+            }
+            let _ = row.set_low();
+            row.into_floating_input();
+        }
+        closed
+    }
+
+    /// Scan every button into a single snapshot for the current frame.
+    pub fn state(&mut self) -> ButtonState {
+        let closed = self.scan();
+        let ambiguous = ghosting(&closed);
+
+        let mut state = ButtonState::default();
+        for (row, buttons) in LAYOUT.iter().enumerate() {
+            for (column, &button) in buttons.iter().enumerate() {
+                let mask = 1 << column;
+                let held = match ambiguous[row] & mask != 0 {
+                    true => self.reported.held(button),
+                    false => closed[row] & mask != 0,
+                };
+                state.set(button, held);
+            }
+        }
+        self.reported = state;
+        state
+    }
+}
+
+/// The columns of each row that are on a rectangle of closed switches, where any one of the four
+/// could be a ghost of the other three.
+fn ghosting(closed: &[u8; ROWS]) -> [u8; ROWS] {
+    let mut ambiguous = [0; ROWS];
+    for first in 0..ROWS {
+        for second in first + 1..ROWS {
+            let shared = closed[first] & closed[second];
+            if shared.count_ones() >= 2 {
+                ambiguous[first] |= shared;
+                ambiguous[second] |= shared;
+            }
+        }
+    }
+    ambiguous
+}
@@ -0,0 +1,108 @@
+use crate::game::{Canvas, Display};
+use embedded_graphics::prelude::Point;
+use embedded_hal::blocking::spi::Write;
+use embedded_hal::digital::v2::OutputPin;
+
+const REG_DIGIT_0: u8 = 0x01;
+const REG_DECODE_MODE: u8 = 0x09;
+const REG_INTENSITY: u8 = 0x0A;
+const REG_SCAN_LIMIT: u8 = 0x0B;
+const REG_SHUTDOWN: u8 = 0x0C;
+const REG_DISPLAY_TEST: u8 = 0x0F;
+
+/// A display made of MODULES chained MAX7219 8x8 LED matrices arranged MODULES_WIDE matrices
+/// across, e.g. eight matrices two wide for a 16x32 panel that fits the playfield at one cell
+/// per LED. Matrices are chained row by row from the top left as seen by the player, with
+/// matrix 0 nearest the Pico.
+pub struct Max7219<SPI, CS, const MODULES: usize, const MODULES_WIDE: usize> {
+    spi: SPI,
+    cs: CS,
+    frame: [[u8; 8]; MODULES],
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, const MODULES: usize, const MODULES_WIDE: usize>
+    Max7219<SPI, CS, MODULES, MODULES_WIDE>
+{
+    pub fn new(spi: SPI, cs: CS) -> Self {
+        Max7219 {
+            spi,
+            cs,
+            frame: [[0; 8]; MODULES],
+        }
+    }
+
+    /// Configure every matrix in the chain for raw (non BCD) output on all eight rows.
+    pub fn init(&mut self, intensity: u8) {
+        self.write_all(REG_DISPLAY_TEST, [0; MODULES]);
+        self.write_all(REG_SCAN_LIMIT, [7; MODULES]);
+        self.write_all(REG_DECODE_MODE, [0; MODULES]);
+        self.write_all(REG_INTENSITY, [intensity & 0xF; MODULES]);
+        self.write_all(REG_SHUTDOWN, [1; MODULES]);
+    }
+
+    /// Write one register on every matrix in a single chip select frame. Data shifts through
+    /// the chain, so the value for the furthest matrix has to be sent first.
+    fn write_all(&mut self, register: u8, data: [u8; MODULES]) {
+        let _ = self.cs.set_low();
+        for module in (0..MODULES).rev() {
+            let _ = self.spi.write(&[register, data[module]]);
+        }
+        let _ = self.cs.set_high();
+    }
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, const MODULES: usize, const MODULES_WIDE: usize> Canvas
+    for Max7219<SPI, CS, MODULES, MODULES_WIDE>
+{
+    fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        let (width, height) = self.size();
+        if x >= width || y >= height {
+            return;
+        }
+
+        let module = (y as usize / 8) * MODULES_WIDE + (x as usize / 8);
+        let bit = 0x80 >> (x % 8);
+        let row = &mut self.frame[module][y as usize % 8];
+        if on {
+            *row |= bit;
+        } else {
+            *row &= !bit;
+        }
+    }
+
+    fn draw_rect(&mut self, (min_x, min_y): (u32, u32), (max_x, max_y): (u32, u32)) {
+        for x in min_x..=max_x {
+            self.set_pixel(x, min_y, true);
+            self.set_pixel(x, max_y, true);
+        }
+        for y in min_y..=max_y {
+            self.set_pixel(min_x, y, true);
+            self.set_pixel(max_x, y, true);
+        }
+    }
+
+    /// Text is not legible at this resolution so it is not drawn.
+    fn text(&mut self, _text: &str, _point: Point) {}
+
+    fn size(&self) -> (u32, u32) {
+        ((MODULES_WIDE * 8) as u32, ((MODULES / MODULES_WIDE) * 8) as u32)
+    }
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, const MODULES: usize, const MODULES_WIDE: usize> Display
+    for Max7219<SPI, CS, MODULES, MODULES_WIDE>
+{
+    fn clear(&mut self) {
+        self.frame = [[0; 8]; MODULES];
+    }
+
+    fn flush(&mut self) {
+        for row in 0..8 {
+            let mut data = [0; MODULES];
+            for module in 0..MODULES {
+                data[module] = self.frame[module][row];
+            }
+            self.write_all(REG_DIGIT_0 + row as u8, data);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+//! Finding another handheld to battle on the same network with multicast DNS (RFC 6762) and
+//! DNS-SD (RFC 6763), so that nobody has to type an address. Each device answers queries for
+//! the versus service and asks for it until another answers, then both show the same short
+//! pairing code so the players can check they found each other before the match starts.
+
+use crate::game::Canvas;
+use crate::input::{Button, Chord, Input};
+use crate::netplay::NETPLAY_PORT;
+use crate::sntp::UdpTransport;
+use crate::text::TextBuffer;
+use core::fmt::Write;
+use embedded_graphics::prelude::Point;
+
+pub const MDNS_PORT: u16 = 5353;
+/// The group queries and answers are sent to, which the transport is expected to have joined.
+pub const MDNS_GROUP: [u8; 4] = [224, 0, 0, 251];
+pub const PACKET_LEN: usize = 512;
+
+/// The service advertised, as the labels of _pico-tetris._udp.local.
+const SERVICE: &[&[u8]] = &[b"_pico-tetris", b"_udp", b"local"];
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Set on the class of records only this device can answer for.
+const CACHE_FLUSH: u16 = 0x8000;
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_AUTHORITATIVE: u16 = 0x0400;
+const TTL_SECONDS: u32 = 120;
+const HEADER_LEN: usize = 12;
+/// Compression pointers followed before a name is given up on, so a loop cannot hang us.
+const MAX_POINTERS: usize = 16;
+
+/// Frames between queries until another device answers, a second at ten frames a second.
+const QUERY_INTERVAL: u8 = 10;
+
+/// Another device offering a match.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Peer {
+    pub address: [u8; 4],
+    pub port: u16,
+}
+
+/// The mDNS messages that matter for pairing, anything else is ignored.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Message {
+    /// A device is looking for the versus service.
+    Query,
+    /// A device answered with where it can be reached.
+    Answer(Peer),
+}
+
+/// A packet being built. It cannot outgrow its buffer, every packet sent is far shorter than
+/// PACKET_LEN.
+pub struct Packet {
+    buffer: [u8; PACKET_LEN],
+    len: usize,
+}
+
+impl Packet {
+    fn new() -> Self {
+        Packet {
+            buffer: [0; PACKET_LEN],
+            len: 0,
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn header(&mut self, flags: u16, questions: u16, answers: u16, additional: u16) {
+        for value in [0, flags, questions, answers, 0, additional] {
+            self.u16(value);
+        }
+    }
+
+    fn name(&mut self, labels: &[&[u8]]) {
+        for label in labels {
+            self.bytes(&[label.len() as u8]);
+            self.bytes(label);
+        }
+        self.bytes(&[0]);
+    }
+
+    /// A record, with its data length filled in after data writes it.
+    fn record(&mut self, name: &[&[u8]], kind: u16, class: u16, data: impl FnOnce(&mut Self)) {
+        self.name(name);
+        self.u16(kind);
+        self.u16(class);
+        self.bytes(&TTL_SECONDS.to_be_bytes());
+        let length_at = self.len;
+        self.u16(0);
+        data(self);
+        let length = (self.len - length_at - 2) as u16;
+        self.buffer[length_at..length_at + 2].copy_from_slice(&length.to_be_bytes());
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+/// The name of this device's instance of the service and of the device itself, pico-tetris-
+/// followed by its address in hex so that devices on one network never share a name.
+fn instance_label(address: [u8; 4]) -> [u8; 20] {
+    const HEX: &[u8] = b"0123456789abcdef";
+    let mut label = *b"pico-tetris-00000000";
+    for (index, byte) in address.iter().enumerate() {
+        label[12 + index * 2] = HEX[usize::from(byte >> 4)];
+        label[13 + index * 2] = HEX[usize::from(byte & 0xf)];
+    }
+    label
+}
+
+/// Ask who offers the versus service.
+pub fn query() -> Packet {
+    let mut packet = Packet::new();
+    packet.header(0, 1, 0, 0);
+    packet.name(SERVICE);
+    packet.u16(TYPE_PTR);
+    packet.u16(CLASS_IN);
+    packet
+}
+
+/// Offer the versus service at address: a pointer from the service to this device's instance,
+/// the port of the instance and the address of the device.
+pub fn response(address: [u8; 4]) -> Packet {
+    let label = instance_label(address);
+    let instance: &[&[u8]] = &[&label, SERVICE[0], SERVICE[1], SERVICE[2]];
+    let host: &[&[u8]] = &[&label, b"local"];
+
+    let mut packet = Packet::new();
+    packet.header(FLAG_RESPONSE | FLAG_AUTHORITATIVE, 0, 1, 2);
+    packet.record(SERVICE, TYPE_PTR, CLASS_IN, |packet| packet.name(instance));
+    packet.record(instance, TYPE_SRV, CLASS_IN | CACHE_FLUSH, |packet| {
+        // Priority and weight, which only matter with more than one server
+        packet.u16(0);
+        packet.u16(0);
+        packet.u16(NETPLAY_PORT);
+        packet.name(host);
+    });
+    packet.record(host, TYPE_A, CLASS_IN | CACHE_FLUSH, |packet| {
+        packet.bytes(&address)
+    });
+    packet
+}
+
+fn u16_at(packet: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *packet.get(offset)?,
+        *packet.get(offset + 1)?,
+    ]))
+}
+
+/// The offset just past the name at offset, where a compression pointer ends it.
+fn skip_name(packet: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(offset)?;
+        match len {
+            0 => return Some(offset + 1),
+            len if len & 0xc0 == 0xc0 => return Some(offset + 2),
+            len => offset += 1 + usize::from(len),
+        }
+    }
+}
+
+/// Whether the name at offset is labels, ignoring case as DNS does.
+fn name_is(packet: &[u8], mut offset: usize, labels: &[&[u8]]) -> bool {
+    let mut labels = labels.iter();
+    let mut pointers = 0;
+    loop {
+        let Some(&len) = packet.get(offset) else {
+            return false;
+        };
+        if len & 0xc0 == 0xc0 {
+            pointers += 1;
+            match u16_at(packet, offset) {
+                Some(pointer) if pointers <= MAX_POINTERS => {
+                    offset = usize::from(pointer & 0x3fff);
+                    continue;
+                }
+                _ => return false,
+            }
+        }
+        let len = usize::from(len);
+        let Some(label) = packet.get(offset + 1..offset + 1 + len) else {
+            return false;
+        };
+        match labels.next() {
+            None => return len == 0,
+            Some(expected) if expected.eq_ignore_ascii_case(label) => offset += 1 + len,
+            Some(_) => return false,
+        }
+    }
+}
+
+/// Read a packet received from the group. Answers are only understood in the form response
+/// sends them, one instance of the service with its port and address.
+pub fn parse(packet: &[u8]) -> Option<Message> {
+    let flags = u16_at(packet, 2)?;
+    let questions = u16_at(packet, 4)?;
+    let records = (6..12)
+        .step_by(2)
+        .map(|offset| u16_at(packet, offset).map(usize::from))
+        .sum::<Option<usize>>()?;
+
+    let mut offset = HEADER_LEN;
+    if flags & FLAG_RESPONSE == 0 {
+        for _ in 0..questions {
+            let end = skip_name(packet, offset)?;
+            let kind = u16_at(packet, end)?;
+            if name_is(packet, offset, SERVICE) && (kind == TYPE_PTR || kind == TYPE_ANY) {
+                return Some(Message::Query);
+            }
+            offset = end + 4;
+        }
+        return None;
+    }
+
+    for _ in 0..questions {
+        offset = skip_name(packet, offset)? + 4;
+    }
+    let (mut offered, mut port, mut address) = (false, None, None);
+    for _ in 0..records {
+        let end = skip_name(packet, offset)?;
+        let kind = u16_at(packet, end)?;
+        let data = end + 10;
+        let data_len = usize::from(u16_at(packet, end + 8)?);
+        match kind {
+            TYPE_PTR => offered |= name_is(packet, offset, SERVICE),
+            TYPE_SRV => port = u16_at(packet, data + 4),
+            TYPE_A if data_len == 4 => {
+                address = packet.get(data..data + 4)?.try_into().ok();
+            }
+            _ => {}
+        }
+        offset = data + data_len;
+    }
+    match (offered, port, address) {
+        (true, Some(port), Some(address)) => Some(Message::Answer(Peer { address, port })),
+        _ => None,
+    }
+}
+
+/// A four digit code both devices of a pair work out alike, whichever way round they found
+/// each other.
+pub fn pairing_code(ours: [u8; 4], theirs: [u8; 4]) -> u16 {
+    let (first, second) = (ours.min(theirs), ours.max(theirs));
+    // FNV-1a, so that neighbouring addresses get unrelated codes
+    let hash = first
+        .iter()
+        .chain(&second)
+        .fold(0x811c_9dc5u32, |hash, &byte| {
+            (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+        });
+    (hash % 10_000) as u16
+}
+
+/// How the player left the versus screen.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Back to the launcher.
+    Back,
+    /// Into a match with the device found.
+    Play,
+}
+
+/// Advertising this device and looking for another, polled once a frame while the versus
+/// screen is open.
+pub struct Pairing {
+    address: [u8; 4],
+    peer: Option<Peer>,
+    /// Frames until the next query.
+    wait: u8,
+}
+
+impl Pairing {
+    /// Start pairing a device with the given address on the network.
+    pub fn new(address: [u8; 4]) -> Self {
+        Pairing {
+            address,
+            peer: None,
+            wait: 0,
+        }
+    }
+
+    /// Answer devices looking for a match and look for one, returning the device found. Keeps
+    /// answering once paired, as the other device may not have heard this one yet. Reads until
+    /// the transport times out, so it should be given a short timeout.
+    pub fn poll<T: UdpTransport>(&mut self, transport: &mut T) -> Result<Option<Peer>, T::Error> {
+        if self.peer.is_none() {
+            match self.wait {
+                0 => {
+                    transport.send(MDNS_PORT, query().as_bytes())?;
+                    self.wait = QUERY_INTERVAL;
+                }
+                _ => self.wait -= 1,
+            }
+        }
+
+        let mut buffer = [0; PACKET_LEN];
+        while let Some(len) = transport.receive(&mut buffer)? {
+            match parse(&buffer[..len]) {
+                Some(Message::Query) => {
+                    transport.send(MDNS_PORT, response(self.address).as_bytes())?
+                }
+                // Our own answers come back from the group too
+                Some(Message::Answer(peer)) if peer.address != self.address => {
+                    self.peer.get_or_insert(peer);
+                }
+                _ => {}
+            }
+        }
+        Ok(self.peer)
+    }
+
+    /// A and B together go back to the launcher, and A starts the match once paired.
+    pub fn update(&self, input: &Input) -> Option<Outcome> {
+        match input.chord {
+            Some(Chord::AB) => Some(Outcome::Back),
+            _ if self.peer.is_some() && input.taps.held(Button::A) => Some(Outcome::Play),
+            _ => None,
+        }
+    }
+
+    /// The device to connect to for the match, if this is the one of the pair that connects:
+    /// the one with the lower address, the other waiting for it.
+    pub fn dial(&self) -> Option<Peer> {
+        self.peer.filter(|peer| self.address < peer.address)
+    }
+
+    /// The code to show on both devices once paired.
+    pub fn code(&self) -> Option<u16> {
+        self.peer
+            .map(|peer| pairing_code(self.address, peer.address))
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas) {
+        match self.code() {
+            Some(code) => {
+                let mut text = TextBuffer::new();
+                let _ = write!(text, "CODE {:04}", code);
+                canvas.text("PAIRED", Point::new(0, 10));
+                canvas.text(text.as_str(), Point::new(0, 22));
+                canvas.text("A TO PLAY", Point::new(0, 34));
+            }
+            None => canvas.text("SEARCHING...", Point::new(0, 10)),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::audio::Audio;
+
+const A4: u32 = 440;
+const B4: u32 = 494;
+const C5: u32 = 523;
+const D5: u32 = 587;
+const E5: u32 = 659;
+const F5: u32 = 698;
+const G5: u32 = 784;
+const A5: u32 = 880;
+const REST: u32 = 0;
+
+// Note lengths in frames of the main loop (100ms) at normal tempo.
+const EIGHTH: u8 = 2;
+const QUARTER: u8 = 4;
+const DOTTED_QUARTER: u8 = 6;
+
+/// A single step of a melody, a frequency in Hz (zero for a rest) held for a number of frames.
+#[derive(Clone, Copy)]
+pub struct Note {
+    pub freq: u32,
+    pub frames: u8,
+}
+
+const fn n(freq: u32, frames: u8) -> Note {
+    Note { freq, frames }
+}
+
+/// The A section of Korobeiniki, the Tetris theme.
+pub const THEME: &[Note] = &[
+    n(E5, QUARTER),
+    n(B4, EIGHTH),
+    n(C5, EIGHTH),
+    n(D5, QUARTER),
+    n(C5, EIGHTH),
+    n(B4, EIGHTH),
+    n(A4, QUARTER),
+    n(A4, EIGHTH),
+    n(C5, EIGHTH),
+    n(E5, QUARTER),
+    n(D5, EIGHTH),
+    n(C5, EIGHTH),
+    n(B4, DOTTED_QUARTER),
+    n(C5, EIGHTH),
+    n(D5, QUARTER),
+    n(E5, QUARTER),
+    n(C5, QUARTER),
+    n(A4, QUARTER),
+    n(A4, QUARTER),
+    n(REST, QUARTER),
+    n(REST, EIGHTH),
+    n(D5, QUARTER),
+    n(F5, EIGHTH),
+    n(A5, QUARTER),
+    n(G5, EIGHTH),
+    n(F5, EIGHTH),
+    n(E5, DOTTED_QUARTER),
+    n(C5, EIGHTH),
+    n(E5, QUARTER),
+    n(D5, EIGHTH),
+    n(C5, EIGHTH),
+    n(B4, QUARTER),
+    n(B4, EIGHTH),
+    n(C5, EIGHTH),
+    n(D5, QUARTER),
+    n(E5, QUARTER),
+    n(C5, QUARTER),
+    n(A4, QUARTER),
+    n(A4, QUARTER),
+    n(REST, QUARTER),
+];
+
+/// A looping note sequencer that drives the audio output once per frame of the main loop.
+pub struct Music {
+    melody: &'static [Note],
+    position: usize,
+    frames_remaining: u8,
+    paused: bool,
+}
+
+impl Music {
+    pub fn new(melody: &'static [Note]) -> Self {
+        Music {
+            melody,
+            position: 0,
+            frames_remaining: 0,
+            paused: false,
+        }
+    }
+
+    /// Stop advancing the melody and silence the audio. The melody continues from the same
+    /// note on the next update after resume.
+    pub fn pause(&mut self, audio: &mut dyn Audio) {
+        if !self.paused {
+            self.paused = true;
+            audio.silence();
+        }
+    }
+
+    pub fn resume(&mut self, audio: &mut dyn Audio) {
+        if self.paused {
+            self.paused = false;
+            audio.tone(self.melody[self.position].freq);
+        }
+    }
+
+    /// Return to the start of the melody, used when a new game begins.
+    pub fn restart(&mut self) {
+        self.position = 0;
+        self.frames_remaining = 0;
+    }
+
+    /// Advance the melody by one frame, changing the tone when a note ends. When fast is
+    /// set notes are played at double tempo.
+    pub fn update(&mut self, audio: &mut dyn Audio, fast: bool) {
+        if self.paused || self.melody.is_empty() {
+            return;
+        }
+
+        let step = if fast { 2 } else { 1 };
+
+        if self.frames_remaining <= step {
+            if self.frames_remaining != 0 {
+                self.position = (self.position + 1) % self.melody.len();
+            }
+            let note = self.melody[self.position];
+            self.frames_remaining = note.frames;
+            audio.tone(note.freq);
+        } else {
+            self.frames_remaining -= step;
+        }
+    }
+}
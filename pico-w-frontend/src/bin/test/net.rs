@@ -0,0 +1,19 @@
+/// A listening TCP socket that serves one client at a time, implemented by stack::TcpPort.
+pub trait TcpConnection {
+    type Error;
+
+    /// True while a client is connected.
+    fn is_connected(&self) -> bool;
+
+    /// Read whatever has arrived without blocking, returning the number of bytes read.
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, Self::Error>;
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Drop the current client so that another can connect.
+    fn close(&mut self);
+
+    /// Connect to a server at address rather than wait for a client, dropping any client
+    /// connected. Listens again once the connection is closed.
+    fn connect(&mut self, address: [u8; 4], port: u16) -> Result<(), Self::Error>;
+}
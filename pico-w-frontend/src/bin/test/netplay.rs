@@ -0,0 +1,220 @@
+//! Hosts a versus match for a player joining over TCP, from the desktop frontend or another
+//! device, or joins one hosted by a device found by pairing. The handshake agrees the match,
+//! then the board is streamed to the other player as spectate messages and garbage is exchanged
+//! each way.
+
+use crate::net::TcpConnection;
+use alloc::vec::Vec;
+use tetris_core::spectate::Encoder;
+use tetris_core::tetris::{Rules, Tetris};
+use tetris_core::versus::Garbage;
+use tetris_net::frame::{encode_framed, Deframer, MAX_MESSAGE_LEN};
+use tetris_net::lobby::{Lobby, Match, MatchConfig};
+use tetris_net::Message;
+
+pub use tetris_net::NETPLAY_PORT;
+
+/// Frames a match being joined is given to connect before hosting again, five seconds.
+const DIAL_FRAMES: u8 = 50;
+
+/// A match against a player on another device, serviced once per frame by the game.
+pub trait Netplay {
+    /// Service the connection, passing on garbage from the other player, and return the match
+    /// once a player has joined and agreed to it.
+    fn poll(&mut self, on_garbage: &mut dyn FnMut(Garbage)) -> Option<Match>;
+
+    /// Send the board after an update, with any garbage the update sent the other player.
+    fn send(&mut self, tetris: &Tetris, garbage: Option<Garbage>);
+}
+
+pub struct NetplayHost<C: TcpConnection> {
+    connection: C,
+    /// The rules offered to each player who joins.
+    rules: Rules,
+    /// Stepped for each match so that no two are dealt the same pieces.
+    seed: u64,
+    /// The handshake with the player who joined, None once the match has started.
+    lobby: Option<Lobby>,
+    /// How the match being played was agreed, None until it has been.
+    config: Option<MatchConfig>,
+    /// Whether the player connected has been sent a hello.
+    greeted: bool,
+    /// Whether the connection was made to a match hosted by the other device, which offers the
+    /// rules, rather than by a player joining this one.
+    joining: bool,
+    /// Frames left to connect to the match being joined in.
+    dial_frames: u8,
+    deframer: Deframer,
+    encoder: Encoder,
+    /// The spectate message for the current frame, before it is framed.
+    message: Vec<u8>,
+    buffer: [u8; MAX_MESSAGE_LEN + 1],
+    /// Updates since the match started.
+    tick: u32,
+}
+
+fn write<C: TcpConnection>(
+    connection: &mut C,
+    buffer: &mut [u8],
+    message: &Message,
+) -> Result<(), ()> {
+    let len = encode_framed(message, buffer).map_err(|_| ())?;
+    connection.write(&buffer[..len]).map_err(|_| ())
+}
+
+impl<C: TcpConnection> NetplayHost<C> {
+    pub fn new(connection: C, rules: Rules, seed: u64) -> Self {
+        NetplayHost {
+            connection,
+            rules,
+            seed,
+            lobby: None,
+            config: None,
+            greeted: false,
+            joining: false,
+            dial_frames: 0,
+            deframer: Deframer::new(),
+            encoder: Encoder::new(),
+            message: Vec::new(),
+            buffer: [0; MAX_MESSAGE_LEN + 1],
+            tick: 0,
+        }
+    }
+
+    /// Drop the player, ending the match if one is being played, so that another can join.
+    fn disconnect(&mut self) {
+        self.connection.close();
+        self.lobby = None;
+        self.config = None;
+        self.greeted = false;
+        self.joining = false;
+        self.deframer = Deframer::new();
+    }
+
+    /// Join the match hosted by the device at address instead, dropping any player connected.
+    /// Goes back to hosting when the match ends or cannot be connected to.
+    pub fn join(&mut self, address: [u8; 4], port: u16) {
+        self.disconnect();
+        if self.connection.connect(address, port).is_ok() {
+            self.joining = true;
+            self.dial_frames = DIAL_FRAMES;
+        }
+    }
+
+    /// Greet a player who has just connected and offer them a match, or greet the host of the
+    /// match being joined.
+    fn greet(&mut self) -> Result<(), ()> {
+        // A step of Knuth's MMIX generator
+        self.seed = (self.seed)
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let lobby = match self.joining {
+            true => Lobby::join(),
+            false => Lobby::host(MatchConfig::new(self.rules), self.seed),
+        };
+        write(&mut self.connection, &mut self.buffer, &lobby.hello())?;
+        self.lobby = Some(lobby);
+        self.greeted = true;
+        Ok(())
+    }
+
+    /// Handle everything that has arrived, returning the match if the handshake finished.
+    fn receive(&mut self, on_garbage: &mut dyn FnMut(Garbage)) -> Result<Option<Match>, ()> {
+        let mut agreed = None;
+        loop {
+            let read = (self.connection)
+                .read(self.deframer.space())
+                .map_err(|_| ())?;
+            if read == 0 {
+                return Ok(agreed);
+            }
+            self.deframer.filled(read);
+
+            while let Some(message) = self.deframer.next_message() {
+                let message = message.map_err(|_| ())?;
+                let Some(ref mut lobby) = self.lobby else {
+                    // Garbage that does not fit the playfield drops the player like any
+                    // malformed message
+                    if let (Message::Garbage { rows, hole, .. }, Some(config)) =
+                        (message, self.config)
+                    {
+                        on_garbage(config.garbage(rows, hole).map_err(|_| ())?);
+                    }
+                    continue;
+                };
+
+                let mut reply = None;
+                let result = lobby.receive(&message, &mut |message| reply = Some(message));
+                if let Some(reply) = reply {
+                    write(&mut self.connection, &mut self.buffer, &reply)?;
+                }
+                if let Some(started) = result.map_err(|_| ())? {
+                    agreed = Some(started);
+                    self.lobby = None;
+                    self.config = Some(started.config);
+                    self.encoder.reset();
+                    self.tick = 0;
+                }
+            }
+        }
+    }
+
+    fn send_frame(&mut self, tetris: &Tetris, garbage: Option<Garbage>) -> Result<(), ()> {
+        if let Some(garbage) = garbage {
+            let message = Message::Garbage {
+                tick: self.tick,
+                rows: garbage.rows as u8,
+                hole: garbage.hole as u8,
+            };
+            write(&mut self.connection, &mut self.buffer, &message)?;
+        }
+
+        let mut message = core::mem::take(&mut self.message);
+        message.clear();
+        self.encoder.encode(tetris, &mut message);
+        // The encoder's length prefix is replaced by the frame's own
+        let result = write(
+            &mut self.connection,
+            &mut self.buffer,
+            &Message::Spectate(&message[1..]),
+        );
+        self.message = message;
+        self.tick = self.tick.wrapping_add(1);
+        result
+    }
+}
+
+impl<C: TcpConnection> Netplay for NetplayHost<C> {
+    /// A player who has just connected is offered a match with the rules this host was made
+    /// with. Any failure drops the player rather than holding up the game.
+    fn poll(&mut self, on_garbage: &mut dyn FnMut(Garbage)) -> Option<Match> {
+        if !self.connection.is_connected() {
+            if self.greeted {
+                self.disconnect();
+            } else if self.joining {
+                self.dial_frames = self.dial_frames.saturating_sub(1);
+                if self.dial_frames == 0 {
+                    self.disconnect();
+                }
+            }
+            return None;
+        }
+        if !self.greeted && self.greet().is_err() {
+            self.disconnect();
+            return None;
+        }
+        self.receive(on_garbage).unwrap_or_else(|()| {
+            self.disconnect();
+            None
+        })
+    }
+
+    fn send(&mut self, tetris: &Tetris, garbage: Option<Garbage>) {
+        if !self.greeted || self.lobby.is_some() {
+            return;
+        }
+        if self.send_frame(tetris, garbage).is_err() {
+            self.disconnect();
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::game::{Canvas, Display};
+use core::convert::Infallible;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_6X10, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    text::{Baseline, Text},
+};
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::spi::Write;
+use embedded_hal::digital::v2::OutputPin;
+
+pub const WIDTH: usize = 84;
+pub const HEIGHT: usize = 48;
+
+/// The controller stores the screen as banks of eight rows, one byte per column.
+const BANKS: usize = HEIGHT / 8;
+
+const CMD_FUNCTION_SET: u8 = 0x20;
+const FUNCTION_EXTENDED: u8 = 0x01;
+const CMD_DISPLAY_NORMAL: u8 = 0x0C;
+const CMD_SET_Y: u8 = 0x40;
+const CMD_SET_X: u8 = 0x80;
+const CMD_TEMPERATURE: u8 = 0x04;
+const CMD_BIAS: u8 = 0x10;
+const CMD_SET_VOP: u8 = 0x80;
+
+/// The 84x48 LCD of the Nokia 5110, driven by a PCD8544 over SPI. DC selects between commands
+/// (low) and data (high).
+pub struct Pcd8544<SPI, CS, DC, RST> {
+    spi: SPI,
+    cs: CS,
+    dc: DC,
+    rst: RST,
+    frame: [[u8; WIDTH]; BANKS],
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, DC: OutputPin, RST: OutputPin> Pcd8544<SPI, CS, DC, RST> {
+    pub fn new(spi: SPI, cs: CS, dc: DC, rst: RST) -> Self {
+        Pcd8544 {
+            spi,
+            cs,
+            dc,
+            rst,
+            frame: [[0; WIDTH]; BANKS],
+        }
+    }
+
+    /// Reset the controller and set the contrast, which varies a lot between modules.
+    pub fn init(&mut self, contrast: u8, delay: &mut impl DelayMs<u8>) {
+        let _ = self.rst.set_low();
+        delay.delay_ms(10);
+        let _ = self.rst.set_high();
+
+        self.command(&[
+            CMD_FUNCTION_SET | FUNCTION_EXTENDED,
+            CMD_SET_VOP | (contrast & 0x7F),
+            CMD_TEMPERATURE,
+            CMD_BIAS | 0x04,
+            CMD_FUNCTION_SET,
+            CMD_DISPLAY_NORMAL,
+        ]);
+    }
+
+    fn command(&mut self, commands: &[u8]) {
+        let _ = self.dc.set_low();
+        let _ = self.cs.set_low();
+        let _ = self.spi.write(commands);
+        let _ = self.cs.set_high();
+    }
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, DC: OutputPin, RST: OutputPin> Canvas
+    for Pcd8544<SPI, CS, DC, RST>
+{
+    fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        let (x, y) = (x as usize, y as usize);
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+
+        let bit = 1 << (y % 8);
+        let column = &mut self.frame[y / 8][x];
+        if on {
+            *column |= bit;
+        } else {
+            *column &= !bit;
+        }
+    }
+
+    fn draw_rect(&mut self, (min_x, min_y): (u32, u32), (max_x, max_y): (u32, u32)) {
+        for x in min_x..=max_x {
+            self.set_pixel(x, min_y, true);
+            self.set_pixel(x, max_y, true);
+        }
+        for y in min_y..=max_y {
+            self.set_pixel(min_x, y, true);
+            self.set_pixel(max_x, y, true);
+        }
+    }
+
+    fn text(&mut self, text: &str, point: Point) {
+        let _ = Text::with_baseline(
+            text,
+            point,
+            MonoTextStyle::new(&FONT_6X10, BinaryColor::On),
+            Baseline::Top,
+        )
+        .draw(self);
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, DC: OutputPin, RST: OutputPin> Display
+    for Pcd8544<SPI, CS, DC, RST>
+{
+    fn clear(&mut self) {
+        self.frame = [[0; WIDTH]; BANKS];
+    }
+
+    /// The address wraps from the end of one bank to the start of the next, so the whole
+    /// frame is sent in one go from the top left.
+    fn flush(&mut self) {
+        self.command(&[CMD_SET_X, CMD_SET_Y]);
+
+        let _ = self.dc.set_high();
+        let _ = self.cs.set_low();
+        for bank in self.frame.iter() {
+            let _ = self.spi.write(bank);
+        }
+        let _ = self.cs.set_high();
+    }
+}
+
+/// Lets embedded-graphics render text into the frame.
+impl<SPI: Write<u8>, CS: OutputPin, DC: OutputPin, RST: OutputPin> DrawTarget
+    for Pcd8544<SPI, CS, DC, RST>
+{
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I: IntoIterator<Item = Pixel<BinaryColor>>>(
+        &mut self,
+        pixels: I,
+    ) -> Result<(), Self::Error> {
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 {
+                self.set_pixel(point.x as u32, point.y as u32, color.is_on());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<SPI: Write<u8>, CS: OutputPin, DC: OutputPin, RST: OutputPin> OriginDimensions
+    for Pcd8544<SPI, CS, DC, RST>
+{
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
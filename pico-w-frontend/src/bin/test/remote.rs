@@ -0,0 +1,161 @@
+//! A control page served over HTTP with on-screen buttons that send the held buttons back over
+//! a WebSocket, so the game can be played from a phone or when a button has failed.
+
+use crate::input::ButtonState;
+use crate::net::TcpConnection;
+use crate::websocket::{accept_key, header, parse_frame, Frame};
+
+pub const HTTP_PORT: u16 = 80;
+
+/// Each button sets bit n for Button::ALL[n] while it is held and the whole state is sent as a
+/// single byte whenever it changes.
+const PAGE: &str = r#"<!DOCTYPE html>
+<html><head><meta name="viewport" content="width=device-width,user-scalable=no">
+<title>Pico Tetris</title>
+<style>
+body{font-family:sans-serif;text-align:center;user-select:none;touch-action:none}
+button{width:5em;height:5em;margin:.3em;font-size:1.2em}
+</style></head><body>
+<div><button data-bit="0">&uarr;</button></div>
+<div><button data-bit="2">&larr;</button><button data-bit="1">&darr;</button><button data-bit="3">&rarr;</button></div>
+<div><button data-bit="5">B</button><button data-bit="4">A</button></div>
+<script>
+let held=0,ws=new WebSocket("ws://"+location.host+"/");
+ws.binaryType="arraybuffer";
+function send(){if(ws.readyState==1)ws.send(new Uint8Array([held]))}
+for(const b of document.querySelectorAll("button")){
+const bit=1<<b.dataset.bit;
+b.onpointerdown=e=>{held|=bit;b.setPointerCapture(e.pointerId);send()};
+b.onpointerup=b.onpointercancel=()=>{held&=~bit;send()};
+}
+</script></body></html>
+"#;
+
+/// A source of button presses other than the physical buttons.
+pub trait RemoteInput {
+    /// Service the connection and return the buttons currently held remotely.
+    fn poll(&mut self) -> ButtonState;
+}
+
+#[derive(PartialEq, Eq)]
+enum State {
+    /// Waiting for the end of an HTTP request.
+    Request,
+    WebSocket,
+}
+
+/// Serves the control page and then the WebSocket it opens on the same port, one client at a
+/// time.
+pub struct RemoteControl<C: TcpConnection> {
+    connection: C,
+    state: State,
+    buffer: [u8; 512],
+    len: usize,
+    held: ButtonState,
+}
+
+impl<C: TcpConnection> RemoteControl<C> {
+    pub fn new(connection: C) -> Self {
+        RemoteControl {
+            connection,
+            state: State::Request,
+            buffer: [0; 512],
+            len: 0,
+            held: ButtonState::default(),
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.connection.close();
+        self.state = State::Request;
+        self.len = 0;
+        self.held = ButtonState::default();
+    }
+
+    /// Answer a complete HTTP request, upgrading it to a WebSocket if it asks for one and
+    /// otherwise sending the page. Returns false once the connection is finished with.
+    fn respond(&mut self, request_len: usize) -> Result<bool, C::Error> {
+        let request = &self.buffer[..request_len];
+        match header(request, b"Sec-WebSocket-Key") {
+            Some(key) => {
+                let accept = accept_key(key);
+                self.connection.write(
+                    b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+                      Connection: Upgrade\r\nSec-WebSocket-Accept: ",
+                )?;
+                self.connection.write(&accept)?;
+                self.connection.write(b"\r\n\r\n")?;
+                self.state = State::WebSocket;
+                Ok(true)
+            }
+            None => {
+                self.connection.write(
+                    b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nConnection: close\r\n\r\n",
+                )?;
+                self.connection.write(PAGE.as_bytes())?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Handle everything buffered so far, returning false if the connection should be dropped.
+    fn process(&mut self) -> Result<bool, C::Error> {
+        loop {
+            match self.state {
+                State::Request => {
+                    let end = self.buffer[..self.len]
+                        .windows(4)
+                        .position(|window| window == b"\r\n\r\n");
+                    match end {
+                        Some(end) => {
+                            if !self.respond(end + 4)? {
+                                return Ok(false);
+                            }
+                            // Anything after the request is the start of the WebSocket stream
+                            self.buffer.copy_within(end + 4..self.len, 0);
+                            self.len -= end + 4;
+                        }
+                        // A request that does not fit is not one the page sends
+                        None => return Ok(self.len < self.buffer.len()),
+                    }
+                }
+                State::WebSocket => match parse_frame(&mut self.buffer[..self.len]) {
+                    Some((frame, used)) => {
+                        match frame {
+                            Frame::Binary(&[bits, ..]) => self.held = ButtonState::from_bits(bits),
+                            Frame::Close => return Ok(false),
+                            _ => {}
+                        }
+                        self.buffer.copy_within(used..self.len, 0);
+                        self.len -= used;
+                    }
+                    None => return Ok(true),
+                },
+            }
+        }
+    }
+}
+
+impl<C: TcpConnection> RemoteInput for RemoteControl<C> {
+    fn poll(&mut self) -> ButtonState {
+        if !self.connection.is_connected() {
+            if self.state != State::Request || self.len != 0 {
+                self.disconnect();
+            }
+            return ButtonState::default();
+        }
+
+        let keep = match self.connection.read(&mut self.buffer[self.len..]) {
+            Ok(read) => {
+                self.len += read;
+                self.process().unwrap_or(false)
+            }
+            Err(_) => false,
+        };
+        if !keep {
+            self.disconnect();
+        }
+
+        self.held
+    }
+}
@@ -0,0 +1,194 @@
+/// Hidden options that are unlocked by entering the Konami code on the title screen.
+#[derive(Clone, Copy, Default)]
+pub struct Unlocks(u8);
+
+impl Unlocks {
+    pub const INVISIBLE: u8 = 1 << 0;
+    pub const BIG: u8 = 1 << 1;
+    pub const TWENTY_G: u8 = 1 << 2;
+    pub const CHEATS: u8 = Self::INVISIBLE | Self::BIG | Self::TWENTY_G;
+    /// The diagnostics screen in the launcher.
+    pub const SERVICE: u8 = 1 << 3;
+
+    pub fn unlock(&mut self, flags: u8) {
+        self.0 |= flags;
+    }
+
+    pub fn is_unlocked(&self, flag: u8) -> bool {
+        self.0 & flag == flag
+    }
+}
+
+use crate::input::Button;
+
+/// What holding a button down for a long press does during a game.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum LongPressAction {
+    /// Long presses behave like the button is simply held.
+    #[default]
+    None,
+    Restart,
+    ExitToLauncher,
+}
+
+impl LongPressAction {
+    const ALL: [LongPressAction; 3] = [
+        LongPressAction::None,
+        LongPressAction::Restart,
+        LongPressAction::ExitToLauncher,
+    ];
+}
+
+/// How Tetris arranges the 128x64 OLED when there is no side display, as players differ in
+/// whether they would rather have a larger playfield or the score and pieces in view.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum LayoutTheme {
+    /// The playfield alone, as large as it fits.
+    Big,
+    /// The playfield beside the score and the next and held pieces.
+    #[default]
+    Hud,
+    /// The playfield turned on its side across the display with the score beside it, pieces
+    /// falling to the left.
+    Sideways,
+}
+
+impl LayoutTheme {
+    const ALL: [LayoutTheme; 3] = [LayoutTheme::Big, LayoutTheme::Hud, LayoutTheme::Sideways];
+
+    /// Short enough to fit beside its entry in the launcher.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LayoutTheme::Big => "Big",
+            LayoutTheme::Hud => "HUD",
+            LayoutTheme::Sideways => "Side",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            LayoutTheme::Big => LayoutTheme::Hud,
+            LayoutTheme::Hud => LayoutTheme::Sideways,
+            LayoutTheme::Sideways => LayoutTheme::Big,
+        }
+    }
+}
+
+/// Device settings shared between the menus and the game.
+#[derive(Clone, Copy)]
+pub struct Settings {
+    pub unlocks: Unlocks,
+    /// How many frames a button must be held to count as a long press.
+    pub long_press_frames: u8,
+    pub a_long_press: LongPressAction,
+    pub b_long_press: LongPressAction,
+    /// Double tapping this button hard drops the current piece.
+    pub hard_drop_button: Button,
+    /// The most frames allowed between the two presses of a double tap.
+    pub double_tap_frames: u8,
+    /// Charge time above which each touch pad counts as touched, indexed by button. Set by
+    /// the touch calibration in the launcher.
+    pub touch_thresholds: [u16; Button::ALL.len()],
+    pub layout: LayoutTheme,
+    /// Ease gravity up or down to keep Tetris just hard enough for the player.
+    pub assist: bool,
+    /// Show how many of each kind of piece have been placed on the side display, in place of
+    /// the score and next piece.
+    pub piece_stats: bool,
+    /// The initials last entered for a high score, offered first the next time.
+    pub initials: [u8; 3],
+    /// Locked pieces vanish from the playfield, once Unlocks::INVISIBLE is unlocked.
+    pub invisible: bool,
+    /// Tetris is played on a playfield half the size with every cell drawn twice as large, once
+    /// Unlocks::BIG is unlocked.
+    pub big: bool,
+    /// Pieces fall to the stack as they spawn, once Unlocks::TWENTY_G is unlocked.
+    pub twenty_g: bool,
+}
+
+impl Settings {
+    /// Bytes taken by the saved form from to_bytes.
+    pub const ENCODED_LEN: usize = 15 + 2 * Button::ALL.len();
+
+    /// The settings as bytes for saving to flash, each choice as its place in the list of
+    /// choices and the touch thresholds little endian.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        let (head, tail) = bytes.split_at_mut(6);
+        head.copy_from_slice(&[
+            self.unlocks.0,
+            self.long_press_frames,
+            self.a_long_press as u8,
+            self.b_long_press as u8,
+            self.hard_drop_button as u8,
+            self.double_tap_frames,
+        ]);
+        let (thresholds, tail) = tail.split_at_mut(2 * Button::ALL.len());
+        for (chunk, threshold) in thresholds.chunks_exact_mut(2).zip(self.touch_thresholds) {
+            chunk.copy_from_slice(&threshold.to_le_bytes());
+        }
+        tail.copy_from_slice(&[
+            self.layout as u8,
+            self.assist as u8,
+            self.piece_stats as u8,
+            self.initials[0],
+            self.initials[1],
+            self.initials[2],
+            self.invisible as u8,
+            self.big as u8,
+            self.twenty_g as u8,
+        ]);
+        bytes
+    }
+
+    /// Settings saved by to_bytes. Choices that are out of range are left at their defaults.
+    pub fn from_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let defaults = Settings::default();
+        fn choice<T: Copy>(all: &[T], byte: u8, default: T) -> T {
+            all.get(byte as usize).copied().unwrap_or(default)
+        }
+        let thresholds = &bytes[6..6 + 2 * Button::ALL.len()];
+        let tail = &bytes[6 + 2 * Button::ALL.len()..];
+        let mut touch_thresholds = defaults.touch_thresholds;
+        for (threshold, chunk) in touch_thresholds.iter_mut().zip(thresholds.chunks_exact(2)) {
+            *threshold = u16::from_le_bytes([chunk[0], chunk[1]]);
+        }
+        Settings {
+            unlocks: Unlocks(bytes[0]),
+            long_press_frames: bytes[1],
+            a_long_press: choice(&LongPressAction::ALL, bytes[2], defaults.a_long_press),
+            b_long_press: choice(&LongPressAction::ALL, bytes[3], defaults.b_long_press),
+            hard_drop_button: choice(&Button::ALL, bytes[4], defaults.hard_drop_button),
+            double_tap_frames: bytes[5],
+            touch_thresholds,
+            layout: choice(&LayoutTheme::ALL, tail[0], defaults.layout),
+            assist: tail[1] != 0,
+            piece_stats: tail[2] != 0,
+            initials: [tail[3], tail[4], tail[5]],
+            invisible: tail[6] != 0,
+            big: tail[7] != 0,
+            twenty_g: tail[8] != 0,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            unlocks: Unlocks::default(),
+            long_press_frames: 5,
+            a_long_press: LongPressAction::None,
+            b_long_press: LongPressAction::Restart,
+            hard_drop_button: Button::Down,
+            double_tap_frames: 3,
+            touch_thresholds: [100; Button::ALL.len()],
+            layout: LayoutTheme::default(),
+            assist: false,
+            piece_stats: false,
+            initials: *b"AAA",
+            invisible: false,
+            big: false,
+            twenty_g: false,
+        }
+    }
+}
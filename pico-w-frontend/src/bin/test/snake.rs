@@ -0,0 +1,194 @@
+use crate::game::{Console, Displays, Game};
+use crate::input::{Button, Chord, Input};
+use crate::settings::Settings;
+use embedded_graphics::prelude::Point;
+
+const WIDTH: u8 = 31;
+const HEIGHT: u8 = 13;
+const CELL_SIZE: u32 = 4;
+const ORIGIN: (u32, u32) = (1, 9);
+const MAX_LENGTH: usize = 64;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn opposite(&self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+}
+
+/// A small game of Snake, mostly here to prove out the launcher. The snake body is kept in a
+/// fixed ring buffer so the game needs no heap.
+pub struct SnakeGame {
+    body: [(u8, u8); MAX_LENGTH],
+    head: usize,
+    length: usize,
+    direction: Direction,
+    food: (u8, u8),
+    seed: u32,
+    alive: bool,
+    exited: bool,
+}
+
+impl SnakeGame {
+    pub fn new() -> Self {
+        let mut game = SnakeGame {
+            body: [(0, 0); MAX_LENGTH],
+            head: 0,
+            length: 0,
+            direction: Direction::Right,
+            food: (0, 0),
+            seed: 0x1234_5678,
+            alive: false,
+            exited: false,
+        };
+        game.reset();
+        game
+    }
+
+    fn reset(&mut self) {
+        self.head = 0;
+        self.length = 1;
+        self.body[0] = (WIDTH / 2, HEIGHT / 2);
+        self.direction = Direction::Right;
+        self.alive = true;
+        self.exited = false;
+        self.place_food();
+    }
+
+    fn segment(&self, idx: usize) -> (u8, u8) {
+        self.body[(self.head + MAX_LENGTH - idx) % MAX_LENGTH]
+    }
+
+    fn occupies(&self, cell: (u8, u8)) -> bool {
+        (0..self.length).any(|idx| self.segment(idx) == cell)
+    }
+
+    fn next_random(&mut self) -> u32 {
+        self.seed = self.seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        self.seed >> 16
+    }
+
+    fn place_food(&mut self) {
+        loop {
+            let cell = (
+                (self.next_random() % WIDTH as u32) as u8,
+                (self.next_random() % HEIGHT as u32) as u8,
+            );
+            if !self.occupies(cell) {
+                self.food = cell;
+                return;
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        let (x, y) = self.segment(0);
+        let next = match self.direction {
+            Direction::Up if y > 0 => (x, y - 1),
+            Direction::Down if y + 1 < HEIGHT => (x, y + 1),
+            Direction::Left if x > 0 => (x - 1, y),
+            Direction::Right if x + 1 < WIDTH => (x + 1, y),
+            _ => {
+                self.alive = false;
+                return;
+            }
+        };
+
+        // The tail moves out of the way this frame unless we are about to grow
+        let grows = next == self.food && self.length < MAX_LENGTH;
+        let tail_cells = if grows { self.length } else { self.length - 1 };
+        if (0..tail_cells).any(|idx| self.segment(idx) == next) {
+            self.alive = false;
+            return;
+        }
+
+        self.head = (self.head + 1) % MAX_LENGTH;
+        self.body[self.head] = next;
+
+        if grows {
+            self.length += 1;
+            self.place_food();
+        }
+    }
+}
+
+impl Game for SnakeGame {
+    fn start(&mut self, _settings: &Settings) {
+        self.reset();
+    }
+
+    fn update(&mut self, input: &Input, _console: &mut Console) {
+        if input.chord == Some(Chord::AB) {
+            self.exited = true;
+            return;
+        }
+        if !self.alive {
+            if input.pressed.held(Button::B) {
+                self.reset();
+            } else if input.pressed.held(Button::A) {
+                self.exited = true;
+            }
+            return;
+        }
+
+        for (button, direction) in [
+            (Button::Up, Direction::Up),
+            (Button::Down, Direction::Down),
+            (Button::Left, Direction::Left),
+            (Button::Right, Direction::Right),
+        ] {
+            if input.pressed.held(button) && direction != self.direction.opposite() {
+                self.direction = direction;
+            }
+        }
+
+        self.advance();
+    }
+
+    fn draw(&self, displays: &mut Displays) {
+        let canvas = &mut *displays.main;
+        let (origin_x, origin_y) = ORIGIN;
+        canvas.draw_rect(
+            (origin_x - 1, origin_y - 1),
+            (
+                origin_x + WIDTH as u32 * CELL_SIZE,
+                origin_y + HEIGHT as u32 * CELL_SIZE,
+            ),
+        );
+
+        let mut fill = |(x, y): (u8, u8)| {
+            for px in 0..CELL_SIZE {
+                for py in 0..CELL_SIZE {
+                    canvas.set_pixel(
+                        origin_x + x as u32 * CELL_SIZE + px,
+                        origin_y + y as u32 * CELL_SIZE + py,
+                        true,
+                    );
+                }
+            }
+        };
+
+        (0..self.length).for_each(|idx| fill(self.segment(idx)));
+        fill(self.food);
+
+        if !self.alive {
+            canvas.text("Game over", Point::new(0, 0));
+        }
+    }
+
+    fn exited(&self) -> bool {
+        self.exited
+    }
+}
@@ -0,0 +1,64 @@
+//! A minimal SNTP (RFC 4330) client used to set the wall clock once Wi-Fi is up.
+
+use crate::clock::WallClock;
+
+pub const SNTP_PORT: u16 = 123;
+pub const PACKET_LEN: usize = 48;
+/// time.google.com, given by address as there is no DNS client.
+pub const SERVER: [u8; 4] = [216, 239, 35, 0];
+/// How long to wait for the server to answer, stalling the launcher meanwhile.
+pub const TIMEOUT_US: u64 = 500_000;
+/// How long to wait before asking again when the server did not answer.
+pub const RETRY_MS: u64 = 60_000;
+
+// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_TO_UNIX_SECONDS: u32 = 2_208_988_800;
+
+const VERSION: u8 = 4;
+const MODE_CLIENT: u8 = 3;
+const MODE_SERVER: u8 = 4;
+
+/// The UDP socket used to reach the time server, implemented by the Wi-Fi stack.
+pub trait UdpTransport {
+    type Error;
+
+    fn send(&mut self, port: u16, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Wait for a datagram, returning the number of bytes received or None on timeout.
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, Self::Error>;
+}
+
+pub fn request() -> [u8; PACKET_LEN] {
+    let mut packet = [0; PACKET_LEN];
+    packet[0] = (VERSION << 3) | MODE_CLIENT;
+    packet
+}
+
+/// Extract the server transmit time from a response as seconds since the Unix epoch. Returns
+/// None for anything that is not a valid server reply, including kiss-o'-death packets.
+pub fn parse_response(packet: &[u8]) -> Option<u32> {
+    if packet.len() < PACKET_LEN || packet[0] & 0x7 != MODE_SERVER || packet[1] == 0 {
+        return None;
+    }
+
+    let ntp_seconds = u32::from_be_bytes([packet[40], packet[41], packet[42], packet[43]]);
+    ntp_seconds.checked_sub(NTP_TO_UNIX_SECONDS)
+}
+
+/// Query the time server once and set the wall clock from the reply. The clock is left
+/// counting uptime if the server does not answer, so callers can simply retry later.
+pub fn sync<T: UdpTransport>(transport: &mut T, clock: &mut WallClock) -> Result<bool, T::Error> {
+    transport.send(SNTP_PORT, &request())?;
+
+    let mut buffer = [0; PACKET_LEN];
+    match transport.receive(&mut buffer)? {
+        Some(len) => match parse_response(&buffer[..len]) {
+            Some(unix_seconds) => {
+                clock.set_unix_time(unix_seconds);
+                Ok(true)
+            }
+            None => Ok(false),
+        },
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,79 @@
+//! Streams the board to a spectator over TCP, as tetris_core::spectate messages carried in
+//! tetris_net frames, so the game can be watched from a browser or the desktop frontend.
+
+use crate::net::TcpConnection;
+use alloc::vec::Vec;
+use tetris_core::spectate::Encoder;
+use tetris_core::tetris::Tetris;
+use tetris_net::frame::{encode_framed, MAX_MESSAGE_LEN};
+use tetris_net::{Message, PROTOCOL_VERSION};
+
+pub use tetris_net::SPECTATE_PORT;
+
+/// Something a game can send its state to once per frame.
+pub trait Spectate {
+    fn send(&mut self, tetris: &Tetris);
+}
+
+pub struct SpectatorStream<C: TcpConnection> {
+    connection: C,
+    encoder: Encoder,
+    /// The spectate message for the current frame, before it is framed.
+    message: Vec<u8>,
+    buffer: [u8; MAX_MESSAGE_LEN + 1],
+    /// Whether the spectator has been sent a hello since connecting.
+    greeted: bool,
+}
+
+impl<C: TcpConnection> SpectatorStream<C> {
+    pub fn new(connection: C) -> Self {
+        SpectatorStream {
+            connection,
+            encoder: Encoder::new(),
+            message: Vec::new(),
+            buffer: [0; MAX_MESSAGE_LEN + 1],
+            greeted: false,
+        }
+    }
+
+    fn write(&mut self, message: &Message) -> Result<(), ()> {
+        let len = encode_framed(message, &mut self.buffer).map_err(|_| ())?;
+        self.connection.write(&self.buffer[..len]).map_err(|_| ())
+    }
+
+    fn send_frame(&mut self, tetris: &Tetris) -> Result<(), ()> {
+        if !self.greeted {
+            self.write(&Message::Hello {
+                version: PROTOCOL_VERSION,
+                seed: 0,
+            })?;
+            self.greeted = true;
+        }
+
+        let mut message = core::mem::take(&mut self.message);
+        message.clear();
+        self.encoder.encode(tetris, &mut message);
+        // The encoder's length prefix is replaced by the frame's own
+        let result = self.write(&Message::Spectate(&message[1..]));
+        self.message = message;
+        result
+    }
+}
+
+impl<C: TcpConnection> Spectate for SpectatorStream<C> {
+    /// Each new spectator is greeted and then starts from a keyframe. A failed write drops the
+    /// spectator rather than holding up the game.
+    fn send(&mut self, tetris: &Tetris) {
+        if !self.connection.is_connected() {
+            self.encoder.reset();
+            self.greeted = false;
+            return;
+        }
+
+        if self.send_frame(tetris).is_err() {
+            self.connection.close();
+            self.encoder.reset();
+            self.greeted = false;
+        }
+    }
+}
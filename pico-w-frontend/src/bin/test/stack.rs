@@ -0,0 +1,454 @@
+//! The TCP/IP stack over the wireless chip: smoltcp's interface fed frames by the chip's driver,
+//! with an address from DHCP. Like the driver it is polled from the main loop rather than run as
+//! a task.
+
+use crate::net::TcpConnection;
+use crate::sntp::UdpTransport;
+use crate::wifi::{WifiStatus, WIFI_STATUS};
+use crate::wireless::{now_us, Cyw43};
+use core::cell::RefCell;
+use core::slice::IterMut;
+use core::task::{Context, Waker};
+use cyw43::NetDriver;
+use embassy_net_driver::{self as driver, Driver, LinkState};
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet, SocketStorage};
+use smoltcp::phy::{self, DeviceCapabilities, Medium};
+use smoltcp::socket::{dhcpv4, tcp, udp};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr, IpEndpoint, Ipv4Address};
+
+/// Sockets for the spectators, the remote control, netplay and the crash report.
+const TCP_SOCKETS: usize = 4;
+/// Sockets for the time server and service discovery.
+const UDP_SOCKETS: usize = 2;
+/// Slots for every socket, the DHCP client's included.
+const SOCKETS: usize = 1 + TCP_SOCKETS + UDP_SOCKETS;
+/// Bytes each TCP socket buffers each way, enough for the remote control's page in one write.
+const TCP_BUFFER: usize = 2048;
+/// The ports connections to other devices are made from, the dynamic ports of RFC 6335.
+const LOCAL_PORTS: core::ops::RangeInclusive<u16> = 49152..=65535;
+/// Datagrams each UDP socket buffers each way, and the bytes they share.
+const UDP_PACKETS: usize = 4;
+const UDP_BUFFER: usize = 1024;
+
+fn now() -> Instant {
+    Instant::from_micros(now_us() as i64)
+}
+
+/// The stack is polled over and over anyway, so the driver is asked for frames with a waker
+/// that does nothing.
+fn context() -> Context<'static> {
+    Context::from_waker(Waker::noop())
+}
+
+struct Received<T>(T);
+
+impl<T: driver::RxToken> phy::RxToken for Received<T> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, f: F) -> R {
+        self.0.consume(f)
+    }
+}
+
+struct Transmit<T>(T);
+
+impl<T: driver::TxToken> phy::TxToken for Transmit<T> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(self, len: usize, f: F) -> R {
+        self.0.consume(len, f)
+    }
+}
+
+/// The chip's driver as a device smoltcp can poll.
+struct Device<'d, D>(&'d mut D);
+
+impl<D: Driver> phy::Device for Device<'_, D> {
+    type RxToken<'a>
+        = Received<D::RxToken<'a>>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = Transmit<D::TxToken<'a>>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let (rx, tx) = self.0.receive(&mut context())?;
+        Some((Received(rx), Transmit(tx)))
+    }
+
+    fn transmit(&mut self, _: Instant) -> Option<Self::TxToken<'_>> {
+        self.0.transmit(&mut context()).map(Transmit)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.medium = Medium::Ethernet;
+        capabilities.max_transmission_unit = self.0.capabilities().max_transmission_unit;
+        capabilities
+    }
+}
+
+/// Why a socket could not be used.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SocketError {
+    /// Not connected, or there is no address to send from yet.
+    Closed,
+    /// No room to queue the data until the stack sends it.
+    Full,
+}
+
+struct TcpMemory {
+    rx: [u8; TCP_BUFFER],
+    tx: [u8; TCP_BUFFER],
+}
+
+impl TcpMemory {
+    const EMPTY: Self = TcpMemory {
+        rx: [0; TCP_BUFFER],
+        tx: [0; TCP_BUFFER],
+    };
+}
+
+struct UdpMemory {
+    rx_metadata: [udp::PacketMetadata; UDP_PACKETS],
+    rx: [u8; UDP_BUFFER],
+    tx_metadata: [udp::PacketMetadata; UDP_PACKETS],
+    tx: [u8; UDP_BUFFER],
+}
+
+impl UdpMemory {
+    const EMPTY: Self = UdpMemory {
+        rx_metadata: [udp::PacketMetadata::EMPTY; UDP_PACKETS],
+        rx: [0; UDP_BUFFER],
+        tx_metadata: [udp::PacketMetadata::EMPTY; UDP_PACKETS],
+        tx: [0; UDP_BUFFER],
+    };
+}
+
+/// The memory the sockets live in, kept by the caller for as long as the network is.
+pub struct Buffers<'a> {
+    sockets: [SocketStorage<'a>; SOCKETS],
+    tcp: [TcpMemory; TCP_SOCKETS],
+    udp: [UdpMemory; UDP_SOCKETS],
+}
+
+impl Buffers<'_> {
+    pub fn new() -> Self {
+        Buffers {
+            sockets: [SocketStorage::EMPTY; SOCKETS],
+            tcp: [TcpMemory::EMPTY; TCP_SOCKETS],
+            udp: [UdpMemory::EMPTY; UDP_SOCKETS],
+        }
+    }
+}
+
+struct Stack<'a> {
+    device: NetDriver<'a>,
+    iface: Interface,
+    sockets: SocketSet<'a>,
+    dhcp: SocketHandle,
+    /// The memory of the TCP sockets not yet opened.
+    tcp_memory: IterMut<'a, TcpMemory>,
+    /// The TCP sockets opened and the ports they listen on.
+    listeners: [Option<(SocketHandle, u16)>; TCP_SOCKETS],
+    /// The port the next connection to another device is made from.
+    local_port: u16,
+    /// The memory of the UDP sockets not yet opened.
+    udp_memory: IterMut<'a, UdpMemory>,
+    /// The signal strength of the network last joined, shown once it gives an address.
+    rssi: i8,
+    link_up: bool,
+}
+
+impl Stack<'_> {
+    fn deconfigure(&mut self) {
+        self.iface.update_ip_addrs(|addresses| addresses.clear());
+        self.iface.routes_mut().remove_default_ipv4_route();
+    }
+}
+
+/// The network, shared by everything that uses it.
+pub struct Network<'a> {
+    stack: RefCell<Stack<'a>>,
+}
+
+impl<'a> Network<'a> {
+    /// A network over the chip's device with no address yet. seed randomises the ports and
+    /// sequence numbers chosen.
+    pub fn new(mut device: NetDriver<'a>, buffers: &'a mut Buffers<'a>, seed: u64) -> Self {
+        // The chip is an Ethernet device
+        let driver::HardwareAddress::Ethernet(mac) = device.hardware_address() else {
+            unreachable!()
+        };
+        let mut config = Config::new(HardwareAddress::Ethernet(EthernetAddress(mac)));
+        config.random_seed = seed;
+        let iface = Interface::new(config, &mut Device(&mut device), now());
+
+        let Buffers { sockets, tcp, udp } = buffers;
+        let mut sockets = SocketSet::new(&mut sockets[..]);
+        let dhcp = sockets.add(dhcpv4::Socket::new());
+        Network {
+            stack: RefCell::new(Stack {
+                device,
+                iface,
+                sockets,
+                dhcp,
+                tcp_memory: tcp.iter_mut(),
+                listeners: [None; TCP_SOCKETS],
+                local_port: *LOCAL_PORTS.start() + (seed % 16384) as u16,
+                udp_memory: udp.iter_mut(),
+                rssi: 0,
+                link_up: false,
+            }),
+        }
+    }
+
+    /// Move whatever frames are waiting through the stack and keep the address up to date. The
+    /// main loop calls this each time it polls the chip's driver.
+    pub fn poll(&self) {
+        let mut stack = self.stack.borrow_mut();
+        let stack = &mut *stack;
+        let link_up = stack.device.link_state(&mut context()) == LinkState::Up;
+        if link_up != stack.link_up {
+            stack.link_up = link_up;
+            // Ask for an address afresh on the next network, dropping the old one
+            stack.sockets.get_mut::<dhcpv4::Socket>(stack.dhcp).reset();
+        }
+
+        // Listen again once each client has gone, so that the next can connect
+        for &(handle, port) in stack.listeners.iter().flatten() {
+            let socket = stack.sockets.get_mut::<tcp::Socket>(handle);
+            match socket.state() {
+                tcp::State::CloseWait => socket.close(),
+                // No stray segments to wait out on a port that is only listened on
+                tcp::State::TimeWait => socket.abort(),
+                tcp::State::Closed => {
+                    let _ = socket.listen(port);
+                }
+                _ => {}
+            }
+        }
+
+        stack
+            .iface
+            .poll(now(), &mut Device(&mut stack.device), &mut stack.sockets);
+
+        match stack.sockets.get_mut::<dhcpv4::Socket>(stack.dhcp).poll() {
+            Some(dhcpv4::Event::Configured(config)) => {
+                stack.iface.update_ip_addrs(|addresses| {
+                    addresses.clear();
+                    let _ = addresses.push(IpCidr::Ipv4(config.address));
+                });
+                match config.router {
+                    Some(router) => {
+                        let _ = stack.iface.routes_mut().add_default_ipv4_route(router);
+                    }
+                    None => {
+                        stack.iface.routes_mut().remove_default_ipv4_route();
+                    }
+                }
+                WIFI_STATUS.send(WifiStatus::Connected { rssi: stack.rssi });
+            }
+            Some(dhcpv4::Event::Deconfigured) => {
+                stack.deconfigure();
+                // Losing the link is reported by the task joining the network
+                if stack.link_up {
+                    WIFI_STATUS.send(WifiStatus::Connecting);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Whether the chip is joined to the network, read from the driver rather than as of the
+    /// last poll.
+    pub fn is_link_up(&self) -> bool {
+        let mut stack = self.stack.borrow_mut();
+        stack.device.link_state(&mut context()) == LinkState::Up
+    }
+
+    /// Note the signal strength of the network just joined, for the status shown once it gives
+    /// an address.
+    pub fn joined(&self, rssi: i8) {
+        self.stack.borrow_mut().rssi = rssi;
+    }
+
+    /// The address DHCP gave, None until it has.
+    pub fn address(&self) -> Option<[u8; 4]> {
+        let stack = self.stack.borrow();
+        stack.iface.ipv4_addr().map(|address| address.0)
+    }
+
+    /// A TCP socket listening on port, serving one client at a time. Panics if every TCP socket
+    /// is taken.
+    pub fn listen<'n>(&'n self, port: u16) -> TcpPort<'n, 'a> {
+        let mut stack = self.stack.borrow_mut();
+        let stack = &mut *stack;
+        let memory = stack
+            .tcp_memory
+            .next()
+            .expect("more TCP sockets than TCP_SOCKETS");
+        let mut socket = tcp::Socket::new(
+            tcp::SocketBuffer::new(&mut memory.rx[..]),
+            tcp::SocketBuffer::new(&mut memory.tx[..]),
+        );
+        // Only fails for port 0
+        let _ = socket.listen(port);
+        let handle = stack.sockets.add(socket);
+        if let Some(listener) = stack
+            .listeners
+            .iter_mut()
+            .find(|listener| listener.is_none())
+        {
+            *listener = Some((handle, port));
+        }
+        TcpPort {
+            network: self,
+            handle,
+        }
+    }
+
+    /// A UDP socket on port sending to remote, that waits up to timeout_us for a reply while
+    /// polling the chip's driver through cyw43. A multicast remote is joined so that the rest
+    /// of the group can be heard. Panics if every UDP socket is taken.
+    pub fn udp<'n, 'c>(
+        &'n self,
+        port: u16,
+        remote: [u8; 4],
+        timeout_us: u64,
+        cyw43: Cyw43<'c>,
+    ) -> UdpPort<'n, 'a, 'c> {
+        let mut stack = self.stack.borrow_mut();
+        let stack = &mut *stack;
+        let remote = Ipv4Address(remote);
+        if remote.is_multicast() {
+            // Only fails if the group table is full
+            let _ = stack
+                .iface
+                .join_multicast_group(&mut Device(&mut stack.device), remote, now());
+        }
+        let memory = stack
+            .udp_memory
+            .next()
+            .expect("more UDP sockets than UDP_SOCKETS");
+        let mut socket = udp::Socket::new(
+            udp::PacketBuffer::new(&mut memory.rx_metadata[..], &mut memory.rx[..]),
+            udp::PacketBuffer::new(&mut memory.tx_metadata[..], &mut memory.tx[..]),
+        );
+        // Only fails for port 0
+        let _ = socket.bind(port);
+        UdpPort {
+            network: self,
+            cyw43,
+            handle: stack.sockets.add(socket),
+            remote,
+            timeout_us,
+        }
+    }
+}
+
+/// A TCP socket listening for clients, which the main loop's polls of the network connect and
+/// send for.
+pub struct TcpPort<'n, 'a> {
+    network: &'n Network<'a>,
+    handle: SocketHandle,
+}
+
+impl TcpConnection for TcpPort<'_, '_> {
+    type Error = SocketError;
+
+    fn is_connected(&self) -> bool {
+        let mut stack = self.network.stack.borrow_mut();
+        let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+        socket.state() == tcp::State::Established
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, SocketError> {
+        let mut stack = self.network.stack.borrow_mut();
+        let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+        socket.recv_slice(buffer).map_err(|_| SocketError::Closed)
+    }
+
+    /// Queue all of data or none of it, so that a message is never cut short.
+    fn write(&mut self, data: &[u8]) -> Result<(), SocketError> {
+        let mut stack = self.network.stack.borrow_mut();
+        let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+        if !socket.may_send() {
+            return Err(SocketError::Closed);
+        }
+        if socket.send_capacity() - socket.send_queue() < data.len() {
+            return Err(SocketError::Full);
+        }
+        socket
+            .send_slice(data)
+            .map(|_| ())
+            .map_err(|_| SocketError::Closed)
+    }
+
+    /// Close once whatever was written has been sent, after which the socket listens again.
+    fn close(&mut self) {
+        let mut stack = self.network.stack.borrow_mut();
+        stack.sockets.get_mut::<tcp::Socket>(self.handle).close();
+    }
+
+    fn connect(&mut self, address: [u8; 4], port: u16) -> Result<(), SocketError> {
+        let mut stack = self.network.stack.borrow_mut();
+        let stack = &mut *stack;
+        let local_port = stack.local_port;
+        stack.local_port = match local_port {
+            port if port == *LOCAL_PORTS.end() => *LOCAL_PORTS.start(),
+            port => port + 1,
+        };
+        let socket = stack.sockets.get_mut::<tcp::Socket>(self.handle);
+        socket.abort();
+        let remote = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address(address)), port);
+        socket
+            .connect(stack.iface.context(), remote, local_port)
+            .map_err(|_| SocketError::Closed)
+    }
+}
+
+/// A UDP socket sending to a single host, such as the time server.
+pub struct UdpPort<'n, 'a, 'c> {
+    network: &'n Network<'a>,
+    cyw43: Cyw43<'c>,
+    handle: SocketHandle,
+    remote: Ipv4Address,
+    timeout_us: u64,
+}
+
+impl UdpPort<'_, '_, '_> {
+    fn try_receive(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        let mut stack = self.network.stack.borrow_mut();
+        let socket = stack.sockets.get_mut::<udp::Socket>(self.handle);
+        socket.recv_slice(buffer).ok().map(|(len, _)| len)
+    }
+}
+
+impl UdpTransport for UdpPort<'_, '_, '_> {
+    type Error = SocketError;
+
+    fn send(&mut self, port: u16, data: &[u8]) -> Result<(), SocketError> {
+        let mut stack = self.network.stack.borrow_mut();
+        let socket = stack.sockets.get_mut::<udp::Socket>(self.handle);
+        socket
+            .send_slice(data, (IpAddress::Ipv4(self.remote), port))
+            .map_err(|error| match error {
+                udp::SendError::BufferFull => SocketError::Full,
+                udp::SendError::Unaddressable => SocketError::Closed,
+            })
+    }
+
+    fn receive(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, SocketError> {
+        let deadline = now_us() + self.timeout_us;
+        loop {
+            if let Some(len) = self.try_receive(buffer) {
+                return Ok(Some(len));
+            }
+            if now_us() >= deadline {
+                return Ok(None);
+            }
+            self.cyw43.poll();
+            self.network.poll();
+        }
+    }
+}
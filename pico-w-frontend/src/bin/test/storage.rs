@@ -0,0 +1,74 @@
+//! The settings and high scores are saved to the last sector of flash, so that they survive a
+//! reset. The sector is left out of the firmware's flash in memory.x.
+
+use crate::flash::{read_page, write_sector, RomFlash, FLASH_SIZE, PAGE_SIZE, SECTOR_SIZE};
+use crate::settings::Settings;
+use cortex_m::interrupt::free;
+use tetris_core::high_score::HighScores;
+
+/// The save sector's offset into the flash.
+const SAVE_OFFSET: u32 = FLASH_SIZE - SECTOR_SIZE;
+
+/// Marks a sector holding a save, erased flash reads as 0xff. Changed whenever the layout of the
+/// page changes, so that an old save is ignored rather than misread.
+const MAGIC: &[u8; 4] = b"SAV5";
+
+/// Where the high scores start in the page, after the magic and the settings.
+const HIGH_SCORES_AT: usize = 4 + Settings::ENCODED_LEN;
+
+/// The page as it is laid out in flash: the magic, the settings, then the high scores.
+fn to_page(settings: &Settings, high_scores: &HighScores) -> [u8; PAGE_SIZE] {
+    let mut page = [0xff; PAGE_SIZE];
+    page[..4].copy_from_slice(MAGIC);
+    page[4..HIGH_SCORES_AT].copy_from_slice(&settings.to_bytes());
+    page[HIGH_SCORES_AT..HIGH_SCORES_AT + HighScores::ENCODED_LEN]
+        .copy_from_slice(&high_scores.to_bytes());
+    page
+}
+
+fn from_page(page: &[u8; PAGE_SIZE]) -> Option<(Settings, HighScores)> {
+    if &page[..4] != MAGIC {
+        return None;
+    }
+    let mut settings = [0; Settings::ENCODED_LEN];
+    settings.copy_from_slice(&page[4..HIGH_SCORES_AT]);
+    let mut high_scores = [0; HighScores::ENCODED_LEN];
+    high_scores.copy_from_slice(&page[HIGH_SCORES_AT..HIGH_SCORES_AT + HighScores::ENCODED_LEN]);
+    Some((
+        Settings::from_bytes(&settings),
+        HighScores::from_bytes(&high_scores),
+    ))
+}
+
+/// Keeps the settings and high scores in flash, writing them only when they change so as not to
+/// wear it out.
+pub struct Storage {
+    /// The page last read from or written to flash.
+    saved: [u8; PAGE_SIZE],
+}
+
+impl Storage {
+    /// The settings and high scores saved on an earlier boot, or the defaults and an empty table
+    /// if there are none.
+    pub fn load() -> (Self, Settings, HighScores) {
+        let page = read_page(SAVE_OFFSET);
+        let (settings, high_scores) = from_page(page).unwrap_or_default();
+        (Storage { saved: *page }, settings, high_scores)
+    }
+
+    /// Save the settings and high scores if they have changed since they were loaded or last
+    /// saved. Writing stalls the handheld for the tens of milliseconds an erase takes, so it is
+    /// done from the launcher rather than during a game.
+    ///
+    /// Builds with the HUB75 panel leave them unsaved, as core1 scans the panel from flash and
+    /// would fault with the flash taken from under it.
+    pub fn save(&mut self, settings: &Settings, high_scores: &HighScores) {
+        let page = to_page(settings, high_scores);
+        if cfg!(feature = "hub75") || page == self.saved {
+            return;
+        }
+        let rom = RomFlash::lookup();
+        free(|_| unsafe { write_sector(&rom, SAVE_OFFSET, Some(&page)) });
+        self.saved = page;
+    }
+}
@@ -0,0 +1,719 @@
+use crate::audio::{Audio, SoundEffect};
+use crate::clock::Timestamp;
+use crate::entropy::RoscEntropy;
+use crate::game::{Canvas, Console, Displays, Game};
+use crate::input::{Button, Chord, DoubleTap, Input};
+use crate::music::{Music, THEME};
+use crate::settings::{LayoutTheme, LongPressAction, Settings};
+use crate::text::TextBuffer;
+use core::fmt::Write;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::{Point, RgbColor};
+use frontend_common::action::{Action, ActionMapper};
+use frontend_common::initials::InitialsEntry;
+use tetris_core::daily::Date;
+use tetris_core::difficulty::Adaptive;
+use tetris_core::grid::Grid;
+use tetris_core::high_score::HighScore;
+use tetris_core::piece::PieceSelector;
+use tetris_core::session::PlayStats;
+use tetris_core::tetris::{
+    GameSummary, Gravity, Rules, Tetris, TetrisState, TopOut, BUFFER_ROWS, GRID_SIZE,
+    VISIBLE_HEIGHT,
+};
+#[cfg(feature = "wifi")]
+use tetris_core::versus::Duel;
+#[cfg(feature = "wifi")]
+use tetris_net::lobby::Match;
+
+/// Buttons that act for as long as they are held.
+const BINDINGS: ActionMapper<'static, Button> = ActionMapper::new(&[
+    (Button::Left, Action::Left),
+    (Button::Right, Action::Right),
+    (Button::Up, Action::Hold),
+    (Button::Down, Action::SoftDrop),
+]);
+
+/// The playfield of big mode, half as wide and tall so that it fills the usual space with every
+/// cell drawn twice the size.
+const BIG_PLAYFIELD: (usize, usize) = (GRID_SIZE.0 / 2, VISIBLE_HEIGHT / 2 + BUFFER_ROWS);
+
+/// Frames left or right must be held after the piece first moves before it slides, and then
+/// between each step, so that a quick tap at the frame rate moves it a single cell.
+const DAS_FRAMES: u8 = 2;
+const ARR_FRAMES: u8 = 1;
+
+/// How far the assist setting may ease gravity off or pick it up from one cell an update.
+const ASSIST_MIN_GRAVITY: Gravity = Gravity::from_ratio(1, 4);
+const ASSIST_MAX_GRAVITY: Gravity = Gravity::SOFT_DROP;
+
+/// Tetris running on the console, with the theme and sound effects playing on the audio output.
+pub struct TetrisGame {
+    tetris: Tetris,
+    entropy: RoscEntropy,
+    music: Music,
+    hard_drop_gesture: DoubleTap,
+    /// The layout picked in the launcher, kept from the last update for drawing.
+    layout: LayoutTheme,
+    /// Adjusts gravity to the player while the assist setting is on.
+    adaptive: Adaptive,
+    /// Copied from the settings each update, so the side display can show piece statistics.
+    piece_stats: bool,
+    /// Initials being entered for a score that made the high score table, and the score.
+    new_high_score: Option<(InitialsEntry, u32)>,
+    /// The garbage of a versus match against a player who joined over Wi-Fi.
+    #[cfg(feature = "wifi")]
+    duel: Option<Duel>,
+    exited: bool,
+    /// Copied from the settings as each game starts, the stack is left undrawn while set.
+    invisible: bool,
+    /// Copied from the settings as each game starts, the assist is left out of 20G games so that
+    /// it does not ease their gravity off.
+    twenty_g: bool,
+}
+
+impl TetrisGame {
+    pub fn new(mut entropy: RoscEntropy) -> Self {
+        TetrisGame {
+            tetris: Tetris::new_with_entropy(&mut entropy),
+            entropy,
+            music: Music::new(THEME),
+            hard_drop_gesture: DoubleTap::default(),
+            layout: LayoutTheme::default(),
+            adaptive: Adaptive::new(ASSIST_MIN_GRAVITY, ASSIST_MAX_GRAVITY),
+            piece_stats: false,
+            new_high_score: None,
+            #[cfg(feature = "wifi")]
+            duel: None,
+            exited: false,
+            invisible: false,
+            twenty_g: false,
+        }
+    }
+
+    /// The score of the game being played, for crash reports.
+    pub fn score(&self) -> u32 {
+        match self.tetris {
+            Tetris::Running(ref state) | Tetris::Paused(ref state) => state.score as u32,
+            Tetris::Finished(summary) => summary.score as u32,
+        }
+    }
+
+    /// Start the match agreed with a player who joined, in place of whatever was being played.
+    #[cfg(feature = "wifi")]
+    fn start_match(&mut self, agreed: Match, settings: &Settings) {
+        self.start(settings);
+        self.tetris = Tetris::new_with_seed(agreed.seed);
+        self.tetris.set_rules(agreed.config.rules);
+        self.duel = Some(Duel::new(agreed.garbage_seed()));
+    }
+
+    /// The d-pad cycles and moves between letters and A keeps each one, the score is added to
+    /// the table once the last is kept.
+    fn enter_initials(&mut self, input: &Input, console: &mut Console) {
+        let Some((ref mut entry, score)) = self.new_high_score else {
+            return;
+        };
+        for button in input.pressed.iter() {
+            match button {
+                Button::Up => entry.up(),
+                Button::Down => entry.down(),
+                Button::Left => entry.left(),
+                Button::Right => entry.right(),
+                Button::A => {
+                    if let Some(initials) = entry.confirm() {
+                        let (date, uptime) = match console.clock.now() {
+                            Timestamp::Unix(seconds) => (
+                                Some(Date::from_days_since_epoch(seconds / (24 * 60 * 60))),
+                                None,
+                            ),
+                            Timestamp::Uptime(seconds) => (None, Some(seconds)),
+                        };
+                        console.high_scores.insert(HighScore {
+                            initials,
+                            score,
+                            date,
+                            uptime,
+                        });
+                        console.settings.initials = initials;
+                        self.new_high_score = None;
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The side display shows the score and next piece, or the piece statistics if they are
+    /// turned on.
+    fn draw_side(&self, side: &mut dyn Canvas, state: &TetrisState) {
+        match self.piece_stats {
+            true => draw_piece_stats(side, state.stats()),
+            false => draw_info(side, state, (0, 0)),
+        }
+    }
+
+    fn update_music(&mut self, audio: &mut dyn Audio) {
+        match self.tetris {
+            Tetris::Running(ref state) => {
+                self.music.resume(audio);
+                self.music.update(audio, stack_is_high(state));
+            }
+            Tetris::Paused(_) => self.music.pause(audio),
+            Tetris::Finished(_) => {
+                self.music.pause(audio);
+                self.music.restart();
+            }
+        }
+    }
+}
+
+/// True once the stack reaches the top half of the playfield, used to speed up the music.
+fn stack_is_high(state: &TetrisState) -> bool {
+    let half = state.visible_height() / 2;
+    (half..state.grid.height).any(|y| (0..state.grid.width).any(|x| state.grid[(x, y)]))
+}
+
+/// Locked cells no longer know which piece they came from so the stack is drawn in one color.
+const STACK_COLOR: Rgb888 = Rgb888::new(96, 96, 96);
+
+/// The usual guideline color of each piece, shown on color displays.
+fn piece_color(kind: PieceSelector) -> Rgb888 {
+    match kind {
+        PieceSelector::Line => Rgb888::CYAN,
+        PieceSelector::J => Rgb888::BLUE,
+        PieceSelector::L => Rgb888::new(255, 96, 0),
+        PieceSelector::O => Rgb888::YELLOW,
+        PieceSelector::S => Rgb888::GREEN,
+        PieceSelector::T => Rgb888::MAGENTA,
+        PieceSelector::Z => Rgb888::RED,
+    }
+}
+
+/// How many times larger each cell is drawn, so that the half size playfield of big mode fills
+/// the same space as the usual one.
+fn zoom(state: &TetrisState) -> usize {
+    (GRID_SIZE.0 / state.grid.width).max(1)
+}
+
+/// Draw the playfield with the stack and falling piece in their colors, each cell scale pixels
+/// times the zoom. The stack is left out in invisible mode.
+fn draw_playfield(
+    canvas: &mut dyn Canvas,
+    state: &TetrisState,
+    (x_off, y_off): (usize, usize),
+    (scale_x, scale_y): (usize, usize),
+    invisible: bool,
+) {
+    let (scale_x, scale_y) = (scale_x * zoom(state), scale_y * zoom(state));
+    if !invisible {
+        canvas.set_color(STACK_COLOR);
+        state.draw_game_grid(
+            |x, y, v| {
+                canvas.set_pixel(x as u32, y as u32, v);
+            },
+            (x_off, y_off),
+            (scale_x, scale_y),
+        );
+    }
+
+    // Draw over the falling piece in its own color
+    let Some(falling) = state.piece_in_play() else {
+        canvas.set_color(Rgb888::WHITE);
+        return;
+    };
+    canvas.set_color(piece_color(falling.kind()));
+    let piece = falling.current_rotation();
+    for x in 0..piece.width {
+        for y in 0..piece.height {
+            let (mut grid_x, grid_y) = (falling.x + x, falling.y + y);
+            if state.rules.wrap {
+                grid_x %= state.grid.width;
+            }
+            if piece[(x, y)] && grid_x < state.grid.width && grid_y < state.visible_height() {
+                let canvas_x = grid_x * scale_x + x_off;
+                let canvas_y = (state.visible_height() - 1 - grid_y) * scale_y + y_off;
+                for px in 0..scale_x {
+                    for py in 0..scale_y {
+                        canvas.set_pixel((canvas_x + px) as u32, (canvas_y + py) as u32, true);
+                    }
+                }
+            }
+        }
+    }
+    canvas.set_color(Rgb888::WHITE);
+}
+
+/// How the playfield and score are arranged, picked from the size of the main display.
+enum Layout {
+    /// One cell per pixel with no text, for LED matrices and panels.
+    Tiny,
+    /// Double size cells beside the score, for small LCDs such as the Nokia 5110.
+    Compact,
+    /// The 128x64 OLED.
+    Full,
+}
+
+impl Layout {
+    fn for_size(width: u32, height: u32) -> Self {
+        if width >= 128 && height >= 64 {
+            Layout::Full
+        } else if width >= 80 && height >= 44 {
+            Layout::Compact
+        } else {
+            Layout::Tiny
+        }
+    }
+}
+
+/// Draw the playfield one cell per pixel aligned to the bottom of the display, optionally with
+/// the next piece beside it when there is room.
+fn draw_tiny(canvas: &mut dyn Canvas, state: &TetrisState, with_next: bool, invisible: bool) {
+    let (width, height) = canvas.size();
+    let field_width = state.grid.width * zoom(state);
+    let offset = (
+        (width as usize).saturating_sub(field_width) / 2,
+        (height as usize).saturating_sub(state.visible_height() * zoom(state)),
+    );
+    draw_playfield(canvas, state, offset, (1, 1), invisible);
+
+    let next_x = (offset.0 + field_width + 2) as u32;
+    if with_next && next_x + 4 <= width {
+        canvas.set_color(piece_color(state.next_piece.kind()));
+        draw_piece(
+            canvas,
+            state.next_piece.current_rotation(),
+            (next_x, offset.1 as u32),
+            1,
+        );
+        canvas.set_color(Rgb888::WHITE);
+    }
+}
+
+/// Draw a piece with its bottom left cell at origin, each cell scale pixels square.
+fn draw_piece(canvas: &mut dyn Canvas, piece: &Grid, (origin_x, origin_y): (u32, u32), scale: u32) {
+    for x in 0..piece.width {
+        for y in 0..piece.height {
+            if piece[(x, y)] {
+                let (canvas_x, canvas_y) = (x as u32, (piece.height - 1 - y) as u32);
+                for px in 0..scale {
+                    for py in 0..scale {
+                        canvas.set_pixel(
+                            origin_x + canvas_x * scale + px,
+                            origin_y + canvas_y * scale + py,
+                            true,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A canvas turned a quarter turn clockwise onto another and scaled up, so that the playfield
+/// can be drawn across the display. The top of the playfield, height pixels tall, is on the
+/// right.
+struct Sideways<'a> {
+    canvas: &'a mut dyn Canvas,
+    origin: (u32, u32),
+    height: u32,
+    scale: u32,
+}
+
+impl Sideways<'_> {
+    fn turn(&self, x: u32, y: u32) -> Option<(u32, u32)> {
+        let turned_x = self.height.checked_sub(y + 1)?;
+        Some((
+            self.origin.0 + turned_x * self.scale,
+            self.origin.1 + x * self.scale,
+        ))
+    }
+}
+
+impl Canvas for Sideways<'_> {
+    fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        let Some((x, y)) = self.turn(x, y) else {
+            return;
+        };
+        for px in 0..self.scale {
+            for py in 0..self.scale {
+                self.canvas.set_pixel(x + px, y + py, on);
+            }
+        }
+    }
+
+    fn draw_rect(&mut self, min: (u32, u32), max: (u32, u32)) {
+        if let (Some(corner), Some(opposite)) = (self.turn(min.0, max.1), self.turn(max.0, min.1)) {
+            let last = self.scale - 1;
+            self.canvas
+                .draw_rect(corner, (opposite.0 + last, opposite.1 + last));
+        }
+    }
+
+    /// Text is written the right way up.
+    fn text(&mut self, text: &str, point: Point) {
+        self.canvas.text(text, point);
+    }
+
+    fn set_color(&mut self, color: Rgb888) {
+        self.canvas.set_color(color);
+    }
+
+    fn size(&self) -> (u32, u32) {
+        (self.canvas.size().1 / self.scale, self.height)
+    }
+}
+
+/// Draw the playfield on its side with the score and pieces beside it.
+fn draw_sideways(canvas: &mut dyn Canvas, state: &TetrisState, invisible: bool) {
+    const SCALE: u32 = 3;
+    let zoom = zoom(state) as u32;
+    let (width, height) = (
+        state.grid.width as u32 * zoom,
+        state.visible_height() as u32 * zoom,
+    );
+    canvas.draw_rect((1, 16), (2 + height * SCALE, 17 + width * SCALE));
+    draw_playfield(
+        &mut Sideways {
+            canvas: &mut *canvas,
+            origin: (2, 17),
+            height,
+            scale: SCALE,
+        },
+        state,
+        (0, 0),
+        (1, 1),
+        invisible,
+    );
+    draw_info(canvas, state, (68, 9));
+}
+
+/// The initials entry screen, each letter spaced out with a mark under the one being changed.
+fn draw_initials_entry(canvas: &mut dyn Canvas, entry: &InitialsEntry, score: u32) {
+    let mut text = TextBuffer::new();
+    let _ = write!(text, "{}", score);
+    canvas.text("HIGH SCORE", Point::new(0, 10));
+    canvas.text(text.as_str(), Point::new(0, 22));
+    let initials = entry.as_str();
+    for index in 0..initials.len() {
+        let x = 12 * index as i32;
+        canvas.text(&initials[index..index + 1], Point::new(x, 40));
+        if index == entry.cursor() {
+            canvas.text("^", Point::new(x, 50));
+        }
+    }
+}
+
+/// The results of a finished game, headed by whether it reached its goal or topped out.
+fn draw_summary(canvas: &mut dyn Canvas, summary: &GameSummary) {
+    let heading = match summary.top_out {
+        TopOut::GoalReached => "COMPLETE",
+        TopOut::BlockOut | TopOut::LockOut | TopOut::Garbage => "GAME OVER",
+    };
+    canvas.text(heading, Point::new(0, 10));
+    let mut score = TextBuffer::new();
+    let _ = write!(score, "{}", summary.score);
+    canvas.text(score.as_str(), Point::new(0, 22));
+    let mut lines = TextBuffer::new();
+    let _ = write!(lines, "L{} LV{}", summary.lines, summary.level);
+    canvas.text(lines.as_str(), Point::new(0, 34));
+}
+
+/// Draw the count of each kind of piece placed, a row for each in its color with a bar as long
+/// as its share of the pieces, and below them the lines and tetrises cleared.
+fn draw_piece_stats(canvas: &mut dyn Canvas, stats: &PlayStats) {
+    let (width, height) = canvas.size();
+    let row_height = height / 8;
+    let counts = &stats.piece_counts;
+    let bar_x = 36;
+    let total = counts.total().max(1);
+    for (index, (kind, count)) in counts.iter().enumerate() {
+        let y = index as u32 * row_height;
+        let mut text = TextBuffer::new();
+        let _ = write!(text, "{} {}", kind.letter(), count);
+        canvas.set_color(piece_color(kind));
+        canvas.text(text.as_str(), Point::new(0, y as i32));
+        let length = width.saturating_sub(bar_x) * count / total;
+        if length > 0 {
+            canvas.draw_rect((bar_x, y + 2), (bar_x + length - 1, y + 3));
+        }
+    }
+    canvas.set_color(Rgb888::WHITE);
+    let mut text = TextBuffer::new();
+    let _ = write!(text, "L{} T{}", stats.lines, stats.clears_of(4));
+    canvas.text(text.as_str(), Point::new(0, (7 * row_height) as i32));
+}
+
+/// Draw the score, next piece and held piece with the top left corner at origin.
+fn draw_info(canvas: &mut dyn Canvas, state: &TetrisState, (origin_x, origin_y): (u32, u32)) {
+    let mut score = TextBuffer::new();
+    let _ = write!(score, "{}", state.score);
+
+    canvas.text("SCORE", Point::new(origin_x as i32, origin_y as i32));
+    canvas.text(
+        score.as_str(),
+        Point::new(origin_x as i32, origin_y as i32 + 10),
+    );
+    canvas.text("NEXT", Point::new(origin_x as i32, origin_y as i32 + 24));
+    canvas.set_color(piece_color(state.next_piece.kind()));
+    draw_piece(
+        canvas,
+        state.next_piece.current_rotation(),
+        (origin_x, origin_y + 36),
+        4,
+    );
+    if let Some(ref held) = state.held_piece {
+        canvas.set_color(Rgb888::WHITE);
+        canvas.text(
+            "HOLD",
+            Point::new(origin_x as i32 + 36, origin_y as i32 + 24),
+        );
+        canvas.set_color(piece_color(held.kind()));
+        draw_piece(
+            canvas,
+            held.current_rotation(),
+            (origin_x + 36, origin_y + 36),
+            4,
+        );
+    }
+    canvas.set_color(Rgb888::WHITE);
+}
+
+/// The rules the handheld plays by, also offered to players who join a match.
+pub fn rules() -> Rules {
+    Rules {
+        das: DAS_FRAMES,
+        arr: ARR_FRAMES,
+        speed_curve: true,
+        ..Rules::default()
+    }
+}
+
+impl Game for TetrisGame {
+    /// The cheats unlocked by the Konami code and turned on in the launcher apply from the
+    /// start of the game.
+    fn start(&mut self, settings: &Settings) {
+        self.tetris = Tetris::new_with_entropy(&mut self.entropy);
+        let rules = rules();
+        self.tetris.set_rules(match settings.twenty_g {
+            true => Rules {
+                gravity: Gravity::TWENTY_G,
+                ..rules
+            },
+            false => rules,
+        });
+        if settings.big {
+            if let Tetris::Running(ref mut state) = self.tetris {
+                state.resize_playfield(BIG_PLAYFIELD);
+            }
+        }
+        self.invisible = settings.invisible;
+        self.twenty_g = settings.twenty_g;
+        self.adaptive = Adaptive::new(ASSIST_MIN_GRAVITY, ASSIST_MAX_GRAVITY);
+        self.music.restart();
+        self.new_high_score = None;
+        #[cfg(feature = "wifi")]
+        self.duel = None;
+        self.exited = false;
+    }
+
+    fn update(&mut self, input: &Input, console: &mut Console) {
+        self.layout = console.settings.layout;
+        self.piece_stats = console.settings.piece_stats;
+        #[cfg(feature = "wifi")]
+        if let Some(ref mut netplay) = console.netplay {
+            let duel = &mut self.duel;
+            let agreed = netplay.poll(&mut |garbage| {
+                if let Some(duel) = duel.as_mut() {
+                    duel.receive(garbage);
+                }
+            });
+            if let Some(agreed) = agreed {
+                self.start_match(agreed, console.settings);
+            }
+        }
+        match input.chord {
+            Some(Chord::AB) => self.exited = true,
+            // The game and music stand still while paused
+            Some(Chord::UpDown) if self.tetris.is_paused() => self.tetris.resume(),
+            Some(Chord::UpDown) => self.tetris.pause(),
+            _ => {}
+        }
+        if self.tetris.is_paused() {
+            self.music.pause(console.audio);
+            return;
+        }
+        if self.new_high_score.is_some() {
+            self.enter_initials(input, console);
+            return;
+        }
+
+        let settings = &console.settings;
+
+        // A rotates clockwise and B counter-clockwise. With a long press bound to the button,
+        // rotation waits for it to be released so that a long press does not also rotate. The
+        // rotate sound is played once per press.
+        let rotation = |button: Button, long_press: LongPressAction| match long_press {
+            LongPressAction::None => (input.held.held(button), input.pressed.held(button)),
+            _ => (input.taps.held(button), input.taps.held(button)),
+        };
+        let (rotate, rotate_pressed) = rotation(Button::A, settings.a_long_press);
+        let (rotate_ccw, rotate_ccw_pressed) = rotation(Button::B, settings.b_long_press);
+        let rotate_sound = rotate_pressed || rotate_ccw_pressed;
+
+        for (button, action) in [
+            (Button::A, settings.a_long_press),
+            (Button::B, settings.b_long_press),
+        ] {
+            if input.long_presses.held(button) {
+                match action {
+                    LongPressAction::None => {}
+                    LongPressAction::Restart => self.start(settings),
+                    LongPressAction::ExitToLauncher => self.exited = true,
+                }
+            }
+        }
+
+        let hard_drop = self.hard_drop_gesture.update(
+            input.pressed.held(settings.hard_drop_button),
+            settings.double_tap_frames,
+        );
+
+        // Rotation and hard drops depend on gestures rather than on what is held
+        let mut actions = BINDINGS.map(input.held.iter());
+        actions.set(Action::Rotate, rotate);
+        actions.set(Action::RotateCcw, rotate_ccw);
+        actions.set(Action::HardDrop, hard_drop);
+
+        let was_playing = !self.tetris.is_finished();
+
+        self.tetris.set_key_state(&actions.key_state());
+        self.tetris.update();
+        #[cfg(feature = "wifi")]
+        let garbage = match self.duel {
+            Some(ref mut duel) => duel.update(&mut self.tetris),
+            None => None,
+        };
+        if settings.assist && !self.twenty_g {
+            self.adaptive.observe(&mut self.tetris);
+        }
+        // Compiled out unless DEFMT_LOG asks for trace, for profiling the core on the device
+        if let Tetris::Running(ref state) = self.tetris {
+            let metrics = state.metrics();
+            defmt::trace!(
+                "update: {} cells touched, {} rows scanned, {} pieces spawned",
+                metrics.cells_touched,
+                metrics.rows_scanned,
+                metrics.pieces_spawned
+            );
+        }
+
+        let effect = match self.tetris {
+            Tetris::Running(ref state) => {
+                // Drops score points too, so the clear is read from the update itself
+                if state.rows_cleared() > 0 {
+                    Some(SoundEffect::LineClear)
+                } else if rotate_sound {
+                    Some(SoundEffect::Rotate)
+                } else {
+                    None
+                }
+            }
+            Tetris::Paused(_) => None,
+            Tetris::Finished(ref summary) => {
+                let high_score = Some(summary.score as u32)
+                    .filter(|&score| was_playing && console.high_scores.qualifies(score));
+                if let Some(score) = high_score {
+                    let entry = InitialsEntry::new(console.settings.initials);
+                    self.new_high_score = Some((entry, score));
+                } else if input.held.held(Button::B) {
+                    self.start(settings);
+                } else if input.pressed.held(Button::A) {
+                    self.exited = true;
+                }
+                was_playing.then_some(SoundEffect::GameOver)
+            }
+        };
+
+        // Effects start after the music so that pausing the music at game over does not cut
+        // the game over sound short.
+        self.update_music(console.audio);
+        if let Some(effect) = effect {
+            console.audio.effect(effect);
+        }
+
+        #[cfg(feature = "wifi")]
+        if let Some(ref mut spectate) = console.spectate {
+            spectate.send(&self.tetris);
+        }
+        #[cfg(feature = "wifi")]
+        if let Some(ref mut netplay) = console.netplay {
+            netplay.send(&self.tetris, garbage);
+        }
+    }
+
+    /// Each cell is drawn twice the size in big mode, and the stack is left out in invisible
+    /// mode.
+    fn draw(&self, displays: &mut Displays) {
+        if self.tetris.is_paused() {
+            displays.main.text("PAUSED", Point::new(0, 10));
+            return;
+        }
+        if let Some((ref entry, score)) = self.new_high_score {
+            draw_initials_entry(&mut *displays.main, entry, score);
+            return;
+        }
+
+        let state = match self.tetris {
+            Tetris::Running(ref state) | Tetris::Paused(ref state) => state,
+            Tetris::Finished(ref summary) => {
+                draw_summary(&mut *displays.main, summary);
+                return;
+            }
+        };
+
+        let main = &mut *displays.main;
+        let (width, height) = main.size();
+
+        let (border_min, border_max, grid_offset, grid_scale, info_origin) =
+            match Layout::for_size(width, height) {
+                Layout::Tiny => {
+                    draw_tiny(main, state, displays.side.is_none(), self.invisible);
+                    if let Some(ref mut side) = displays.side {
+                        self.draw_side(&mut **side, state);
+                    }
+                    return;
+                }
+                Layout::Compact => ((1, 1), (22, 42), (2, 2), (2, 2), (26, 1)),
+                // With a side display the whole main display is given to a larger playfield
+                Layout::Full if displays.side.is_some() => {
+                    ((1, 1), (62, 62), (2, 2), (6, 3), (0, 0))
+                }
+                Layout::Full => match self.layout {
+                    LayoutTheme::Big => {
+                        main.draw_rect((49, 1), (80, 62));
+                        draw_playfield(main, state, (50, 2), (3, 3), self.invisible);
+                        return;
+                    }
+                    LayoutTheme::Hud => ((1, 9), (43, 50), (2, 10), (4, 2), (50, 9)),
+                    LayoutTheme::Sideways => {
+                        draw_sideways(main, state, self.invisible);
+                        return;
+                    }
+                },
+            };
+
+        main.draw_rect(border_min, border_max);
+        draw_playfield(main, state, grid_offset, grid_scale, self.invisible);
+
+        match displays.side {
+            Some(ref mut side) => self.draw_side(&mut **side, state),
+            None => draw_info(main, state, info_origin),
+        }
+    }
+
+    fn exited(&self) -> bool {
+        self.exited
+    }
+}
@@ -0,0 +1,37 @@
+use core::fmt;
+
+/// A small fixed size string for formatting text on the screen without allocating.
+pub struct TextBuffer {
+    buffer: [u8; 24],
+    len: usize,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        TextBuffer {
+            buffer: [0; 24],
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole str values are ever written to the buffer so it is always valid UTF-8
+        core::str::from_utf8(&self.buffer[..self.len]).unwrap_or("")
+    }
+}
+
+impl fmt::Write for TextBuffer {
+    /// Text that does not fit is truncated rather than treated as an error.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let mut encoded = [0; 4];
+            let encoded = c.encode_utf8(&mut encoded).as_bytes();
+            if self.len + encoded.len() > self.buffer.len() {
+                break;
+            }
+            self.buffer[self.len..self.len + encoded.len()].copy_from_slice(encoded);
+            self.len += encoded.len();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::game::Canvas;
+use crate::input::{Button, ButtonState};
+use crate::settings::Settings;
+use embedded_graphics::prelude::Point;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use rp2040_hal::gpio::DynPin;
+
+const PADS: usize = Button::ALL.len();
+
+/// Cycles to hold a pad low so it is fully discharged before timing the charge.
+const DISCHARGE_CYCLES: u32 = 200;
+/// Give up timing a pad after this many polls, so a shorted or missing pad cannot hang a frame.
+const MAX_CHARGE_TIME: u16 = 2000;
+
+/// Touch pads in place of the buttons, one per button on the same GPIO. Each pad charges
+/// through a high value resistor from the button power pin and a finger on the pad adds
+/// capacitance, so a touched pad takes longer to read high after being discharged.
+pub struct TouchPads {
+    /// Indexed by button.
+    pads: [DynPin; PADS],
+    readings: [u16; PADS],
+}
+
+impl TouchPads {
+    pub fn new(pads: [DynPin; PADS]) -> Self {
+        TouchPads {
+            pads,
+            readings: [0; PADS],
+        }
+    }
+
+    /// Discharge a pad then count polls until it reads high again.
+    fn charge_time(pad: &mut DynPin) -> u16 {
+        pad.into_push_pull_output();
+        let _ = pad.set_low();
+        cortex_m::asm::delay(DISCHARGE_CYCLES);
+        pad.into_floating_input();
+
+        let mut time = 0;
+        while time < MAX_CHARGE_TIME && !pad.is_high().unwrap_or(true) {
+            time += 1;
+        }
+        time
+    }
+
+    /// Measure every pad, reporting those that took longer than their threshold to charge as
+    /// held.
+    pub fn state(&mut self, settings: &Settings) -> ButtonState {
+        let mut state = ButtonState::default();
+        for button in Button::ALL {
+            let reading = Self::charge_time(&mut self.pads[button as usize]);
+            self.readings[button as usize] = reading;
+            state.set(button, reading > settings.touch_thresholds[button as usize]);
+        }
+        state
+    }
+
+    /// The charge times measured by the last call to state, indexed by button.
+    pub fn readings(&self) -> [u16; PADS] {
+        self.readings
+    }
+}
+
+/// How many frames the pads are sampled for while calibrating, two seconds of the main loop.
+const CALIBRATION_FRAMES: u8 = 20;
+/// The least a touch must add to the charge time, so noise on a pad with a very short
+/// untouched charge time does not register.
+const MIN_TOUCH_MARGIN: u16 = 10;
+
+/// Finds the untouched charge time of every pad, which depends on the pad size and wiring,
+/// and sets the touch thresholds above it. Started from the launcher.
+pub struct TouchCalibrator {
+    frames: u8,
+    baseline: [u16; PADS],
+}
+
+impl TouchCalibrator {
+    pub fn new() -> Self {
+        TouchCalibrator {
+            frames: 0,
+            baseline: [0; PADS],
+        }
+    }
+
+    /// Feed the readings for this frame, returning true once calibration is finished and the
+    /// new thresholds are saved to the settings.
+    pub fn update(&mut self, readings: [u16; PADS], settings: &mut Settings) -> bool {
+        for (baseline, reading) in self.baseline.iter_mut().zip(readings) {
+            *baseline = (*baseline).max(reading);
+        }
+
+        self.frames += 1;
+        if self.frames < CALIBRATION_FRAMES {
+            return false;
+        }
+
+        settings.touch_thresholds = self
+            .baseline
+            .map(|baseline| baseline + (baseline / 2).max(MIN_TOUCH_MARGIN));
+        true
+    }
+
+    pub fn draw(&self, canvas: &mut dyn Canvas) {
+        canvas.text("Touch setup", Point::new(31, 10));
+        canvas.text("Keep hands off", Point::new(22, 24));
+        canvas.text("the pads", Point::new(40, 34));
+
+        let progress = 100 * self.frames as u32 / CALIBRATION_FRAMES as u32;
+        canvas.draw_rect((13, 50), (114, 56));
+        for x in 14..14 + progress {
+            for y in 51..56 {
+                canvas.set_pixel(x, y, true);
+            }
+        }
+    }
+}
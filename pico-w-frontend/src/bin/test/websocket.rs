@@ -0,0 +1,153 @@
+//! Just enough of WebSockets (RFC 6455) for a browser to send small binary messages to the
+//! device: the opening handshake and unfragmented frames with payloads under 126 bytes.
+
+const GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+
+/// SHA-1 of data, only used to answer the handshake.
+fn sha1(data: &[&[u8]]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let len: usize = data.iter().map(|part| part.len()).sum();
+
+    // The message followed by a single set bit, zero padding and the length in bits
+    let padded_len = (len + 9 + 63) / 64 * 64;
+    let byte_at = |i: usize| -> u8 {
+        if i < len {
+            let mut i = i;
+            for part in data {
+                if i < part.len() {
+                    return part[i];
+                }
+                i -= part.len();
+            }
+            unreachable!()
+        } else if i == len {
+            0x80
+        } else if i >= padded_len - 8 {
+            ((len as u64 * 8) >> ((padded_len - 1 - i) * 8)) as u8
+        } else {
+            0
+        }
+    };
+
+    for block in (0..padded_len).step_by(64) {
+        let mut w = [0u32; 80];
+        for t in 0..16 {
+            w[t] = u32::from_be_bytes([
+                byte_at(block + t * 4),
+                byte_at(block + t * 4 + 1),
+                byte_at(block + t * 4 + 2),
+                byte_at(block + t * 4 + 3),
+            ]);
+        }
+        for t in 16..80 {
+            w[t] = (w[t - 3] ^ w[t - 8] ^ w[t - 14] ^ w[t - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (t, word) in w.iter().enumerate() {
+            let (f, k) = match t {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8; 20]) -> [u8; 28] {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = [b'='; 28];
+    for (group, chunk) in data.chunks(3).enumerate() {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (i, byte)| bits | (*byte as u32) << (16 - i * 8));
+        for i in 0..=chunk.len() {
+            out[group * 4 + i] = ALPHABET[(bits >> (18 - i * 6)) as usize & 0x3F];
+        }
+    }
+    out
+}
+
+/// The Sec-WebSocket-Accept value for the key the browser sent.
+pub fn accept_key(key: &[u8]) -> [u8; 28] {
+    base64(&sha1(&[key, GUID]))
+}
+
+/// Find the value of a header in an HTTP request, matching the name case insensitively.
+pub fn header<'a>(request: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    request.split(|&byte| byte == b'\n').find_map(|line| {
+        let colon = line.iter().position(|&byte| byte == b':')?;
+        if !line[..colon].eq_ignore_ascii_case(name) {
+            return None;
+        }
+        let value = &line[colon + 1..];
+        let start = value.iter().position(|byte| !byte.is_ascii_whitespace())?;
+        let end = value.iter().rposition(|byte| !byte.is_ascii_whitespace())?;
+        Some(&value[start..=end])
+    })
+}
+
+/// A message received from the browser.
+pub enum Frame<'a> {
+    Binary(&'a [u8]),
+    Close,
+    /// Anything else, such as text or ping frames, is ignored.
+    Other,
+}
+
+/// Parse one masked frame from the browser, unmasking the payload in place. Returns the frame
+/// and the number of bytes it used, or None if it has not fully arrived yet.
+pub fn parse_frame(buffer: &mut [u8]) -> Option<(Frame<'_>, usize)> {
+    if buffer.len() < 6 {
+        return None;
+    }
+
+    let opcode = buffer[0] & 0x0F;
+    let len = (buffer[1] & 0x7F) as usize;
+    // Longer payloads are never sent by the control page, treat them as a request to close.
+    if len >= 126 {
+        return Some((Frame::Close, buffer.len()));
+    }
+    if buffer.len() < 6 + len {
+        return None;
+    }
+
+    let mask = [buffer[2], buffer[3], buffer[4], buffer[5]];
+    let payload = &mut buffer[6..6 + len];
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    let frame = match opcode {
+        OPCODE_BINARY => Frame::Binary(payload),
+        OPCODE_CLOSE => Frame::Close,
+        _ => Frame::Other,
+    };
+    Some((frame, 6 + len))
+}
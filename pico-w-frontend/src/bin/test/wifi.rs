@@ -0,0 +1,91 @@
+//! The state of the Wi-Fi link, sent from the network task to the main loop and shown as an
+//! antenna in the top right corner of the main display.
+
+use crate::game::Canvas;
+use core::cell::Cell;
+use cortex_m::interrupt::{free, Mutex};
+
+/// Signal strengths in dBm needed for each bar after the first, weakest first.
+const BAR_RSSI: [i8; 3] = [-80, -70, -60];
+/// Frames the antenna is shown and then hidden for while connecting.
+const BLINK_FRAMES: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WifiStatus {
+    /// Joining the network or waiting for an address.
+    Connecting,
+    /// Joined, with the signal strength of the access point in dBm.
+    Connected { rssi: i8 },
+    /// Joining failed or the link was lost, the network task will try again.
+    Error,
+}
+
+impl WifiStatus {
+    /// Bars of signal to show, one to four once connected.
+    fn bars(&self) -> u32 {
+        match self {
+            WifiStatus::Connected { rssi } => {
+                1 + BAR_RSSI.iter().filter(|&&needed| *rssi >= needed).count() as u32
+            }
+            WifiStatus::Connecting | WifiStatus::Error => 0,
+        }
+    }
+}
+
+/// Carries the latest status from the network task, which may run from an interrupt, to the
+/// main loop. Only the latest is kept since the display has no use for those it missed.
+pub struct StatusChannel {
+    status: Mutex<Cell<WifiStatus>>,
+}
+
+impl StatusChannel {
+    pub const fn new() -> Self {
+        StatusChannel {
+            status: Mutex::new(Cell::new(WifiStatus::Connecting)),
+        }
+    }
+
+    pub fn send(&self, status: WifiStatus) {
+        free(|cs| self.status.borrow(cs).set(status));
+    }
+
+    pub fn latest(&self) -> WifiStatus {
+        free(|cs| self.status.borrow(cs).get())
+    }
+}
+
+/// Written by the task joining the network and by the network stack once it has an address.
+pub static WIFI_STATUS: StatusChannel = StatusChannel::new();
+
+/// Draw an antenna with signal bars beside it into the top right corner, the antenna blinking
+/// while connecting and crossed out after an error. frame counts up once per frame.
+pub fn draw_status(canvas: &mut dyn Canvas, status: WifiStatus, frame: u32) {
+    let (width, _) = canvas.size();
+    let x = width - 13;
+
+    let blinked_off = status == WifiStatus::Connecting && (frame / BLINK_FRAMES) % 2 == 1;
+    if !blinked_off {
+        // A mast with a V on top
+        for y in 2..8 {
+            canvas.set_pixel(x + 2, y, true);
+        }
+        for offset in 0..3 {
+            canvas.set_pixel(x + offset, offset, true);
+            canvas.set_pixel(x + 4 - offset, offset, true);
+        }
+    }
+    if status == WifiStatus::Error {
+        for offset in 0..5 {
+            canvas.set_pixel(x + 6 + offset, 2 + offset, true);
+            canvas.set_pixel(x + 10 - offset, 2 + offset, true);
+        }
+    }
+
+    // Each bar two pixels taller than the last, filled up to the signal strength
+    for bar in 0..status.bars() {
+        let bar_x = x + 6 + bar * 2;
+        for y in 6 - bar * 2..8 {
+            canvas.set_pixel(bar_x, y, true);
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! The CYW43439 wireless chip of the Pico W, which carries the Wi-Fi and the board's LED. Its
+//! driver is async, so rather than run under an executor it is polled from the main loop as one
+//! future, with the driver's timers read off the RP2040's microsecond timer.
+
+use crate::led::WirelessGpio;
+use crate::stack::Network;
+use crate::wifi::{WifiStatus, WIFI_STATUS};
+use core::cell::{Cell, RefCell, RefMut};
+use core::convert::Infallible;
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use cortex_m::interrupt::{free, Mutex};
+use cyw43::{
+    Control, JoinOptions, NetDriver, PowerManagementMode, Runner, ScanOptions, SpiBusCyw43, State,
+};
+use embassy_futures::block_on;
+use embassy_futures::join::join3;
+use embassy_futures::select::select;
+use embassy_time_driver::{AlarmHandle, Driver};
+use embedded_hal::digital::v2::OutputPin;
+use rp2040_hal::gpio::bank0::{Gpio23, Gpio24, Gpio25, Gpio29};
+use rp2040_hal::gpio::{self, PushPullOutput};
+use rp2040_hal::pac;
+
+/// The chip's firmware and regulatory data, see cyw43-firmware/README.md for where to get them.
+const FIRMWARE: &[u8] = include_bytes!("../../../cyw43-firmware/43439A0.bin");
+const CLM: &[u8] = include_bytes!("../../../cyw43-firmware/43439A0_clm.bin");
+
+/// The network joined, given when building with the wifi feature.
+const SSID: &str = env!("WIFI_SSID");
+const PASSWORD: &str = env!("WIFI_PASSWORD");
+/// How long to wait before joining again after failing to or losing the network.
+const RETRY_US: u64 = 10_000_000;
+
+/// The Ethernet address of mdns::MDNS_GROUP, which the chip drops frames to unless told not to.
+const MDNS_MAC: [u8; 6] = [0x01, 0x00, 0x5e, 0x00, 0x00, 0xfb];
+
+/// The GPIOs of the chip, WL_GPIO0 to WL_GPIO2.
+const GPIOS: u8 = 3;
+/// How long setting a GPIO waits for the chip to take it, so that the LED can be blinked from
+/// busy loops that do not poll the driver, without holding up the main loop while it is busy.
+const GPIO_WAIT_US: u64 = 10_000;
+
+/// The data line, which doubles as the chip's interrupt between transfers, its chip select and
+/// clock.
+const DIO: u32 = 1 << 24;
+const CS: u32 = 1 << 25;
+const CLK: u32 = 1 << 29;
+
+/// Microseconds since boot off the free running timer, read without owning it as the WallClock
+/// does.
+pub fn now_us() -> u64 {
+    let timer = unsafe { &*pac::TIMER::ptr() };
+    loop {
+        let high = timer.timerawh.read().bits();
+        let low = timer.timerawl.read().bits();
+        // The low half wrapped between the reads if the high half moved on
+        if timer.timerawh.read().bits() == high {
+            return (high as u64) << 32 | low as u64;
+        }
+    }
+}
+
+/// The clock embassy's timers run on. The driver is polled over and over anyway, so timers are
+/// checked as it is and need no alarm to wake it.
+struct TimerDriver;
+
+impl Driver for TimerDriver {
+    fn now(&self) -> u64 {
+        now_us()
+    }
+
+    unsafe fn allocate_alarm(&self) -> Option<AlarmHandle> {
+        None
+    }
+
+    fn set_alarm_callback(&self, _alarm: AlarmHandle, _callback: fn(*mut ()), _ctx: *mut ()) {}
+
+    fn set_alarm(&self, _alarm: AlarmHandle, _timestamp: u64) -> bool {
+        false
+    }
+}
+
+embassy_time_driver::time_driver_impl!(static TIMER_DRIVER: TimerDriver = TimerDriver);
+
+struct TimerQueue;
+
+impl embassy_time_queue_driver::TimerQueue for TimerQueue {
+    fn schedule_wake(&'static self, _at: u64, _waker: &Waker) {}
+}
+
+embassy_time_queue_driver::timer_queue_impl!(static TIMER_QUEUE: TimerQueue = TimerQueue);
+
+/// WL_ON, which powers the chip up and holds it in reset while low. The driver takes an
+/// embedded-hal 1.0 pin.
+pub struct PowerPin(pub gpio::Pin<Gpio23, PushPullOutput>);
+
+impl embedded_hal_1::digital::ErrorType for PowerPin {
+    type Error = Infallible;
+}
+
+impl embedded_hal_1::digital::OutputPin for PowerPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set_high()
+    }
+}
+
+/// The half duplex SPI the chip is wired to, clocked from the CPU through the SIO rather than
+/// by a PIO state machine, leaving both PIO blocks free for the displays and audio.
+/// Words go out and come back most significant bit first, the chip taking each bit on the
+/// rising edge of the clock and giving its own on the falling edge.
+pub struct Spi {
+    _dio: gpio::Pin<Gpio24, PushPullOutput>,
+    _cs: gpio::Pin<Gpio25, PushPullOutput>,
+    _clk: gpio::Pin<Gpio29, PushPullOutput>,
+}
+
+impl Spi {
+    pub fn new(
+        dio: gpio::Pin<Gpio24, PushPullOutput>,
+        mut cs: gpio::Pin<Gpio25, PushPullOutput>,
+        mut clk: gpio::Pin<Gpio29, PushPullOutput>,
+    ) -> Self {
+        cs.set_high().unwrap();
+        clk.set_low().unwrap();
+        let spi = Spi {
+            _dio: dio,
+            _cs: cs,
+            _clk: clk,
+        };
+        // Between transfers the chip drives the data line to raise its interrupt
+        spi.sio().gpio_oe_clr.write(|w| unsafe { w.bits(DIO) });
+        spi
+    }
+
+    fn sio(&self) -> &pac::sio::RegisterBlock {
+        unsafe { &*pac::SIO::ptr() }
+    }
+
+    fn select(&mut self) {
+        let sio = self.sio();
+        sio.gpio_out_clr.write(|w| unsafe { w.bits(CS) });
+        sio.gpio_oe_set.write(|w| unsafe { w.bits(DIO) });
+    }
+
+    /// Hand the data line to the chip for its reply.
+    fn turn_around(&mut self) {
+        self.sio().gpio_oe_clr.write(|w| unsafe { w.bits(DIO) });
+    }
+
+    fn deselect(&mut self) {
+        self.sio().gpio_out_set.write(|w| unsafe { w.bits(CS) });
+    }
+
+    fn write_word(&mut self, word: u32) {
+        let sio = self.sio();
+        for bit in (0..32).rev() {
+            match (word >> bit) & 1 {
+                0 => sio.gpio_out_clr.write(|w| unsafe { w.bits(DIO) }),
+                _ => sio.gpio_out_set.write(|w| unsafe { w.bits(DIO) }),
+            }
+            sio.gpio_out_set.write(|w| unsafe { w.bits(CLK) });
+            sio.gpio_out_clr.write(|w| unsafe { w.bits(CLK) });
+        }
+    }
+
+    fn read_word(&mut self) -> u32 {
+        let sio = self.sio();
+        let mut word = 0;
+        for _ in 0..32 {
+            sio.gpio_out_set.write(|w| unsafe { w.bits(CLK) });
+            word = word << 1 | (sio.gpio_in.read().bits() & DIO != 0) as u32;
+            sio.gpio_out_clr.write(|w| unsafe { w.bits(CLK) });
+        }
+        word
+    }
+}
+
+impl SpiBusCyw43 for Spi {
+    /// Send a command and its data, returning the status the chip answers with.
+    async fn cmd_write(&mut self, write: &[u32]) -> u32 {
+        self.select();
+        for &word in write {
+            self.write_word(word);
+        }
+        self.turn_around();
+        let status = self.read_word();
+        self.deselect();
+        status
+    }
+
+    /// Send a command and read its reply, returning the status that follows it.
+    async fn cmd_read(&mut self, write: u32, read: &mut [u32]) -> u32 {
+        self.select();
+        self.write_word(write);
+        self.turn_around();
+        for word in read.iter_mut() {
+            *word = self.read_word();
+        }
+        let status = self.read_word();
+        self.deselect();
+        status
+    }
+
+    async fn wait_for_event(&mut self) {
+        poll_fn(|_| match self.sio().gpio_in.read().bits() & DIO {
+            0 => Poll::Pending,
+            _ => Poll::Ready(()),
+        })
+        .await
+    }
+}
+
+/// Power the chip up and load its firmware, blocking until it answers, then return its network
+/// device, the handle controlling it and the runner that services it.
+pub fn start(
+    state: &mut State,
+    power: PowerPin,
+    spi: Spi,
+) -> (NetDriver<'_>, Control<'_>, Runner<'_, PowerPin, Spi>) {
+    block_on(cyw43::new(state, power, spi, FIRMWARE))
+}
+
+/// Load the regulatory data, let the chip save power between beacons and have it pass on the
+/// mDNS group's frames, blocking until done. runner is the driver's runner, which must be
+/// polled meanwhile.
+pub fn init<R: Future>(runner: Pin<&mut R>, control: &mut Control<'_>) {
+    block_on(select(runner, async {
+        control.init(CLM).await;
+        control
+            .set_power_management(PowerManagementMode::PowerSave)
+            .await;
+        let _ = control.add_multicast_address(MDNS_MAC).await;
+    }));
+}
+
+/// Wait until us microseconds from now.
+async fn sleep(us: u64) {
+    let until = now_us() + us;
+    poll_fn(|_| match now_us() < until {
+        true => Poll::Pending,
+        false => Poll::Ready(()),
+    })
+    .await
+}
+
+/// Wait for the control handle, which the tasks sharing it hold across their awaits.
+async fn lock<'c, 'a>(control: &'c RefCell<Control<'a>>) -> RefMut<'c, Control<'a>> {
+    poll_fn(|_| match control.try_borrow_mut() {
+        Ok(control) => Poll::Ready(control),
+        Err(_) => Poll::Pending,
+    })
+    .await
+}
+
+/// The levels the firmware wants on the chip's GPIOs and those last set on the chip, a bit
+/// each. The driver's task owns the control handle, so the main loop passes the levels through
+/// here.
+#[derive(Clone, Copy)]
+struct GpioLevels {
+    wanted: u8,
+    set: u8,
+}
+
+static GPIO_LEVELS: Mutex<Cell<GpioLevels>> =
+    Mutex::new(Cell::new(GpioLevels { wanted: 0, set: 0 }));
+
+fn gpio_levels() -> GpioLevels {
+    free(|cs| GPIO_LEVELS.borrow(cs).get())
+}
+
+/// Keep the chip's GPIOs at the levels wanted.
+async fn drive_gpios(control: &RefCell<Control<'_>>) {
+    loop {
+        let levels = poll_fn(|_| match gpio_levels() {
+            levels if levels.wanted != levels.set => Poll::Ready(levels),
+            _ => Poll::Pending,
+        })
+        .await;
+        let mut control = lock(control).await;
+        for gpio in 0..GPIOS {
+            let high = (levels.wanted >> gpio) & 1 == 1;
+            if high != ((levels.set >> gpio) & 1 == 1) {
+                control.gpio_set(gpio, high).await;
+            }
+        }
+        free(|cs| {
+            let cell = GPIO_LEVELS.borrow(cs);
+            cell.set(GpioLevels {
+                set: levels.wanted,
+                ..cell.get()
+            })
+        });
+    }
+}
+
+/// The signal strength of the network in dBm, read from a scan as the chip is not asked for it
+/// once joined. The weakest possible if the network was not seen.
+async fn signal_strength(control: &RefCell<Control<'_>>) -> i8 {
+    let mut control = lock(control).await;
+    let mut scanner = control.scan(ScanOptions::default()).await;
+    let mut rssi = i16::MIN;
+    while let Some(bss) = scanner.next().await {
+        if bss.ssid[..bss.ssid_len as usize] == *SSID.as_bytes() {
+            rssi = rssi.max(bss.rssi);
+        }
+    }
+    rssi.clamp(i8::MIN as i16, i8::MAX as i16) as i8
+}
+
+/// Join the network and stay joined, joining again whenever it fails or the link is lost.
+async fn connect(control: &RefCell<Control<'_>>, network: &Network<'_>) {
+    loop {
+        WIFI_STATUS.send(WifiStatus::Connecting);
+        let rssi = signal_strength(control).await;
+        let joined = lock(control)
+            .await
+            .join(SSID, JoinOptions::new(PASSWORD.as_bytes()))
+            .await;
+        if joined.is_ok() {
+            // Connected is sent once DHCP gives an address
+            network.joined(rssi);
+            poll_fn(|_| match network.is_link_up() {
+                true => Poll::Pending,
+                false => Poll::Ready(()),
+            })
+            .await;
+        }
+        WIFI_STATUS.send(WifiStatus::Error);
+        sleep(RETRY_US).await;
+    }
+}
+
+/// The driver's work: the runner moving data to and from the chip, and the tasks using the
+/// control handle.
+pub async fn run<R: Future>(runner: Pin<&mut R>, control: Control<'_>, network: &Network<'_>) {
+    let control = RefCell::new(control);
+    join3(runner, drive_gpios(&control), connect(&control, network)).await;
+}
+
+/// The future returned by run, pinned where the main loop keeps it.
+pub type Task<'a> = RefCell<Pin<&'a mut dyn Future<Output = ()>>>;
+
+/// The chip's driver, shared by everything that needs it polled.
+#[derive(Clone, Copy)]
+pub struct Cyw43<'a> {
+    task: &'a Task<'a>,
+}
+
+impl<'a> Cyw43<'a> {
+    pub fn new(task: &'a Task<'a>) -> Self {
+        Cyw43 { task }
+    }
+
+    /// Give the driver a turn, doing whatever it can without waiting. The main loop calls this
+    /// each time it samples the buttons.
+    pub fn poll(&self) {
+        // Already being polled further up the stack
+        let Ok(mut task) = self.task.try_borrow_mut() else {
+            return;
+        };
+        let _ = task.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+    }
+}
+
+impl WirelessGpio for Cyw43<'_> {
+    fn set_gpio(&mut self, gpio: u8, high: bool) {
+        let mask = 1 << gpio;
+        let wanted = free(|cs| {
+            let cell = GPIO_LEVELS.borrow(cs);
+            let mut levels = cell.get();
+            levels.wanted = (levels.wanted & !mask) | (high as u8) << gpio;
+            cell.set(levels);
+            levels.wanted
+        });
+        let deadline = now_us() + GPIO_WAIT_US;
+        while (gpio_levels().set ^ wanted) & mask != 0 && now_us() < deadline {
+            self.poll();
+        }
+    }
+}
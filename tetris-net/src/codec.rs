@@ -0,0 +1,125 @@
+use core::fmt;
+
+/// The buffer given to encode a message into is too small for it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EncodeError;
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Buffer too small for the message")
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// The message ended early.
+    Truncated,
+    UnknownTag(u8),
+    /// A field holds a value that no encoder would produce, such as an oversized board.
+    Invalid,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Truncated => write!(f, "Message ended early"),
+            DecodeError::UnknownTag(tag) => write!(f, "Unknown message tag {}", tag),
+            DecodeError::Invalid => write!(f, "Message holds an invalid value"),
+        }
+    }
+}
+
+/// Writes fields into a fixed buffer.
+pub(crate) struct Writer<'a> {
+    buffer: &'a mut [u8],
+    position: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Writer {
+            buffer,
+            position: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.position
+    }
+
+    pub fn u8(&mut self, value: u8) -> Result<(), EncodeError> {
+        let byte = self.buffer.get_mut(self.position).ok_or(EncodeError)?;
+        *byte = value;
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Seven bits per byte, least significant first, with the top bit set on all but the last.
+    pub fn varint(&mut self, mut value: u64) -> Result<(), EncodeError> {
+        while value >= 0x80 {
+            self.u8(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.u8(value as u8)
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<(), EncodeError> {
+        let end = self.position + bytes.len();
+        self.buffer
+            .get_mut(self.position..end)
+            .ok_or(EncodeError)?
+            .copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+}
+
+/// Reads fields from a received message.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.data.get(self.position).ok_or(DecodeError::Truncated)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    pub fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::Invalid)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        u32::try_from(self.varint()?).map_err(|_| DecodeError::Invalid)
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(DecodeError::Truncated)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    /// Everything that has not been read yet.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position..];
+        self.position = self.data.len();
+        rest
+    }
+}
@@ -0,0 +1,99 @@
+//! Framing for stream transports: each message is sent with its length in a single byte in
+//! front of it.
+
+use crate::codec::{DecodeError, EncodeError};
+use crate::message::Message;
+
+/// The longest message that can be framed, large enough for a snapshot of the largest board.
+pub const MAX_MESSAGE_LEN: usize = 255;
+
+/// Encode message into buffer with its length in front, returning the number of bytes used.
+pub fn encode_framed(message: &Message, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+    let (prefix, body) = buffer.split_first_mut().ok_or(EncodeError)?;
+    let body_len = body.len().min(MAX_MESSAGE_LEN);
+    let len = message.encode(&mut body[..body_len])?;
+    *prefix = len as u8;
+    Ok(len + 1)
+}
+
+/// Collects bytes read from a stream and splits them into messages.
+pub struct Deframer {
+    buffer: [u8; MAX_MESSAGE_LEN + 1],
+    len: usize,
+    /// Bytes at the front of the buffer taken by the message last returned from next_message.
+    consumed: usize,
+}
+
+impl Default for Deframer {
+    fn default() -> Self {
+        Deframer {
+            buffer: [0; MAX_MESSAGE_LEN + 1],
+            len: 0,
+            consumed: 0,
+        }
+    }
+}
+
+impl Deframer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn compact(&mut self) {
+        self.buffer.copy_within(self.consumed..self.len, 0);
+        self.len -= self.consumed;
+        self.consumed = 0;
+    }
+
+    /// Where to read the next bytes from the stream into, to be followed by a call to filled
+    /// with the number of bytes read. Never empty, as the buffer holds any whole message.
+    pub fn space(&mut self) -> &mut [u8] {
+        self.compact();
+        &mut self.buffer[self.len..]
+    }
+
+    pub fn filled(&mut self, len: usize) {
+        self.len += len;
+    }
+
+    /// The next message if it has arrived in full. A message that fails to decode is skipped
+    /// over, so the stream carries on from the one after it.
+    pub fn next_message(&mut self) -> Option<Result<Message<'_>, DecodeError>> {
+        self.compact();
+        let len = *self.buffer[..self.len].first()? as usize;
+        if self.len < len + 1 {
+            return None;
+        }
+        self.consumed = len + 1;
+        Some(Message::decode(&self.buffer[1..len + 1]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::frame::{encode_framed, Deframer};
+    use crate::message::Message;
+
+    #[test]
+    fn messages_split_across_reads_are_reassembled() {
+        let mut stream = [0; 64];
+        let mut len = encode_framed(&Message::Resync { tick: 500 }, &mut stream).unwrap();
+        len += encode_framed(&Message::Spectate(b"F"), &mut stream[len..]).unwrap();
+
+        let mut deframer = Deframer::new();
+        let mut received = 0;
+        for byte in &stream[..len] {
+            deframer.space()[0] = *byte;
+            deframer.filled(1);
+            while let Some(message) = deframer.next_message() {
+                match (received, message) {
+                    (0, Ok(Message::Resync { tick: 500 })) => {}
+                    (1, Ok(Message::Spectate(b"F"))) => {}
+                    (_, message) => panic!("Unexpected message {:?}", message),
+                }
+                received += 1;
+            }
+        }
+        assert_eq!(received, 2);
+    }
+}
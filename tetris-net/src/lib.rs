@@ -0,0 +1,21 @@
+#![no_std]
+
+//! The messages exchanged between devices for netplay and spectating, with a compact encoding
+//! that needs no allocation so the same code runs on the Pico and the desktop.
+//!
+//! Messages start with a tag byte and use variable length integers, so most fit in a handful of
+//! bytes. Datagram transports such as UDP send one message per packet, stream transports such as
+//! TCP prefix each message with its length (see the frame module).
+
+mod codec;
+pub mod frame;
+pub mod lobby;
+pub mod message;
+
+pub use codec::{DecodeError, EncodeError};
+pub use message::{Message, PROTOCOL_VERSION};
+
+/// The TCP port the Pico W streams its game to spectators on.
+pub const SPECTATE_PORT: u16 = 7878;
+/// The TCP port a versus match is hosted on.
+pub const NETPLAY_PORT: u16 = 7879;
@@ -0,0 +1,279 @@
+//! The handshake that starts a versus match between two devices, whichever frontends they run.
+//! Both send a hello as they connect, then the host offers the match: the size of the playfield
+//! and the rules, which set the speed and how pieces are dealt. The player joining accepts it if
+//! they can play it, and both start from the seed in the host's hello.
+
+use crate::codec::DecodeError;
+use crate::message::{Message, PROTOCOL_VERSION};
+use core::fmt;
+use tetris_core::tetris::{Rules, GRID_SIZE};
+use tetris_core::versus::Garbage;
+
+/// How a match is played, agreed by both players before it starts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MatchConfig {
+    pub width: u8,
+    pub height: u8,
+    pub rules: Rules,
+}
+
+impl MatchConfig {
+    /// A match on the playfield of this build of the core.
+    pub fn new(rules: Rules) -> Self {
+        MatchConfig {
+            width: GRID_SIZE.0 as u8,
+            height: GRID_SIZE.1 as u8,
+            rules,
+        }
+    }
+
+    /// The garbage a Garbage message sends, rejected if its hole is outside the playfield.
+    pub fn garbage(&self, rows: u8, hole: u8) -> Result<Garbage, DecodeError> {
+        if hole >= self.width {
+            return Err(DecodeError::Invalid);
+        }
+        Ok(Garbage {
+            rows: rows as usize,
+            hole: hole as usize,
+        })
+    }
+}
+
+/// Mixed into the seed of a match for its garbage holes, so that they are picked from a stream
+/// unrelated to the pieces.
+const GARBAGE_SEED_MIX: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A match both players have agreed to, started from the same seed on each device.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Match {
+    pub config: MatchConfig,
+    pub seed: u64,
+}
+
+impl Match {
+    /// The seed both players pick the holes of the garbage they send from, so that each is
+    /// sent the same garbage.
+    pub fn garbage_seed(&self) -> u64 {
+        self.seed ^ GARBAGE_SEED_MIX
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LobbyError {
+    /// The other device speaks another version of the protocol.
+    Version(u8),
+    /// The host offered a playfield of a size this device cannot play on.
+    Unsupported { width: u8, height: u8 },
+    /// The player joining turned the match down.
+    Declined,
+    /// A message that has no place in the handshake.
+    Unexpected,
+}
+
+impl fmt::Display for LobbyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LobbyError::Version(version) => write!(f, "Unsupported protocol version {}", version),
+            LobbyError::Unsupported { width, height } => {
+                write!(f, "Cannot play on a {}x{} playfield", width, height)
+            }
+            LobbyError::Declined => write!(f, "The other player declined the match"),
+            LobbyError::Unexpected => write!(f, "Unexpected message before the match"),
+        }
+    }
+}
+
+enum Role {
+    Host {
+        config: MatchConfig,
+        seed: u64,
+    },
+    /// The seed arrives in the host's hello.
+    Guest {
+        seed: Option<u64>,
+    },
+}
+
+/// One side of the handshake. Messages from the other device are fed to receive until it
+/// returns the match or an error.
+pub struct Lobby {
+    role: Role,
+}
+
+impl Lobby {
+    /// Host a match played with config, the pieces dealt from seed.
+    pub fn host(config: MatchConfig, seed: u64) -> Self {
+        Lobby {
+            role: Role::Host { config, seed },
+        }
+    }
+
+    /// Join a match hosted by the other device.
+    pub fn join() -> Self {
+        Lobby {
+            role: Role::Guest { seed: None },
+        }
+    }
+
+    /// The hello to send as the connection opens.
+    pub fn hello(&self) -> Message<'static> {
+        let seed = match self.role {
+            Role::Host { seed, .. } => seed,
+            Role::Guest { .. } => 0,
+        };
+        Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed,
+        }
+    }
+
+    /// Handle a message from the other device, calling send with any reply, and return the
+    /// match once it has been agreed.
+    pub fn receive(
+        &mut self,
+        message: &Message,
+        send: &mut dyn FnMut(Message<'static>),
+    ) -> Result<Option<Match>, LobbyError> {
+        match (&mut self.role, message) {
+            (_, &Message::Hello { version, .. }) if version != PROTOCOL_VERSION => {
+                Err(LobbyError::Version(version))
+            }
+            (Role::Host { config, .. }, Message::Hello { .. }) => {
+                send(Message::Offer(*config));
+                Ok(None)
+            }
+            (Role::Host { config, seed }, &Message::Accept { accepted }) => match accepted {
+                true => Ok(Some(Match {
+                    config: *config,
+                    seed: *seed,
+                })),
+                false => Err(LobbyError::Declined),
+            },
+            (
+                Role::Guest { seed },
+                &Message::Hello {
+                    seed: host_seed, ..
+                },
+            ) => {
+                *seed = Some(host_seed);
+                Ok(None)
+            }
+            (Role::Guest { seed: Some(seed) }, &Message::Offer(config)) => {
+                let playable = MatchConfig::new(config.rules) == config;
+                send(Message::Accept { accepted: playable });
+                match playable {
+                    true => Ok(Some(Match {
+                        config,
+                        seed: *seed,
+                    })),
+                    false => Err(LobbyError::Unsupported {
+                        width: config.width,
+                        height: config.height,
+                    }),
+                }
+            }
+            _ => Err(LobbyError::Unexpected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::codec::DecodeError;
+    use crate::lobby::{Lobby, LobbyError, Match, MatchConfig};
+    use crate::message::Message;
+    use tetris_core::tetris::{Rules, GRID_SIZE};
+    use tetris_core::versus::Garbage;
+
+    /// Pass messages between host and guest until neither has anything more to say, returning
+    /// what each ended with.
+    fn handshake(
+        host: &mut Lobby,
+        guest: &mut Lobby,
+    ) -> (
+        Result<Option<Match>, LobbyError>,
+        Result<Option<Match>, LobbyError>,
+    ) {
+        let mut to_host = Some(guest.hello());
+        let mut to_guest = Some(host.hello());
+        let (mut host_result, mut guest_result) = (Ok(None), Ok(None));
+        while to_host.is_some() || to_guest.is_some() {
+            let mut replies = (None, None);
+            if let Some(message) = to_host.take() {
+                host_result = host.receive(&message, &mut |reply| replies.0 = Some(reply));
+            }
+            if let Some(message) = to_guest.take() {
+                guest_result = guest.receive(&message, &mut |reply| replies.1 = Some(reply));
+            }
+            (to_guest, to_host) = replies;
+        }
+        (host_result, guest_result)
+    }
+
+    #[test]
+    fn both_players_start_the_match_offered() {
+        let config = MatchConfig::new(Rules {
+            max_drought: Some(10),
+            ..Rules::default()
+        });
+        let mut host = Lobby::host(config, 99);
+        let mut guest = Lobby::join();
+        let expected = Some(Match { config, seed: 99 });
+        assert_eq!(
+            handshake(&mut host, &mut guest),
+            (Ok(expected), Ok(expected))
+        );
+    }
+
+    #[test]
+    fn a_playfield_of_another_size_is_declined() {
+        let config = MatchConfig {
+            width: 12,
+            ..MatchConfig::new(Rules::default())
+        };
+        let mut host = Lobby::host(config, 99);
+        let mut guest = Lobby::join();
+        assert_eq!(
+            handshake(&mut host, &mut guest),
+            (
+                Err(LobbyError::Declined),
+                Err(LobbyError::Unsupported {
+                    width: 12,
+                    height: GRID_SIZE.1 as u8
+                })
+            )
+        );
+    }
+
+    #[test]
+    fn garbage_with_a_hole_outside_the_playfield_is_rejected() {
+        let config = MatchConfig::new(Rules::default());
+        let width = GRID_SIZE.0 as u8;
+        assert_eq!(
+            config.garbage(2, width - 1),
+            Ok(Garbage {
+                rows: 2,
+                hole: GRID_SIZE.0 - 1
+            })
+        );
+        assert_eq!(config.garbage(2, width), Err(DecodeError::Invalid));
+    }
+
+    #[test]
+    fn another_protocol_version_is_refused() {
+        let mut guest = Lobby::join();
+        let hello = Message::Hello {
+            version: 0,
+            seed: 1,
+        };
+        assert_eq!(
+            guest.receive(&hello, &mut |_| {}),
+            Err(LobbyError::Version(0))
+        );
+        let offer = Message::Offer(MatchConfig::new(Rules::default()));
+        assert_eq!(
+            Lobby::join().receive(&offer, &mut |_| {}),
+            Err(LobbyError::Unexpected)
+        );
+    }
+}
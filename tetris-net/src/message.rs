@@ -0,0 +1,258 @@
+use crate::codec::{DecodeError, EncodeError, Reader, Writer};
+use crate::lobby::MatchConfig;
+use tetris_core::spectate::{Board, MAX_HEIGHT, MAX_WIDTH};
+use tetris_core::tetris::{KeyState, Rules};
+
+/// Sent in Hello. The layout of Hello never changes, so two devices can always tell whether
+/// they understand each other's other messages.
+pub const PROTOCOL_VERSION: u8 = 8;
+
+const TAG_HELLO: u8 = b'H';
+const TAG_INPUT: u8 = b'I';
+const TAG_GARBAGE: u8 = b'G';
+const TAG_SNAPSHOT: u8 = b'S';
+const TAG_RESYNC: u8 = b'R';
+const TAG_SPECTATE: u8 = b'V';
+const TAG_OFFER: u8 = b'O';
+const TAG_ACCEPT: u8 = b'A';
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Message<'a> {
+    /// The first message on every connection. The seed is the one the sender's next game will
+    /// start from, so that both players get the same pieces, or zero when it has no game to
+    /// share.
+    Hello { version: u8, seed: u64 },
+    /// The keys held during a tick of the sender's game.
+    Input { tick: u32, keys: KeyState },
+    /// Rows of garbage for the receiver's board, each with an empty cell in column hole.
+    Garbage { tick: u32, rows: u8, hole: u8 },
+    /// The sender's whole board as of tick, sent in answer to a resync.
+    Snapshot { tick: u32, board: Board },
+    /// Ask for a snapshot after missing messages, tick is the last one that was received.
+    Resync { tick: u32 },
+    /// A message of the spectator stream from tetris_core::spectate, without its length
+    /// prefix.
+    Spectate(&'a [u8]),
+    /// The host of a match proposing how it is played, answered with an accept.
+    Offer(MatchConfig),
+    /// Whether the player joining can play the match offered, it starts once accepted.
+    Accept { accepted: bool },
+}
+
+impl<'a> Message<'a> {
+    /// Encode the message into buffer, returning the number of bytes used.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, EncodeError> {
+        let mut out = Writer::new(buffer);
+        match self {
+            Message::Hello { version, seed } => {
+                out.u8(TAG_HELLO)?;
+                out.u8(*version)?;
+                out.varint(*seed)?;
+            }
+            Message::Input { tick, keys } => {
+                out.u8(TAG_INPUT)?;
+                out.varint(*tick as u64)?;
+                out.u8(keys.to_bits())?;
+            }
+            Message::Garbage { tick, rows, hole } => {
+                out.u8(TAG_GARBAGE)?;
+                out.varint(*tick as u64)?;
+                out.u8(*rows)?;
+                out.u8(*hole)?;
+            }
+            Message::Snapshot { tick, board } => {
+                out.u8(TAG_SNAPSHOT)?;
+                out.varint(*tick as u64)?;
+                out.u8(board.width as u8)?;
+                out.u8(board.height as u8)?;
+                out.varint(board.score as u64)?;
+
+                // One bit per cell, row by row from the bottom
+                let cells = board.width * board.height;
+                for byte in 0..cells.div_ceil(8) {
+                    let bits = (0..8)
+                        .map(|bit| byte * 8 + bit)
+                        .filter(|&cell| cell < cells)
+                        .filter(|&cell| board.get(cell % board.width, cell / board.width))
+                        .fold(0, |bits, cell| bits | 1 << (cell % 8));
+                    out.u8(bits)?;
+                }
+            }
+            Message::Resync { tick } => {
+                out.u8(TAG_RESYNC)?;
+                out.varint(*tick as u64)?;
+            }
+            Message::Spectate(frame) => {
+                out.u8(TAG_SPECTATE)?;
+                out.bytes(frame)?;
+            }
+            Message::Offer(config) => {
+                out.u8(TAG_OFFER)?;
+                out.u8(config.width)?;
+                out.u8(config.height)?;
+                out.bytes(&config.rules.to_bytes())?;
+            }
+            Message::Accept { accepted } => {
+                out.u8(TAG_ACCEPT)?;
+                out.u8(*accepted as u8)?;
+            }
+        }
+        Ok(out.len())
+    }
+
+    /// Decode a whole message, borrowing from data where the message holds bytes.
+    pub fn decode(data: &'a [u8]) -> Result<Self, DecodeError> {
+        let mut input = Reader::new(data);
+        let message = match input.u8()? {
+            TAG_HELLO => Message::Hello {
+                version: input.u8()?,
+                seed: input.varint()?,
+            },
+            TAG_INPUT => Message::Input {
+                tick: input.u32()?,
+                keys: KeyState::from_bits(input.u8()?),
+            },
+            TAG_GARBAGE => {
+                let (tick, rows, hole) = (input.u32()?, input.u8()?, input.u8()?);
+                if rows as usize > MAX_HEIGHT || hole as usize >= MAX_WIDTH {
+                    return Err(DecodeError::Invalid);
+                }
+                Message::Garbage { tick, rows, hole }
+            }
+            TAG_SNAPSHOT => {
+                let tick = input.u32()?;
+                let (width, height) = (input.u8()? as usize, input.u8()? as usize);
+                if width > MAX_WIDTH || height > MAX_HEIGHT {
+                    return Err(DecodeError::Invalid);
+                }
+                let mut board = Board {
+                    width,
+                    height,
+                    rows: [0; MAX_HEIGHT],
+                    score: input.u32()?,
+                };
+
+                let packed = input.bytes((width * height).div_ceil(8))?;
+                for cell in 0..width * height {
+                    if packed[cell / 8] & (1 << (cell % 8)) != 0 {
+                        board.rows[cell / width] |= 1 << (cell % width);
+                    }
+                }
+                Message::Snapshot { tick, board }
+            }
+            TAG_RESYNC => Message::Resync { tick: input.u32()? },
+            TAG_SPECTATE => Message::Spectate(input.rest()),
+            TAG_OFFER => {
+                let (width, height) = (input.u8()?, input.u8()?);
+                let mut rules = [0; Rules::ENCODED_LEN];
+                rules.copy_from_slice(input.bytes(Rules::ENCODED_LEN)?);
+                Message::Offer(MatchConfig {
+                    width,
+                    height,
+                    rules: Rules::from_bytes(&rules),
+                })
+            }
+            TAG_ACCEPT => Message::Accept {
+                accepted: match input.u8()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(DecodeError::Invalid),
+                },
+            },
+            tag => return Err(DecodeError::UnknownTag(tag)),
+        };
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lobby::MatchConfig;
+    use crate::message::{Message, PROTOCOL_VERSION};
+    use crate::DecodeError;
+    use tetris_core::spectate::{Board, MAX_HEIGHT};
+    use tetris_core::tetris::{KeyState, Rules};
+
+    fn round_trip(message: Message) -> usize {
+        let mut buffer = [0; 128];
+        let len = message.encode(&mut buffer).unwrap();
+        assert_eq!(Message::decode(&buffer[..len]), Ok(message));
+        len
+    }
+
+    #[test]
+    fn every_message_round_trips() {
+        round_trip(Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed: u64::MAX,
+        });
+        round_trip(Message::Input {
+            tick: 70_000,
+            keys: KeyState {
+                left: true,
+                hard_drop: true,
+                hold: true,
+                soft_drop: true,
+                ..KeyState::default()
+            },
+        });
+        round_trip(Message::Garbage {
+            tick: 3,
+            rows: 4,
+            hole: 7,
+        });
+        round_trip(Message::Resync { tick: 0 });
+        round_trip(Message::Spectate(b"K\x0a\x14"));
+        round_trip(Message::Offer(MatchConfig::new(Rules {
+            max_drought: Some(8),
+            ..Rules::default()
+        })));
+        round_trip(Message::Accept { accepted: true });
+
+        let mut rows = [0; MAX_HEIGHT];
+        rows[0] = 0b1111101111;
+        rows[1] = 0b0000100000;
+        round_trip(Message::Snapshot {
+            tick: 12,
+            board: Board {
+                width: 10,
+                height: 20,
+                rows,
+                score: 4000,
+            },
+        });
+    }
+
+    #[test]
+    fn inputs_are_compact() {
+        let len = round_trip(Message::Input {
+            tick: 100,
+            keys: KeyState::default(),
+        });
+        assert_eq!(len, 3);
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        assert_eq!(Message::decode(&[]), Err(DecodeError::Truncated));
+        assert_eq!(Message::decode(b"X"), Err(DecodeError::UnknownTag(b'X')));
+        assert_eq!(Message::decode(&[b'I', 0x80]), Err(DecodeError::Truncated));
+        assert_eq!(
+            Message::decode(&[b'S', 0, 40, 20, 0]),
+            Err(DecodeError::Invalid)
+        );
+        assert_eq!(
+            Message::decode(&[b'G', 0, 2, 200]),
+            Err(DecodeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn a_small_buffer_is_an_error() {
+        let message = Message::Hello {
+            version: PROTOCOL_VERSION,
+            seed: 1,
+        };
+        assert!(message.encode(&mut [0; 2]).is_err());
+    }
+}